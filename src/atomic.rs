@@ -0,0 +1,94 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// Which writes are flushed to disk before they count as done, from the
+// core.fsync config. Without it a crash can lose writes the OS had not
+// flushed yet, though never leave a half-written file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FsyncPolicy {
+    pub objects: bool,
+    pub refs: bool,
+}
+
+impl FsyncPolicy {
+    // A comma-separated list of "objects", "refs", "all" and "none", later
+    // names adding to (or for "none", clearing) earlier ones. Unknown names
+    // are ignored, as git does.
+    pub fn parse(value: &str) -> FsyncPolicy {
+        let mut policy = FsyncPolicy::default();
+        for name in value.split(',').map(str::trim) {
+            match name {
+                "objects" => policy.objects = true,
+                "refs" => policy.refs = true,
+                "all" => {
+                    policy.objects = true;
+                    policy.refs = true;
+                }
+                "none" => policy = FsyncPolicy::default(),
+                _ => {}
+            }
+        }
+        policy
+    }
+}
+
+// Temporary files of one process get different names across threads
+static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+// Writes `data` to a temporary file next to `path`, then renames it over
+// `path`: readers see the old content or the new one, never part of it,
+// and a failed write leaves `path` as it was. With `fsync`, the file and
+// the directory entry are flushed before returning.
+pub fn write_atomic(path: &Path, data: &[u8], fsync: bool, read_only: bool) -> io::Result<()> {
+    let name = path.file_name().map(|name| name.to_string_lossy());
+    let temp = path.with_file_name(format!(
+        ".{}.tmp-{}-{}",
+        name.unwrap_or_default(),
+        process::id(),
+        TEMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+
+    let result = write_temp(&temp, data, fsync, read_only).and_then(|()| fs::rename(&temp, path));
+    if result.is_err() {
+        fs::remove_file(&temp).ok();
+    }
+    result?;
+
+    if fsync {
+        sync_dir(path)?;
+    }
+    Ok(())
+}
+
+// A rename is only durable once the directory holding `path` is flushed
+pub(crate) fn sync_dir(path: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        fs::File::open(dir)?.sync_all()?;
+    }
+    Ok(())
+}
+
+fn write_temp(temp: &Path, data: &[u8], fsync: bool, read_only: bool) -> io::Result<()> {
+    let mut file = fs::File::options()
+        .write(true)
+        .create_new(true)
+        .open(temp)?;
+    file.write_all(data)?;
+    if fsync {
+        file.sync_all()?;
+    }
+    if read_only {
+        let mut permissions = file.metadata()?.permissions();
+        permissions.set_readonly(true);
+        file.set_permissions(permissions)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,66 @@
+use crate::repository::Repository;
+use std::fs;
+use std::path::Path;
+
+// Per-path attributes, read from the root of the worktree like .bgitignore
+pub const ATTRIBUTES_FILE: &str = ".bgitattributes";
+
+impl Repository {
+    // Whether .bgitattributes marks `path` as binary (`binary`, `-diff`,
+    // `-text`) or as text (`diff`, `text`). None when no line applies.
+    // The last matching line wins.
+    pub fn binary_attribute(&self, path: &str) -> Option<bool> {
+        // A bare repository has no worktree to read them from
+        if self.bare {
+            return None;
+        }
+        let content = fs::read_to_string(Path::new(&self.worktree).join(ATTRIBUTES_FILE)).ok()?;
+
+        let mut binary = None;
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut parts = line.split_whitespace();
+            let pattern = match parts.next() {
+                Some(pattern) => pattern,
+                None => continue,
+            };
+            if !Self::attribute_pattern_matches(pattern, path) {
+                continue;
+            }
+
+            for attribute in parts {
+                match attribute {
+                    "binary" | "-diff" | "-text" => binary = Some(true),
+                    "diff" | "text" => binary = Some(false),
+                    _ => {}
+                }
+            }
+        }
+        binary
+    }
+
+    // Patterns without a slash match the file name in any directory,
+    // others the whole path. `*` does not cross directories.
+    fn attribute_pattern_matches(pattern: &str, path: &str) -> bool {
+        let (pattern, subject) = if pattern.contains('/') {
+            (pattern.trim_start_matches('/'), path)
+        } else {
+            (pattern, path.rsplit('/').next().unwrap_or(path))
+        };
+
+        let regex_pattern = format!(
+            "^{}$",
+            regex::escape(pattern)
+                .replace(r"\*", "[^/]*")
+                .replace(r"\?", "[^/]")
+        );
+        match regex::Regex::new(&regex_pattern) {
+            Ok(re) => re.is_match(subject),
+            Err(_) => false,
+        }
+    }
+}
@@ -0,0 +1,83 @@
+// Binary content: detection and the "GIT binary patch" encoding used by
+// `diff --binary`
+
+// Like git, only the start of a file is looked at
+const BINARY_CHECK_LENGTH: usize = 8000;
+
+// Bytes of (deflated) data per line of a binary patch
+const BINARY_LINE_LENGTH: usize = 52;
+
+const BASE85_ALPHABET: &[u8; 85] =
+    b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz!#$%&()*+-;<=>?@^_`{|}~";
+
+// Content is binary when a NUL byte appears near its start
+pub fn is_binary(content: &[u8]) -> bool {
+    content[..content.len().min(BINARY_CHECK_LENGTH)].contains(&0)
+}
+
+// Git's base85: every 4 bytes (zero padded) become 5 characters
+pub fn encode_base85(data: &[u8]) -> String {
+    let mut output = String::new();
+    for chunk in data.chunks(4) {
+        let mut word = [0u8; 4];
+        word[..chunk.len()].copy_from_slice(chunk);
+        let mut value = u32::from_be_bytes(word);
+
+        let mut encoded = [0u8; 5];
+        for slot in encoded.iter_mut().rev() {
+            *slot = BASE85_ALPHABET[(value % 85) as usize];
+            value /= 85;
+        }
+        output.push_str(std::str::from_utf8(&encoded).unwrap());
+    }
+    output
+}
+
+// A "literal <size>" hunk of a binary patch: the zlib stream of the
+// content, base85 encoded in lines prefixed by their decoded length
+pub fn binary_literal(content: &[u8]) -> String {
+    let mut output = format!("literal {}\n", content.len());
+    for chunk in zlib_store(content).chunks(BINARY_LINE_LENGTH) {
+        let length = match chunk.len() {
+            n @ 1..=26 => (b'A' + n as u8 - 1) as char,
+            n => (b'a' + n as u8 - 27) as char,
+        };
+        output.push(length);
+        output.push_str(&encode_base85(chunk));
+        output.push('\n');
+    }
+    output.push('\n');
+    output
+}
+
+// zlib stream made of uncompressed (stored) deflate blocks
+fn zlib_store(data: &[u8]) -> Vec<u8> {
+    let mut output = vec![0x78, 0x01];
+
+    let mut blocks = data.chunks(u16::MAX as usize).peekable();
+    if blocks.peek().is_none() {
+        // An empty final block
+        output.extend_from_slice(&[0x01, 0x00, 0x00, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        let length = block.len() as u16;
+        output.push(last as u8);
+        output.extend_from_slice(&length.to_le_bytes());
+        output.extend_from_slice(&(!length).to_le_bytes());
+        output.extend_from_slice(block);
+    }
+
+    output.extend_from_slice(&adler32(data).to_be_bytes());
+    output
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    const MOD_ADLER: u32 = 65521;
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + byte as u32) % MOD_ADLER;
+        b = (b + a) % MOD_ADLER;
+    }
+    (b << 16) | a
+}
@@ -1,136 +1,1354 @@
+use crate::Error;
+use crate::differ::{
+    DEFAULT_RENAME_THRESHOLD, DiffFormat, DiffOptions, DiffTarget, WhitespaceOptions, WordDiffMode,
+};
+use crate::fsck::FsckOptions;
+use crate::gc::GcOptions;
+use crate::oid::HashAlgorithm;
+use crate::output::{ColorMode, OutputFormat};
+use crate::porcelain::{PorcelainVersion, StatusOptions};
+use crate::rebase::RebaseOptions;
+use crate::repository::{InitOptions, LogOptions, MergeOptions};
+use crate::sequencer::SequencerOptions;
+use crate::stash::StashCommand;
 use std::env;
+use std::error;
+use std::fmt;
+
+// Exit codes, as in git. 1 reports differences (diff --exit-code),
+// conflicts, a missing config key, or problems found by fsck.
+pub const EXIT_FAILURE: i32 = 1;
+// A command that could not be carried out
+pub const EXIT_FATAL: i32 = 128;
+// An invalid command line
+pub const EXIT_USAGE: i32 = 129;
 
 pub enum Command {
-    Init,
+    Help(Option<&'static str>),
+    Version,
+    Init(Option<String>, InitOptions),
     HashObject(String),
-    CatFile(String),
+    CatFile(String, CatFileMode),
     WriteTree,
     ReadTree(String),
     GetTree(String),
     Commit(String),
-    Log,
+    Log(LogOptions),
     Checkout(String),
     Tag(String, String),
     Visualize,
-    IterRefs,
-    Branch(Option<String>),
-    Status,
+    IterRefs(OutputFormat),
+    Branch(Option<String>, OutputFormat),
+    Status(StatusOptions),
     Reset(String),
-    Show(String),
-    Diff,
-    Merge(String),
-    Rebase(String),
-    Unknown(String),
+    Show(String, DiffFormat, OutputFormat),
+    Diff(DiffTarget, DiffOptions),
+    Merge(String, MergeOptions),
+    Rebase(String, RebaseOptions),
+    RebaseContinue,
+    RebaseSkip,
+    RebaseAbort,
+    Config(String, Option<String>),
+    CherryPick(Vec<String>, SequencerOptions),
+    Revert(Vec<String>, SequencerOptions),
+    SequencerContinue,
+    SequencerAbort,
+    Stash(StashCommand),
+    Fsck(FsckOptions),
+    // `gc` and `prune`
+    Gc(GcOptions),
 }
 
-impl Command {
-    pub fn from_args(args: &[String]) -> Command {
-        if args.is_empty() {
-            return Command::Unknown("No command provided".to_string());
+// What `cat-file` prints of an object
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CatFileMode {
+    // The content as stored, byte for byte
+    Raw,
+    // -t
+    Type,
+    // -s, the size of the content
+    Size,
+    // -e, nothing: the exit code says whether the object exists
+    Exists,
+    // -p, trees one entry per line, blobs and commits as they are
+    Pretty,
+}
+
+// A command line that cannot be parsed, shown with the usage of the command
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UsageError {
+    pub message: String,
+    pub usage: Option<String>,
+}
+
+impl UsageError {
+    fn new(message: impl Into<String>) -> UsageError {
+        UsageError {
+            message: message.into(),
+            usage: None,
         }
+    }
+}
 
-        match args[0].as_str() {
-            "init" => Command::Init,
-            "hash-object" => {
-                if args.len() < 2 {
-                    return Command::Unknown("No file path provided for hash-object".to_string());
-                }
-                Command::HashObject(args[1].clone())
-            }
-            "cat-file" => {
-                if args.len() < 2 {
-                    return Command::Unknown("No hash provided for cat-file".to_string());
+impl fmt::Display for UsageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "error: {}", self.message)?;
+        if let Some(usage) = &self.usage {
+            write!(f, "\n\n{}", usage)?;
+        }
+        Ok(())
+    }
+}
+
+// How a failed command reports an error of the library: the message
+// followed by its causes, e.g. "Failed to read HEAD file: Permission denied"
+pub fn error_message(error: &Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error::Error::source(error);
+    while let Some(cause) = source {
+        message.push_str(&format!(": {}", cause));
+        source = cause.source();
+    }
+    message
+}
+
+// What to do about an error, for those that have an obvious next step
+pub fn error_hint(error: &Error) -> Option<&'static str> {
+    match error {
+        Error::NotARepository(_) => {
+            Some("run 'bgit init' to create a repository, or use -C or BGIT_DIR to find one")
+        }
+        Error::BareRepository => Some("set BGIT_WORK_TREE to the directory to work in"),
+        Error::Conflict(_) => Some("fix the conflicts in these paths first"),
+        Error::InvalidRef(_) => Some("'bgit iter-refs' lists the refs of the repository"),
+        Error::Corrupt(_) => Some("the repository may be damaged"),
+        Error::Locked(_) => Some(
+            "if no other bgit process is running, one was killed: run again with --break-stale-locks",
+        ),
+        _ => None,
+    }
+}
+
+// Conflicts are an expected outcome and exit with 1, like in git, anything
+// else means the command could not be carried out
+pub fn error_exit_code(error: &Error) -> i32 {
+    match error {
+        Error::Conflict(_) => EXIT_FAILURE,
+        _ => EXIT_FATAL,
+    }
+}
+
+// An option, declared as it is shown in the help: "-p, --patch" for a flag,
+// "--format <format>" for a required value and "-M, --find-renames[=<n>]"
+// for an optional one, which must then be attached (-M50, --find-renames=50)
+pub struct OptionSpec {
+    label: &'static str,
+    help: &'static str,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Takes {
+    Nothing,
+    Value,
+    OptionalValue,
+}
+
+const fn opt(label: &'static str, help: &'static str) -> OptionSpec {
+    OptionSpec { label, help }
+}
+
+impl OptionSpec {
+    // The "-p, --patch" part of the label, and what the option takes
+    fn names(&self) -> (&'static str, Takes) {
+        let label = self.label;
+        if let Some(end) = label.find("[=") {
+            (&label[..end], Takes::OptionalValue)
+        } else if let Some(end) = label.find(" <") {
+            (&label[..end], Takes::Value)
+        } else {
+            (label, Takes::Nothing)
+        }
+    }
+
+    fn short(&self) -> Option<char> {
+        let (names, _) = self.names();
+        names
+            .split(", ")
+            .find(|name| !name.starts_with("--"))
+            .and_then(|name| name.chars().nth(1))
+    }
+
+    fn long(&self) -> Option<&'static str> {
+        let (names, _) = self.names();
+        names.split(", ").find_map(|name| name.strip_prefix("--"))
+    }
+
+    // How parsed options are named: the long name, or the letter of
+    // options without one
+    fn key(&self) -> &'static str {
+        let (names, _) = self.names();
+        self.long().unwrap_or(&names[1..])
+    }
+}
+
+pub struct CommandSpec {
+    pub name: &'static str,
+    pub about: &'static str,
+    // Synopsis lines, after "bgit <name>"
+    usage: &'static [&'static str],
+    // Positional arguments: "<x>" is required, "[<x>]" optional, and a
+    // trailing "..." takes any number of them
+    args: &'static [&'static str],
+    options: &'static [&'static [OptionSpec]],
+    // Whether arguments after "--" are paths, instead of positional
+    // arguments that look like options
+    paths: bool,
+}
+
+// The command line of a command, checked against its spec
+#[derive(Debug, Default)]
+struct Matches {
+    // Options in the order they were given, by key, with their value
+    options: Vec<(&'static str, Option<String>)>,
+    args: Vec<String>,
+    paths: Vec<String>,
+}
+
+impl Matches {
+    fn has(&self, key: &str) -> bool {
+        self.options.iter().any(|(name, _)| *name == key)
+    }
+
+    // The last value given for `key`
+    fn value(&self, key: &str) -> Option<&str> {
+        self.options
+            .iter()
+            .rev()
+            .find(|(name, _)| *name == key)
+            .and_then(|(_, value)| value.as_deref())
+    }
+}
+
+impl CommandSpec {
+    fn option_specs(&self) -> impl Iterator<Item = &'static OptionSpec> {
+        self.options.iter().flat_map(|group| group.iter())
+    }
+
+    pub fn usage(&self) -> String {
+        self.usage
+            .iter()
+            .enumerate()
+            .map(|(index, line)| {
+                let prefix = if index == 0 { "usage:" } else { "   or:" };
+                format!("{} bgit {} {}", prefix, self.name, line)
+                    .trim_end()
+                    .to_string()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    pub fn help(&self) -> String {
+        let mut help = format!("{}\n\n{}\n", self.usage(), self.about);
+        let width = self
+            .option_specs()
+            .map(|option| option.label.len())
+            .max()
+            .unwrap_or(0);
+        if width > 0 {
+            help.push('\n');
+        }
+        for option in self.option_specs() {
+            // Long-only options line up with the long names of the others
+            let indent = if option.label.starts_with("--") {
+                "    "
+            } else {
+                ""
+            };
+            let label = format!("{}{}", indent, option.label);
+            help.push_str(&format!(
+                "    {:<width$}  {}\n",
+                label,
+                option.help,
+                width = width + 4
+            ));
+        }
+        help
+    }
+
+    fn error(&self, message: impl Into<String>) -> UsageError {
+        UsageError {
+            message: message.into(),
+            usage: Some(self.usage()),
+        }
+    }
+
+    fn parse(&self, args: &[String]) -> Result<Matches, UsageError> {
+        let mut matches = Matches::default();
+
+        let mut rest = args.iter();
+        while let Some(arg) = rest.next() {
+            if arg == "--" {
+                let after = rest.by_ref().cloned();
+                if self.paths {
+                    matches.paths.extend(after);
+                } else {
+                    matches.args.extend(after);
                 }
-                Command::CatFile(args[1].clone())
+                break;
             }
-            "write-tree" => {
-                if args.len() > 1 {
-                    return Command::Unknown("write-tree does not take any arguments".to_string());
+
+            if let Some(long) = arg.strip_prefix("--") {
+                let (name, value) = match long.split_once('=') {
+                    Some((name, value)) => (name, Some(value.to_string())),
+                    None => (long, None),
+                };
+                let option = self
+                    .option_specs()
+                    .find(|option| option.long() == Some(name))
+                    .ok_or_else(|| self.error(format!("unknown option: --{}", name)))?;
+                let value = match (option.names().1, value) {
+                    (Takes::Nothing, Some(_)) => {
+                        return Err(self.error(format!("--{} does not take a value", name)));
+                    }
+                    (Takes::Value, None) => match rest.next() {
+                        Some(value) => Some(value.clone()),
+                        None => return Err(self.error(format!("--{} requires a value", name))),
+                    },
+                    (_, value) => value,
+                };
+                matches.options.push((option.key(), value));
+            } else if arg.len() > 1 && arg.starts_with('-') {
+                // Flags can be grouped (-nx), a value ends the group (-M50)
+                for (index, letter) in arg.char_indices().skip(1) {
+                    let option = self
+                        .option_specs()
+                        .find(|option| option.short() == Some(letter))
+                        .ok_or_else(|| self.error(format!("unknown option: -{}", letter)))?;
+                    let attached = &arg[index + letter.len_utf8()..];
+                    let value = match option.names().1 {
+                        Takes::Nothing => {
+                            matches.options.push((option.key(), None));
+                            continue;
+                        }
+                        Takes::Value if attached.is_empty() => match rest.next() {
+                            Some(value) => Some(value.clone()),
+                            None => {
+                                return Err(self.error(format!("-{} requires a value", letter)));
+                            }
+                        },
+                        Takes::OptionalValue if attached.is_empty() => None,
+                        _ => Some(attached.to_string()),
+                    };
+                    matches.options.push((option.key(), value));
+                    break;
                 }
-                Command::WriteTree
+            } else {
+                matches.args.push(arg.clone());
             }
-            "read-tree" => {
-                if args.len() < 2 {
-                    return Command::Unknown("No tree hash provided for read-tree".to_string());
-                }
-                Command::ReadTree(args[1].clone())
+        }
+
+        let required = self.args.iter().filter(|arg| !arg.starts_with('[')).count();
+        let unlimited = self.args.iter().any(|arg| arg.ends_with("..."));
+        if matches.args.len() < required {
+            return Err(self.error(format!("missing {}", self.args[matches.args.len()])));
+        }
+        if !unlimited && matches.args.len() > self.args.len() {
+            return Err(self.error(format!(
+                "unexpected argument: {}",
+                matches.args[self.args.len()]
+            )));
+        }
+        Ok(matches)
+    }
+}
+
+const FORMAT_OPTIONS: &[OptionSpec] =
+    &[opt("--format <format>", "Print text, or json for scripts")];
+
+// Output formats shared by diff, show and log
+const DIFF_FORMAT_OPTIONS: &[OptionSpec] = &[
+    opt("-p, --patch", "Show the changes as a patch"),
+    opt("--stat", "Show a histogram of the changed lines per file"),
+    opt("--numstat", "Show the inserted and deleted lines per file"),
+    opt("--shortstat", "Show only the total of changed lines"),
+    opt("--name-only", "Show only the changed paths"),
+    opt("--name-status", "Show the changed paths with their status"),
+];
+
+const DIFF_OPTIONS: &[OptionSpec] = &[
+    opt(
+        "-M, --find-renames[=<n>]",
+        "Detect renames of files at least <n>% similar (50)",
+    ),
+    opt("--no-renames", "Do not detect renames"),
+    opt("-C, --find-copies", "Also detect copies of unchanged files"),
+    opt("--binary", "Show binary files as base85 patches"),
+    opt("-w, --ignore-all-space", "Ignore whitespace"),
+    opt(
+        "-b, --ignore-space-change",
+        "Ignore changes in the amount of whitespace",
+    ),
+    opt(
+        "--ignore-blank-lines",
+        "Ignore added or removed blank lines",
+    ),
+    opt(
+        "--ignore-cr-at-eol",
+        "Ignore carriage returns at the end of lines",
+    ),
+    opt(
+        "--word-diff[=<mode>]",
+        "Show changed words: plain, color or porcelain",
+    ),
+    opt("--color-words", "Same as --word-diff=color"),
+    opt(
+        "--word-diff-regex <regex>",
+        "What a word is, implies --word-diff",
+    ),
+    opt(
+        "--cached",
+        "Compare with the next commit instead of the worktree",
+    ),
+    opt("--staged", "Same as --cached"),
+    opt("--no-index", "Compare two files outside of the repository"),
+    opt("--exit-code", "Exit with 1 when there are differences"),
+    opt("--quiet", "Print nothing, implies --exit-code"),
+];
+
+const REBASE_OPTIONS: &[OptionSpec] = &[
+    opt("-i, --interactive", "Edit the list of commits first"),
+    opt(
+        "--autosquash",
+        "Move fixup! and squash! commits after their target",
+    ),
+    opt(
+        "--onto <commit>",
+        "Replay onto <commit> instead of <upstream>",
+    ),
+    opt("--continue", "Continue after resolving conflicts"),
+    opt("--skip", "Skip the current commit"),
+    opt("--abort", "Restore the original branch"),
+];
+
+const SEQUENCER_OPTIONS: &[OptionSpec] = &[
+    opt("-n, --no-commit", "Only update the working directory"),
+    opt("-x", "Record the original commit in the message"),
+    opt(
+        "-m, --mainline <parent>",
+        "Parent number of merge commits to compare with",
+    ),
+    opt("--continue", "Continue after resolving conflicts"),
+    opt("--abort", "Cancel the whole operation"),
+];
+
+pub const COMMANDS: &[CommandSpec] = &[
+    CommandSpec {
+        name: "init",
+        about: "Create an empty repository, in the current directory by default.",
+        usage: &["[--bare] [--initial-branch <name>] [--object-format <format>] [<directory>]"],
+        args: &["[<directory>]"],
+        options: &[&[
+            opt("--bare", "Create a repository without a worktree"),
+            opt("-b, --initial-branch <name>", "Name of the first branch"),
+            opt(
+                "--object-format <format>",
+                "Hash of object ids: sha1 (the default) or sha256",
+            ),
+        ]],
+        paths: false,
+    },
+    CommandSpec {
+        name: "status",
+        about: "Show the branch and the changed files.",
+        usage: &["[--porcelain[=<version>]] [-z] [-b]"],
+        args: &[],
+        options: &[&[
+            opt(
+                "--porcelain[=<version>]",
+                "Print the stable format for scripts, v1 or v2",
+            ),
+            opt("-z", "End entries with NUL, implies --porcelain"),
+            opt("-b, --branch", "Also show the branch in porcelain output"),
+        ]],
+        paths: false,
+    },
+    CommandSpec {
+        name: "commit",
+        about: "Record the working directory as a new commit.",
+        usage: &["[-m] <message>"],
+        args: &["[<message>]"],
+        options: &[&[opt("-m, --message <message>", "The commit message")]],
+        paths: false,
+    },
+    CommandSpec {
+        name: "log",
+        about: "Show the commit history.",
+        usage: &["[<options>] [--follow [--] <path>]"],
+        args: &["[<path>]"],
+        options: &[
+            DIFF_FORMAT_OPTIONS,
+            FORMAT_OPTIONS,
+            &[opt(
+                "--follow",
+                "Only show commits changing <path>, across renames",
+            )],
+        ],
+        paths: true,
+    },
+    CommandSpec {
+        name: "branch",
+        about: "List branches, or create one at HEAD.",
+        usage: &["[--format <format>]", "<name>"],
+        args: &["[<name>]"],
+        options: &[FORMAT_OPTIONS],
+        paths: false,
+    },
+    CommandSpec {
+        name: "checkout",
+        about: "Switch the working directory to a branch or commit.",
+        usage: &["<commit>"],
+        args: &["<commit>"],
+        options: &[],
+        paths: false,
+    },
+    CommandSpec {
+        name: "diff",
+        about: "Show changes between commits and the working directory.",
+        usage: &[
+            "[<options>] [<commit> [<commit>]] [--] [<path>...]",
+            "[<options>] <commit>..<commit> [--] [<path>...]",
+            "[<options>] --cached [<commit>] [--] [<path>...]",
+            "[<options>] --no-index <path> <path>",
+        ],
+        args: &["[<commit>]", "[<commit>]"],
+        options: &[DIFF_FORMAT_OPTIONS, DIFF_OPTIONS, FORMAT_OPTIONS],
+        paths: true,
+    },
+    CommandSpec {
+        name: "merge",
+        about: "Merge a branch into the current branch.",
+        usage: &["[-X <option>] <branch>"],
+        args: &["<branch>"],
+        options: &[&[opt(
+            "-X, --strategy-option <option>",
+            "ignore-space-change, ignore-all-space or ignore-cr-at-eol",
+        )]],
+        paths: false,
+    },
+    CommandSpec {
+        name: "rebase",
+        about: "Replay the commits of the current branch onto another base.",
+        usage: &[
+            "[-i] [--autosquash] [--onto <commit>] <upstream>",
+            "--continue | --skip | --abort",
+        ],
+        args: &["[<upstream>]"],
+        options: &[REBASE_OPTIONS],
+        paths: false,
+    },
+    CommandSpec {
+        name: "cherry-pick",
+        about: "Apply the changes of existing commits.",
+        usage: &[
+            "[-n] [-x] [-m <parent>] <commit>...",
+            "--continue | --abort",
+        ],
+        args: &["[<commit>...]"],
+        options: &[SEQUENCER_OPTIONS],
+        paths: false,
+    },
+    CommandSpec {
+        name: "revert",
+        about: "Record commits undoing existing commits.",
+        usage: &[
+            "[-n] [-x] [-m <parent>] <commit>...",
+            "--continue | --abort",
+        ],
+        args: &["[<commit>...]"],
+        options: &[SEQUENCER_OPTIONS],
+        paths: false,
+    },
+    CommandSpec {
+        name: "reset",
+        about: "Move the current branch to a commit and update the working directory.",
+        usage: &["<commit>"],
+        args: &["<commit>"],
+        options: &[],
+        paths: false,
+    },
+    CommandSpec {
+        name: "tag",
+        about: "Create a tag pointing to a commit.",
+        usage: &["<name> <commit>"],
+        args: &["<name>", "<commit>"],
+        options: &[],
+        paths: false,
+    },
+    CommandSpec {
+        name: "show",
+        about: "Show a commit and its changes.",
+        usage: &["[<options>] <commit>"],
+        args: &["<commit>"],
+        options: &[DIFF_FORMAT_OPTIONS, FORMAT_OPTIONS],
+        paths: false,
+    },
+    CommandSpec {
+        name: "stash",
+        about: "Shelve the changes of the working directory.",
+        usage: &[
+            "[push [-m <message>] [--] [<path>...]]",
+            "list",
+            "show [-p] [<stash>]",
+            "apply [<stash>]",
+            "pop [<stash>]",
+            "drop [<stash>]",
+            "branch <branch> [<stash>]",
+        ],
+        args: &[],
+        options: &[],
+        paths: false,
+    },
+    CommandSpec {
+        name: "config",
+        about: "Get or set a repository option.",
+        usage: &["<key> [<value>]"],
+        args: &["<key>", "[<value>]"],
+        options: &[],
+        paths: false,
+    },
+    CommandSpec {
+        name: "hash-object",
+        about: "Store a file as a blob and print its hash.",
+        usage: &["<file>"],
+        args: &["<file>"],
+        options: &[],
+        paths: false,
+    },
+    CommandSpec {
+        name: "cat-file",
+        about: "Print the content, type or size of an object.",
+        usage: &["[-t | -s | -e | -p] <object>"],
+        args: &["<object>"],
+        options: &[&[
+            opt("-t", "Print the type of the object"),
+            opt("-s", "Print the size of the content"),
+            opt(
+                "-e",
+                "Print nothing, exit with 1 if the object does not exist",
+            ),
+            opt("-p", "Print the content, trees one entry per line"),
+        ]],
+        paths: false,
+    },
+    CommandSpec {
+        name: "write-tree",
+        about: "Store the working directory as a tree and print its hash.",
+        usage: &[""],
+        args: &[],
+        options: &[],
+        paths: false,
+    },
+    CommandSpec {
+        name: "read-tree",
+        about: "Replace the working directory with a tree.",
+        usage: &["<tree>"],
+        args: &["<tree>"],
+        options: &[],
+        paths: false,
+    },
+    CommandSpec {
+        name: "get-tree",
+        about: "List the entries of a tree.",
+        usage: &["<tree>"],
+        args: &["<tree>"],
+        options: &[],
+        paths: false,
+    },
+    CommandSpec {
+        name: "iter-refs",
+        about: "List all refs with the objects they point to.",
+        usage: &["[--format <format>]"],
+        args: &[],
+        options: &[FORMAT_OPTIONS],
+        paths: false,
+    },
+    CommandSpec {
+        name: "fsck",
+        about: "Verify the objects and refs, and list unreachable objects.",
+        usage: &["[--connectivity-only] [--lost-found]"],
+        args: &[],
+        options: &[&[
+            opt(
+                "--connectivity-only",
+                "Only check that reachable objects exist, without reading blobs",
+            ),
+            opt("--lost-found", "Write dangling objects to .bgit/lost-found"),
+        ]],
+        paths: false,
+    },
+    CommandSpec {
+        name: "gc",
+        about: "Delete unreachable objects older than gc.pruneExpire.",
+        usage: &["[--dry-run] [--prune <date>]"],
+        args: &[],
+        options: &[&[
+            opt(
+                "-n, --dry-run",
+                "Only list the objects that would be deleted",
+            ),
+            opt(
+                "--prune <date>",
+                "Delete unreachable objects older than <date> (default 2.weeks.ago)",
+            ),
+        ]],
+        paths: false,
+    },
+    CommandSpec {
+        name: "prune",
+        about: "Delete unreachable objects.",
+        usage: &["[--dry-run] [--expire <date>]"],
+        args: &[],
+        options: &[&[
+            opt(
+                "-n, --dry-run",
+                "Only list the objects that would be deleted",
+            ),
+            opt(
+                "--expire <date>",
+                "Only delete objects older than <date>, like 2.weeks.ago or now",
+            ),
+        ]],
+        paths: false,
+    },
+    CommandSpec {
+        name: "visualize",
+        about: "Draw the commit graph.",
+        usage: &[""],
+        args: &[],
+        options: &[],
+        paths: false,
+    },
+    CommandSpec {
+        name: "help",
+        about: "Show the help of a command.",
+        usage: &["[<command>]"],
+        args: &["[<command>]"],
+        options: &[],
+        paths: false,
+    },
+    CommandSpec {
+        name: "version",
+        about: "Print the version of bgit.",
+        usage: &[""],
+        args: &[],
+        options: &[],
+        paths: false,
+    },
+];
+
+// Subcommands of stash, named "stash <subcommand>" for their usage
+const STASH_COMMANDS: &[CommandSpec] = &[
+    CommandSpec {
+        name: "stash push",
+        about: "Shelve the changes.",
+        usage: &["[-m <message>] [--] [<path>...]"],
+        args: &["[<path>...]"],
+        options: &[&[opt("-m, --message <message>", "Describe the stash")]],
+        paths: true,
+    },
+    CommandSpec {
+        name: "stash list",
+        about: "List the stashes.",
+        usage: &[""],
+        args: &[],
+        options: &[],
+        paths: false,
+    },
+    CommandSpec {
+        name: "stash show",
+        about: "Show the changes of a stash.",
+        usage: &["[-p] [<stash>]"],
+        args: &["[<stash>]"],
+        options: &[&[opt("-p, --patch", "Show the full patch")]],
+        paths: false,
+    },
+    CommandSpec {
+        name: "stash apply",
+        about: "Apply a stash.",
+        usage: &["[<stash>]"],
+        args: &["[<stash>]"],
+        options: &[],
+        paths: false,
+    },
+    CommandSpec {
+        name: "stash pop",
+        about: "Apply a stash and drop it.",
+        usage: &["[<stash>]"],
+        args: &["[<stash>]"],
+        options: &[],
+        paths: false,
+    },
+    CommandSpec {
+        name: "stash drop",
+        about: "Drop a stash.",
+        usage: &["[<stash>]"],
+        args: &["[<stash>]"],
+        options: &[],
+        paths: false,
+    },
+    CommandSpec {
+        name: "stash branch",
+        about: "Apply a stash on a new branch.",
+        usage: &["<branch> [<stash>]"],
+        args: &["<branch>", "[<stash>]"],
+        options: &[],
+        paths: false,
+    },
+];
+
+pub fn command_spec(name: &str) -> Option<&'static CommandSpec> {
+    COMMANDS.iter().find(|spec| spec.name == name)
+}
+
+// `bgit help`: the global usage and every command
+pub fn general_help() -> String {
+    let mut help = String::from(
+        "usage: bgit [-C <path>] [--color[=<when>]] [--no-color] [--no-pager] [--break-stale-locks]\n            <command> [<args>]\n\nCommands:\n",
+    );
+    let width = COMMANDS
+        .iter()
+        .map(|spec| spec.name.len())
+        .max()
+        .unwrap_or(0);
+    for spec in COMMANDS {
+        help.push_str(&format!(
+            "    {:<width$}  {}\n",
+            spec.name,
+            spec.about,
+            width = width
+        ));
+    }
+    help.push_str("\nSee 'bgit help <command>' for the options of a command.\n");
+    help
+}
+
+impl Command {
+    // Commands whose output can be long enough to page
+    pub fn uses_pager(&self) -> bool {
+        matches!(
+            self,
+            Command::Log(_)
+                | Command::Show(..)
+                | Command::Diff(..)
+                | Command::Branch(None, _)
+                | Command::Stash(StashCommand::List | StashCommand::Show { .. })
+        )
+    }
+
+    // Commands that can run outside of a repository
+    pub fn needs_repository(&self) -> bool {
+        !matches!(
+            self,
+            Command::Help(_)
+                | Command::Version
+                | Command::Init(..)
+                | Command::Diff(DiffTarget::NoIndex(..), _)
+        )
+    }
+
+    // Commands that read or write the worktree, and so cannot run in a bare
+    // repository
+    pub fn needs_worktree(&self) -> bool {
+        matches!(
+            self,
+            Command::Commit(_)
+                | Command::Checkout(_)
+                | Command::ReadTree(_)
+                | Command::WriteTree
+                | Command::Status(_)
+                | Command::Reset(_)
+                | Command::Diff(DiffTarget::WorkingTree(_) | DiffTarget::Cached(_), _)
+                | Command::Merge(..)
+                | Command::Rebase(..)
+                | Command::RebaseContinue
+                | Command::RebaseSkip
+                | Command::RebaseAbort
+                | Command::CherryPick(..)
+                | Command::Revert(..)
+                | Command::SequencerContinue
+                | Command::SequencerAbort
+                | Command::Stash(
+                    StashCommand::Push { .. }
+                        | StashCommand::Apply(_)
+                        | StashCommand::Pop(_)
+                        | StashCommand::Branch(..)
+                )
+        )
+    }
+
+    // Commands that change the worktree, and hold its lock while they run.
+    // Those that only read it need not wait for them.
+    pub fn changes_worktree(&self) -> bool {
+        self.needs_worktree()
+            && !matches!(
+                self,
+                Command::WriteTree | Command::Status(_) | Command::Diff(..)
+            )
+    }
+
+    // The color.<name> config that applies to the command, before color.ui
+    pub fn color_config(&self) -> Option<&'static str> {
+        match self {
+            Command::Log(_) | Command::Show(..) | Command::Diff(..) | Command::Stash(_) => {
+                Some("color.diff")
             }
-            "get-tree" => {
-                if args.len() < 2 {
-                    return Command::Unknown("No tree hash provided for get-tree".to_string());
-                }
-                Command::GetTree(args[1].clone())
+            Command::Status(_) => Some("color.status"),
+            Command::Branch(..) => Some("color.branch"),
+            _ => None,
+        }
+    }
+
+    pub fn from_args(args: &[String]) -> Result<Command, UsageError> {
+        let Some((name, rest)) = args.split_first() else {
+            return Err(UsageError {
+                message: "no command given".to_string(),
+                usage: Some(general_help()),
+            });
+        };
+        match name.as_str() {
+            "-h" | "--help" => return Ok(Command::Help(None)),
+            "--version" => return Ok(Command::Version),
+            _ => {}
+        }
+
+        let spec = command_spec(name).ok_or_else(|| {
+            UsageError::new(format!("'{}' is not a bgit command, see 'bgit help'", name))
+        })?;
+        // -h and --help anywhere before "--" show the help of the command
+        if rest
+            .iter()
+            .take_while(|arg| *arg != "--")
+            .any(|arg| arg == "-h" || arg == "--help")
+        {
+            return Ok(Command::Help(Some(spec.name)));
+        }
+        if spec.name == "stash" {
+            return Self::parse_stash(rest);
+        }
+
+        let matches = spec.parse(rest)?;
+        // The number of positional arguments was checked against the spec
+        let arg = |index: usize| matches.args[index].clone();
+
+        let command = match spec.name {
+            "help" => match matches.args.first() {
+                Some(name) => Command::Help(Some(
+                    command_spec(name)
+                        .ok_or_else(|| UsageError::new(format!("no help for '{}'", name)))?
+                        .name,
+                )),
+                None => Command::Help(None),
+            },
+            "version" => Command::Version,
+            "init" => Command::Init(
+                matches.args.first().cloned(),
+                InitOptions {
+                    bare: matches.has("bare"),
+                    initial_branch: matches.value("initial-branch").map(str::to_string),
+                    object_format: match matches.value("object-format") {
+                        Some(name) => HashAlgorithm::from_name(name).ok_or_else(|| {
+                            spec.error(format!("unknown object format '{}'", name))
+                        })?,
+                        None => HashAlgorithm::default(),
+                    },
+                },
+            ),
+            "hash-object" => Command::HashObject(arg(0)),
+            "cat-file" => Self::parse_cat_file(spec, &matches)?,
+            "write-tree" => Command::WriteTree,
+            "read-tree" => Command::ReadTree(arg(0)),
+            "get-tree" => Command::GetTree(arg(0)),
+            "commit" => match (matches.value("message"), matches.args.first()) {
+                (Some(message), None) => Command::Commit(message.to_string()),
+                (None, Some(message)) => Command::Commit(message.clone()),
+                (Some(_), Some(_)) => return Err(spec.error("the message was given twice")),
+                (None, None) => return Err(spec.error("missing <message>")),
+            },
+            "log" => Self::parse_log(spec, matches)?,
+            "checkout" => Command::Checkout(arg(0)),
+            "tag" => Command::Tag(arg(0), arg(1)),
+            "iter-refs" => Command::IterRefs(Self::parse_output_format(spec, &matches)?),
+            "visualize" => Command::Visualize,
+            "branch" => Command::Branch(
+                matches.args.first().cloned(),
+                Self::parse_output_format(spec, &matches)?,
+            ),
+            "status" => Self::parse_status(spec, &matches)?,
+            "reset" => Command::Reset(arg(0)),
+            "show" => {
+                let format = Self::parse_diff_format(&matches).unwrap_or(DiffFormat::Patch);
+                Command::Show(arg(0), format, Self::parse_output_format(spec, &matches)?)
             }
-            "commit" => {
-                if args.len() < 2 {
-                    return Command::Unknown("No commit message provided for commit".to_string());
+            "diff" => Self::parse_diff(spec, matches)?,
+            "merge" => Self::parse_merge(spec, &matches)?,
+            "rebase" => Self::parse_rebase(spec, &matches)?,
+            "config" => Command::Config(arg(0), matches.args.get(1).cloned()),
+            "cherry-pick" => Self::parse_sequencer(spec, matches, Command::CherryPick)?,
+            "revert" => Self::parse_sequencer(spec, matches, Command::Revert)?,
+            "fsck" => Command::Fsck(FsckOptions {
+                connectivity_only: matches.has("connectivity-only"),
+                lost_found: matches.has("lost-found"),
+            }),
+            "gc" | "prune" => Command::Gc(GcOptions {
+                dry_run: matches.has("dry-run"),
+                expire: matches
+                    .value(if spec.name == "gc" { "prune" } else { "expire" })
+                    .map(str::to_string),
+            }),
+            name => unreachable!("no parser for {}", name),
+        };
+        Ok(command)
+    }
+
+    fn parse_stash(args: &[String]) -> Result<Command, UsageError> {
+        // push is the default, also when only its options are given
+        let (name, rest) = match args.split_first() {
+            Some((name, rest)) if !name.starts_with('-') => (name.as_str(), rest),
+            _ => ("push", args),
+        };
+        let spec = STASH_COMMANDS
+            .iter()
+            .find(|spec| spec.name.strip_prefix("stash ") == Some(name))
+            .ok_or_else(|| UsageError {
+                message: format!("unknown stash subcommand: {}", name),
+                usage: command_spec("stash").map(CommandSpec::usage),
+            })?;
+        let matches = spec.parse(rest)?;
+        let stash = |index: usize| matches.args.get(index).cloned();
+
+        let command = match name {
+            "push" => StashCommand::Push {
+                message: matches.value("message").map(str::to_string),
+                paths: matches.args.iter().chain(&matches.paths).cloned().collect(),
+            },
+            "list" => StashCommand::List,
+            "show" => StashCommand::Show {
+                stash: stash(0),
+                patch: matches.has("patch"),
+            },
+            "apply" => StashCommand::Apply(stash(0)),
+            "pop" => StashCommand::Pop(stash(0)),
+            "drop" => StashCommand::Drop(stash(0)),
+            "branch" => StashCommand::Branch(matches.args[0].clone(), stash(1)),
+            name => unreachable!("no parser for stash {}", name),
+        };
+        Ok(Command::Stash(command))
+    }
+
+    fn parse_log(spec: &CommandSpec, matches: Matches) -> Result<Command, UsageError> {
+        let options = LogOptions {
+            diff: Self::parse_diff_format(&matches),
+            format: Self::parse_output_format(spec, &matches)?,
+            ..Default::default()
+        };
+        let mut paths: Vec<String> = matches.args.into_iter().chain(matches.paths).collect();
+        let follow = matches.options.iter().any(|(key, _)| *key == "follow");
+        match (follow, paths.len()) {
+            (_, 0) if follow => Err(spec.error("--follow requires a path")),
+            (true, 1) => Ok(Command::Log(LogOptions {
+                follow: paths.pop(),
+                ..options
+            })),
+            (true, _) => Err(spec.error("--follow takes a single path")),
+            (false, 0) => Ok(Command::Log(options)),
+            (false, _) => Err(spec.error("paths can only be given with --follow")),
+        }
+    }
+
+    fn parse_diff(spec: &CommandSpec, matches: Matches) -> Result<Command, UsageError> {
+        let mut options = DiffOptions {
+            format: Self::parse_diff_format(&matches).unwrap_or(DiffFormat::Patch),
+            output: Self::parse_output_format(spec, &matches)?,
+            paths: matches.paths,
+            ..Default::default()
+        };
+        let mut cached = false;
+        let mut no_index = false;
+
+        for (key, value) in &matches.options {
+            match *key {
+                "find-renames" => {
+                    options.rename_threshold =
+                        Some(Self::parse_rename_threshold(spec, value.as_deref())?)
                 }
-                Command::Commit(args[1].clone())
-            }
-            "log" => Command::Log,
-            "checkout" => {
-                if args.len() < 2 {
-                    return Command::Unknown("No commit hash provided for checkout".to_string());
+                "no-renames" => options.rename_threshold = None,
+                "find-copies" => options.find_copies = true,
+                "binary" => options.binary = true,
+                "ignore-all-space" => options.whitespace.ignore_all_space = true,
+                "ignore-space-change" => options.whitespace.ignore_space_change = true,
+                "ignore-blank-lines" => options.whitespace.ignore_blank_lines = true,
+                "ignore-cr-at-eol" => options.whitespace.ignore_cr_at_eol = true,
+                "word-diff" => {
+                    options.word_diff = Some(match value.as_deref() {
+                        None | Some("plain") => WordDiffMode::Plain,
+                        Some("color") => WordDiffMode::Color,
+                        Some("porcelain") => WordDiffMode::Porcelain,
+                        Some(mode) => {
+                            return Err(spec.error(format!("unknown word diff mode: {}", mode)));
+                        }
+                    })
                 }
-                Command::Checkout(args[1].clone())
-            }
-            "tag" => {
-                if args.len() < 3 {
-                    return Command::Unknown("No commit hash provided for tag".to_string());
+                "color-words" => options.word_diff = Some(WordDiffMode::Color),
+                "word-diff-regex" => {
+                    options.word_regex = value.clone();
+                    // Like git, a word regex turns on word diff
+                    options.word_diff.get_or_insert(WordDiffMode::Plain);
                 }
-                Command::Tag(args[1].clone(), args[2].clone())
-            }
-            "iter-refs" => {
-                if args.len() > 1 {
-                    return Command::Unknown("iter-refs does not take any arguments".to_string());
+                "cached" | "staged" => cached = true,
+                "no-index" => no_index = true,
+                "exit-code" => options.exit_code = true,
+                "quiet" => {
+                    options.quiet = true;
+                    options.exit_code = true;
                 }
-                Command::IterRefs
+                _ => {}
             }
-            "visualize" => Command::Visualize,
-            "branch" => match args.len() {
-                1 => Command::Branch(None),
-                2 => Command::Branch(Some(args[1].clone())),
-                _ => Command::Unknown("Invalid number of arguments for branch".to_string()),
-            },
-            "status" => Command::Status,
-            "reset" => {
-                if args.len() < 2 {
-                    return Command::Unknown("No commit hash provided for reset".to_string());
-                }
-                Command::Reset(args[1].clone())
+        }
+
+        // "a..b" is the same as "a b", a missing side meaning HEAD
+        let mut revisions = matches.args;
+        if !no_index
+            && let [range] = revisions.as_slice()
+            && let Some((old, new)) = range.split_once("..")
+        {
+            let or_head = |rev: &str| if rev.is_empty() { "HEAD" } else { rev }.to_string();
+            revisions = vec![or_head(old), or_head(new)];
+        }
+
+        let target = match (no_index, cached, revisions.as_slice()) {
+            (true, false, [from, to]) if options.paths.is_empty() => {
+                DiffTarget::NoIndex(from.clone(), to.clone())
             }
-            "show" => {
-                if args.len() < 2 {
-                    return Command::Unknown("No commit hash provided for show".to_string());
-                }
-                Command::Show(args[1].clone())
+            (true, _, _) => return Err(spec.error("--no-index takes exactly two paths")),
+            (false, true, []) => DiffTarget::Cached(None),
+            (false, true, [rev]) => DiffTarget::Cached(Some(rev.clone())),
+            (false, true, _) => return Err(spec.error("--cached takes a single commit")),
+            (false, false, []) => DiffTarget::WorkingTree(None),
+            (false, false, [rev]) => DiffTarget::WorkingTree(Some(rev.clone())),
+            (false, false, [old, new]) => DiffTarget::Revisions(old.clone(), new.clone()),
+            (false, false, _) => unreachable!("at most two revisions"),
+        };
+        Ok(Command::Diff(target, options))
+    }
+
+    fn parse_status(spec: &CommandSpec, matches: &Matches) -> Result<Command, UsageError> {
+        let porcelain = match matches.options.iter().find(|(key, _)| *key == "porcelain") {
+            Some((_, Some(version))) => {
+                Some(PorcelainVersion::parse(version).map_err(|e| spec.error(e))?)
             }
-            "diff" => {
-                if args.len() > 1 {
-                    return Command::Unknown("diff does not take any arguments".to_string());
-                }
-                Command::Diff
+            Some((_, None)) => Some(PorcelainVersion::V1),
+            None => None,
+        };
+        let nul_terminated = matches.has("z");
+        Ok(Command::Status(StatusOptions {
+            // Like git, -z alone means the v1 porcelain format
+            porcelain: porcelain.or(nul_terminated.then_some(PorcelainVersion::V1)),
+            nul_terminated,
+            branch: matches.has("branch"),
+        }))
+    }
+
+    // --format <text|json>
+    fn parse_output_format(
+        spec: &CommandSpec,
+        matches: &Matches,
+    ) -> Result<OutputFormat, UsageError> {
+        match matches.value("format") {
+            Some(format) => OutputFormat::parse(format).map_err(|e| spec.error(e)),
+            None => Ok(OutputFormat::Text),
+        }
+    }
+
+    fn parse_merge(spec: &CommandSpec, matches: &Matches) -> Result<Command, UsageError> {
+        let mut options = MergeOptions::default();
+        for (key, value) in &matches.options {
+            if *key == "strategy-option" {
+                Self::parse_whitespace_option(
+                    value.as_deref().unwrap_or(""),
+                    &mut options.whitespace,
+                )
+                .map_err(|e| spec.error(e))?;
             }
-            "merge" => {
-                if args.len() < 2 {
-                    return Command::Unknown("No branch name provided for merge".to_string());
+        }
+        Ok(Command::Merge(matches.args[0].clone(), options))
+    }
+
+    // Merge strategy options (-X) about whitespace
+    fn parse_whitespace_option(
+        value: &str,
+        whitespace: &mut WhitespaceOptions,
+    ) -> Result<(), String> {
+        match value {
+            "ignore-space-change" => whitespace.ignore_space_change = true,
+            "ignore-all-space" => whitespace.ignore_all_space = true,
+            "ignore-cr-at-eol" => whitespace.ignore_cr_at_eol = true,
+            _ => return Err(format!("Unknown merge strategy option: {}", value)),
+        }
+        Ok(())
+    }
+
+    fn parse_cat_file(spec: &CommandSpec, matches: &Matches) -> Result<Command, UsageError> {
+        let modes: Vec<CatFileMode> = matches
+            .options
+            .iter()
+            .map(|(key, _)| match *key {
+                "t" => CatFileMode::Type,
+                "s" => CatFileMode::Size,
+                "e" => CatFileMode::Exists,
+                _ => CatFileMode::Pretty,
+            })
+            .collect();
+        let mode = match modes[..] {
+            [] => CatFileMode::Raw,
+            [mode] => mode,
+            _ => return Err(spec.error("-t, -s, -e and -p cannot be combined")),
+        };
+        Ok(Command::CatFile(matches.args[0].clone(), mode))
+    }
+
+    fn parse_rebase(spec: &CommandSpec, matches: &Matches) -> Result<Command, UsageError> {
+        if matches.has("continue") {
+            return Ok(Command::RebaseContinue);
+        }
+        if matches.has("skip") {
+            return Ok(Command::RebaseSkip);
+        }
+        if matches.has("abort") {
+            return Ok(Command::RebaseAbort);
+        }
+
+        let options = RebaseOptions {
+            interactive: matches.has("interactive"),
+            autosquash: matches.has("autosquash"),
+            onto: matches.value("onto").map(str::to_string),
+        };
+        match matches.args.first() {
+            Some(upstream) => Ok(Command::Rebase(upstream.clone(), options)),
+            None => Err(spec.error("missing <upstream>")),
+        }
+    }
+
+    // The last output format flag shared by diff, show and log
+    fn parse_diff_format(matches: &Matches) -> Option<DiffFormat> {
+        matches
+            .options
+            .iter()
+            .rev()
+            .find_map(|(key, _)| match *key {
+                "patch" => Some(DiffFormat::Patch),
+                "stat" => Some(DiffFormat::Stat),
+                "numstat" => Some(DiffFormat::NumStat),
+                "shortstat" => Some(DiffFormat::ShortStat),
+                "name-only" => Some(DiffFormat::NameOnly),
+                "name-status" => Some(DiffFormat::NameStatus),
+                _ => None,
+            })
+    }
+
+    // The value of -M<n>, -M<n>% or --find-renames=<n>
+    fn parse_rename_threshold(spec: &CommandSpec, value: Option<&str>) -> Result<u32, UsageError> {
+        let Some(value) = value else {
+            return Ok(DEFAULT_RENAME_THRESHOLD);
+        };
+        match value.trim_end_matches('%').parse::<u32>() {
+            Ok(threshold) if threshold <= 100 => Ok(threshold),
+            _ => Err(spec.error(format!("invalid rename threshold: {}", value))),
+        }
+    }
+
+    // Shared argument parsing for cherry-pick and revert
+    fn parse_sequencer(
+        spec: &CommandSpec,
+        matches: Matches,
+        command: fn(Vec<String>, SequencerOptions) -> Command,
+    ) -> Result<Command, UsageError> {
+        if matches.has("continue") {
+            return Ok(Command::SequencerContinue);
+        }
+        if matches.has("abort") {
+            return Ok(Command::SequencerAbort);
+        }
+
+        let mainline = match matches.value("mainline") {
+            Some(number) => Some(
+                number
+                    .parse::<usize>()
+                    .map_err(|_| spec.error(format!("invalid parent number: {}", number)))?,
+            ),
+            None => None,
+        };
+        let options = SequencerOptions {
+            mainline,
+            no_commit: matches.has("no-commit"),
+            record_origin: matches.has("x"),
+        };
+        if matches.args.is_empty() {
+            return Err(spec.error("missing <commit>"));
+        }
+        Ok(command(matches.args, options))
+    }
+}
+
+// Options every command accepts
+#[derive(Debug, Clone, Default)]
+pub struct GlobalOptions {
+    // --color[=<when>] and --no-color, None leaves it to the config
+    pub color: Option<ColorMode>,
+    // --no-pager
+    pub no_pager: bool,
+    // -C <path>, before the command: run as if started in these
+    // directories, each relative to the previous one
+    pub directories: Vec<String>,
+    // --break-stale-locks: remove the lock files left by a bgit process
+    // that was killed
+    pub break_stale_locks: bool,
+}
+
+impl GlobalOptions {
+    // Take the global options out of `args`. They come before the command:
+    // everything from the command on is its own, also when it looks like a
+    // global option, such as the message in `commit -m --no-pager`.
+    pub fn from_args(args: &[String]) -> Result<(GlobalOptions, Vec<String>), String> {
+        let mut options = GlobalOptions::default();
+
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "-C" => match iter.next() {
+                    Some(path) => options.directories.push(path.clone()),
+                    None => return Err("-C requires a path".to_string()),
+                },
+                "--color" => options.color = Some(ColorMode::Always),
+                "--no-color" => options.color = Some(ColorMode::Never),
+                "--no-pager" => options.no_pager = true,
+                "--break-stale-locks" => options.break_stale_locks = true,
+                flag if flag.starts_with("--color=") => {
+                    options.color = Some(ColorMode::parse(&flag["--color=".len()..])?);
                 }
-                Command::Merge(args[1].clone())
-            }
-            "rebase" => {
-                if args.len() < 2 {
-                    return Command::Unknown(
-                        "No target branch/commit provided for rebase".to_string(),
-                    );
+                _ => {
+                    let rest = std::iter::once(arg).chain(iter).cloned().collect();
+                    return Ok((options, rest));
                 }
-                Command::Rebase(args[1].clone())
             }
-            cmd => Command::Unknown(format!("Unknown command: {}", cmd)),
         }
+
+        Ok((options, Vec::new()))
     }
 }
 
-pub fn parse_args() -> Command {
+pub fn parse_args() -> Result<(GlobalOptions, Command), UsageError> {
     let args: Vec<String> = env::args().skip(1).collect();
-    Command::from_args(&args)
+    let (options, args) = GlobalOptions::from_args(&args).map_err(UsageError::new)?;
+    Ok((options, Command::from_args(&args)?))
 }
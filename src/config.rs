@@ -0,0 +1,154 @@
+use crate::error::Error;
+use crate::repository::Repository;
+use std::fs;
+use std::path::Path;
+
+// Name of the repository configuration file inside the gitdir
+pub const CONFIG_FILE: &str = "config";
+
+// Minimal git-style configuration file:
+//
+//   [core]
+//       editor = vim
+//
+// Keys are addressed as "section.key" (or "section.subsection.key")
+#[derive(Debug, Default, Clone)]
+pub struct Config {
+    // (section, key, value) in file order
+    entries: Vec<(String, String, String)>,
+}
+
+impl Config {
+    pub fn parse(content: &str) -> Result<Config, Error> {
+        let mut entries = Vec::new();
+        let mut section: Option<String> = None;
+
+        for (number, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+
+            if let Some(header) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                // [section] or [section "subsection"]
+                let name = match header.split_once(' ') {
+                    Some((name, sub)) => format!("{}.{}", name, sub.trim().trim_matches('"')),
+                    None => header.to_string(),
+                };
+                section = Some(name.to_lowercase());
+                continue;
+            }
+
+            let section = section.clone().ok_or_else(|| {
+                Error::Corrupt(format!(
+                    "Invalid config line {}: key outside section",
+                    number + 1
+                ))
+            })?;
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                // A bare key is a boolean set to true
+                None => (line, "true"),
+            };
+            entries.push((section, key.to_lowercase(), value.to_string()));
+        }
+
+        Ok(Config { entries })
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        let (section, key) = Self::split_name(name)?;
+        // The last occurrence wins, like git
+        self.entries
+            .iter()
+            .rev()
+            .find(|(s, k, _)| *s == section && *k == key)
+            .map(|(_, _, value)| value.as_str())
+    }
+
+    pub fn get_bool(&self, name: &str) -> Option<bool> {
+        match self.get(name)?.to_lowercase().as_str() {
+            "true" | "yes" | "on" | "1" => Some(true),
+            "false" | "no" | "off" | "0" => Some(false),
+            _ => None,
+        }
+    }
+
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), Error> {
+        let (section, key) = Self::split_name(name)
+            .ok_or_else(|| Error::Other(format!("Invalid config key: {}", name)))?;
+
+        match self
+            .entries
+            .iter_mut()
+            .rev()
+            .find(|(s, k, _)| *s == section && *k == key)
+        {
+            Some(entry) => entry.2 = value.to_string(),
+            None => {
+                // Keep keys of a section together
+                let position = self
+                    .entries
+                    .iter()
+                    .rposition(|(s, _, _)| *s == section)
+                    .map(|p| p + 1)
+                    .unwrap_or(self.entries.len());
+                self.entries
+                    .insert(position, (section, key, value.to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn to_string_pretty(&self) -> String {
+        let mut output = String::new();
+        let mut current: Option<&str> = None;
+
+        for (section, key, value) in &self.entries {
+            if current != Some(section.as_str()) {
+                match section.split_once('.') {
+                    Some((name, sub)) => output.push_str(&format!("[{} \"{}\"]\n", name, sub)),
+                    None => output.push_str(&format!("[{}]\n", section)),
+                }
+                current = Some(section);
+            }
+            output.push_str(&format!("\t{} = {}\n", key, value));
+        }
+
+        output
+    }
+
+    // "core.editor" -> ("core", "editor"), "branch.main.remote" -> ("branch.main", "remote")
+    fn split_name(name: &str) -> Option<(String, String)> {
+        let (section, key) = name.rsplit_once('.')?;
+        if section.is_empty() || key.is_empty() {
+            return None;
+        }
+        Some((section.to_lowercase(), key.to_lowercase()))
+    }
+}
+
+impl Repository {
+    pub fn config(&self) -> Result<Config, Error> {
+        let path = self.git_path(CONFIG_FILE)?;
+        if !Path::new(&path).exists() {
+            return Ok(Config::default());
+        }
+        let content =
+            fs::read_to_string(&path).map_err(|e| Error::io("Failed to read config", e))?;
+        Config::parse(&content)
+    }
+
+    pub fn get_config(&self, name: &str) -> Option<String> {
+        self.config()
+            .ok()
+            .and_then(|config| config.get(name).map(|value| value.to_string()))
+    }
+
+    pub fn set_config(&self, name: &str, value: &str) -> Result<(), Error> {
+        let mut config = self.config()?;
+        config.set(name, value)?;
+        fs::write(self.git_path(CONFIG_FILE)?, config.to_string_pretty())
+            .map_err(|e| Error::io("Failed to write config", e))
+    }
+}
@@ -0,0 +1,164 @@
+use crate::differ::Differ;
+use crate::error::Error;
+
+// Labels of the conflict markers, for HEAD's side, the base and the side
+// being merged
+pub const LABELS: [&str; 3] = ["HEAD", "BASE", "MERGE_HEAD"];
+
+// A change from the base to one side: lines `base.0..=base.1` of the base
+// became `side.0..=side.1`, counted from 1. An empty range after line n is
+// (n + 1, n).
+#[derive(Debug, Clone, Copy)]
+struct Hunk {
+    base: (usize, usize),
+    side: (usize, usize),
+}
+
+// Merge `mine` and `yours`, both changed from `older`, the way `diff3 -m`
+// does: changes of one side are taken, and changes of both sides that
+// overlap or touch are a conflict, even when they are the same. Returns the
+// merged content and whether there was a conflict.
+pub fn merge(mine: &[u8], older: &[u8], yours: &[u8]) -> Result<(Vec<u8>, bool), Error> {
+    let threads = [hunks(mine, older)?, hunks(yours, older)?];
+    let mine_lines = lines(mine);
+    let older_lines = lines(older);
+    let yours_lines = lines(yours);
+
+    let mut merged = Vec::new();
+    let mut conflict = false;
+    // The next hunk of each side, and how far its lines are from the
+    // base's after the last block
+    let mut next = [0, 0];
+    let mut offset: [isize; 2] = [0, 0];
+    // The next line of `mine` to copy
+    let mut mine_next = 1;
+
+    while next[0] < threads[0].len() || next[1] < threads[1].len() {
+        // A block starts with the first hunk in the base and takes the
+        // hunks of the other side that overlap or touch it, until neither
+        // side has one that does
+        let first = match (threads[0].get(next[0]), threads[1].get(next[1])) {
+            (Some(a), Some(b)) if a.base.0 > b.base.0 => 1,
+            (Some(_), _) => 0,
+            _ => 1,
+        };
+        let mut using: [Option<(usize, usize)>; 2] = [None, None];
+        using[first] = Some((next[first], next[first]));
+        let low = threads[first][next[first]].base.0;
+        let mut high = threads[first][next[first]].base.1;
+        let mut high_thread = first;
+        next[first] += 1;
+
+        loop {
+            let other = 1 - high_thread;
+            match threads[other].get(next[other]) {
+                Some(hunk) if hunk.base.0 <= high + 1 => {
+                    using[other] = Some((using[other].map_or(next[other], |u| u.0), next[other]));
+                    next[other] += 1;
+                    if hunk.base.1 > high {
+                        high = hunk.base.1;
+                        high_thread = other;
+                    }
+                }
+                _ => break,
+            }
+        }
+
+        // The lines of each side in the block, 1-based and inclusive
+        let mut ranges = [(0, 0); 2];
+        for side in 0..2 {
+            let (side_low, side_high) = match using[side] {
+                Some((first, last)) => {
+                    let (first, last) = (threads[side][first], threads[side][last]);
+                    (
+                        first.side.0 as isize - first.base.0 as isize + low as isize,
+                        last.side.1 as isize - last.base.1 as isize + high as isize,
+                    )
+                }
+                None => (low as isize + offset[side], high as isize + offset[side]),
+            };
+            offset[side] = side_high - high as isize;
+            ranges[side] = (side_low as usize, side_high as usize);
+        }
+        let ours = &mine_lines[ranges[0].0 - 1..ranges[0].1];
+        let theirs = &yours_lines[ranges[1].0 - 1..ranges[1].1];
+        let base = &older_lines[low - 1..high];
+
+        // Only our side changed: its lines are copied with the rest
+        if using[1].is_none() {
+            continue;
+        }
+
+        merged.extend(mine_lines[mine_next - 1..ranges[0].0 - 1].concat());
+        if using[0].is_none() {
+            merged.extend(theirs.concat());
+        } else {
+            conflict = true;
+            if ours != theirs {
+                merged.extend(format!("<<<<<<< {}\n", LABELS[0]).as_bytes());
+                merged.extend(ours.concat());
+                merged.extend(format!("||||||| {}\n", LABELS[1]).as_bytes());
+            } else {
+                merged.extend(format!("<<<<<<< {}\n", LABELS[1]).as_bytes());
+            }
+            merged.extend(base.concat());
+            merged.extend(b"=======\n");
+            merged.extend(theirs.concat());
+            merged.extend(format!(">>>>>>> {}\n", LABELS[2]).as_bytes());
+        }
+        mine_next = ranges[0].1 + 1;
+    }
+    merged.extend(mine_lines[mine_next - 1..].concat());
+
+    Ok((merged, conflict))
+}
+
+fn lines(content: &[u8]) -> Vec<&[u8]> {
+    content.split_inclusive(|&b| b == b'\n').collect()
+}
+
+// The changes from `base` to `side`, read from `diff side base` as diff3
+// does
+fn hunks(side: &[u8], base: &[u8]) -> Result<Vec<Hunk>, Error> {
+    let output = Differ::diff_contents(&["-a", "--horizon-lines=100", "--"], side, base)?;
+    if !output.status.success() && output.status.code() != Some(1) {
+        return Err(Error::Other(format!(
+            "diff command failed with status {:?}: {}",
+            output.status.code(),
+            String::from_utf8_lossy(&output.stderr)
+        )));
+    }
+
+    let corrupt = |line: &str| Error::Other(format!("Unexpected diff output: {}", line));
+    // "<first>[,<last>]"
+    let range = |field: &str| -> Option<(usize, usize)> {
+        match field.split_once(',') {
+            Some((first, last)) => Some((first.parse().ok()?, last.parse().ok()?)),
+            None => field.parse().ok().map(|line| (line, line)),
+        }
+    };
+
+    let mut hunks = Vec::new();
+    // Commands are "<side range>[acd]<base range>", the other lines are
+    // the changed lines themselves
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        if !line.starts_with(|c: char| c.is_ascii_digit()) {
+            continue;
+        }
+        let (at, command) = line
+            .char_indices()
+            .find(|(_, c)| matches!(c, 'a' | 'c' | 'd'))
+            .ok_or_else(|| corrupt(line))?;
+        let mut side = range(&line[..at]).ok_or_else(|| corrupt(line))?;
+        let mut base = range(&line[at + 1..]).ok_or_else(|| corrupt(line))?;
+        // Lines added to the base were removed from the side, after its
+        // line given, and the other way around
+        match command {
+            'a' => side = (side.0 + 1, side.0),
+            'd' => base = (base.0 + 1, base.0),
+            _ => {}
+        }
+        hunks.push(Hunk { base, side });
+    }
+    Ok(hunks)
+}
@@ -1,53 +1,262 @@
+use crate::binary;
+use crate::diff3;
+use crate::error::Error;
+use crate::oid::Oid;
+use crate::output::{Color, OutputFormat, paint};
+use crate::porcelain;
 use crate::repository::{HEAD, ObjectType, Repository};
+use regex::Regex;
 use std::collections::{HashMap, HashSet, VecDeque};
 use std::io::Write;
-use std::process::Command;
-use tempfile::NamedTempFile;
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
 
 // Define the result structure for compare_trees
 // (path, type, list_of_oids_across_compared_trees)
-pub type TreeComparisonResult = Vec<(String, ObjectType, Vec<Option<String>>)>;
+pub type TreeComparisonResult = Vec<(String, ObjectType, Vec<Option<Oid>>)>;
+
+// Result of merge_trees_with_conflicts
+// (path -> file content or directory marker, conflicted_paths)
+pub type MergedTree = (HashMap<String, Result<Vec<u8>, ()>>, Vec<String>);
+
+// Similarity (percent) used by -M when no threshold is given, like git
+pub const DEFAULT_RENAME_THRESHOLD: u32 = 50;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffOptions {
+    // Minimum similarity for a deleted and an added file to be shown as a
+    // rename, None turns rename detection off
+    pub rename_threshold: Option<u32>,
+    // Also pair added files with files kept in the old tree (-C)
+    pub find_copies: bool,
+    // Only compare these files or directories, everything when empty
+    pub paths: Vec<String>,
+    pub format: DiffFormat,
+    // Show changed binary files as base85 patches instead of a one-line note
+    pub binary: bool,
+    pub whitespace: WhitespaceOptions,
+    // Show changed words instead of changed lines
+    pub word_diff: Option<WordDiffMode>,
+    // What a word is for --word-diff, DEFAULT_WORD_REGEX when None
+    pub word_regex: Option<String>,
+    // --format=json, for --name-status only
+    pub output: OutputFormat,
+    // Exit with 1 when there are differences (--exit-code), --quiet also
+    // prints nothing
+    pub exit_code: bool,
+    pub quiet: bool,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        DiffOptions {
+            rename_threshold: Some(DEFAULT_RENAME_THRESHOLD),
+            find_copies: false,
+            paths: Vec::new(),
+            format: DiffFormat::Patch,
+            binary: false,
+            whitespace: WhitespaceOptions::default(),
+            word_diff: None,
+            word_regex: None,
+            output: OutputFormat::Text,
+            exit_code: false,
+            quiet: false,
+        }
+    }
+}
+
+// Whitespace differences that do not count as changes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WhitespaceOptions {
+    // -w: lines differing only in whitespace are equal
+    pub ignore_all_space: bool,
+    // -b: a run of whitespace equals any other, trailing whitespace is ignored
+    pub ignore_space_change: bool,
+    // Added or removed empty lines (diff only)
+    pub ignore_blank_lines: bool,
+    // A CR at the end of a line
+    pub ignore_cr_at_eol: bool,
+}
+
+impl WhitespaceOptions {
+    pub fn is_set(&self) -> bool {
+        *self != WhitespaceOptions::default()
+    }
+
+    // The matching flags of the diff command
+    fn diff_args(&self) -> Vec<&'static str> {
+        let mut args = Vec::new();
+        if self.ignore_all_space {
+            args.push("--ignore-all-space");
+        }
+        if self.ignore_space_change {
+            args.push("--ignore-space-change");
+        }
+        if self.ignore_blank_lines {
+            args.push("--ignore-blank-lines");
+        }
+        if self.ignore_cr_at_eol {
+            args.push("--strip-trailing-cr");
+        }
+        args
+    }
+}
+
+// --word-diff output: colored words, [-removed-]{+added+} markers, or one
+// token per line prefixed by ' ', '-' or '+' with '~' ending each line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WordDiffMode {
+    Color,
+    Plain,
+    Porcelain,
+}
+
+// Words are runs of non-whitespace characters, like git
+pub const DEFAULT_WORD_REGEX: &str = r"\S+";
+
+// How a diff is printed: the full patch or one of the summaries
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DiffFormat {
+    #[default]
+    Patch,
+    Stat,
+    NumStat,
+    ShortStat,
+    NameOnly,
+    NameStatus,
+}
+
+// Width of the +/- histogram of --stat for the most changed file
+const STAT_GRAPH_WIDTH: usize = 40;
+
+// Lines inserted and deleted in one changed file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileStat {
+    pub change: FileChange,
+    pub insertions: usize,
+    pub deletions: usize,
+    // Sizes in bytes before and after, for binary files which have no lines
+    pub binary_sizes: Option<(usize, usize)>,
+}
+
+// The changes between two trees as data, which `Differ::format_tree_diff`
+// renders
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeDiff {
+    // The trees compared, None for the empty tree
+    pub old_tree: Option<Oid>,
+    pub new_tree: Option<Oid>,
+    pub changes: Vec<FileChange>,
+    // The unified diff of their content, uncolored
+    pub patch: Vec<u8>,
+}
+
+// What `diff` compares
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffTarget {
+    // A commit (HEAD by default) against the worktree
+    WorkingTree(Option<String>),
+    // A commit (HEAD by default) against what the next commit would record
+    Cached(Option<String>),
+    // Two commits
+    Revisions(String, String),
+    // Two files, which do not need to be in a repository
+    NoIndex(String, String),
+}
+
+// Whether `path` is one of `specs` or inside one of them. No specs match
+// every path.
+pub fn matches_pathspec(path: &str, specs: &[String]) -> bool {
+    specs.is_empty()
+        || specs.iter().any(|spec| {
+            let spec = spec.trim_end_matches('/');
+            spec.is_empty()
+                || spec == "."
+                || path == spec
+                || path.starts_with(&format!("{}/", spec))
+        })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Added,
+    Deleted,
+    Modified,
+    Renamed,
+    Copied,
+}
+
+impl ChangeKind {
+    // Letter used by --name-status
+    pub fn letter(&self) -> char {
+        match self {
+            ChangeKind::Added => 'A',
+            ChangeKind::Deleted => 'D',
+            ChangeKind::Modified => 'M',
+            ChangeKind::Renamed => 'R',
+            ChangeKind::Copied => 'C',
+        }
+    }
+}
+
+// One file that differs between two trees. old_path and new_path are the
+// same unless the file was renamed or copied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileChange {
+    pub kind: ChangeKind,
+    pub old_path: String,
+    pub new_path: String,
+    pub old_oid: Option<Oid>,
+    pub new_oid: Option<Oid>,
+    // Percentage of content kept, for renames and copies
+    pub similarity: Option<u32>,
+}
+
+impl FileChange {
+    // The path to show for this change
+    pub fn path(&self) -> &str {
+        match self.kind {
+            ChangeKind::Deleted => &self.old_path,
+            _ => &self.new_path,
+        }
+    }
+}
 
 pub struct Differ<'a> {
     repo: &'a Repository,
+    options: DiffOptions,
 }
 
 impl<'a> Differ<'a> {
     pub fn new(repo: &'a Repository) -> Self {
-        Self { repo }
+        Self::with_options(repo, DiffOptions::default())
+    }
+
+    pub fn with_options(repo: &'a Repository, options: DiffOptions) -> Self {
+        Self { repo, options }
     }
 
-    // Refactored compare_trees using BFS
-    pub fn compare_trees(&self, trees: &[&str]) -> Result<TreeComparisonResult, String> {
+    // Refactored compare_trees using BFS. None stands for the empty tree.
+    pub fn compare_trees(&self, trees: &[Option<Oid>]) -> Result<TreeComparisonResult, Error> {
         let num_trees = trees.len();
-        // Store path -> (ObjectType, Vec<Option<String>>)
-        let mut entries: HashMap<String, (ObjectType, Vec<Option<String>>)> = HashMap::new();
+        // Store path -> (ObjectType, Vec<Option<Oid>>)
+        let mut entries: HashMap<String, (ObjectType, Vec<Option<Oid>>)> = HashMap::new();
         // Keep track of visited tree OIDs for each version to avoid redundant processing
-        let mut visited_trees: Vec<HashSet<String>> = vec![HashSet::new(); num_trees];
+        let mut visited_trees: Vec<HashSet<Oid>> = vec![HashSet::new(); num_trees];
         // Queue for BFS: (tree_index, tree_oid, path_prefix)
-        let mut queue: VecDeque<(usize, String, String)> = VecDeque::new();
+        let mut queue: VecDeque<(usize, Oid, String)> = VecDeque::new();
 
         // Initial population of the queue with root trees
-        for (i, &tree_oid) in trees.iter().enumerate() {
-            if !tree_oid.is_empty() {
-                queue.push_back((i, tree_oid.to_string(), "".to_string()));
-                visited_trees[i].insert(tree_oid.to_string());
+        for (i, tree_oid) in trees.iter().enumerate() {
+            if let Some(tree_oid) = *tree_oid {
+                queue.push_back((i, tree_oid, "".to_string()));
+                visited_trees[i].insert(tree_oid);
             }
         }
 
         while let Some((tree_index, current_tree_oid, prefix)) = queue.pop_front() {
             // Get data for the current tree OID
-            let tree_data = match self.repo.get_tree_data(&current_tree_oid) {
-                Ok(data) => data,
-                Err(e) => {
-                    // Handle case where tree OID might be invalid or unreadable
-                    eprintln!(
-                        "Warning: Could not read tree data for OID {} at index {}: {}. Skipping.",
-                        current_tree_oid, tree_index, e
-                    );
-                    continue; // Skip this tree and proceed
-                }
-            };
+            let tree_data = self.repo.get_tree_data(current_tree_oid)?;
 
             for (_, name, oid, obj_type) in tree_data {
                 let path = if prefix.is_empty() {
@@ -59,7 +268,7 @@ impl<'a> Differ<'a> {
                 // --- Entry Management ---
                 let (entry_type, oids) = entries
                     .entry(path.clone())
-                    .or_insert_with(|| (obj_type.clone(), vec![None; num_trees]));
+                    .or_insert_with(|| (obj_type, vec![None; num_trees]));
 
                 // Type conflict resolution (prefer Tree)
                 if *entry_type != obj_type && obj_type == ObjectType::Tree {
@@ -68,18 +277,18 @@ impl<'a> Differ<'a> {
 
                 // Update OID for the current tree index
                 if oids.len() > tree_index {
-                    oids[tree_index] = Some(oid.clone());
+                    oids[tree_index] = Some(oid);
                 } else {
-                    return Err(format!(
+                    return Err(Error::Other(format!(
                         "Logic error: OID vector index out of bounds for path {}",
                         path
-                    ));
+                    )));
                 }
                 // --- End Entry Management ---
 
                 // If it's a tree and not visited yet for this index, add to queue
-                if obj_type == ObjectType::Tree && visited_trees[tree_index].insert(oid.clone()) {
-                    queue.push_back((tree_index, oid.clone(), path.clone()));
+                if obj_type == ObjectType::Tree && visited_trees[tree_index].insert(oid) {
+                    queue.push_back((tree_index, oid, path.clone()));
                 }
             }
         }
@@ -94,128 +303,889 @@ impl<'a> Differ<'a> {
         Ok(result)
     }
 
-    pub fn diff_trees(&self, old_tree: &str, new_tree: &str) -> Result<Vec<u8>, String> {
+    pub fn diff_trees(
+        &self,
+        old_tree: Option<Oid>,
+        new_tree: Option<Oid>,
+    ) -> Result<Vec<u8>, Error> {
+        self.patch(
+            &self.changed_files(old_tree, new_tree)?,
+            self.options.word_diff,
+        )
+    }
+
+    // The changes between two trees and their patch, left to the caller to
+    // format
+    pub fn tree_diff(
+        &self,
+        old_tree: Option<Oid>,
+        new_tree: Option<Oid>,
+    ) -> Result<TreeDiff, Error> {
+        let changes = self.changed_files(old_tree, new_tree)?;
+        let patch = self.patch(&changes, None)?;
+        Ok(TreeDiff {
+            old_tree,
+            new_tree,
+            changes,
+            patch,
+        })
+    }
+
+    // The unified diffs of `changes`, each rewritten to show changed words
+    // when `word_diff` asks for it
+    fn patch(
+        &self,
+        changes: &[FileChange],
+        word_diff: Option<WordDiffMode>,
+    ) -> Result<Vec<u8>, Error> {
         let mut output = Vec::new();
-        // diff_trees now needs adapting if its caller relies on old compare_trees output format
-        // compare_trees now returns Vec<(String, ObjectType, Vec<Option<String>>)>
-        // We only want to diff blobs
+        let word_regex = Self::word_regex(&self.options)?;
+
+        for change in changes {
+            if let Some(similarity) = change.similarity {
+                let verb = match change.kind {
+                    ChangeKind::Copied => "copy",
+                    _ => "rename",
+                };
+                output.extend_from_slice(
+                    format!(
+                        "similarity index {}%\n{} from {}\n{} to {}\n",
+                        similarity, verb, change.old_path, verb, change.new_path
+                    )
+                    .as_bytes(),
+                );
+                // Identical content has no hunks to show
+                if change.old_oid == change.new_oid {
+                    continue;
+                }
+            }
+
+            let diff = self.diff_blobs(
+                change.old_oid,
+                change.new_oid,
+                &change.old_path,
+                &change.new_path,
+            )?;
+            match word_diff {
+                Some(mode) => output.extend_from_slice(&Self::word_diff(&diff, mode, &word_regex)),
+                None => output.extend_from_slice(&diff),
+            }
+        }
+
+        Ok(output)
+    }
+
+    fn word_regex(options: &DiffOptions) -> Result<Regex, Error> {
+        let pattern = options.word_regex.as_deref().unwrap_or(DEFAULT_WORD_REGEX);
+        Regex::new(pattern)
+            .map_err(|e| Error::Other(format!("Invalid word regex {}: {}", pattern, e)))
+    }
+
+    // Patches are colorized line by line, word diffs carry their own colors
+    pub fn format_patch(diff: &[u8], options: &DiffOptions) -> String {
+        match options.word_diff {
+            Some(_) => String::from_utf8_lossy(diff).into_owned(),
+            None => Self::colorize_diff(diff),
+        }
+    }
+
+    // Rewrite the hunks of a unified diff to show changed words: each block
+    // of removed lines and the added lines following it are compared word by
+    // word
+    fn word_diff(diff: &[u8], mode: WordDiffMode, word_regex: &Regex) -> Vec<u8> {
+        let text = String::from_utf8_lossy(diff);
+        let mut output = String::new();
+        let mut removed: Vec<&str> = Vec::new();
+        let mut added: Vec<&str> = Vec::new();
+        let mut in_hunk = false;
+
+        let flush = |output: &mut String, removed: &mut Vec<&str>, added: &mut Vec<&str>| {
+            if removed.is_empty() && added.is_empty() {
+                return;
+            }
+            let segments = Self::word_segments(&removed.join("\n"), &added.join("\n"), word_regex);
+            output.push_str(&Self::render_words(&segments, mode));
+            removed.clear();
+            added.clear();
+        };
+
+        for line in text.lines() {
+            if line.starts_with("@@") {
+                flush(&mut output, &mut removed, &mut added);
+                in_hunk = true;
+                output.push_str(line);
+                output.push('\n');
+            } else if !in_hunk {
+                // File headers
+                output.push_str(line);
+                output.push('\n');
+            } else if let Some(rest) = line.strip_prefix('-') {
+                removed.push(rest);
+            } else if let Some(rest) = line.strip_prefix('+') {
+                added.push(rest);
+            } else if let Some(rest) = line.strip_prefix(' ') {
+                flush(&mut output, &mut removed, &mut added);
+                output.push_str(&Self::render_words(&[(' ', rest.to_string())], mode));
+            }
+            // "\ No newline at end of file" has no word equivalent
+        }
+        flush(&mut output, &mut removed, &mut added);
+
+        output.into_bytes()
+    }
+
+    // Split `old` -> `new` into unchanged (' '), removed ('-') and added ('+')
+    // text, comparing the words matched by `word_regex`. The whitespace
+    // between words is taken from the new text.
+    pub fn word_segments(old: &str, new: &str, word_regex: &Regex) -> Vec<(char, String)> {
+        let words = |text: &str| -> Vec<(usize, usize)> {
+            word_regex
+                .find_iter(text)
+                .map(|m| (m.start(), m.end()))
+                .collect()
+        };
+        let (old_words, new_words) = (words(old), words(new));
+        let (n, m) = (old_words.len(), new_words.len());
+        let same = |i: usize, j: usize| {
+            old[old_words[i].0..old_words[i].1] == new[new_words[j].0..new_words[j].1]
+        };
+
+        // lengths[i][j]: longest common subsequence of old_words[i..], new_words[j..]
+        let mut lengths = vec![vec![0usize; m + 1]; n + 1];
+        for i in (0..n).rev() {
+            for j in (0..m).rev() {
+                lengths[i][j] = if same(i, j) {
+                    lengths[i + 1][j + 1] + 1
+                } else {
+                    lengths[i + 1][j].max(lengths[i][j + 1])
+                };
+            }
+        }
+
+        let mut segments: Vec<(char, String)> = Vec::new();
+        let mut push = |kind: char, text: &str| {
+            if text.is_empty() {
+                return;
+            }
+            match segments.last_mut() {
+                Some(last) if last.0 == kind => last.1.push_str(text),
+                _ => segments.push((kind, text.to_string())),
+            }
+        };
+
+        let (mut i, mut j) = (0, 0);
+        let (mut old_pos, mut new_pos) = (0, 0);
+        while i < n || j < m {
+            if i < n && j < m && same(i, j) {
+                push(' ', &new[new_pos..new_words[j].1]);
+                old_pos = old_words[i].1;
+                new_pos = new_words[j].1;
+                i += 1;
+                j += 1;
+                continue;
+            }
+
+            // A run of changed words up to the next common one
+            let (first_i, first_j) = (i, j);
+            while (i < n || j < m) && !(i < n && j < m && same(i, j)) {
+                if j == m || (i < n && lengths[i + 1][j] >= lengths[i][j + 1]) {
+                    i += 1;
+                } else {
+                    j += 1;
+                }
+            }
+
+            if j > first_j {
+                push(' ', &new[new_pos..new_words[first_j].0]);
+            } else {
+                push(' ', &old[old_pos..old_words[first_i].0]);
+            }
+            if i > first_i {
+                push('-', &old[old_words[first_i].0..old_words[i - 1].1]);
+                old_pos = old_words[i - 1].1;
+            }
+            if j > first_j {
+                push('+', &new[new_words[first_j].0..new_words[j - 1].1]);
+                new_pos = new_words[j - 1].1;
+            }
+        }
+        push(' ', &new[new_pos..]);
+
+        segments
+    }
+
+    // One block of word segments, ending with a newline
+    fn render_words(segments: &[(char, String)], mode: WordDiffMode) -> String {
+        let mut output = String::new();
+        for (kind, text) in segments {
+            match mode {
+                WordDiffMode::Plain => match kind {
+                    '-' => output.push_str(&format!("[-{}-]", text)),
+                    '+' => output.push_str(&format!("{{+{}+}}", text)),
+                    _ => output.push_str(text),
+                },
+                WordDiffMode::Color => match kind {
+                    '-' => output.push_str(&paint(Color::Red, text)),
+                    '+' => output.push_str(&paint(Color::Green, text)),
+                    _ => output.push_str(text),
+                },
+                WordDiffMode::Porcelain => {
+                    for (index, piece) in text.split('\n').enumerate() {
+                        if index > 0 {
+                            output.push_str("~\n");
+                        }
+                        if !piece.is_empty() {
+                            output.push_str(&format!("{}{}\n", kind, piece));
+                        }
+                    }
+                }
+            }
+        }
+        match mode {
+            WordDiffMode::Porcelain => output.push_str("~\n"),
+            _ => output.push('\n'),
+        }
+        output
+    }
+
+    // The diff between two trees in the format of the options, patches
+    // being colorized
+    pub fn format_diff(
+        &self,
+        old_tree: Option<Oid>,
+        new_tree: Option<Oid>,
+    ) -> Result<String, Error> {
+        if self.options.output == OutputFormat::Json {
+            if self.options.format != DiffFormat::NameStatus {
+                return Err(Error::Other(
+                    "--format=json requires --name-status".to_string(),
+                ));
+            }
+            let changes = self.changed_files(old_tree, new_tree)?;
+            return Ok(porcelain::changes_json(&changes).to_string());
+        }
+
+        match self.options.format {
+            DiffFormat::Patch => Ok(Self::format_patch(
+                &self.diff_trees(old_tree, new_tree)?,
+                &self.options,
+            )),
+            DiffFormat::NameOnly | DiffFormat::NameStatus => Ok(Self::format_names(
+                &self.changed_files(old_tree, new_tree)?,
+                self.options.format,
+            )),
+            format => Ok(Self::format_stats(
+                &self.file_stats(old_tree, new_tree)?,
+                format,
+            )),
+        }
+    }
+
+    // Render a `tree_diff` in the format of the options, colored when
+    // color is on
+    pub fn format_tree_diff(&self, diff: &TreeDiff) -> Result<String, Error> {
+        match self.options.format {
+            DiffFormat::Patch => {
+                let patch = match self.options.word_diff {
+                    Some(mode) => {
+                        Self::word_diff(&diff.patch, mode, &Self::word_regex(&self.options)?)
+                    }
+                    None => diff.patch.clone(),
+                };
+                Ok(Self::format_patch(&patch, &self.options))
+            }
+            DiffFormat::NameOnly | DiffFormat::NameStatus => {
+                Ok(Self::format_names(&diff.changes, self.options.format))
+            }
+            format => Ok(Self::format_stats(
+                &self.file_stats(diff.old_tree, diff.new_tree)?,
+                format,
+            )),
+        }
+    }
+
+    // Render --name-only or --name-status
+    fn format_names(changes: &[FileChange], format: DiffFormat) -> String {
+        changes
+            .iter()
+            .map(|change| match (format, change.similarity) {
+                (DiffFormat::NameOnly, _) => format!("{}\n", change.path()),
+                (_, Some(similarity)) => format!(
+                    "{}{:03}\t{}\t{}\n",
+                    change.kind.letter(),
+                    similarity,
+                    change.old_path,
+                    change.new_path
+                ),
+                (_, None) => format!("{}\t{}\n", change.kind.letter(), change.path()),
+            })
+            .collect()
+    }
+
+    // Count inserted and deleted lines of every changed file
+    pub fn file_stats(
+        &self,
+        old_tree: Option<Oid>,
+        new_tree: Option<Oid>,
+    ) -> Result<Vec<FileStat>, Error> {
+        let mut stats = Vec::new();
+        for change in self.changed_files(old_tree, new_tree)? {
+            let old_content = self.blob_content(change.old_oid)?;
+            let new_content = self.blob_content(change.new_oid)?;
+
+            let mut binary_sizes = None;
+            let (insertions, deletions) = if change.old_oid == change.new_oid {
+                (0, 0)
+            } else if self.is_binary(&change.new_path, &old_content, &new_content) {
+                binary_sizes = Some((old_content.len(), new_content.len()));
+                (0, 0)
+            } else {
+                Self::count_changes(&self.diff_blobs(
+                    change.old_oid,
+                    change.new_oid,
+                    &change.old_path,
+                    &change.new_path,
+                )?)
+            };
+            stats.push(FileStat {
+                change,
+                insertions,
+                deletions,
+                binary_sizes,
+            });
+        }
+        Ok(stats)
+    }
+
+    // Render --stat, --numstat or --shortstat
+    pub fn format_stats(stats: &[FileStat], format: DiffFormat) -> String {
+        let name = |stat: &FileStat| match stat.change.similarity {
+            Some(_) => format!("{} => {}", stat.change.old_path, stat.change.new_path),
+            None => stat.change.path().to_string(),
+        };
+
+        match format {
+            DiffFormat::NumStat => stats
+                .iter()
+                .map(|stat| match stat.binary_sizes {
+                    Some(_) => format!("-\t-\t{}\n", name(stat)),
+                    None => format!("{}\t{}\t{}\n", stat.insertions, stat.deletions, name(stat)),
+                })
+                .collect(),
+            DiffFormat::ShortStat => Self::format_shortstat(stats),
+            _ => {
+                if stats.is_empty() {
+                    return String::new();
+                }
+
+                let name_width = stats.iter().map(|stat| name(stat).len()).max().unwrap_or(0);
+                let most_changed = stats
+                    .iter()
+                    .map(|stat| stat.insertions + stat.deletions)
+                    .max()
+                    .unwrap_or(0);
+                let mut count_width = most_changed.to_string().len();
+                if stats.iter().any(|stat| stat.binary_sizes.is_some()) {
+                    count_width = count_width.max("Bin".len());
+                }
+                // Histograms only shrink when they would not fit
+                let scale = |count: usize| {
+                    if most_changed <= STAT_GRAPH_WIDTH || count == 0 {
+                        count
+                    } else {
+                        (count * STAT_GRAPH_WIDTH / most_changed).max(1)
+                    }
+                };
+
+                let mut output = String::new();
+                for stat in stats {
+                    if let Some((old_size, new_size)) = stat.binary_sizes {
+                        output.push_str(&format!(
+                            " {:<name_width$} | {:>count_width$} {} -> {} bytes\n",
+                            name(stat),
+                            "Bin",
+                            old_size,
+                            new_size
+                        ));
+                        continue;
+                    }
+                    let total = scale(stat.insertions + stat.deletions);
+                    let deletions = scale(stat.deletions).min(total);
+                    let line = format!(
+                        " {:<name_width$} | {:>count_width$} {}{}",
+                        name(stat),
+                        stat.insertions + stat.deletions,
+                        "+".repeat(total - deletions),
+                        "-".repeat(deletions),
+                    );
+                    output.push_str(line.trim_end());
+                    output.push('\n');
+                }
+                output.push_str(&Self::format_shortstat(stats));
+                output
+            }
+        }
+    }
+
+    // " 2 files changed, 3 insertions(+), 1 deletion(-)"
+    fn format_shortstat(stats: &[FileStat]) -> String {
+        if stats.is_empty() {
+            return String::new();
+        }
+        let plural = |count: usize, one: &str, many: &str| {
+            format!("{} {}", count, if count == 1 { one } else { many })
+        };
+        let insertions: usize = stats.iter().map(|stat| stat.insertions).sum();
+        let deletions: usize = stats.iter().map(|stat| stat.deletions).sum();
+
+        let mut parts = vec![plural(stats.len(), "file changed", "files changed")];
+        if insertions > 0 || deletions == 0 {
+            parts.push(plural(insertions, "insertion(+)", "insertions(+)"));
+        }
+        if deletions > 0 || insertions == 0 {
+            parts.push(plural(deletions, "deletion(-)", "deletions(-)"));
+        }
+        format!(" {}\n", parts.join(", "))
+    }
+
+    // Inserted and deleted lines of a unified diff, skipping its file headers
+    fn count_changes(diff: &[u8]) -> (usize, usize) {
+        let mut insertions = 0;
+        let mut deletions = 0;
+        for line in diff.split(|&b| b == b'\n').skip(2) {
+            match line.first() {
+                Some(b'+') => insertions += 1,
+                Some(b'-') => deletions += 1,
+                _ => {}
+            }
+        }
+        (insertions, deletions)
+    }
+
+    // Files that differ between two trees, sorted by path, with renames and
+    // copies paired up according to the options. None stands for the empty
+    // tree.
+    pub fn changed_files(
+        &self,
+        old_tree: Option<Oid>,
+        new_tree: Option<Oid>,
+    ) -> Result<Vec<FileChange>, Error> {
         let entries = self.compare_trees(&[old_tree, new_tree])?;
 
+        let mut changes = Vec::new();
+        let mut added = Vec::new();
+        let mut deleted = Vec::new();
+        // Every file of the old tree, candidate sources for copies
+        let mut old_files = Vec::new();
+
         for (path, obj_type, oids) in entries {
-            if obj_type == ObjectType::Blob
-                && oids.first().unwrap_or(&None) != oids.get(1).unwrap_or(&None)
+            if obj_type != ObjectType::Blob || !matches_pathspec(&path, &self.options.paths) {
+                continue;
+            }
+            let old_oid = oids.first().copied().flatten();
+            let new_oid = oids.get(1).copied().flatten();
+            if let Some(oid) = old_oid {
+                old_files.push((path.clone(), oid));
+            }
+
+            match (old_oid, new_oid) {
+                (Some(old), Some(new)) if old != new => changes.push(FileChange {
+                    kind: ChangeKind::Modified,
+                    old_path: path.clone(),
+                    new_path: path,
+                    old_oid: Some(old),
+                    new_oid: Some(new),
+                    similarity: None,
+                }),
+                (Some(old), None) => deleted.push((path, old)),
+                (None, Some(new)) => added.push((path, new)),
+                _ => {}
+            }
+        }
+
+        if let Some(threshold) = self.options.rename_threshold {
+            self.detect_renames(
+                threshold,
+                &mut added,
+                &mut deleted,
+                &old_files,
+                &mut changes,
+            )?;
+        }
+
+        changes.extend(deleted.into_iter().map(|(path, oid)| FileChange {
+            kind: ChangeKind::Deleted,
+            old_path: path.clone(),
+            new_path: path,
+            old_oid: Some(oid),
+            new_oid: None,
+            similarity: None,
+        }));
+        changes.extend(added.into_iter().map(|(path, oid)| FileChange {
+            kind: ChangeKind::Added,
+            old_path: path.clone(),
+            new_path: path,
+            old_oid: None,
+            new_oid: Some(oid),
+            similarity: None,
+        }));
+
+        changes.sort_by(|a, b| a.path().cmp(b.path()));
+        Ok(changes)
+    }
+
+    // Pair added files with deleted ones (renames) or, with find_copies, with
+    // any file of the old tree (copies). Identical blobs are paired first,
+    // then the most similar candidate at or above the threshold.
+    fn detect_renames(
+        &self,
+        threshold: u32,
+        added: &mut Vec<(String, Oid)>,
+        deleted: &mut Vec<(String, Oid)>,
+        old_files: &[(String, Oid)],
+        changes: &mut Vec<FileChange>,
+    ) -> Result<(), Error> {
+        let pair = |kind,
+                    (old_path, old_oid): (String, Oid),
+                    (new_path, new_oid): (String, Oid),
+                    similarity| FileChange {
+            kind,
+            old_path,
+            new_path,
+            old_oid: Some(old_oid),
+            new_oid: Some(new_oid),
+            similarity: Some(similarity),
+        };
+
+        // Exact matches
+        let mut remaining = Vec::new();
+        for (path, oid) in added.drain(..) {
+            if let Some(index) = deleted.iter().position(|(_, old)| *old == oid) {
+                changes.push(pair(
+                    ChangeKind::Renamed,
+                    deleted.remove(index),
+                    (path, oid),
+                    100,
+                ));
+            } else if let Some(source) = old_files
+                .iter()
+                .find(|(_, old)| self.options.find_copies && *old == oid)
             {
-                // Correctly get Option<&str> for diff_blobs
-                let oid1 = oids.first().unwrap_or(&None).as_deref();
-                let oid2 = oids.get(1).unwrap_or(&None).as_deref();
-                let diff = self.diff_blobs(oid1, oid2, &path)?;
-                output.extend_from_slice(&diff);
+                changes.push(pair(ChangeKind::Copied, source.clone(), (path, oid), 100));
+            } else {
+                remaining.push((path, oid));
             }
         }
 
-        Ok(output)
+        // Similar content
+        for (path, oid) in remaining {
+            let content = self.repo.read_object(oid, ObjectType::Blob)?;
+
+            let mut best: Option<(u32, ChangeKind, (String, Oid))> = None;
+            let candidates = deleted
+                .iter()
+                .map(|source| (ChangeKind::Renamed, source))
+                .chain(
+                    old_files
+                        .iter()
+                        .filter(|_| self.options.find_copies)
+                        .map(|source| (ChangeKind::Copied, source)),
+                );
+            for (kind, source) in candidates {
+                let score = Self::similarity(
+                    &self.repo.read_object(source.1, ObjectType::Blob)?,
+                    &content,
+                );
+                // Renames win ties over copies, being listed first
+                if score >= threshold && best.as_ref().is_none_or(|(top, _, _)| score > *top) {
+                    best = Some((score, kind, source.clone()));
+                }
+            }
+
+            match best {
+                Some((score, kind, source)) => {
+                    if kind == ChangeKind::Renamed {
+                        deleted.retain(|candidate| *candidate != source);
+                    }
+                    changes.push(pair(kind, source, (path, oid), score));
+                }
+                None => added.push((path, oid)),
+            }
+        }
+
+        Ok(())
+    }
+
+    // Share of the larger file made of lines found in both, in percent
+    pub fn similarity(old: &[u8], new: &[u8]) -> u32 {
+        let largest = old.len().max(new.len());
+        if largest == 0 {
+            return 100;
+        }
+
+        let mut lines: HashMap<&[u8], usize> = HashMap::new();
+        for line in old.split_inclusive(|&b| b == b'\n') {
+            *lines.entry(line).or_default() += 1;
+        }
+
+        let mut common = 0;
+        for line in new.split_inclusive(|&b| b == b'\n') {
+            if let Some(count) = lines.get_mut(line)
+                && *count > 0
+            {
+                *count -= 1;
+                common += line.len();
+            }
+        }
+
+        (common * 100 / largest) as u32
     }
 
     fn diff_blobs(
         &self,
-        from_oid: Option<&str>,
-        to_oid: Option<&str>,
-        path: &str,
-    ) -> Result<Vec<u8>, String> {
-        let mut from_file =
-            NamedTempFile::new().map_err(|e| format!("Failed to create temp file: {}", e))?;
-        let mut to_file =
-            NamedTempFile::new().map_err(|e| format!("Failed to create temp file: {}", e))?;
-
-        if let Some(oid) = from_oid {
-            let content = self.repo.get_object(oid)?;
-            from_file
-                .write_all(&content)
-                .map_err(|e| format!("Failed to write to temp file: {}", e))?;
-        }
-        if let Some(oid) = to_oid {
-            let content = self.repo.get_object(oid)?;
-            to_file
-                .write_all(&content)
-                .map_err(|e| format!("Failed to write to temp file: {}", e))?;
-        }
-
-        from_file
-            .flush()
-            .map_err(|e| format!("Failed to flush temp file: {}", e))?;
-        to_file
-            .flush()
-            .map_err(|e| format!("Failed to flush temp file: {}", e))?;
+        from_oid: Option<Oid>,
+        to_oid: Option<Oid>,
+        from_path: &str,
+        to_path: &str,
+    ) -> Result<Vec<u8>, Error> {
+        let from_content = self.blob_content(from_oid)?;
+        let to_content = self.blob_content(to_oid)?;
+
+        if self.is_binary(to_path, &from_content, &to_content) {
+            if from_content == to_content {
+                return Ok(Vec::new());
+            }
+            if self.options.binary {
+                return Ok(format!(
+                    "--- a/{}\n+++ b/{}\nGIT binary patch\n{}{}",
+                    from_path,
+                    to_path,
+                    binary::binary_literal(&to_content),
+                    binary::binary_literal(&from_content)
+                )
+                .into_bytes());
+            }
+            return Ok(Self::binary_files_differ(from_path, to_path));
+        }
+
+        let args = Self::patch_args(from_path, to_path, &self.options.whitespace);
+        Ok(Self::diff_contents(&args, &from_content, &to_content)?.stdout)
+    }
+
+    // Diff two files of the filesystem, as `diff --no-index`
+    pub fn diff_files(from: &Path, to: &Path, options: &DiffOptions) -> Result<Vec<u8>, Error> {
+        for path in [from, to] {
+            if !path.is_file() {
+                return Err(Error::Other(format!(
+                    "Could not access '{}'",
+                    path.display()
+                )));
+            }
+        }
+        let (from_label, to_label) = (from.to_string_lossy(), to.to_string_lossy());
+
+        let read = |path: &Path| {
+            std::fs::read(path)
+                .map_err(|e| Error::io(format!("Failed to read {}", path.display()), e))
+        };
+        let (from_content, to_content) = (read(from)?, read(to)?);
+        if binary::is_binary(&from_content) || binary::is_binary(&to_content) {
+            if from_content == to_content {
+                return Ok(Vec::new());
+            }
+            return Ok(Self::binary_files_differ(&from_label, &to_label));
+        }
+
+        let diff = Self::run_diff(from, to, &from_label, &to_label, &options.whitespace)?;
+        match options.word_diff {
+            Some(mode) => Ok(Self::word_diff(&diff, mode, &Self::word_regex(options)?)),
+            None => Ok(diff),
+        }
+    }
+
+    fn binary_files_differ(from_path: &str, to_path: &str) -> Vec<u8> {
+        format!("Binary files a/{} and b/{} differ\n", from_path, to_path).into_bytes()
+    }
+
+    // A path is binary when .bgitattributes says so, otherwise when either
+    // version contains a NUL byte
+    fn is_binary(&self, path: &str, old_content: &[u8], new_content: &[u8]) -> bool {
+        self.repo
+            .binary_attribute(path)
+            .unwrap_or_else(|| binary::is_binary(old_content) || binary::is_binary(new_content))
+    }
+
+    // Content of a blob, empty for a missing side
+    fn blob_content(&self, oid: Option<Oid>) -> Result<Vec<u8>, Error> {
+        match oid {
+            Some(oid) => self.repo.read_object(oid, ObjectType::Blob),
+            None => Ok(Vec::new()),
+        }
+    }
 
+    fn run_diff(
+        from: &Path,
+        to: &Path,
+        from_label: &str,
+        to_label: &str,
+        whitespace: &WhitespaceOptions,
+    ) -> Result<Vec<u8>, Error> {
         let output = Command::new("diff")
-            .args([
-                "--unified",
-                "--show-c-function",
-                "--label",
-                &format!("a/{}", path),
-                from_file.path().to_str().unwrap(),
-                "--label",
-                &format!("b/{}", path),
-                to_file.path().to_str().unwrap(),
-            ])
+            .args(Self::patch_args(from_label, to_label, whitespace))
+            .args([from, to])
             .output()
-            .map_err(|e| format!("Failed to run diff command: {}", e))?;
+            .map_err(|e| Error::io("Failed to run diff command", e))?;
 
         Ok(output.stdout)
     }
 
-    pub fn diff_current_working_tree(&self) -> Result<Vec<u8>, String> {
-        let working_tree = self.repo.get_working_tree()?;
-        let head_tree = match self.repo.get_commit(HEAD) {
-            Ok(commit) => commit.tree,
-            Err(_) => "".to_string(), // Use empty string for empty tree if no commits
+    // Arguments of `diff` for a unified patch, before the two files
+    fn patch_args(from_label: &str, to_label: &str, whitespace: &WhitespaceOptions) -> Vec<String> {
+        let mut args: Vec<String> = whitespace
+            .diff_args()
+            .into_iter()
+            .map(String::from)
+            .collect();
+        args.extend([
+            "--unified".to_string(),
+            "--show-c-function".to_string(),
+            "--label".to_string(),
+            format!("a/{}", from_label),
+            "--label".to_string(),
+            format!("b/{}", to_label),
+        ]);
+        args
+    }
+
+    // Run `diff` with `args` on two contents, which it reads from pipes so
+    // that nothing is written to disk
+    #[cfg(unix)]
+    pub(crate) fn diff_contents(
+        args: &[impl AsRef<str>],
+        from: &[u8],
+        to: &[u8],
+    ) -> Result<Output, Error> {
+        use std::io;
+        use std::os::fd::AsRawFd;
+        use std::os::unix::process::CommandExt;
+        use std::thread;
+
+        let pipe = || io::pipe().map_err(|e| Error::io("Failed to create pipe", e));
+        let (from_reader, from_writer) = pipe()?;
+        let (to_reader, to_writer) = pipe()?;
+        let fds = [from_reader.as_raw_fd(), to_reader.as_raw_fd()];
+
+        let mut command = Command::new("diff");
+        command
+            .args(args.iter().map(|arg| arg.as_ref()))
+            .args(fds.map(|fd| format!("/dev/fd/{}", fd)))
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        // The pipes are closed on exec like every file std opens; diff has
+        // to keep the ends it reads
+        unsafe {
+            command.pre_exec(move || {
+                for fd in fds {
+                    if libc::fcntl(fd, libc::F_SETFD, 0) == -1 {
+                        return Err(io::Error::last_os_error());
+                    }
+                }
+                Ok(())
+            });
+        }
+        let child = command
+            .spawn()
+            .map_err(|e| Error::io("Failed to run diff command", e))?;
+        drop((from_reader, to_reader));
+
+        // Both inputs are written while the output is read, and each pipe
+        // is closed once written. diff may exit before reading everything,
+        // which only fails the write.
+        thread::scope(|scope| {
+            for (mut writer, content) in [(from_writer, from), (to_writer, to)] {
+                scope.spawn(move || writer.write_all(content));
+            }
+            child.wait_with_output()
+        })
+        .map_err(|e| Error::io("Failed to run diff command", e))
+    }
+
+    #[cfg(not(unix))]
+    pub(crate) fn diff_contents(
+        args: &[impl AsRef<str>],
+        from: &[u8],
+        to: &[u8],
+    ) -> Result<Output, Error> {
+        let temp_file = |content: &[u8]| -> Result<tempfile::NamedTempFile, Error> {
+            let mut file = tempfile::NamedTempFile::new()
+                .map_err(|e| Error::io("Failed to create temp file", e))?;
+            file.write_all(content)
+                .and_then(|_| file.flush())
+                .map_err(|e| Error::io("Failed to write temp file", e))?;
+            Ok(file)
         };
-        let diff = self.diff_trees(&head_tree, &working_tree)?;
-        Ok(diff)
+        let (from_file, to_file) = (temp_file(from)?, temp_file(to)?);
+        Command::new("diff")
+            .args(args.iter().map(|arg| arg.as_ref()))
+            .args([from_file.path(), to_file.path()])
+            .stdin(Stdio::null())
+            .output()
+            .map_err(|e| Error::io("Failed to run diff command", e))
     }
 
-    pub fn iter_changed_files(&self) -> Result<Vec<String>, String> {
+    pub fn diff_current_working_tree(&self) -> Result<Vec<u8>, Error> {
         let working_tree = self.repo.get_working_tree()?;
-        let head_tree = match self.repo.get_commit(HEAD) {
-            Ok(commit) => commit.tree,
-            Err(_) => "".to_string(), // Use empty string for empty tree if no commits
-        };
+        let head_tree = self.head_tree()?;
+        let diff = self.diff_trees(head_tree, Some(working_tree))?;
+        Ok(diff)
+    }
 
-        // Compare working tree (index 0) vs head tree (index 1)
-        let entries = self.compare_trees(&[&working_tree, &head_tree])?;
+    pub fn iter_changed_files(&self) -> Result<Vec<String>, Error> {
+        let working_tree = self.repo.get_working_tree()?;
+        let head_tree = self.head_tree()?;
 
-        Ok(entries
+        Ok(self
+            .changed_files(head_tree, Some(working_tree))?
             .into_iter()
-            // Filter where OID at index 0 (worktree) differs from index 1 (HEAD)
-            .filter(|(_path, _obj_type, oids)| {
-                oids.first().unwrap_or(&None) != oids.get(1).unwrap_or(&None)
-            })
-            .map(|(path, _obj_type, oids)| {
-                // Correctly destructure tuple
-                // Determine status based on presence/absence of OIDs
-                match (oids.first().unwrap_or(&None), oids.get(1).unwrap_or(&None)) {
-                    (Some(_), None) => format!("\x1b[32m{}[0m", path), // Added
-                    (None, Some(_)) => format!("\x1b[31m{}[0m", path), // Deleted
-                    (Some(_), Some(_)) => format!("\x1b[33m{}[0m", path), // Modified
-                    (None, None) => path, // Should not happen due to filter
+            .map(|change| {
+                // Relative to the current directory
+                let old_path = self.repo.display_path(&change.old_path);
+                let new_path = self.repo.display_path(&change.new_path);
+                match change.kind {
+                    ChangeKind::Added => paint(Color::Green, &new_path),
+                    ChangeKind::Deleted => paint(Color::Red, &old_path),
+                    ChangeKind::Modified => paint(Color::Yellow, &new_path),
+                    ChangeKind::Renamed => paint(
+                        Color::Cyan,
+                        &format!("renamed: {} -> {}", old_path, new_path),
+                    ),
+                    ChangeKind::Copied => paint(
+                        Color::Cyan,
+                        &format!("copied: {} -> {}", old_path, new_path),
+                    ),
                 }
             })
             .collect())
     }
 
+    // Tree of the HEAD commit, None (the empty tree) if there are no commits
+    fn head_tree(&self) -> Result<Option<Oid>, Error> {
+        match self.repo.get_oid_hash(HEAD) {
+            Ok(head) => Ok(Some(self.repo.get_commit(head)?.tree)),
+            Err(_) => Ok(None),
+        }
+    }
+
     pub fn colorize_diff(diff: &[u8]) -> String {
         let mut colored = String::new();
         let diff_str = String::from_utf8_lossy(diff);
 
         for line in diff_str.lines() {
             if line.starts_with('+') && !line.starts_with("+++") {
-                colored.push_str("\x1b[32m"); // Green
-                colored.push_str(line);
-                colored.push_str("\x1b[0m"); // Reset
+                colored.push_str(&paint(Color::Green, line));
             } else if line.starts_with('-') && !line.starts_with("---") {
-                colored.push_str("\x1b[31m"); // Red
-                colored.push_str(line);
-                colored.push_str("\x1b[0m"); // Reset
+                colored.push_str(&paint(Color::Red, line));
             } else {
                 colored.push_str(line);
             }
@@ -228,16 +1198,29 @@ impl<'a> Differ<'a> {
     // Refactored merge_trees
     pub fn merge_trees(
         &self,
-        t_head: &str,
-        t_other: &str,
-        t_base: Option<&str>,
+        t_head: Oid,
+        t_other: Oid,
+        t_base: Option<Oid>,
         // Return type distinguishes files (Ok) from directories (Err)
-    ) -> Result<HashMap<String, Result<Vec<u8>, ()>>, String> {
+    ) -> Result<HashMap<String, Result<Vec<u8>, ()>>, Error> {
+        let (tree, _conflicts) = self.merge_trees_with_conflicts(t_head, t_other, t_base)?;
+        Ok(tree)
+    }
+
+    // Same as merge_trees, but also returns the (sorted) paths whose content
+    // could not be merged cleanly and now contains conflict markers
+    pub fn merge_trees_with_conflicts(
+        &self,
+        t_head: Oid,
+        t_other: Oid,
+        t_base: Option<Oid>,
+    ) -> Result<MergedTree, Error> {
         let mut tree: HashMap<String, Result<Vec<u8>, ()>> = HashMap::new();
-        let base = t_base.unwrap_or(""); // Use empty string for None base
+        let mut conflicts = Vec::new();
 
-        // Get comparison result including object types
-        let entries = self.compare_trees(&[base, t_head, t_other])?;
+        // Get comparison result including object types, no base is the
+        // empty tree
+        let entries = self.compare_trees(&[t_base, Some(t_head), Some(t_other)])?;
 
         for (path, obj_type, oids) in entries {
             let base_oid = oids.first().unwrap_or(&None);
@@ -247,19 +1230,21 @@ impl<'a> Differ<'a> {
             match obj_type {
                 ObjectType::Blob => {
                     // Handle blobs: merge content
-                    let merged_content = self.merge_blobs_three_way(
-                        base_oid.as_deref(),
-                        head_oid.as_deref(),
-                        other_oid.as_deref(),
-                    )?;
+                    let (merged_content, conflicted) =
+                        self.merge_blobs_three_way(&path, *base_oid, *head_oid, *other_oid)?;
 
                     // Check if file was deleted in both branches relative to base
                     if base_oid.is_some() && head_oid.is_none() && other_oid.is_none() {
                         // Deleted in both, skip.
+                    } else if Self::deleted_on_one_side(base_oid, head_oid, other_oid) {
+                        // Deleted on one side and untouched on the other, skip.
                     } else if head_oid.is_none() && other_oid.is_none() {
                         // Not present in base, head, or other (shouldn't happen due to compare_trees logic?)
                         // Or, if base was None, means it was only added then deleted - skip.
                     } else {
+                        if conflicted {
+                            conflicts.push(path.clone());
+                        }
                         tree.insert(path, Ok(merged_content));
                     }
                 }
@@ -272,6 +1257,8 @@ impl<'a> Differ<'a> {
                     // present in base but deleted in *both* head and other.
                     if base_oid.is_some() && head_oid.is_none() && other_oid.is_none() {
                         // Deleted in both relative to base, skip.
+                    } else if Self::deleted_on_one_side(base_oid, head_oid, other_oid) {
+                        // Deleted on one side and untouched on the other, skip.
                     } else if head_oid.is_some() || other_oid.is_some() {
                         // Exists in head or other (and not deleted in both relative to base)
                         tree.insert(path, Err(())); // Mark as directory
@@ -281,96 +1268,158 @@ impl<'a> Differ<'a> {
                 }
                 ObjectType::Commit => {
                     // This shouldn't happen within a tree comparison
-                    return Err(format!(
+                    return Err(Error::Other(format!(
                         "Unexpected Commit object type found for path {}",
                         path
-                    ));
+                    )));
                 }
             }
         }
-        Ok(tree)
+        conflicts.sort();
+        Ok((tree, conflicts))
+    }
+
+    // A deletion wins over a side that left the entry unchanged
+    fn deleted_on_one_side(
+        base_oid: &Option<Oid>,
+        head_oid: &Option<Oid>,
+        other_oid: &Option<Oid>,
+    ) -> bool {
+        base_oid.is_some()
+            && ((head_oid.is_none() && other_oid == base_oid)
+                || (other_oid.is_none() && head_oid == base_oid))
     }
 
+    // Returns the merged content and whether there was a conflict
     fn merge_blobs_three_way(
         &self,
-        o_base: Option<&str>,
-        o_head: Option<&str>,
-        o_other: Option<&str>,
-    ) -> Result<Vec<u8>, String> {
+        path: &str,
+        o_base: Option<Oid>,
+        o_head: Option<Oid>,
+        o_other: Option<Oid>,
+    ) -> Result<(Vec<u8>, bool), Error> {
         // Handle the trivial case where all inputs are None
         if o_base.is_none() && o_head.is_none() && o_other.is_none() {
-            return Ok(Vec::new());
+            return Ok((Vec::new(), false));
         }
 
         // Get content or empty vec
         let base_content = match o_base {
-            Some(oid) => self.repo.get_object(oid)?,
+            Some(oid) => self.repo.read_object(oid, ObjectType::Blob)?,
             None => Vec::new(),
         };
         let head_content = match o_head {
-            Some(oid) => self.repo.get_object(oid)?,
+            Some(oid) => self.repo.read_object(oid, ObjectType::Blob)?,
             None => Vec::new(),
         };
         let other_content = match o_other {
-            Some(oid) => self.repo.get_object(oid)?,
+            Some(oid) => self.repo.read_object(oid, ObjectType::Blob)?,
             None => Vec::new(),
         };
 
-        // Use helper function to create temp files
-        let base_file = self.create_temp_file_with_content(&base_content, "base")?;
-        let head_file = self.create_temp_file_with_content(&head_content, "head")?;
-        let other_file = self.create_temp_file_with_content(&other_content, "other")?;
-
-        // Call diff3 without labels
-        let output = Command::new("diff3")
-            .args([
-                "-m", // Merge output with conflict markers
-                "-L",
-                "HEAD",                             // Label for HEAD version
-                head_file.path().to_str().unwrap(), // My file (HEAD)
-                "-L",
-                "BASE",                             // Label for BASE version
-                base_file.path().to_str().unwrap(), // Older file (BASE)
-                "-L",
-                "MERGE_HEAD",                        // Label for OTHER version
-                other_file.path().to_str().unwrap(), // Your file (OTHER)
-            ])
-            .output()
-            .map_err(|e| {
-                format!(
-                    "Failed to run diff3 command: {}. Ensure diffutils is installed.",
-                    e
-                )
-            })?;
+        // Conflict markers would corrupt binary content: take the only side
+        // that changed, or keep HEAD's version and report a conflict
+        if self.is_binary(path, &base_content, &head_content)
+            || self.is_binary(path, &base_content, &other_content)
+        {
+            return Ok(if o_head == o_other || o_other == o_base {
+                (head_content, false)
+            } else if o_head == o_base {
+                (other_content, false)
+            } else if o_head.is_none() {
+                (other_content, true)
+            } else {
+                (head_content, true)
+            });
+        }
 
-        // diff3 returns status 1 for conflicts, 0 for success.
-        // We accept both as valid merge results (stdout contains markers on conflict).
-        // Status > 1 indicates an error.
-        if output.status.success() || output.status.code() == Some(1) {
-            Ok(output.stdout)
+        // Whitespace-only changes should not conflict with real ones
+        let (head_content, other_content) = if self.options.whitespace.is_set() {
+            self.ignore_whitespace_changes(&base_content, &head_content, &other_content)?
         } else {
-            Err(format!(
-                "diff3 command failed with status {:?}: {}",
+            (head_content, other_content)
+        };
+
+        diff3::merge(&head_content, &base_content, &other_content)
+    }
+
+    // Prepare both sides for -Xignore-*: lines theirs only changed in
+    // whitespace are reset to the base so ours win, and lines ours only
+    // changed in whitespace are reset where theirs has a real change so
+    // theirs win
+    fn ignore_whitespace_changes(
+        &self,
+        base: &[u8],
+        head: &[u8],
+        other: &[u8],
+    ) -> Result<(Vec<u8>, Vec<u8>), Error> {
+        let head_unchanged = self.unchanged_lines(base, head)?;
+        let other_unchanged = self.unchanged_lines(base, other)?;
+        let mut head_lines: Vec<&[u8]> = head.split_inclusive(|&b| b == b'\n').collect();
+        let mut other_lines: Vec<&[u8]> = other.split_inclusive(|&b| b == b'\n').collect();
+
+        for (index, base_line) in base.split_inclusive(|&b| b == b'\n').enumerate() {
+            match (head_unchanged[index], other_unchanged[index]) {
+                (_, Some(other_index)) => other_lines[other_index] = base_line,
+                (Some(head_index), None) => head_lines[head_index] = base_line,
+                (None, None) => {}
+            }
+        }
+
+        Ok((head_lines.concat(), other_lines.concat()))
+    }
+
+    // For every line of `base`, the index of the same line in `side` when
+    // the whitespace options make them equal, None when it was changed
+    fn unchanged_lines(&self, base: &[u8], side: &[u8]) -> Result<Vec<Option<usize>>, Error> {
+        let mut args = self.options.whitespace.diff_args();
+        args.push("--unified=0");
+        let output = Self::diff_contents(&args, base, side)?;
+        if !output.status.success() && output.status.code() != Some(1) {
+            return Err(Error::Other(format!(
+                "diff command failed with status {:?}: {}",
                 output.status.code(),
                 String::from_utf8_lossy(&output.stderr)
-            ))
+            )));
         }
-    }
 
-    // Helper function to create, write, and flush a temp file
-    fn create_temp_file_with_content(
-        &self,
-        content: &[u8],
-        label: &str, // For error messages
-    ) -> Result<NamedTempFile, String> {
-        let mut temp_file = NamedTempFile::new()
-            .map_err(|e| format!("Failed to create temp file ({}): {}", label, e))?;
-        temp_file
-            .write_all(content)
-            .map_err(|e| format!("Failed to write temp file ({}): {}", label, e))?;
-        temp_file
-            .flush()
-            .map_err(|e| format!("Failed to flush temp file ({}): {}", label, e))?;
-        Ok(temp_file)
+        let base_count = base.split_inclusive(|&b| b == b'\n').count();
+        let mut unchanged = vec![None; base_count];
+        let (mut base_next, mut side_next) = (0, 0);
+
+        // "@@ -<start>[,<count>] +<start>[,<count>] @@": an empty range
+        // starts after the given line, others at it
+        let range = |field: &str| {
+            let (start, count) = match field[1..].split_once(',') {
+                Some((start, count)) => (start.parse().unwrap_or(0), count.parse().unwrap_or(0)),
+                None => (field[1..].parse().unwrap_or(0), 1),
+            };
+            let first: usize = if count == 0 { start } else { start - 1 };
+            (first, count)
+        };
+
+        for line in String::from_utf8_lossy(&output.stdout).lines() {
+            if !line.starts_with("@@") {
+                continue;
+            }
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let (base_first, base_len) = range(fields[1]);
+            let (side_first, side_len) = range(fields[2]);
+
+            while base_next < base_first {
+                unchanged[base_next] = Some(side_next);
+                base_next += 1;
+                side_next += 1;
+            }
+            base_next = base_first + base_len;
+            side_next = side_first + side_len;
+        }
+        while base_next < base_count {
+            unchanged[base_next] = Some(side_next);
+            base_next += 1;
+            side_next += 1;
+        }
+
+        Ok(unchanged)
     }
 }
@@ -0,0 +1,214 @@
+use crate::error::Error;
+use crate::oid::HashAlgorithm;
+use crate::repository::{GIT_DIR, HEAD, OBJECT_FORMAT_CONFIG, Repository};
+use std::env;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+// Finding the repository of a directory, like git does: the nearest parent
+// with a .bgit directory, unless the environment says otherwise
+
+// Repository directory to use instead of looking for one
+pub const DIR_ENV: &str = "BGIT_DIR";
+// Worktree to use instead of the directory containing .bgit
+pub const WORK_TREE_ENV: &str = "BGIT_WORK_TREE";
+// Directories, separated like PATH, that discovery does not go up into
+pub const CEILING_DIRECTORIES_ENV: &str = "BGIT_CEILING_DIRECTORIES";
+// Set to keep looking on other filesystems than the starting directory's
+pub const ACROSS_FILESYSTEM_ENV: &str = "BGIT_DISCOVERY_ACROSS_FILESYSTEM";
+
+#[derive(Debug, Clone, Default)]
+pub struct DiscoveryOptions {
+    // Discovery stops below these directories, they are never searched
+    // themselves unless discovery starts there
+    pub ceilings: Vec<PathBuf>,
+    pub across_filesystems: bool,
+}
+
+impl DiscoveryOptions {
+    pub fn from_env() -> DiscoveryOptions {
+        let ceilings = env::var_os(CEILING_DIRECTORIES_ENV)
+            .map(|value| {
+                env::split_paths(&value)
+                    .filter(|path| path.is_absolute())
+                    .map(|path| fs::canonicalize(&path).unwrap_or(path))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let across_filesystems = env::var(ACROSS_FILESYSTEM_ENV)
+            .is_ok_and(|value| matches!(value.as_str(), "1" | "true" | "yes" | "on"));
+        DiscoveryOptions {
+            ceilings,
+            across_filesystems,
+        }
+    }
+}
+
+#[cfg(unix)]
+fn device(path: &Path) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    fs::metadata(path).ok().map(|metadata| metadata.dev())
+}
+
+#[cfg(not(unix))]
+fn device(_path: &Path) -> Option<u64> {
+    None
+}
+
+fn canonical(path: &str) -> Result<PathBuf, Error> {
+    fs::canonicalize(path).map_err(|e| Error::io(format!("Cannot access {}", path), e))
+}
+
+// "src/" for `dir` inside `worktree` at src, "" outside of it or at the top
+fn prefix_of(dir: &Path, worktree: &Path) -> String {
+    match dir.strip_prefix(worktree) {
+        Ok(relative) if !relative.as_os_str().is_empty() => {
+            format!("{}/", relative.to_string_lossy())
+        }
+        _ => String::new(),
+    }
+}
+
+// A bare repository: HEAD, objects and refs directly in `dir`, and
+// core.bare set, unlike the .bgit directory of a repository with a worktree
+fn is_bare_repository(dir: &Path) -> bool {
+    dir.join(HEAD).is_file()
+        && dir.join("objects").is_dir()
+        && dir.join("refs").is_dir()
+        && Repository::new_bare(&dir.to_string_lossy())
+            .get_config("core.bare")
+            .is_some_and(|value| value == "true")
+}
+
+impl Repository {
+    // The repository `path` belongs to, found in `path` or its parents
+    pub fn discover(path: &str) -> Result<Repository, Error> {
+        Self::discover_with_options(path, &DiscoveryOptions::from_env())
+    }
+
+    pub fn discover_with_options(
+        path: &str,
+        options: &DiscoveryOptions,
+    ) -> Result<Repository, Error> {
+        let start = canonical(path)?;
+        let start_device = device(&start);
+
+        let mut dir = start.as_path();
+        loop {
+            if dir.join(GIT_DIR).is_dir() {
+                let mut repo = Repository::new(&dir.to_string_lossy());
+                repo.prefix = prefix_of(&start, dir);
+                return Ok(repo);
+            }
+            if is_bare_repository(dir) {
+                return Ok(Repository::new_bare(&dir.to_string_lossy()));
+            }
+
+            let Some(parent) = dir.parent() else {
+                break;
+            };
+            if options.ceilings.iter().any(|ceiling| ceiling == parent) {
+                break;
+            }
+            if !options.across_filesystems && device(parent) != start_device {
+                return Err(Error::NotARepository(format!(
+                    "not a bgit repository (or any parent up to mount point {}), stopping at the filesystem boundary since {} is not set",
+                    dir.display(),
+                    ACROSS_FILESYSTEM_ENV
+                )));
+            }
+            dir = parent;
+        }
+
+        Err(Error::NotARepository(format!(
+            "not a bgit repository (or any of the parent directories): {}",
+            GIT_DIR
+        )))
+    }
+
+    // The repository of `path`, honoring BGIT_DIR and BGIT_WORK_TREE
+    pub fn open(path: &str) -> Result<Repository, Error> {
+        let cwd = canonical(path)?;
+
+        let mut repo = match env::var(DIR_ENV) {
+            Ok(gitdir) => {
+                let gitdir = cwd.join(gitdir);
+                if is_bare_repository(&gitdir) {
+                    Repository::new_bare(&gitdir.to_string_lossy())
+                } else {
+                    // Without BGIT_WORK_TREE, the current directory is the
+                    // worktree
+                    Repository::with_dirs(&cwd.to_string_lossy(), &gitdir.to_string_lossy(), false)
+                }
+            }
+            Err(_) => Self::discover(path)?,
+        };
+        if !Path::new(&repo.gitdir).is_dir() {
+            return Err(Error::NotARepository(format!(
+                "not a bgit repository: {}",
+                repo.gitdir
+            )));
+        }
+        // Objects of an unknown format cannot even be named
+        if let Some(format) = repo.get_config(OBJECT_FORMAT_CONFIG)
+            && HashAlgorithm::from_name(&format).is_none()
+        {
+            return Err(Error::Other(format!("unknown object format '{}'", format)));
+        }
+
+        if let Ok(worktree) = env::var(WORK_TREE_ENV) {
+            let worktree = canonical(&cwd.join(worktree).to_string_lossy())?;
+            repo.prefix = prefix_of(&cwd, &worktree);
+            repo.worktree = worktree.to_string_lossy().to_string();
+            repo.bare = false;
+        }
+        Ok(repo)
+    }
+
+    // A path given relative to the current directory, as a path from the
+    // top of the worktree
+    pub fn resolve_path(&self, path: &str) -> Result<String, Error> {
+        let mut components: Vec<String> = Vec::new();
+        for component in Path::new(&self.prefix).join(path).components() {
+            match component {
+                Component::Normal(name) => components.push(name.to_string_lossy().to_string()),
+                Component::ParentDir => {
+                    if components.pop().is_none() {
+                        return Err(Error::Other(format!("{}: outside of the worktree", path)));
+                    }
+                }
+                Component::CurDir => {}
+                Component::RootDir | Component::Prefix(_) => {
+                    // Absolute paths must point into the worktree
+                    let absolute = fs::canonicalize(path).unwrap_or_else(|_| path.into());
+                    return match absolute.strip_prefix(&self.worktree) {
+                        Ok(relative) => Ok(relative.to_string_lossy().to_string()),
+                        Err(_) => Err(Error::Other(format!("{}: outside of the worktree", path))),
+                    };
+                }
+            }
+        }
+        Ok(components.join("/"))
+    }
+
+    // A path from the top of the worktree, as shown to someone in the
+    // current directory
+    pub fn display_path(&self, path: &str) -> String {
+        let mut prefix = self.prefix.as_str();
+        let mut path = path;
+        // Drop the directories both have in common
+        while let Some((dir, rest)) = prefix.split_once('/') {
+            match path
+                .strip_prefix(dir)
+                .and_then(|rest| rest.strip_prefix('/'))
+            {
+                Some(remaining) => {
+                    path = remaining;
+                    prefix = rest;
+                }
+                None => break,
+            }
+        }
+        format!("{}{}", "../".repeat(prefix.matches('/').count()), path)
+    }
+}
@@ -0,0 +1,91 @@
+use std::error;
+use std::fmt;
+use std::io;
+
+use crate::oid::Oid;
+use crate::repository::ObjectType;
+
+// Errors returned by the library. Callers match on the variant to tell a
+// missing object from a corrupt one, the message is for people
+#[derive(Debug)]
+pub enum Error {
+    // An object id with no object in the repository
+    NotFound(Oid),
+    // An object, or a file under the repository directory, that cannot be
+    // parsed
+    Corrupt(String),
+    // An object of another type than the one asked for, a blob given as a
+    // commit
+    WrongType {
+        oid: Oid,
+        expected: ObjectType,
+        actual: ObjectType,
+    },
+    // A ref or revision that does not resolve, or a name that is not a
+    // valid ref name
+    InvalidRef(String),
+    // Paths whose conflicts must be resolved first
+    Conflict(Vec<String>),
+    // A filesystem or process error, with what was being done
+    Io {
+        context: String,
+        source: io::Error,
+    },
+    // No repository where one was looked for
+    NotARepository(String),
+    // An operation on the worktree of a bare repository
+    BareRepository,
+    // Refs, the config or other files of an in-memory repository, which
+    // has no directory to keep them in
+    InMemory,
+    // A lock file, which another process holds or one that died left
+    // behind
+    Locked(String),
+    // Anything else, usually an operation the current state does not allow
+    Other(String),
+}
+
+impl Error {
+    pub fn io(context: impl Into<String>, source: io::Error) -> Error {
+        Error::Io {
+            context: context.into(),
+            source,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::NotFound(oid) => write!(f, "Object not found: {}", oid),
+            Error::InvalidRef(name) => write!(f, "Invalid reference: {}", name),
+            Error::WrongType {
+                oid,
+                expected,
+                actual,
+            } => write!(f, "Object {} is a {}, not a {}", oid, actual, expected),
+            Error::Conflict(paths) => write!(f, "Unresolved conflicts in: {}", paths.join(", ")),
+            // The io error itself is the source, see `Error::source`
+            Error::Io { context, .. } => write!(f, "{}", context),
+            Error::BareRepository => write!(f, "this operation must be run in a work tree"),
+            Error::InMemory => write!(f, "an in-memory repository has no refs or config"),
+            Error::Locked(path) => write!(
+                f,
+                "Unable to create '{}': another bgit process seems to be running in this repository",
+                path
+            ),
+            Error::Corrupt(message) | Error::NotARepository(message) | Error::Other(message) => {
+                write!(f, "{}", message)
+            }
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,321 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fmt;
+use std::fs;
+
+use crate::error::Error;
+use crate::oid::Oid;
+use crate::repository::{HEAD, ObjectType, RefValue, Repository};
+
+// Where `fsck --lost-found` writes dangling objects, inside the gitdir
+pub const LOST_FOUND_DIR: &str = "lost-found";
+
+// Options of `fsck`
+#[derive(Debug, Clone, Default)]
+pub struct FsckOptions {
+    // Only check that the objects reachable from refs are present: no
+    // rehashing, and reachable blobs are not read (--connectivity-only)
+    pub connectivity_only: bool,
+    // Write dangling objects to lost-found/commit/ or lost-found/other/
+    pub lost_found: bool,
+}
+
+// Something wrong found by `fsck`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FsckProblem {
+    // The content of an object does not hash to its id
+    HashMismatch {
+        oid: Oid,
+        actual: Oid,
+    },
+    // An object that cannot be read or parsed, and why
+    Corrupt {
+        oid: Oid,
+        reason: String,
+    },
+    // `from` refers to `to` as an `expected`, but there is no such object
+    BrokenLink {
+        from: Oid,
+        to: Oid,
+        expected: ObjectType,
+    },
+    // A ref, or its log, that cannot be read or points at no valid object
+    BadRef {
+        name: String,
+        reason: String,
+    },
+}
+
+impl fmt::Display for FsckProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FsckProblem::HashMismatch { oid, actual } => {
+                write!(f, "hash mismatch for {}: content hashes to {}", oid, actual)
+            }
+            // The reason names the object already
+            FsckProblem::Corrupt { reason, .. } => write!(f, "{}", reason),
+            FsckProblem::BrokenLink { from, to, expected } => {
+                write!(f, "broken link from {} to {} {}", from, expected, to)
+            }
+            FsckProblem::BadRef { name, reason } => write!(f, "{}: {}", name, reason),
+        }
+    }
+}
+
+// What `fsck` found
+#[derive(Debug, Default)]
+pub struct FsckReport {
+    pub problems: Vec<FsckProblem>,
+    // Unreachable objects no other object refers to, sorted by id
+    pub dangling: Vec<(ObjectType, Oid)>,
+    // The other unreachable objects, those the dangling ones lead to
+    pub unreachable: Vec<(ObjectType, Oid)>,
+}
+
+// An object as fsck read it
+#[derive(Debug, Clone)]
+enum Checked {
+    Missing,
+    // Reported as a problem already
+    Invalid,
+    // The type and the objects it refers to, with the type they must have
+    Valid(ObjectType, Vec<(Oid, ObjectType)>),
+}
+
+struct Fsck<'a> {
+    repo: &'a Repository,
+    verify_hashes: bool,
+    objects: BTreeMap<Oid, Checked>,
+    problems: Vec<FsckProblem>,
+}
+
+impl Fsck<'_> {
+    fn check(&mut self, oid: Oid) -> Checked {
+        if let Some(checked) = self.objects.get(&oid) {
+            return checked.clone();
+        }
+        let checked = match self.read(oid) {
+            Ok(checked) => checked,
+            Err(reason) => {
+                self.problems.push(FsckProblem::Corrupt { oid, reason });
+                Checked::Invalid
+            }
+        };
+        self.objects.insert(oid, checked.clone());
+        checked
+    }
+
+    fn read(&mut self, oid: Oid) -> Result<Checked, String> {
+        let data = match self.repo.odb.read(oid) {
+            Ok(data) => data,
+            Err(Error::NotFound(_)) => return Ok(Checked::Missing),
+            Err(e) => return Err(e.to_string()),
+        };
+        if self.verify_hashes {
+            let actual = self.repo.object_format.hash(&data);
+            if actual != oid {
+                self.problems
+                    .push(FsckProblem::HashMismatch { oid, actual });
+                return Ok(Checked::Invalid);
+            }
+        }
+
+        let (obj_type, _) = self.repo.get_object(oid).map_err(|e| e.to_string())?;
+        let links = match obj_type {
+            ObjectType::Blob => Vec::new(),
+            ObjectType::Tree => self
+                .repo
+                .get_tree_data(oid)
+                .map_err(|e| e.to_string())?
+                .into_iter()
+                .map(|(_, _, entry, entry_type)| (entry, entry_type))
+                .collect(),
+            ObjectType::Commit => {
+                let commit = self.repo.get_commit(oid).map_err(|e| e.to_string())?;
+                let mut links = vec![(commit.tree, ObjectType::Tree)];
+                links.extend(
+                    commit
+                        .parents
+                        .iter()
+                        .map(|parent| (*parent, ObjectType::Commit)),
+                );
+                links
+            }
+        };
+        Ok(Checked::Valid(obj_type, links))
+    }
+
+    // Whether `to`, which `from` refers to, is an object of the right type
+    fn check_link(&mut self, from: Oid, to: Oid, expected: ObjectType, read_blobs: bool) {
+        let valid = if expected == ObjectType::Blob && !read_blobs {
+            self.repo.odb.exists(to).unwrap_or(false)
+        } else {
+            match self.check(to) {
+                Checked::Missing => false,
+                Checked::Invalid => true,
+                Checked::Valid(obj_type, _) => obj_type == expected,
+            }
+        };
+        if !valid {
+            self.problems
+                .push(FsckProblem::BrokenLink { from, to, expected });
+        }
+    }
+
+    // The commits (or for tags, any object) that refs, their logs and the
+    // state of a merge or cherry-pick keep alive
+    fn roots(&mut self) -> Result<Vec<(String, Oid, Option<ObjectType>)>, Error> {
+        let mut roots = Vec::new();
+        let mut bad_ref = |name: &str, reason: String| {
+            self.problems.push(FsckProblem::BadRef {
+                name: name.to_string(),
+                reason,
+            })
+        };
+
+        for name in &self.repo.root_ref_names()? {
+            let expected = if name.starts_with("refs/tags/") {
+                None
+            } else {
+                Some(ObjectType::Commit)
+            };
+            match self.repo.get_ref(name, false) {
+                Ok(RefValue::Direct(oid)) => roots.push((name.clone(), oid, expected)),
+                Ok(RefValue::Symbolic(target)) if name == HEAD => {
+                    if !target.starts_with("refs/heads/") {
+                        bad_ref(name, format!("points to {}, not a branch", target));
+                    }
+                }
+                Ok(RefValue::Symbolic(target)) => {
+                    bad_ref(name, format!("is a symbolic ref to {}", target));
+                }
+                // A branch without commits yet
+                Ok(RefValue::Unborn) => {}
+                Err(e) => bad_ref(name, e.to_string()),
+            }
+
+            match self.repo.read_reflog(name) {
+                Ok(entries) => {
+                    for entry in entries {
+                        for oid in [entry.old, entry.new] {
+                            if !oid.is_zero() {
+                                roots.push((format!("{} reflog", name), oid, expected));
+                            }
+                        }
+                    }
+                }
+                Err(e) => bad_ref(&format!("{} reflog", name), e.to_string()),
+            }
+        }
+        Ok(roots)
+    }
+}
+
+impl Repository {
+    // Checks that every object is intact and every ref points at one, and
+    // finds the objects no ref can reach
+    pub fn fsck(&self, options: &FsckOptions) -> Result<FsckReport, Error> {
+        let mut fsck = Fsck {
+            repo: self,
+            verify_hashes: !options.connectivity_only,
+            objects: BTreeMap::new(),
+            problems: Vec::new(),
+        };
+        let oids = self.odb.oids()?;
+
+        // Every object is rehashed and parsed, and its links checked
+        if !options.connectivity_only {
+            for &oid in &oids {
+                if let Checked::Valid(_, links) = fsck.check(oid) {
+                    for (to, expected) in links {
+                        fsck.check_link(oid, to, expected, true);
+                    }
+                }
+            }
+        }
+
+        // Walk from the refs. Without the full check, the links of the
+        // reachable objects are checked on the way, without reading blobs.
+        let mut reachable = BTreeSet::new();
+        let mut queue = VecDeque::new();
+        for (name, oid, expected) in fsck.roots()? {
+            match fsck.check(oid) {
+                Checked::Missing => fsck.problems.push(FsckProblem::BadRef {
+                    name,
+                    reason: format!("points to missing object {}", oid),
+                }),
+                Checked::Valid(obj_type, _) if expected.is_some_and(|t| t != obj_type) => {
+                    fsck.problems.push(FsckProblem::BadRef {
+                        name,
+                        reason: format!("points to {} {}, not a commit", obj_type, oid),
+                    })
+                }
+                _ => queue.push_back(oid),
+            }
+        }
+        while let Some(oid) = queue.pop_front() {
+            if !reachable.insert(oid) {
+                continue;
+            }
+            let Checked::Valid(_, links) = fsck.check(oid) else {
+                continue;
+            };
+            for (to, expected) in links {
+                if reachable.contains(&to) {
+                    continue;
+                }
+                if options.connectivity_only {
+                    fsck.check_link(oid, to, expected, false);
+                }
+                if expected == ObjectType::Blob && options.connectivity_only {
+                    reachable.insert(to);
+                } else {
+                    queue.push_back(to);
+                }
+            }
+        }
+
+        // Unreachable objects are dangling unless another one refers to them
+        let mut unreachable = Vec::new();
+        let mut referenced = BTreeSet::new();
+        for oid in oids.into_iter().filter(|oid| !reachable.contains(oid)) {
+            if let Checked::Valid(obj_type, links) = fsck.check(oid) {
+                referenced.extend(links.into_iter().map(|(to, _)| to));
+                unreachable.push((obj_type, oid));
+            }
+        }
+        let (dangling, unreachable): (Vec<_>, Vec<_>) = unreachable
+            .into_iter()
+            .partition(|(_, oid)| !referenced.contains(oid));
+
+        if options.lost_found {
+            self.write_lost_found(&dangling)?;
+        }
+
+        Ok(FsckReport {
+            problems: fsck.problems,
+            dangling,
+            unreachable,
+        })
+    }
+
+    // Commits go to lost-found/commit/ and the rest to lost-found/other/, in
+    // a file named by their id holding the id, or the content for a blob
+    fn write_lost_found(&self, dangling: &[(ObjectType, Oid)]) -> Result<(), Error> {
+        for (obj_type, oid) in dangling {
+            let kind = match obj_type {
+                ObjectType::Commit => "commit",
+                _ => "other",
+            };
+            let dir = self.git_path(&format!("{}/{}", LOST_FOUND_DIR, kind))?;
+            fs::create_dir_all(&dir)
+                .map_err(|e| Error::io(format!("Failed to create {}", dir), e))?;
+            let content = match obj_type {
+                ObjectType::Blob => self.read_object(*oid, ObjectType::Blob)?,
+                _ => format!("{}\n", oid).into_bytes(),
+            };
+            fs::write(format!("{}/{}", dir, oid), content)
+                .map_err(|e| Error::io(format!("Failed to write {}/{}", dir, oid), e))?;
+        }
+        Ok(())
+    }
+}
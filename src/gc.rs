@@ -0,0 +1,162 @@
+use std::collections::BTreeSet;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use crate::error::Error;
+use crate::oid::Oid;
+use crate::repository::{HEAD, MERGE_HEAD, ObjectType, RefValue, Repository};
+use crate::sequencer::{CHERRY_PICK_HEAD, REVERT_HEAD};
+
+// How old an unreachable object must be for gc to delete it, unless
+// gc.pruneExpire says otherwise. A younger one may have just been written
+// by a command that has not updated its ref yet.
+pub const DEFAULT_PRUNE_EXPIRE: &str = "2.weeks.ago";
+
+// Options of `gc` and `prune`
+#[derive(Debug, Clone, Default)]
+pub struct GcOptions {
+    // Only report what would be deleted (--dry-run)
+    pub dry_run: bool,
+    // "2.weeks.ago", "now" or "never", see `parse_expire`. gc.pruneExpire,
+    // or DEFAULT_PRUNE_EXPIRE, when None.
+    pub expire: Option<String>,
+}
+
+// What `gc` deleted
+#[derive(Debug, Default)]
+pub struct GcReport {
+    // The unreachable objects deleted, or that would be with `dry_run`
+    pub pruned: Vec<Oid>,
+    // The space they took
+    pub bytes: u64,
+    // Unreachable objects kept because they are more recent than the expiry
+    pub recent: Vec<Oid>,
+}
+
+// The age from which objects expire, "<n>.<unit>.ago" with a unit from
+// seconds to weeks, or "now". None for "never".
+pub fn parse_expire(value: &str) -> Result<Option<Duration>, Error> {
+    let invalid = || Error::Other(format!("Invalid expiry date: {}", value));
+    let words: Vec<&str> = value
+        .split(['.', ' '])
+        .filter(|word| !word.is_empty())
+        .collect();
+    match words[..] {
+        ["now"] => Ok(Some(Duration::ZERO)),
+        ["never"] => Ok(None),
+        [count, unit, "ago"] => {
+            let count: u64 = count.parse().map_err(|_| invalid())?;
+            let seconds = match unit.strip_suffix('s').unwrap_or(unit) {
+                "second" => 1,
+                "minute" => 60,
+                "hour" => 60 * 60,
+                "day" => 24 * 60 * 60,
+                "week" => 7 * 24 * 60 * 60,
+                _ => return Err(invalid()),
+            };
+            let seconds = count.checked_mul(seconds).ok_or_else(invalid)?;
+            Ok(Some(Duration::from_secs(seconds)))
+        }
+        _ => Err(invalid()),
+    }
+}
+
+impl Repository {
+    // The refs that keep objects alive: HEAD, everything under refs/ (the
+    // stash included), and the heads of a merge, cherry-pick or revert in
+    // progress
+    pub fn root_ref_names(&self) -> Result<Vec<String>, Error> {
+        let mut names = vec![HEAD.to_string()];
+        names.extend(self.ref_names("")?);
+        for name in [MERGE_HEAD, CHERRY_PICK_HEAD, REVERT_HEAD] {
+            if Path::new(&self.git_path(name)?).exists() {
+                names.push(name.to_string());
+            }
+        }
+        Ok(names)
+    }
+
+    // Every object reachable from the root refs and the values in their
+    // logs. A ref or object that cannot be read is an error rather than
+    // the end of the walk, so that gc never deletes what it leads to.
+    pub fn reachable_objects(&self) -> Result<BTreeSet<Oid>, Error> {
+        let mut queue = Vec::new();
+        for name in self.root_ref_names()? {
+            if let RefValue::Direct(oid) = self.get_ref(&name, false)? {
+                queue.push((oid, None));
+            }
+            for entry in self.read_reflog(&name)? {
+                for oid in [entry.old, entry.new] {
+                    if !oid.is_zero() {
+                        queue.push((oid, None));
+                    }
+                }
+            }
+        }
+
+        let mut reachable = BTreeSet::new();
+        while let Some((oid, obj_type)) = queue.pop() {
+            if !reachable.insert(oid) {
+                continue;
+            }
+            // Tags may point at any object, links say what they lead to
+            let obj_type = match obj_type {
+                Some(obj_type) => obj_type,
+                None => self.get_object(oid)?.0,
+            };
+            match obj_type {
+                ObjectType::Blob => {}
+                ObjectType::Tree => {
+                    for (_, _, entry, entry_type) in self.get_tree_data(oid)? {
+                        queue.push((entry, Some(entry_type)));
+                    }
+                }
+                ObjectType::Commit => {
+                    let commit = self.get_commit(oid)?;
+                    queue.push((commit.tree, Some(ObjectType::Tree)));
+                    queue.extend(
+                        commit
+                            .parents
+                            .iter()
+                            .map(|parent| (*parent, Some(ObjectType::Commit))),
+                    );
+                }
+            }
+        }
+        Ok(reachable)
+    }
+
+    // Deletes the unreachable objects older than the expiry
+    pub fn gc(&self, options: &GcOptions) -> Result<GcReport, Error> {
+        let expire = match &options.expire {
+            Some(expire) => expire.clone(),
+            None => self
+                .get_config("gc.pruneExpire")
+                .unwrap_or_else(|| DEFAULT_PRUNE_EXPIRE.to_string()),
+        };
+        let expire = parse_expire(&expire)?;
+        let reachable = self.reachable_objects()?;
+        let now = SystemTime::now();
+
+        let mut report = GcReport::default();
+        for oid in self.odb.oids()? {
+            if reachable.contains(&oid) {
+                continue;
+            }
+            let stat = self.odb.stat(oid)?;
+            // A clock set back makes objects look recent, and keeps them
+            let age = now.duration_since(stat.modified).unwrap_or_default();
+            match expire {
+                Some(expire) if age >= expire => {
+                    if !options.dry_run {
+                        self.odb.delete(oid)?;
+                    }
+                    report.pruned.push(oid);
+                    report.bytes += stat.size;
+                }
+                _ => report.recent.push(oid),
+            }
+        }
+        Ok(report)
+    }
+}
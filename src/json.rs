@@ -0,0 +1,101 @@
+use crate::oid::Oid;
+use std::fmt;
+
+// Minimal JSON values for --format=json output. Objects keep their keys in
+// insertion order so that the output is stable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(i64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    pub fn object(entries: Vec<(&str, Json)>) -> Json {
+        Json::Object(
+            entries
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value))
+                .collect(),
+        )
+    }
+
+    pub fn string(value: &str) -> Json {
+        Json::String(value.to_string())
+    }
+
+    // A string, or null when absent
+    pub fn optional(value: Option<&str>) -> Json {
+        value.map_or(Json::Null, Json::string)
+    }
+
+    pub fn strings<S: AsRef<str>>(values: &[S]) -> Json {
+        Json::Array(
+            values
+                .iter()
+                .map(|value| Json::string(value.as_ref()))
+                .collect(),
+        )
+    }
+
+    // Object ids as hex strings
+    pub fn oids(oids: &[Oid]) -> Json {
+        Json::Array(
+            oids.iter()
+                .map(|oid| Json::String(oid.to_string()))
+                .collect(),
+        )
+    }
+
+    fn write_string(f: &mut fmt::Formatter, value: &str) -> fmt::Result {
+        f.write_str("\"")?;
+        for c in value.chars() {
+            match c {
+                '"' => f.write_str("\\\"")?,
+                '\\' => f.write_str("\\\\")?,
+                '\n' => f.write_str("\\n")?,
+                '\r' => f.write_str("\\r")?,
+                '\t' => f.write_str("\\t")?,
+                c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+                c => write!(f, "{}", c)?,
+            }
+        }
+        f.write_str("\"")
+    }
+}
+
+// Compact output, on a single line
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Json::Null => f.write_str("null"),
+            Json::Bool(value) => write!(f, "{}", value),
+            Json::Number(value) => write!(f, "{}", value),
+            Json::String(value) => Self::write_string(f, value),
+            Json::Array(values) => {
+                f.write_str("[")?;
+                for (index, value) in values.iter().enumerate() {
+                    if index > 0 {
+                        f.write_str(",")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                f.write_str("]")
+            }
+            Json::Object(entries) => {
+                f.write_str("{")?;
+                for (index, (key, value)) in entries.iter().enumerate() {
+                    if index > 0 {
+                        f.write_str(",")?;
+                    }
+                    Self::write_string(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                f.write_str("}")
+            }
+        }
+    }
+}
@@ -1,10 +1,85 @@
+pub mod atomic;
+pub mod attributes;
+pub mod binary;
 pub mod cli;
+pub mod config;
+pub mod diff3;
 pub mod differ;
+pub mod discovery;
+pub mod error;
+pub mod fsck;
+pub mod gc;
+pub mod graph;
+pub mod json;
+pub mod lock;
+pub mod odb;
+pub mod oid;
+pub mod output;
+pub mod porcelain;
+pub mod rebase;
+pub mod reflog;
+pub mod replay;
 pub mod repository;
+pub mod sequencer;
+pub mod stash;
+
+pub use error::Error;
+pub use oid::Oid;
 
 #[cfg(test)]
 mod tests {
+    use crate::Error;
+    use crate::Oid;
+    use crate::atomic::FsyncPolicy;
+    use crate::differ::{
+        ChangeKind, DiffFormat, DiffOptions, DiffTarget, Differ, FileChange, FileStat,
+        WhitespaceOptions, WordDiffMode,
+    };
+    use crate::oid::HashAlgorithm;
+    use crate::rebase::{REBASE_DIR, RebaseOptions, RebaseState, RebaseStop};
+    use crate::repository::{
+        GIT_DIR, HEAD, InitOptions, MERGE_HEAD, MergeOutcome, ObjectType, RefValue, Repository,
+    };
+    use crate::stash::STASH_REF;
+    use std::fs;
+    use std::path::Path;
+    use tempfile::TempDir;
+    use tempfile::tempdir;
+
+    // The object format of the repositories `init_repo` creates
+    const OBJECT_FORMAT: HashAlgorithm = HashAlgorithm::Sha1;
+
     // This will include all tests from the tests directory
-    include!("tests/repository_tests.rs");
-    include!("tests/diff_tests.rs");
+    include!("tests/helpers.rs");
+    include!("tests/config_tests.rs");
+    include!("tests/sequencer_tests.rs");
+    include!("tests/output_tests.rs");
+    include!("tests/porcelain_tests.rs");
+    include!("tests/cli_tests.rs");
+    include!("tests/discovery_tests.rs");
+    include!("tests/oid_tests.rs");
+    include!("tests/odb_tests.rs");
+    include!("tests/fsck_tests.rs");
+    include!("tests/gc_tests.rs");
+    include!("tests/lock_tests.rs");
+    include!("tests/object_format_tests.rs");
+
+    // These suites run once per object format, each in its own module
+    macro_rules! object_format_suites {
+        ($($module:ident: $format:ident),*) => {$(
+            mod $module {
+                use super::*;
+
+                const OBJECT_FORMAT: HashAlgorithm = HashAlgorithm::$format;
+
+                include!("tests/helpers.rs");
+                include!("tests/repository_tests.rs");
+                include!("tests/diff_tests.rs");
+                include!("tests/rebase_tests.rs");
+                include!("tests/stash_tests.rs");
+            }
+        )*};
+    }
+
+    object_format_suites!(sha1: Sha1, sha256: Sha256);
 }
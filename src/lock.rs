@@ -0,0 +1,184 @@
+use std::fs;
+use std::io::{self, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::atomic::sync_dir;
+use crate::error::Error;
+
+// Added to the name of a file to lock it: "HEAD.lock" locks HEAD
+pub const LOCK_SUFFIX: &str = ".lock";
+
+// Commands that change the worktree hold "index.lock" while they run, so
+// that two of them cannot interleave. bgit has no index, the name is git's.
+pub const WORKTREE_LOCK: &str = "index";
+
+// How long to wait for a lock unless core.lockTimeout says otherwise
+pub const DEFAULT_LOCK_TIMEOUT: Duration = Duration::from_millis(100);
+
+const RETRY_INTERVAL: Duration = Duration::from_millis(10);
+
+// A lock whose owner is not known, because it was killed before writing
+// it or while committing, is only taken for left behind after this long
+pub const STALE_LOCK_AGE: Duration = Duration::from_secs(10 * 60);
+
+// A lock file holds "pid <id>\n", the process holding it, until it is
+// committed
+const OWNER_PREFIX: &str = "pid ";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LockOptions {
+    // How long to wait for another process to release a lock, None to wait
+    // for ever
+    pub timeout: Option<Duration>,
+    // Remove a lock that is already there instead of waiting for it when
+    // it was left by a process that was killed: its owner is no longer
+    // running, or it has none and is older than STALE_LOCK_AGE
+    // (--break-stale-locks)
+    pub break_stale: bool,
+}
+
+impl Default for LockOptions {
+    fn default() -> LockOptions {
+        LockOptions {
+            timeout: Some(DEFAULT_LOCK_TIMEOUT),
+            break_stale: false,
+        }
+    }
+}
+
+impl LockOptions {
+    // core.lockTimeout is in milliseconds, negative to wait for ever. An
+    // invalid value keeps the default.
+    pub fn from_config(timeout: Option<&str>) -> LockOptions {
+        let timeout = match timeout.and_then(|value| value.trim().parse::<i64>().ok()) {
+            Some(millis) if millis < 0 => None,
+            Some(millis) => Some(Duration::from_millis(millis as u64)),
+            None => Some(DEFAULT_LOCK_TIMEOUT),
+        };
+        LockOptions {
+            timeout,
+            ..Default::default()
+        }
+    }
+}
+
+// A lock on `path`, held by creating `path.lock`, which only one process
+// can do. `commit` writes the new content to the lock file and renames it
+// over `path`. Dropping the lock otherwise, also when unwinding from a
+// panic, removes the lock file and leaves `path` as it was.
+#[derive(Debug)]
+pub struct LockFile {
+    path: PathBuf,
+    lock_path: PathBuf,
+    file: Option<fs::File>,
+}
+
+impl LockFile {
+    pub fn acquire(path: &Path, options: &LockOptions) -> Result<LockFile, Error> {
+        let mut lock_path = path.as_os_str().to_os_string();
+        lock_path.push(LOCK_SUFFIX);
+        let lock_path = PathBuf::from(lock_path);
+
+        let deadline = options.timeout.map(|timeout| Instant::now() + timeout);
+        loop {
+            match fs::File::options()
+                .write(true)
+                .create_new(true)
+                .open(&lock_path)
+            {
+                Ok(mut file) => {
+                    // Only there to tell a stale lock, so not worth failing
+                    // for
+                    writeln!(file, "{}{}", OWNER_PREFIX, std::process::id()).ok();
+                    return Ok(LockFile {
+                        path: path.to_path_buf(),
+                        lock_path,
+                        file: Some(file),
+                    });
+                }
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    if options.break_stale && is_stale(&lock_path) {
+                        fs::remove_file(&lock_path).ok();
+                        continue;
+                    }
+                    if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                        return Err(Error::Locked(lock_path.display().to_string()));
+                    }
+                    thread::sleep(RETRY_INTERVAL);
+                }
+                Err(e) => {
+                    return Err(Error::io(
+                        format!("Failed to create {}", lock_path.display()),
+                        e,
+                    ));
+                }
+            }
+        }
+    }
+
+    // Replaces the locked file with `content`, and releases the lock
+    pub fn commit(mut self, content: &[u8], fsync: bool) -> io::Result<()> {
+        if let Some(mut file) = self.file.take() {
+            // The owner gives way to the content
+            file.set_len(0)?;
+            file.rewind()?;
+            file.write_all(content)?;
+            if fsync {
+                file.sync_all()?;
+            }
+        }
+        fs::rename(&self.lock_path, &self.path)?;
+        // The lock file is gone, and may already be another process's
+        self.lock_path.clear();
+        if fsync {
+            sync_dir(&self.path)?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for LockFile {
+    fn drop(&mut self) {
+        if !self.lock_path.as_os_str().is_empty() {
+            self.file.take();
+            fs::remove_file(&self.lock_path).ok();
+        }
+    }
+}
+
+// Whether the lock at `lock_path` was left by a process that was killed
+fn is_stale(lock_path: &Path) -> bool {
+    let owner = fs::read_to_string(lock_path).ok().and_then(|content| {
+        content
+            .trim_end()
+            .strip_prefix(OWNER_PREFIX)
+            .and_then(|pid| pid.parse::<u32>().ok())
+    });
+    match owner.and_then(process_running) {
+        Some(running) => !running,
+        None => fs::metadata(lock_path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| modified.elapsed().ok())
+            .is_some_and(|age| age >= STALE_LOCK_AGE),
+    }
+}
+
+// Whether process `pid` exists, None when that cannot be told
+#[cfg(unix)]
+fn process_running(pid: u32) -> Option<bool> {
+    let pid = libc::pid_t::try_from(pid).ok().filter(|&pid| pid > 0)?;
+    // Signal 0 is not sent, only checked. EPERM: it exists but belongs to
+    // another user.
+    if unsafe { libc::kill(pid, 0) } == 0 {
+        return Some(true);
+    }
+    Some(io::Error::last_os_error().raw_os_error() == Some(libc::EPERM))
+}
+
+#[cfg(not(unix))]
+fn process_running(_pid: u32) -> Option<bool> {
+    None
+}
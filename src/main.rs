@@ -1,212 +1,574 @@
-mod cli;
-mod differ;
-mod repository;
 mod visualizer;
 
-use cli::Command;
-use differ::Differ;
-use repository::{MERGE_HEAD, ObjectType, Repository};
+use bgit::Error;
+use bgit::cli::{self, CatFileMode, Command, EXIT_FAILURE, EXIT_FATAL, EXIT_USAGE};
+use bgit::differ::{DiffFormat, DiffOptions, DiffTarget, Differ};
+use bgit::output::{self, Color, ColorMode, OutputFormat, paint};
+use bgit::rebase::{RebaseOutcome, RebaseState, RebaseStop};
+use bgit::repository::{Commit, LogEntry, MERGE_HEAD, MergeOutcome, ObjectType, Repository};
+use bgit::sequencer::{SequencerAction, SequencerOutcome, SequencerState};
+use bgit::stash::StashCommand;
+use std::env;
 use std::fs;
+use std::io::{self, Write};
 use std::path::Path;
+use std::process;
 use visualizer::Visualizer;
 
 fn main() {
-    let repo = Repository::new(".");
+    let (options, command) = match cli::parse_args() {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(EXIT_USAGE);
+        }
+    };
+    for directory in &options.directories {
+        if let Err(e) = env::set_current_dir(directory) {
+            eprintln!("fatal: cannot change to '{}': {}", directory, e);
+            process::exit(EXIT_FATAL);
+        }
+    }
+    let mut repo = if matches!(command, Command::Init(..)) {
+        // A new repository goes in the current directory, even inside
+        // another one
+        Repository::new(".")
+    } else {
+        match Repository::open(".") {
+            Ok(repo) => repo,
+            Err(_) if !command.needs_repository() => Repository::new("."),
+            Err(e) => fail(e),
+        }
+    };
+
+    // --color wins over color.<command>, which wins over color.ui
+    let stdout_is_tty = output::stdout_is_tty();
+    let configured_color = command
+        .color_config()
+        .and_then(|name| repo.get_config(name))
+        .or_else(|| repo.get_config("color.ui"))
+        .and_then(|value| ColorMode::parse(&value).ok());
+    let color = options.color.or(configured_color).unwrap_or_default();
+    output::set_color(output::should_color(color, stdout_is_tty));
+
+    if stdout_is_tty && !options.no_pager && command.uses_pager() {
+        output::start_pager(&output::pager_command(repo.get_config("core.pager")));
+    }
+
+    repo.locks.break_stale = options.break_stale_locks;
+
+    match run(repo, command) {
+        Ok(code) => process::exit(code),
+        Err(e) => fail(e),
+    }
+}
+
+fn fail(error: Error) -> ! {
+    let code = cli::error_exit_code(&error);
+    let prefix = if code == EXIT_FATAL { "fatal" } else { "error" };
+    eprintln!("{}: {}", prefix, cli::error_message(&error));
+    if let Some(hint) = cli::error_hint(&error) {
+        eprintln!("hint: {}", hint);
+    }
+    process::exit(code);
+}
 
-    match cli::parse_args() {
-        Command::Init => {
-            if let Err(e) = repo.init() {
-                eprintln!("Error: {}", e);
-                std::process::exit(1);
+// Runs `command` and returns the exit code
+fn run(repo: Repository, command: Command) -> Result<i32, Error> {
+    if command.needs_worktree() {
+        repo.require_worktree()?;
+    }
+    // Released when the command returns, or panics
+    let _lock = if command.changes_worktree() {
+        Some(repo.lock_worktree()?)
+    } else {
+        None
+    };
+
+    match command {
+        Command::Help(None) => print!("{}", cli::general_help()),
+        Command::Help(Some(name)) => {
+            if let Some(spec) = cli::command_spec(name) {
+                print!("{}", spec.help());
             }
         }
-        Command::HashObject(file_path) => match fs::read(&file_path) {
-            Ok(data) => match repo.hash_object(&data, ObjectType::Blob) {
-                Ok(hash) => println!("{}", hash),
-                Err(e) => {
-                    eprintln!("Error: {}", e);
-                    std::process::exit(1);
+        Command::Version => println!("bgit version {}", env!("CARGO_PKG_VERSION")),
+        Command::Init(directory, options) => {
+            let repo = Repository::init_at(directory.as_deref().unwrap_or("."), &options)?;
+            let path = fs::canonicalize(&repo.gitdir)
+                .map_err(|e| Error::io("Failed to get absolute path", e))?;
+            println!("Initialized empty bGit repository in {}", path.display());
+        }
+        Command::HashObject(file_path) => {
+            let data = fs::read(&file_path)
+                .map_err(|e| Error::io(format!("Error reading file {}", file_path), e))?;
+            println!("{}", repo.hash_object(&data, ObjectType::Blob)?);
+        }
+        Command::CatFile(name, mode) => {
+            let oid = repo.get_oid_hash(&name)?;
+            let (obj_type, content) = match repo.get_object(oid) {
+                Err(Error::NotFound(_)) if mode == CatFileMode::Exists => {
+                    return Ok(EXIT_FAILURE);
                 }
-            },
-            Err(e) => {
-                eprintln!("Error reading file: {}", e);
-                std::process::exit(1);
-            }
-        },
-        Command::CatFile(hash) => {
-            match repo.get_object(&hash) {
-                Ok(data) => {
-                    // Convert bytes to string and print
-                    if let Ok(content) = String::from_utf8(data) {
-                        print!("{}", content);
-                    } else {
-                        eprintln!("Error: Object content is not valid UTF-8");
-                        std::process::exit(1);
+                result => result?,
+            };
+            match mode {
+                CatFileMode::Exists => {}
+                CatFileMode::Type => println!("{}", obj_type),
+                CatFileMode::Size => println!("{}", content.len()),
+                CatFileMode::Pretty if obj_type == ObjectType::Tree => {
+                    for (mode, name, hash, obj_type) in repo.get_tree_data(oid)? {
+                        println!("{:0>6} {} {}\t{}", mode, obj_type, hash, name);
                     }
                 }
-                Err(e) => {
-                    eprintln!("Error: {}", e);
-                    std::process::exit(1);
-                }
+                // Blobs may not be text, they are written as they are
+                CatFileMode::Raw | CatFileMode::Pretty => io::stdout()
+                    .write_all(&content)
+                    .map_err(|e| Error::io("Failed to write the object", e))?,
             }
         }
-        Command::WriteTree => match repo.create_tree(Path::new(&repo.worktree)) {
-            Ok(hash) => println!("{}", hash),
-            Err(e) => {
-                eprintln!("Error: {}", e);
-                std::process::exit(1);
-            }
-        },
+        Command::WriteTree => println!("{}", repo.create_tree(Path::new(&repo.worktree))?),
         Command::ReadTree(tree_oid) => {
-            let worktree_path = Path::new(&repo.worktree);
-            match repo.read_tree(&tree_oid, worktree_path) {
-                Ok(_) => println!("Tree {} extracted successfully", tree_oid),
-                Err(e) => {
-                    eprintln!("Error: {}", e);
-                    std::process::exit(1);
+            repo.read_tree(repo.get_oid_hash(&tree_oid)?, Path::new(&repo.worktree))?;
+            println!("Tree {} extracted successfully", tree_oid);
+        }
+        Command::GetTree(tree_oid) => {
+            for (mode, name, hash, obj_type) in repo.get_tree_data(repo.get_oid_hash(&tree_oid)?)? {
+                println!("{} {:?} {} {}", mode, obj_type, name, hash);
+            }
+        }
+        Command::Commit(message) => println!("{}", repo.create_commit(&message)?),
+        Command::Log(mut options) => {
+            options.follow = options
+                .follow
+                .map(|path| repo.resolve_path(&path))
+                .transpose()?;
+            if options.format == OutputFormat::Json {
+                println!("{}", repo.log_json(&options)?);
+            } else {
+                let differ = diff_format_differ(&repo, options.diff.unwrap_or_default());
+                for entry in repo.log_with_options(&options)? {
+                    print_log_entry(&differ, &entry)?;
                 }
             }
         }
-        Command::GetTree(tree_oid) => match repo.get_tree_data(&tree_oid) {
-            Ok(data) => {
-                for (mode, name, hash, obj_type) in data {
-                    println!("{} {:?} {} {}", mode, obj_type, name, hash);
+        Command::Checkout(commit_hash) => {
+            repo.checkout(&commit_hash)?;
+            println!("Checked out commit {}", commit_hash);
+        }
+        Command::Tag(tag_name, commit_hash) => {
+            repo.create_tag(&tag_name, repo.get_oid_hash(&commit_hash)?)?;
+            println!("Tag {} created successfully", tag_name);
+        }
+        Command::Visualize => {
+            Visualizer::new(repo).visualize()?;
+        }
+        Command::IterRefs(OutputFormat::Json) => println!("{}", repo.refs_json()?),
+        Command::IterRefs(OutputFormat::Text) => {
+            // Branches without commits have no commit to show
+            for (name, value) in repo.iter_refs("")? {
+                if let Some(oid) = value.oid() {
+                    println!("{} {}", oid, name);
                 }
             }
-            Err(e) => {
-                eprintln!("Error: {}", e);
-                std::process::exit(1);
+        }
+        Command::Branch(None, OutputFormat::Json) => println!("{}", repo.branches_json()?),
+        Command::Branch(None, OutputFormat::Text) => {
+            for name in repo.iter_branch_names()? {
+                println!("{}", name);
             }
+        }
+        Command::Branch(Some(branch_name), _) => {
+            repo.create_branch(&branch_name, None)?;
+            println!("Branch {} created successfully", branch_name);
+        }
+        Command::Status(options) => match options.porcelain {
+            Some(version) => print!("{}", repo.status_porcelain(version, &options)?),
+            None => print_status(&repo)?,
         },
-        Command::Commit(message) => match repo.create_commit(&message) {
-            Ok(hash) => println!("{}", hash),
-            Err(e) => {
-                eprintln!("Error: {}", e);
-                std::process::exit(1);
+        Command::Reset(commit_hash) => {
+            repo.reset(repo.get_oid_hash(&commit_hash)?)?;
+            println!("Reset to commit {}", commit_hash);
+        }
+        Command::Show(commit_hash, _, OutputFormat::Json) => {
+            println!("{}", repo.show_json(&commit_hash)?)
+        }
+        Command::Show(commit_hash, format, _) => {
+            let show = repo.show(repo.get_oid_hash(&commit_hash)?)?;
+            print_commit(&show.commit);
+            let differ = diff_format_differ(&repo, format);
+            for diff in &show.diffs {
+                println!("{}", differ.format_tree_diff(diff)?);
             }
-        },
-        Command::Log => match repo.log() {
-            Ok(_) => (),
-            Err(e) => {
-                eprintln!("Error: {}", e);
-                std::process::exit(1);
+        }
+        Command::Diff(target, mut options) => {
+            options.paths = resolve_paths(&repo, &options.paths)?;
+            let diff = repo.diff_target(&target, &options)?;
+            if !options.quiet {
+                if diff.is_empty() {
+                    println!("No changes");
+                } else {
+                    println!("{}", diff);
+                }
             }
-        },
-        Command::Checkout(commit_hash) => match repo.checkout(&commit_hash) {
-            Ok(_) => println!("Checked out commit {}", commit_hash),
-            Err(e) => {
-                eprintln!("Error: {}", e);
-                std::process::exit(1);
+            // Like diff(1), --no-index always reports differences
+            let changed = match target {
+                DiffTarget::NoIndex(..) => !diff.is_empty(),
+                _ if options.exit_code => !repo.diff_target_changes(&target, &options)?.is_empty(),
+                _ => false,
+            };
+            if changed {
+                return Ok(EXIT_FAILURE);
             }
-        },
-        Command::Tag(tag_name, commit_hash) => match repo.create_tag(&tag_name, &commit_hash) {
-            Ok(_) => println!("Tag {} created successfully", tag_name),
-            Err(e) => {
-                eprintln!("Error: {}", e);
-                std::process::exit(1);
+        }
+        Command::Merge(branch_name, options) => {
+            match repo.merge_with_options(&branch_name, &options)? {
+                MergeOutcome::FastForward => println!(
+                    "Successfully merged branch {} into current branch.\nFast-forward merge, no need to commit.",
+                    branch_name
+                ),
+                MergeOutcome::Merged => println!(
+                    "Successfully merged branch {} into current branch.\nPlease commit the merge.",
+                    branch_name
+                ),
+                MergeOutcome::Conflicted(paths) => {
+                    print_conflicts(&paths);
+                    println!("Automatic merge failed; fix conflicts and then commit the result.");
+                    return Ok(EXIT_FAILURE);
+                }
             }
+        }
+        Command::Rebase(branch_name, options) => {
+            return print_rebase_outcome(&repo, repo.rebase_with_options(&branch_name, &options)?);
+        }
+        Command::RebaseContinue => return print_rebase_outcome(&repo, repo.rebase_continue()?),
+        Command::RebaseSkip => return print_rebase_outcome(&repo, repo.rebase_skip()?),
+        Command::RebaseAbort => {
+            let state = repo.rebase_abort()?;
+            println!(
+                "Rebase aborted, HEAD restored to {}.",
+                state.orig_head.short(7)
+            );
+        }
+        Command::Config(key, Some(value)) => repo.set_config(&key, &value)?,
+        Command::Config(key, None) => match repo.get_config(&key) {
+            Some(value) => println!("{}", value),
+            None => return Ok(EXIT_FAILURE),
         },
-        Command::Visualize => match Visualizer::new(repo).visualize() {
-            Ok(_output) => (),
-            Err(e) => {
-                eprintln!("Error: {}", e);
-                std::process::exit(1);
+        Command::CherryPick(commits, options) => {
+            return print_sequencer_outcome(&repo, repo.cherry_pick(&commits, &options)?);
+        }
+        Command::Revert(commits, options) => {
+            return print_sequencer_outcome(&repo, repo.revert(&commits, &options)?);
+        }
+        Command::SequencerContinue => {
+            return print_sequencer_outcome(&repo, repo.sequencer_continue()?);
+        }
+        Command::SequencerAbort => {
+            let state = repo.sequencer_abort()?;
+            println!(
+                "{} aborted, HEAD restored to {}.",
+                state.action.command(),
+                state.orig_head.short(7)
+            );
+        }
+        Command::Fsck(options) => {
+            let report = repo.fsck(&options)?;
+            for problem in &report.problems {
+                eprintln!("error: {}", problem);
             }
-        },
-        Command::IterRefs => match repo.iter_refs("") {
-            Ok(output) => println!("{:?}", output),
-            Err(e) => {
-                eprintln!("Error: {}", e);
-                std::process::exit(1);
+            for (obj_type, oid) in &report.unreachable {
+                println!("unreachable {} {}", obj_type, oid);
             }
-        },
-        Command::Branch(branch_name) => {
-            if let Some(branch_name) = branch_name {
-                match repo.create_branch(&branch_name, None) {
-                    Ok(_) => println!("Branch {} created successfully", branch_name),
-                    Err(e) => {
-                        eprintln!("Error: {}", e);
-                        std::process::exit(1);
+            for (obj_type, oid) in &report.dangling {
+                println!("dangling {} {}", obj_type, oid);
+            }
+            if !report.problems.is_empty() {
+                return Ok(EXIT_FAILURE);
+            }
+        }
+        Command::Gc(options) => {
+            let report = repo.gc(&options)?;
+            let verb = if options.dry_run {
+                "Would prune"
+            } else {
+                "Pruned"
+            };
+            for oid in &report.pruned {
+                println!("{} {}", verb, oid);
+            }
+            println!(
+                "{} {} unreachable objects, {} bytes.",
+                verb,
+                report.pruned.len(),
+                report.bytes
+            );
+            if !report.recent.is_empty() {
+                println!(
+                    "Kept {} unreachable objects more recent than the expiry.",
+                    report.recent.len()
+                );
+            }
+        }
+        Command::Stash(command) => {
+            let conflicts = match command {
+                StashCommand::Push { message, paths } => {
+                    let stash =
+                        repo.stash_push(message.as_deref(), &resolve_paths(&repo, &paths)?)?;
+                    println!(
+                        "Saved working directory and local changes: {}",
+                        repo.get_commit(stash)?.message
+                    );
+                    Vec::new()
+                }
+                StashCommand::List => {
+                    for (index, entry) in repo.stash_list()?.iter().enumerate() {
+                        println!("stash@{{{}}}: {}", index, entry.message);
                     }
+                    Vec::new()
                 }
-            } else {
-                match repo.iter_branch_names() {
-                    Ok(names) => names.iter().for_each(|name| {
-                        println!("{}", name);
-                    }),
-                    Err(e) => {
-                        eprintln!("Error: {}", e);
-                        std::process::exit(1);
+                StashCommand::Show { stash, patch } => {
+                    println!("{}", repo.stash_show(stash.as_deref(), patch)?);
+                    Vec::new()
+                }
+                StashCommand::Apply(stash) => {
+                    let (name, _, _) = repo.stash_resolve(stash.as_deref())?;
+                    let conflicts = repo.stash_apply(stash.as_deref())?;
+                    if conflicts.is_empty() {
+                        println!("Applied {}", name);
                     }
+                    print_conflicts(&conflicts);
+                    conflicts
                 }
+                StashCommand::Pop(stash) => {
+                    let (name, _, _) = repo.stash_resolve(stash.as_deref())?;
+                    print_stash_pop(&name, repo.stash_pop(stash.as_deref())?)
+                }
+                StashCommand::Drop(stash) => {
+                    let (name, _, _) = repo.stash_resolve(stash.as_deref())?;
+                    println!("Dropped {} ({})", name, repo.stash_drop(stash.as_deref())?);
+                    Vec::new()
+                }
+                StashCommand::Branch(branch_name, stash) => {
+                    let (name, _, _) = repo.stash_resolve(stash.as_deref())?;
+                    print_stash_pop(&name, repo.stash_branch(&branch_name, stash.as_deref())?)
+                }
+            };
+            if !conflicts.is_empty() {
+                return Ok(EXIT_FAILURE);
             }
         }
-        Command::Status => {
-            let head = repo.get_oid_hash("@").unwrap();
-            let branch = repo.get_branch_name().unwrap();
-            let changed_files = Differ::new(&repo).iter_changed_files().unwrap();
+    }
+    Ok(0)
+}
 
-            if branch.is_none() {
-                println!("HEAD detached at {}", head);
-            } else {
-                println!("On branch {}", branch.unwrap());
-            }
+// Paths from the command line, relative to the current directory
+fn resolve_paths(repo: &Repository, paths: &[String]) -> Result<Vec<String>, Error> {
+    paths.iter().map(|path| repo.resolve_path(path)).collect()
+}
 
-            if let Ok(merge_head) = repo.get_ref(MERGE_HEAD, true) {
-                if !merge_head.value.is_empty() {
-                    println!("Merging with {}", merge_head.value);
-                }
-            }
+fn print_status(repo: &Repository) -> Result<(), Error> {
+    let branch = repo.get_branch_name()?;
+    let changed_files = Differ::new(repo).iter_changed_files()?;
 
-            println!("\nCurrent changes:");
-            for file in changed_files {
-                println!("{}", file);
-            }
+    match branch {
+        Some(branch) => println!("On branch {}", branch),
+        None => println!("HEAD detached at {}", repo.get_oid_hash("@")?),
+    }
+
+    if let Ok(state) = RebaseState::load(repo) {
+        println!("Rebasing onto {}", state.onto);
+        if let Some(stopped) = state.stopped {
+            println!("Stopped at {} due to conflicts", stopped);
         }
-        Command::Reset(commit_hash) => match repo.reset(&commit_hash) {
-            Ok(_) => println!("Reset to commit {}", commit_hash),
-            Err(e) => {
-                eprintln!("Error: {}", e);
-                std::process::exit(1);
-            }
-        },
-        Command::Show(commit_hash) => match repo.show(&commit_hash) {
-            Ok(_) => (),
-            Err(e) => {
-                eprintln!("Error: {}", e);
-                std::process::exit(1);
-            }
-        },
-        Command::Diff => match repo.diff() {
-            Ok(diff) => {
-                if diff.is_empty() {
-                    println!("No changes");
-                } else {
-                    println!("{}", diff);
-                }
-            }
-            Err(e) => {
-                eprintln!("Error: {}", e);
-                std::process::exit(1);
-            }
-        },
-        Command::Merge(branch_name) => match repo.merge(&branch_name) {
-            Ok(_) => (),
-            Err(e) => {
-                eprintln!("Error: {}", e);
-                std::process::exit(1);
-            }
-        },
-        Command::Rebase(branch_name) => match repo.rebase(&branch_name) {
-            Ok(_) => (),
-            Err(e) => {
-                eprintln!("Error: {}", e);
-                std::process::exit(1);
-            }
+    }
+
+    if let Ok(state) = SequencerState::load(repo) {
+        match state.action {
+            SequencerAction::Pick => println!("Cherry-pick in progress"),
+            SequencerAction::Revert => println!("Revert in progress"),
+        }
+        if let Some(stopped) = state.stopped {
+            println!("Stopped at {} due to conflicts", stopped);
+        }
+    }
+
+    if let Some(merge_head) = repo
+        .get_ref(MERGE_HEAD, true)
+        .ok()
+        .and_then(|value| value.oid())
+    {
+        println!("Merging with {}", merge_head);
+    }
+
+    println!("\nCurrent changes:");
+    for file in changed_files {
+        println!("{}", file);
+    }
+    Ok(())
+}
+
+fn print_conflicts(paths: &[String]) {
+    for path in paths {
+        println!("CONFLICT (content): Merge conflict in {}", path);
+    }
+}
+
+fn subject(message: &str) -> &str {
+    message.lines().next().unwrap_or("")
+}
+
+// Renders the diffs of `log` and `show` in one format
+fn diff_format_differ(repo: &Repository, format: DiffFormat) -> Differ<'_> {
+    Differ::with_options(
+        repo,
+        DiffOptions {
+            format,
+            ..Default::default()
         },
-        Command::Unknown(msg) => {
-            eprintln!("Error: {}", msg);
-            std::process::exit(1);
+    )
+}
+
+fn print_log_entry(differ: &Differ, entry: &LogEntry) -> Result<(), Error> {
+    let commit = &entry.commit;
+    // Branches and tags by their last path component
+    let mut refs = Vec::new();
+    for (prefix, kind) in [("refs/heads/", "branch"), ("refs/tags/", "tag")] {
+        for name in entry.refs.iter().filter(|name| name.starts_with(prefix)) {
+            refs.push(format!(
+                "{}: {}",
+                kind,
+                name.split('/').next_back().unwrap()
+            ));
+        }
+    }
+
+    println!();
+    println!(
+        "{}",
+        paint(Color::Yellow, &format!("commit {}", commit.oid))
+    );
+    for parent in &commit.parents {
+        println!("parents {}", parent);
+    }
+    println!("tree {}", commit.tree);
+    if let Some(author) = &commit.author {
+        println!("Author: {}", author);
+    }
+    println!("Date:   {}", commit.timestamp);
+    if !refs.is_empty() {
+        println!("Refs:   {}", refs.join(", "));
+    }
+    println!();
+    println!("    {}", commit.message);
+    println!();
+    if let Some(diff) = &entry.diff {
+        println!("{}", differ.format_tree_diff(diff)?);
+    }
+    Ok(())
+}
+
+fn print_commit(commit: &Commit) {
+    println!("Commit: {}", commit.oid);
+    println!("Tree: {}", commit.tree);
+    if commit.parents.is_empty() {
+        println!("Parent: None");
+    }
+    for parent in &commit.parents {
+        println!("Parent: {}", parent);
+    }
+    if let Some(author) = &commit.author {
+        println!("Author: {}", author);
+    }
+    println!("Timestamp: {}", commit.timestamp);
+    println!("Message: {}", commit.message);
+}
+
+// A rebase that stopped for the user exits with 1
+fn print_rebase_outcome(repo: &Repository, outcome: RebaseOutcome) -> Result<i32, Error> {
+    for oid in &outcome.skipped {
+        println!(
+            "Skipped previously applied commit {}: {}",
+            oid.short(7),
+            subject(&repo.get_commit(*oid)?.message)
+        );
+    }
+    match outcome.stopped {
+        None => {
+            println!(
+                "Successfully rebased branch onto target {}.",
+                outcome.onto.short(7)
+            );
+            Ok(0)
         }
+        Some(RebaseStop::Conflicted {
+            commit,
+            subject,
+            paths,
+        }) => {
+            print_conflicts(&paths);
+            println!(
+                "Could not apply {}... {}\nResolve all conflicts manually, then run \"bgit rebase --continue\".\nTo skip this commit run \"bgit rebase --skip\", to restore the original branch run \"bgit rebase --abort\".",
+                commit.short(7),
+                subject
+            );
+            Ok(EXIT_FAILURE)
+        }
+        Some(RebaseStop::Edit { commit, subject }) => {
+            println!(
+                "Stopped at {}... {}\nYou can amend the commit now by changing the working directory,\nthen run \"bgit rebase --continue\".",
+                commit.short(7),
+                subject
+            );
+            Ok(0)
+        }
+        Some(RebaseStop::ExecFailed(command)) => {
+            println!(
+                "Execution failed: {}\nYou can fix the problem, and then run \"bgit rebase --continue\".",
+                command
+            );
+            Ok(EXIT_FAILURE)
+        }
+    }
+}
+
+// A cherry-pick or revert that stopped on conflicts exits with 1
+fn print_sequencer_outcome(repo: &Repository, outcome: SequencerOutcome) -> Result<i32, Error> {
+    let command = outcome.action.command();
+    for oid in &outcome.empty {
+        println!("The {} of {} is empty, skipping.", command, oid.short(7));
+    }
+    for oid in &outcome.commits {
+        println!(
+            "[{}] {}",
+            oid.short(7),
+            subject(&repo.get_commit(*oid)?.message)
+        );
+    }
+    match outcome.conflicted {
+        Some((oid, paths)) => {
+            print_conflicts(&paths);
+            println!(
+                "Could not {} {}... {}\nResolve all conflicts manually, then run \"bgit {} --continue\".\nTo cancel the whole operation run \"bgit {} --abort\".",
+                command,
+                oid.short(7),
+                subject(&repo.get_commit(oid)?.message),
+                command,
+                command
+            );
+            Ok(EXIT_FAILURE)
+        }
+        None => Ok(0),
+    }
+}
+
+// Popping a stash keeps it when it left conflicts
+fn print_stash_pop(name: &str, conflicts: Vec<String>) -> Vec<String> {
+    if conflicts.is_empty() {
+        println!("Applied {}", name);
+    } else {
+        print_conflicts(&conflicts);
+        println!("The stash entry is kept in case you need it again.");
     }
+    conflicts
 }
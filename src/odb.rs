@@ -0,0 +1,230 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use crate::atomic::write_atomic;
+use crate::error::Error;
+use crate::oid::{HashAlgorithm, Oid};
+
+// Where a repository keeps its objects: loose files, memory, and later
+// packs. Objects are stored as written: the "{type} {size}\0" header
+// followed by the content, under the id of both.
+// Repository builds and parses the header, a backend only stores bytes.
+pub trait ObjectDatabase {
+    // The object stored under `oid`, NotFound when there is none
+    fn read(&self, oid: Oid) -> Result<Vec<u8>, Error>;
+
+    // Stores `data` under `oid`. Writing an object that exists is a no-op
+    // as far as readers can tell, apart from its modification time.
+    fn write(&self, oid: Oid, data: &[u8]) -> Result<(), Error>;
+
+    fn exists(&self, oid: Oid) -> Result<bool, Error>;
+
+    // The ids of every stored object, sorted
+    fn oids(&self) -> Result<Vec<Oid>, Error>;
+
+    // The space an object takes and when it was written, for gc
+    fn stat(&self, oid: Oid) -> Result<ObjectStat, Error>;
+
+    // Removes an object, NotFound when there is none
+    fn delete(&self, oid: Oid) -> Result<(), Error>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ObjectStat {
+    // Bytes taken by the stored object, header included
+    pub size: u64,
+    pub modified: SystemTime,
+}
+
+// One file per object in `objects/`, named by its id: the first two hex
+// digits are a directory, the rest the file name. Files are read-only and
+// only ever appear complete, written to a temporary file first.
+pub struct LooseObjectDatabase {
+    dir: PathBuf,
+    // Flush each object to disk before it counts as written
+    fsync: bool,
+    // Only files named by an id of this format are objects
+    object_format: HashAlgorithm,
+}
+
+impl LooseObjectDatabase {
+    pub fn new(dir: impl AsRef<Path>) -> LooseObjectDatabase {
+        LooseObjectDatabase {
+            dir: dir.as_ref().to_path_buf(),
+            fsync: false,
+            object_format: HashAlgorithm::default(),
+        }
+    }
+
+    pub fn with_fsync(self, fsync: bool) -> LooseObjectDatabase {
+        LooseObjectDatabase { fsync, ..self }
+    }
+
+    pub fn with_object_format(self, object_format: HashAlgorithm) -> LooseObjectDatabase {
+        LooseObjectDatabase {
+            object_format,
+            ..self
+        }
+    }
+
+    fn object_path(&self, oid: Oid) -> PathBuf {
+        let hex = oid.to_string();
+        let (dir, file) = hex.split_at(2);
+        self.dir.join(dir).join(file)
+    }
+}
+
+impl ObjectDatabase for LooseObjectDatabase {
+    fn read(&self, oid: Oid) -> Result<Vec<u8>, Error> {
+        fs::read(self.object_path(oid)).map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => Error::NotFound(oid),
+            _ => Error::io(format!("Failed to read object {}", oid), e),
+        })
+    }
+
+    fn write(&self, oid: Oid, data: &[u8]) -> Result<(), Error> {
+        let path = self.object_path(oid);
+        // The same id is the same content. Its time is refreshed instead,
+        // so that gc sees the object as just written; when that fails, the
+        // object is written again.
+        if let Ok(file) = fs::File::open(&path)
+            && file.set_modified(SystemTime::now()).is_ok()
+        {
+            return Ok(());
+        }
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .map_err(|e| Error::io("Failed to create object directory", e))?;
+        }
+        write_atomic(&path, data, self.fsync, true)
+            .map_err(|e| Error::io(format!("Failed to write object {}", oid), e))
+    }
+
+    fn exists(&self, oid: Oid) -> Result<bool, Error> {
+        Ok(self.object_path(oid).is_file())
+    }
+
+    fn oids(&self) -> Result<Vec<Oid>, Error> {
+        let mut oids = Vec::new();
+        let dirs = match fs::read_dir(&self.dir) {
+            Ok(dirs) => dirs,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(oids),
+            Err(e) => return Err(Error::io("Failed to read objects directory", e)),
+        };
+
+        for dir in dirs {
+            let dir = dir.map_err(|e| Error::io("Failed to read objects directory", e))?;
+            let prefix = dir.file_name().to_string_lossy().to_string();
+            // Anything else than the fan-out directories is not a loose
+            // object
+            if prefix.len() != 2 || !dir.path().is_dir() {
+                continue;
+            }
+            for file in fs::read_dir(dir.path())
+                .map_err(|e| Error::io("Failed to read objects directory", e))?
+            {
+                let file = file.map_err(|e| Error::io("Failed to read objects directory", e))?;
+                let name = format!("{}{}", prefix, file.file_name().to_string_lossy());
+                if let Ok(oid) = name.parse::<Oid>()
+                    && oid.algorithm() == self.object_format
+                {
+                    oids.push(oid);
+                }
+            }
+        }
+        oids.sort();
+        Ok(oids)
+    }
+
+    fn stat(&self, oid: Oid) -> Result<ObjectStat, Error> {
+        let metadata = fs::metadata(self.object_path(oid)).map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => Error::NotFound(oid),
+            _ => Error::io(format!("Failed to read object {}", oid), e),
+        })?;
+        Ok(ObjectStat {
+            size: metadata.len(),
+            modified: metadata
+                .modified()
+                .map_err(|e| Error::io(format!("Failed to read object {}", oid), e))?,
+        })
+    }
+
+    fn delete(&self, oid: Oid) -> Result<(), Error> {
+        let path = self.object_path(oid);
+        fs::remove_file(&path).map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => Error::NotFound(oid),
+            _ => Error::io(format!("Failed to delete object {}", oid), e),
+        })?;
+        // The fan-out directory goes with its last object
+        if let Some(dir) = path.parent() {
+            fs::remove_dir(dir).ok();
+        }
+        Ok(())
+    }
+}
+
+// Objects kept in memory and lost with the repository, for callers that
+// only need to build and merge trees
+#[derive(Default)]
+pub struct MemoryObjectDatabase {
+    // Each object with the time it was last written
+    objects: Mutex<BTreeMap<Oid, (SystemTime, Vec<u8>)>>,
+}
+
+impl MemoryObjectDatabase {
+    pub fn new() -> MemoryObjectDatabase {
+        MemoryObjectDatabase::default()
+    }
+
+    fn objects(&self) -> std::sync::MutexGuard<'_, BTreeMap<Oid, (SystemTime, Vec<u8>)>> {
+        // A panic while holding the lock cannot leave a half-written entry
+        self.objects
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl ObjectDatabase for MemoryObjectDatabase {
+    fn read(&self, oid: Oid) -> Result<Vec<u8>, Error> {
+        self.objects()
+            .get(&oid)
+            .map(|(_, data)| data.clone())
+            .ok_or(Error::NotFound(oid))
+    }
+
+    fn write(&self, oid: Oid, data: &[u8]) -> Result<(), Error> {
+        // Writing an object again makes it recent, as a loose file would be
+        self.objects()
+            .insert(oid, (SystemTime::now(), data.to_vec()));
+        Ok(())
+    }
+
+    fn exists(&self, oid: Oid) -> Result<bool, Error> {
+        Ok(self.objects().contains_key(&oid))
+    }
+
+    fn oids(&self) -> Result<Vec<Oid>, Error> {
+        Ok(self.objects().keys().copied().collect())
+    }
+
+    fn stat(&self, oid: Oid) -> Result<ObjectStat, Error> {
+        self.objects()
+            .get(&oid)
+            .map(|(modified, data)| ObjectStat {
+                size: data.len() as u64,
+                modified: *modified,
+            })
+            .ok_or(Error::NotFound(oid))
+    }
+
+    fn delete(&self, oid: Oid) -> Result<(), Error> {
+        self.objects()
+            .remove(&oid)
+            .map(|_| ())
+            .ok_or(Error::NotFound(oid))
+    }
+}
@@ -0,0 +1,151 @@
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::error::Error;
+
+// Length in bytes of the longest object id, a SHA-256
+pub const MAX_OID_LEN: usize = 32;
+
+// The hash object ids are computed with. A repository uses one, recorded
+// as extensions.objectFormat in its config; SHA-1 when there is none.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum HashAlgorithm {
+    #[default]
+    Sha1,
+    Sha256,
+}
+
+impl HashAlgorithm {
+    // The name used by `init --object-format` and in the config
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HashAlgorithm::Sha1 => "sha1",
+            HashAlgorithm::Sha256 => "sha256",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<HashAlgorithm> {
+        match name.to_lowercase().as_str() {
+            "sha1" => Some(HashAlgorithm::Sha1),
+            "sha256" => Some(HashAlgorithm::Sha256),
+            _ => None,
+        }
+    }
+
+    // Length of an id in bytes, twice as many hex digits
+    pub fn oid_len(&self) -> usize {
+        match self {
+            HashAlgorithm::Sha1 => 20,
+            HashAlgorithm::Sha256 => 32,
+        }
+    }
+
+    // The id of an object with this header and content
+    pub fn hash(&self, object_data: &[u8]) -> Oid {
+        match self {
+            HashAlgorithm::Sha1 => Oid::new(*self, &Sha1::digest(object_data)),
+            HashAlgorithm::Sha256 => Oid::new(*self, &Sha256::digest(object_data)),
+        }
+    }
+
+    // The algorithm whose ids are `len` bytes long
+    fn with_len(len: usize) -> Option<HashAlgorithm> {
+        [HashAlgorithm::Sha1, HashAlgorithm::Sha256]
+            .into_iter()
+            .find(|algorithm| algorithm.oid_len() == len)
+    }
+}
+
+impl fmt::Display for HashAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+// The id of an object, the hash of its header and content. Stored as raw
+// bytes with the algorithm that made them, parsed from and printed as 40
+// hex digits for SHA-1 and 64 for SHA-256.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Oid {
+    algorithm: HashAlgorithm,
+    // The id, then zeros up to MAX_OID_LEN
+    bytes: [u8; MAX_OID_LEN],
+}
+
+impl Oid {
+    fn new(algorithm: HashAlgorithm, id: &[u8]) -> Oid {
+        let mut bytes = [0; MAX_OID_LEN];
+        bytes[..id.len()].copy_from_slice(id);
+        Oid { algorithm, bytes }
+    }
+
+    // All zeros, the "no commit" side of reflog and stash entries
+    pub fn zero(algorithm: HashAlgorithm) -> Oid {
+        Oid {
+            algorithm,
+            bytes: [0; MAX_OID_LEN],
+        }
+    }
+
+    // The id of raw bytes, as stored in trees. Their length tells the
+    // algorithm.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Oid, Error> {
+        let algorithm = HashAlgorithm::with_len(bytes.len()).ok_or_else(|| {
+            Error::Corrupt(format!(
+                "Invalid object id: {} bytes instead of {} or {}",
+                bytes.len(),
+                HashAlgorithm::Sha1.oid_len(),
+                HashAlgorithm::Sha256.oid_len()
+            ))
+        })?;
+        Ok(Oid::new(algorithm, bytes))
+    }
+
+    pub fn algorithm(&self) -> HashAlgorithm {
+        self.algorithm
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..self.algorithm.oid_len()]
+    }
+
+    // The first `len` hex digits, at most all of them
+    pub fn short(&self, len: usize) -> String {
+        let mut hex = self.to_string();
+        hex.truncate(len);
+        hex
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.bytes == [0; MAX_OID_LEN]
+    }
+}
+
+impl FromStr for Oid {
+    type Err = Error;
+
+    // Exactly 40 or 64 hex digits, either case. Anything else is not an id
+    // and should be resolved as a ref instead.
+    fn from_str(s: &str) -> Result<Oid, Error> {
+        let invalid = || Error::InvalidRef(s.to_string());
+        let algorithm = HashAlgorithm::with_len(s.len() / 2).ok_or_else(invalid)?;
+        let mut bytes = [0; MAX_OID_LEN];
+        // Also fails on an odd length, one digit more than a full id
+        hex::decode_to_slice(s, &mut bytes[..algorithm.oid_len()]).map_err(|_| invalid())?;
+        Ok(Oid { algorithm, bytes })
+    }
+}
+
+impl fmt::Display for Oid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(&hex::encode(self.as_bytes()))
+    }
+}
+
+impl fmt::Debug for Oid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Oid({})", self)
+    }
+}
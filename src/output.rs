@@ -0,0 +1,177 @@
+use std::cell::Cell;
+use std::env;
+use std::io::IsTerminal;
+
+// When commands color their output: --color=<when>, color.* config
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColorMode {
+    // Only when stdout is a terminal and NO_COLOR is not set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorMode {
+    // Values of --color and of the color.* config, where true means auto
+    // like in git
+    pub fn parse(value: &str) -> Result<ColorMode, String> {
+        match value.to_lowercase().as_str() {
+            "auto" | "true" | "yes" | "on" => Ok(ColorMode::Auto),
+            "always" => Ok(ColorMode::Always),
+            "never" | "false" | "no" | "off" => Ok(ColorMode::Never),
+            _ => Err(format!("Invalid color mode: {}", value)),
+        }
+    }
+}
+
+// What commands print: text for people, or JSON for scripts (--format=json)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+impl OutputFormat {
+    pub fn parse(value: &str) -> Result<OutputFormat, String> {
+        match value {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            _ => Err(format!("Unknown output format: {}", value)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Color {
+    Red,
+    Green,
+    Yellow,
+    Cyan,
+}
+
+impl Color {
+    fn code(&self) -> u8 {
+        match self {
+            Color::Red => 31,
+            Color::Green => 32,
+            Color::Yellow => 33,
+            Color::Cyan => 36,
+        }
+    }
+}
+
+thread_local! {
+    // The library colors by default, the binary decides once at startup
+    static COLOR_ENABLED: Cell<bool> = const { Cell::new(true) };
+}
+
+pub fn set_color(enabled: bool) {
+    COLOR_ENABLED.with(|cell| cell.set(enabled));
+}
+
+pub fn color_enabled() -> bool {
+    COLOR_ENABLED.with(|cell| cell.get())
+}
+
+// `text` wrapped in the escapes of `color`, or as is when color is off
+pub fn paint(color: Color, text: &str) -> String {
+    if color_enabled() {
+        format!("\x1b[{}m{}\x1b[0m", color.code(), text)
+    } else {
+        text.to_string()
+    }
+}
+
+// Whether output going to a terminal (or not) should be colored
+pub fn should_color(mode: ColorMode, stdout_is_tty: bool) -> bool {
+    match mode {
+        ColorMode::Always => true,
+        ColorMode::Never => false,
+        ColorMode::Auto => {
+            stdout_is_tty
+                && env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+                && env::var("TERM").map_or(true, |term| term != "dumb")
+        }
+    }
+}
+
+pub fn stdout_is_tty() -> bool {
+    std::io::stdout().is_terminal()
+}
+
+// The pager to use: $BGIT_PAGER, then core.pager, then $PAGER, then less
+pub fn pager_command(configured: Option<String>) -> String {
+    env::var("BGIT_PAGER")
+        .ok()
+        .or(configured)
+        .or_else(|| env::var("PAGER").ok())
+        .unwrap_or_else(|| "less".to_string())
+}
+
+#[cfg(unix)]
+mod pager {
+    use std::io::Write;
+    use std::os::fd::AsRawFd;
+    use std::process::{Child, Command, Stdio};
+    use std::sync::Mutex;
+
+    static PAGER: Mutex<Option<Child>> = Mutex::new(None);
+
+    // Send the rest of stdout through `pager`. The pager is waited for when
+    // the process exits, including through std::process::exit.
+    pub fn start(pager: &str) {
+        if pager.is_empty() || pager == "cat" {
+            return;
+        }
+
+        let mut command = Command::new("sh");
+        command.args(["-c", pager]).stdin(Stdio::piped());
+        // Quit when the output fits on one screen, keep colors, do not
+        // clear the screen
+        if std::env::var_os("LESS").is_none() {
+            command.env("LESS", "FRX");
+        }
+        let Ok(mut child) = command.spawn() else {
+            // Without a pager, output goes to the terminal as usual
+            return;
+        };
+
+        let Some(stdin) = child.stdin.take() else {
+            return;
+        };
+        let _ = std::io::stdout().flush();
+        // SAFETY: both descriptors are open, stdout now refers to the pipe
+        if unsafe { libc::dup2(stdin.as_raw_fd(), libc::STDOUT_FILENO) } < 0 {
+            return;
+        }
+        drop(stdin);
+
+        *PAGER.lock().unwrap() = Some(child);
+        // SAFETY: wait_for_pager is a plain function without arguments
+        unsafe {
+            libc::atexit(wait_for_pager);
+        }
+    }
+
+    extern "C" fn wait_for_pager() {
+        let _ = std::io::stdout().flush();
+        // The pager reads until our end of the pipe is closed
+        // SAFETY: nothing is written to stdout after this point
+        unsafe {
+            libc::close(libc::STDOUT_FILENO);
+        }
+        if let Ok(mut pager) = PAGER.lock()
+            && let Some(mut child) = pager.take()
+        {
+            let _ = child.wait();
+        }
+    }
+}
+
+#[cfg(unix)]
+pub use pager::start as start_pager;
+
+#[cfg(not(unix))]
+pub fn start_pager(_pager: &str) {}
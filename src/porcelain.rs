@@ -0,0 +1,252 @@
+use crate::differ::{Differ, FileChange};
+use crate::error::Error;
+use crate::json::Json;
+use crate::oid::Oid;
+use crate::repository::{Commit, HEAD, LogOptions, RefValue, Repository};
+
+// Machine-readable output: `status --porcelain` and --format=json.
+// The formats are documented in the README and must stay stable.
+
+// Mode shown for every file, bGit does not record modes
+const FILE_MODE: &str = "100644";
+// Mode of a missing file
+const NO_MODE: &str = "000000";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PorcelainVersion {
+    V1,
+    V2,
+}
+
+impl PorcelainVersion {
+    pub fn parse(value: &str) -> Result<PorcelainVersion, String> {
+        match value {
+            "v1" | "1" => Ok(PorcelainVersion::V1),
+            "v2" | "2" => Ok(PorcelainVersion::V2),
+            _ => Err(format!("Unsupported porcelain version: {}", value)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct StatusOptions {
+    // None prints the status for people
+    pub porcelain: Option<PorcelainVersion>,
+    // -z: entries end with NUL instead of a newline and paths are not
+    // joined with " -> "
+    pub nul_terminated: bool,
+    // -b/--branch: start with the branch
+    pub branch: bool,
+}
+
+// {"status", "path", "old_path", "similarity"}; old_path and similarity
+// are null unless the file was renamed or copied
+pub fn change_json(change: &FileChange) -> Json {
+    let (old_path, similarity) = match change.similarity {
+        Some(similarity) => (
+            Json::string(&change.old_path),
+            Json::Number(similarity as i64),
+        ),
+        None => (Json::Null, Json::Null),
+    };
+    Json::object(vec![
+        ("status", Json::String(change.kind.letter().to_string())),
+        ("path", Json::string(change.path())),
+        ("old_path", old_path),
+        ("similarity", similarity),
+    ])
+}
+
+pub fn changes_json(changes: &[FileChange]) -> Json {
+    Json::Array(changes.iter().map(change_json).collect())
+}
+
+impl Repository {
+    // There is no index: every change is staged, since the next commit
+    // records the worktree as it is
+    pub fn status_porcelain(
+        &self,
+        version: PorcelainVersion,
+        options: &StatusOptions,
+    ) -> Result<String, Error> {
+        let head = self.get_oid_hash(HEAD).ok();
+        let head_tree = match head {
+            Some(head) => Some(self.get_commit(head)?.tree),
+            None => None,
+        };
+        let changes = Differ::new(self).changed_files(head_tree, Some(self.get_working_tree()?))?;
+        let end = if options.nul_terminated { '\0' } else { '\n' };
+
+        let mut output = String::new();
+        if options.branch {
+            let branch = self.get_branch_name()?;
+            match version {
+                PorcelainVersion::V1 => output.push_str(&format!(
+                    "## {}{}",
+                    branch.as_deref().unwrap_or("HEAD (no branch)"),
+                    end
+                )),
+                PorcelainVersion::V2 => {
+                    output.push_str(&format!(
+                        "# branch.oid {}{}",
+                        head.map_or("(initial)".to_string(), |head| head.to_string()),
+                        end
+                    ));
+                    output.push_str(&format!(
+                        "# branch.head {}{}",
+                        branch.as_deref().unwrap_or("(detached)"),
+                        end
+                    ));
+                }
+            }
+        }
+
+        for change in &changes {
+            // Like git, paths are relative to the current directory
+            let change = FileChange {
+                old_path: self.display_path(&change.old_path),
+                new_path: self.display_path(&change.new_path),
+                ..change.clone()
+            };
+            match version {
+                PorcelainVersion::V1 => output.push_str(&Self::porcelain_v1_entry(&change, end)),
+                PorcelainVersion::V2 => output.push_str(&self.porcelain_v2_entry(&change, end)),
+            }
+        }
+
+        Ok(output)
+    }
+
+    // "XY path", "R  old -> new", or "R  new\0old\0" with -z
+    fn porcelain_v1_entry(change: &FileChange, end: char) -> String {
+        let letter = change.kind.letter();
+        match change.similarity {
+            Some(_) if end == '\0' => {
+                format!("{}  {}\0{}\0", letter, change.new_path, change.old_path)
+            }
+            Some(_) => format!("{}  {} -> {}\n", letter, change.old_path, change.new_path),
+            None => format!("{}  {}{}", letter, change.path(), end),
+        }
+    }
+
+    // "1 XY N... <mH> <mI> <mW> <hH> <hI> <path>", or for renames and
+    // copies "2 XY N... <mH> <mI> <mW> <hH> <hI> <X><score> <path><sep><origPath>"
+    fn porcelain_v2_entry(&self, change: &FileChange, end: char) -> String {
+        let mode = |oid: &Option<Oid>| if oid.is_some() { FILE_MODE } else { NO_MODE };
+        let fields = format!(
+            "{}. N... {} {} {} {} {}",
+            change.kind.letter(),
+            mode(&change.old_oid),
+            mode(&change.new_oid),
+            mode(&change.new_oid),
+            change.old_oid.unwrap_or(Oid::zero(self.object_format)),
+            change.new_oid.unwrap_or(Oid::zero(self.object_format)),
+        );
+
+        match change.similarity {
+            Some(similarity) => {
+                let separator = if end == '\0' { '\0' } else { '\t' };
+                format!(
+                    "2 {} {}{} {}{}{}{}",
+                    fields,
+                    change.kind.letter(),
+                    similarity,
+                    change.new_path,
+                    separator,
+                    change.old_path,
+                    end
+                )
+            }
+            None => format!("1 {} {}{}", fields, change.path(), end),
+        }
+    }
+
+    // {"commit", "tree", "parents", "author", "date", "message", "refs"};
+    // author is null when none was recorded, refs are the full names of the
+    // refs pointing at the commit
+    pub fn commit_json(&self, hash: Oid, commit: &Commit) -> Result<Json, Error> {
+        let refs = self.refs_pointing_at(hash)?;
+
+        Ok(Json::object(vec![
+            ("commit", Json::String(hash.to_string())),
+            ("tree", Json::String(commit.tree.to_string())),
+            ("parents", Json::oids(&commit.parents)),
+            ("author", Json::optional(commit.author.as_deref())),
+            ("date", Json::string(&commit.timestamp)),
+            ("message", Json::string(&commit.message)),
+            ("refs", Json::strings(&refs)),
+        ]))
+    }
+
+    // The commits `log` would show, as an array of commit objects
+    pub fn log_json(&self, options: &LogOptions) -> Result<String, Error> {
+        if options.diff.is_some() {
+            return Err(Error::Other(
+                "--format=json cannot be combined with -p, --stat, ...".to_string(),
+            ));
+        }
+        let mut commits = Vec::new();
+        for hash in self.log_commits(options)? {
+            let commit = self.get_commit(hash)?;
+            commits.push(self.commit_json(hash, &commit)?);
+        }
+        Ok(Json::Array(commits).to_string())
+    }
+
+    // A commit object with its "changes" against the first parent
+    pub fn show_json(&self, revision: &str) -> Result<String, Error> {
+        let hash = self.get_oid_hash(revision)?;
+        let commit = self.get_commit(hash)?;
+        let parent_tree = match commit.parents.first() {
+            Some(parent) => Some(self.get_commit(*parent)?.tree),
+            None => None,
+        };
+        let changes = Differ::new(self).changed_files(parent_tree, Some(commit.tree))?;
+
+        let mut json = self.commit_json(hash, &commit)?;
+        if let Json::Object(entries) = &mut json {
+            entries.push(("changes".to_string(), changes_json(&changes)));
+        }
+        Ok(json.to_string())
+    }
+
+    // [{"name", "commit", "current"}] for every branch
+    pub fn branches_json(&self) -> Result<String, Error> {
+        let current = self.get_branch_name()?;
+        let branches = self
+            .iter_refs("refs/heads/")?
+            .into_iter()
+            .map(|(name, value)| {
+                let name = name.trim_start_matches("refs/heads/");
+                Json::object(vec![
+                    ("name", Json::string(name)),
+                    ("commit", Self::ref_oid_json(&value)),
+                    ("current", Json::Bool(current.as_deref() == Some(name))),
+                ])
+            })
+            .collect();
+        Ok(Json::Array(branches).to_string())
+    }
+
+    // [{"name", "oid"}] for every ref
+    pub fn refs_json(&self) -> Result<String, Error> {
+        let refs = self
+            .iter_refs("")?
+            .into_iter()
+            .map(|(name, value)| {
+                Json::object(vec![
+                    ("name", Json::String(name)),
+                    ("oid", Self::ref_oid_json(&value)),
+                ])
+            })
+            .collect();
+        Ok(Json::Array(refs).to_string())
+    }
+
+    // The commit of a ref, null for a branch without commits
+    fn ref_oid_json(value: &RefValue) -> Json {
+        value
+            .oid()
+            .map_or(Json::Null, |oid| Json::String(oid.to_string()))
+    }
+}
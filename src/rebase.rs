@@ -0,0 +1,781 @@
+use crate::error::Error;
+use crate::oid::Oid;
+use crate::repository::{Commit, HEAD, RefValue, Repository};
+use std::fs;
+use std::path::Path;
+
+// Directory inside the gitdir holding the state of an in-progress rebase
+pub const REBASE_DIR: &str = "rebase-merge";
+
+// Commands understood in the rebase todo list
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TodoAction {
+    Pick,
+    Reword,
+    Edit,
+    Squash,
+    Fixup,
+    Drop,
+    Exec,
+}
+
+impl TodoAction {
+    fn as_str(&self) -> &'static str {
+        match self {
+            TodoAction::Pick => "pick",
+            TodoAction::Reword => "reword",
+            TodoAction::Edit => "edit",
+            TodoAction::Squash => "squash",
+            TodoAction::Fixup => "fixup",
+            TodoAction::Drop => "drop",
+            TodoAction::Exec => "exec",
+        }
+    }
+
+    fn parse(word: &str) -> Option<TodoAction> {
+        match word {
+            "pick" | "p" => Some(TodoAction::Pick),
+            "reword" | "r" => Some(TodoAction::Reword),
+            "edit" | "e" => Some(TodoAction::Edit),
+            "squash" | "s" => Some(TodoAction::Squash),
+            "fixup" | "f" => Some(TodoAction::Fixup),
+            "drop" | "d" => Some(TodoAction::Drop),
+            "exec" | "x" => Some(TodoAction::Exec),
+            _ => None,
+        }
+    }
+}
+
+// One line of the todo file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TodoItem {
+    pub action: TodoAction,
+    // Commit to apply, empty for exec lines
+    pub oid: String,
+    // Commit subject, or the shell command for exec lines
+    pub subject: String,
+}
+
+impl TodoItem {
+    pub fn pick(oid: &str, subject: &str) -> TodoItem {
+        TodoItem {
+            action: TodoAction::Pick,
+            oid: oid.to_string(),
+            subject: subject.to_string(),
+        }
+    }
+
+    fn to_line(&self) -> String {
+        match self.action {
+            TodoAction::Exec => format!("exec {}", self.subject),
+            action => format!("{} {} {}", action.as_str(), self.oid, self.subject),
+        }
+    }
+
+    fn from_line(line: &str) -> Result<TodoItem, Error> {
+        let line = line.trim();
+        let (word, rest) = line.split_once(' ').unwrap_or((line, ""));
+        let action = TodoAction::parse(word)
+            .ok_or_else(|| Error::Other(format!("Invalid rebase todo line: {}", line)))?;
+
+        if action == TodoAction::Exec {
+            if rest.trim().is_empty() {
+                return Err(Error::Other(format!(
+                    "Missing command in rebase todo line: {}",
+                    line
+                )));
+            }
+            return Ok(TodoItem {
+                action,
+                oid: String::new(),
+                subject: rest.trim().to_string(),
+            });
+        }
+
+        let (oid, subject) = rest.trim().split_once(' ').unwrap_or((rest.trim(), ""));
+        if oid.is_empty() {
+            return Err(Error::Other(format!(
+                "Missing commit in rebase todo line: {}",
+                line
+            )));
+        }
+        Ok(TodoItem {
+            action,
+            oid: oid.to_string(),
+            subject: subject.to_string(),
+        })
+    }
+}
+
+// Options for starting a rebase
+#[derive(Debug, Clone, Default)]
+pub struct RebaseOptions {
+    // Let the user edit the todo list before it is executed
+    pub interactive: bool,
+    // Move "fixup! <subject>" and "squash! <subject>" commits after their target
+    pub autosquash: bool,
+    // Replay onto this commit instead of the upstream (--onto <newbase>)
+    pub onto: Option<String>,
+}
+
+// What a rebase command did
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RebaseOutcome {
+    // The commit the branch is replayed onto
+    pub onto: Oid,
+    // Commits left out because the target already has their changes
+    pub skipped: Vec<Oid>,
+    // Why the rebase stopped, None once the branch was moved
+    pub stopped: Option<RebaseStop>,
+}
+
+// A rebase waiting for the user, to --continue, --skip or --abort
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RebaseStop {
+    // Applying `commit` left conflict markers in `paths`
+    Conflicted {
+        commit: Oid,
+        subject: String,
+        paths: Vec<String>,
+    },
+    // An "edit" step: HEAD is `commit`, to amend before continuing
+    Edit {
+        commit: Oid,
+        subject: String,
+    },
+    // The command of an "exec" step failed
+    ExecFailed(String),
+}
+
+const TODO_HELP: &str = "#
+# Commands:
+# p, pick <commit> = use commit
+# r, reword <commit> = use commit, but edit the commit message
+# e, edit <commit> = use commit, but stop for amending
+# s, squash <commit> = use commit, but meld into previous commit
+# f, fixup <commit> = like \"squash\", but discard this commit's log message
+# x, exec <command> = run command (the rest of the line) using shell
+# d, drop <commit> = remove commit
+#
+# These lines can be re-ordered; they are executed from top to bottom.
+# If you remove everything, the rebase will be aborted.
+";
+
+// Everything needed to resume, skip or abort a rebase, persisted in
+// .bgit/rebase-merge/ between invocations
+#[derive(Debug, Clone)]
+pub struct RebaseState {
+    // Branch that is being rebased, None for a detached HEAD
+    pub head_name: Option<String>,
+    pub onto: Oid,
+    pub orig_head: Oid,
+    // Merge base of the rebased commits and onto, shown in the todo list
+    pub base: Oid,
+    pub todo: Vec<TodoItem>,
+    pub done: Vec<TodoItem>,
+    // Commit that could not be applied cleanly, with its conflicting paths
+    pub stopped: Option<Oid>,
+    pub conflicts: Vec<String>,
+    // Commit created by an "edit" step, amended on --continue
+    pub amend: Option<Oid>,
+}
+
+impl RebaseState {
+    fn dir(repo: &Repository) -> Result<String, Error> {
+        repo.git_path(REBASE_DIR)
+    }
+
+    pub fn exists(repo: &Repository) -> bool {
+        repo.git_path(REBASE_DIR)
+            .is_ok_and(|dir| Path::new(&dir).exists())
+    }
+
+    pub fn load(repo: &Repository) -> Result<RebaseState, Error> {
+        if !Self::exists(repo) {
+            return Err(Error::Other("No rebase in progress".to_string()));
+        }
+
+        let dir = Self::dir(repo)?;
+        let read = |name: &str| -> Result<String, Error> {
+            fs::read_to_string(format!("{}/{}", dir, name))
+                .map(|content| content.trim_end().to_string())
+                .map_err(|e| Error::io(format!("Failed to read rebase state {}", name), e))
+        };
+        let read_optional = |name: &str| -> String {
+            fs::read_to_string(format!("{}/{}", dir, name))
+                .map(|content| content.trim_end().to_string())
+                .unwrap_or_default()
+        };
+        let parse_oid = |name: &str, content: String| -> Result<Oid, Error> {
+            repo.parse_oid(&content)
+                .map_err(|_| Error::Corrupt(format!("Invalid rebase state {}", name)))
+        };
+        let parse_optional_oid = |name: &str| -> Result<Option<Oid>, Error> {
+            match read_optional(name) {
+                content if content.is_empty() => Ok(None),
+                content => parse_oid(name, content).map(Some),
+            }
+        };
+        let parse_todo = |content: String| -> Result<Vec<TodoItem>, Error> {
+            content
+                .lines()
+                .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+                .map(TodoItem::from_line)
+                .collect()
+        };
+
+        let head_name = read("head-name")?;
+
+        Ok(RebaseState {
+            head_name: head_name
+                .strip_prefix("refs/heads/")
+                .map(|name| name.to_string()),
+            onto: parse_oid("onto", read("onto")?)?,
+            orig_head: parse_oid("orig-head", read("orig-head")?)?,
+            base: parse_oid("base", read("base")?)?,
+            todo: parse_todo(read("git-rebase-todo")?)?,
+            done: parse_todo(read_optional("done"))?,
+            stopped: parse_optional_oid("stopped-sha")?,
+            conflicts: read_optional("conflicts")
+                .lines()
+                .map(|line| line.to_string())
+                .collect(),
+            amend: parse_optional_oid("amend")?,
+        })
+    }
+
+    pub fn save(&self, repo: &Repository) -> Result<(), Error> {
+        let dir = Self::dir(repo)?;
+        fs::create_dir_all(&dir)
+            .map_err(|e| Error::io(format!("Failed to create {} directory", REBASE_DIR), e))?;
+
+        let write = |name: &str, content: String| -> Result<(), Error> {
+            fs::write(format!("{}/{}", dir, name), content)
+                .map_err(|e| Error::io(format!("Failed to write rebase state {}", name), e))
+        };
+        let lines = |items: &[TodoItem]| -> String {
+            items
+                .iter()
+                .map(|item| format!("{}\n", item.to_line()))
+                .collect()
+        };
+
+        let head_name = match &self.head_name {
+            Some(branch) => format!("refs/heads/{}", branch),
+            None => "detached HEAD".to_string(),
+        };
+        write("head-name", format!("{}\n", head_name))?;
+        write("onto", format!("{}\n", self.onto))?;
+        write("orig-head", format!("{}\n", self.orig_head))?;
+        write("base", format!("{}\n", self.base))?;
+        write("git-rebase-todo", lines(&self.todo))?;
+        write("done", lines(&self.done))?;
+        write("msgnum", format!("{}\n", self.done.len()))?;
+        write("end", format!("{}\n", self.done.len() + self.todo.len()))?;
+        write(
+            "stopped-sha",
+            self.stopped
+                .as_ref()
+                .map(|oid| format!("{}\n", oid))
+                .unwrap_or_default(),
+        )?;
+        write(
+            "conflicts",
+            self.conflicts
+                .iter()
+                .map(|path| format!("{}\n", path))
+                .collect(),
+        )?;
+        write(
+            "amend",
+            self.amend
+                .as_ref()
+                .map(|oid| format!("{}\n", oid))
+                .unwrap_or_default(),
+        )
+    }
+
+    fn todo_path(repo: &Repository) -> Result<String, Error> {
+        Ok(format!("{}/git-rebase-todo", Self::dir(repo)?))
+    }
+
+    pub fn remove(repo: &Repository) -> Result<(), Error> {
+        fs::remove_dir_all(Self::dir(repo)?)
+            .map_err(|e| Error::io(format!("Failed to remove {} directory", REBASE_DIR), e))
+    }
+}
+
+impl Repository {
+    pub fn rebase_in_progress(&self) -> bool {
+        RebaseState::exists(self)
+    }
+
+    pub fn rebase(&self, target: &str) -> Result<RebaseOutcome, Error> {
+        self.rebase_with_options(target, &RebaseOptions::default())
+    }
+
+    pub fn rebase_with_options(
+        &self,
+        upstream: &str,
+        options: &RebaseOptions,
+    ) -> Result<RebaseOutcome, Error> {
+        if self.rebase_in_progress() {
+            return Err(Error::Other(
+                "A rebase is already in progress. Use rebase --continue, --skip or --abort"
+                    .to_string(),
+            ));
+        }
+
+        // 1. Store original branch state (for potential abort)
+        let original_branch = self.get_branch_name()?;
+
+        // 2. Get upstream, the commit to replay onto, and current HEAD
+        let upstream_oid = self.get_oid_hash(upstream)?;
+        let target_oid = match &options.onto {
+            Some(onto) => self.get_oid_hash(onto)?,
+            None => upstream_oid,
+        };
+        let current_head_oid = self.get_oid_hash("HEAD")?;
+        if self.get_working_tree()? != self.get_commit(current_head_oid)?.tree {
+            return Err(Error::Other(
+                "Your local changes would be overwritten by rebase. Commit your changes or stash them to proceed."
+                    .to_string(),
+            ));
+        }
+
+        // 3. Find common ancestor
+        let base_commit = self.get_merge_base(current_head_oid, upstream_oid)?;
+
+        // 4. Collect commits to rebase (upstream..HEAD), leaving out the ones
+        // whose changes already made it into the target
+        let applied: Vec<String> = self
+            .commits_to_replay(current_head_oid, target_oid)?
+            .into_iter()
+            .map(|oid| {
+                self.get_commit(oid)
+                    .and_then(|commit| self.patch_id(&commit))
+            })
+            .collect::<Result<_, _>>()?;
+
+        let mut todo = Vec::new();
+        let mut skipped = Vec::new();
+        for oid in self.commits_to_replay(upstream_oid, current_head_oid)? {
+            let commit = self.get_commit(oid)?;
+            if !applied.is_empty() && applied.contains(&self.patch_id(&commit)?) {
+                skipped.push(oid);
+                continue;
+            }
+            todo.push(TodoItem::pick(
+                &oid.to_string(),
+                commit.message.lines().next().unwrap_or(""),
+            ));
+        }
+
+        if options.autosquash {
+            todo = Self::autosquash(todo);
+        }
+
+        // 5. Persist the plan so the rebase can be resumed or aborted
+        let state = RebaseState {
+            head_name: original_branch,
+            onto: target_oid,
+            orig_head: current_head_oid,
+            base: base_commit,
+            todo,
+            done: Vec::new(),
+            stopped: None,
+            conflicts: Vec::new(),
+            amend: None,
+        };
+        state.save(self)?;
+
+        // 6. Let the user rewrite the plan
+        if options.interactive
+            && let Err(e) = self.rebase_edit_todo(&state)
+        {
+            RebaseState::remove(self)?;
+            return Err(e);
+        }
+
+        // 7. Switch to target commit (detached HEAD)
+        self.checkout(&target_oid.to_string())?;
+
+        // 8. Apply each commit on top of the target
+        Ok(RebaseOutcome {
+            skipped,
+            ..self.rebase_run()?
+        })
+    }
+
+    // Open the todo list in the editor and validate what comes back
+    fn rebase_edit_todo(&self, state: &RebaseState) -> Result<(), Error> {
+        let todo_path = RebaseState::todo_path(self)?;
+        let mut content: String = state
+            .todo
+            .iter()
+            .map(|item| format!("{}\n", item.to_line()))
+            .collect();
+        content.push_str(&format!(
+            "\n# Rebase {}..{} onto {} ({} commands)\n{}",
+            state.base.short(7),
+            state.orig_head.short(7),
+            state.onto.short(7),
+            state.todo.len(),
+            TODO_HELP
+        ));
+        fs::write(&todo_path, content)
+            .map_err(|e| Error::io("Failed to write rebase todo list", e))?;
+
+        self.launch_editor(Path::new(&todo_path))?;
+
+        let state = RebaseState::load(self)?;
+        if state.todo.is_empty() {
+            return Err(Error::Other("Nothing to do".to_string()));
+        }
+
+        let mut has_commit = false;
+        for item in &state.todo {
+            match item.action {
+                TodoAction::Exec => continue,
+                TodoAction::Squash | TodoAction::Fixup if !has_commit => {
+                    return Err(Error::Other(format!(
+                        "Cannot '{}' without a previous commit",
+                        item.action.as_str()
+                    )));
+                }
+                _ => {}
+            }
+            self.get_oid_hash(&item.oid)
+                .and_then(|oid| self.get_commit(oid))
+                .map_err(|_| Error::InvalidRef(item.oid.clone()))?;
+            has_commit |= item.action != TodoAction::Drop;
+        }
+
+        Ok(())
+    }
+
+    // Reorder "fixup! X" / "squash! X" commits right after the commit whose
+    // subject (or hash prefix) is X, turning them into fixup/squash steps.
+    // Fixups whose target is not found stay where they are.
+    fn autosquash(todo: Vec<TodoItem>) -> Vec<TodoItem> {
+        // (item, oid of the commit it was attached to)
+        let mut result: Vec<(TodoItem, Option<String>)> = Vec::new();
+
+        for mut item in todo {
+            let mut target = item.subject.as_str();
+            let mut action = None;
+            loop {
+                if let Some(rest) = target.strip_prefix("fixup! ") {
+                    action.get_or_insert(TodoAction::Fixup);
+                    target = rest;
+                } else if let Some(rest) = target.strip_prefix("squash! ") {
+                    action.get_or_insert(TodoAction::Squash);
+                    target = rest;
+                } else {
+                    break;
+                }
+            }
+
+            let found = match action {
+                Some(_) if !target.is_empty() => Self::autosquash_target(&result, target),
+                _ => None,
+            };
+
+            match (action, found) {
+                (Some(action), Some(position)) => {
+                    let target_oid = result[position].0.oid.clone();
+                    // Keep the relative order of several fixups for one commit
+                    let mut insert_at = position + 1;
+                    while insert_at < result.len()
+                        && result[insert_at].1.as_deref() == Some(target_oid.as_str())
+                    {
+                        insert_at += 1;
+                    }
+                    item.action = action;
+                    result.insert(insert_at, (item, Some(target_oid)));
+                }
+                _ => result.push((item, None)),
+            }
+        }
+
+        result.into_iter().map(|(item, _)| item).collect()
+    }
+
+    // Position of the commit a fixup for `target` belongs to: the first one
+    // with exactly that subject, else the only one whose hash starts with
+    // `target`, given as at least 4 hex digits
+    fn autosquash_target(items: &[(TodoItem, Option<String>)], target: &str) -> Option<usize> {
+        let candidates = items
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, attached))| attached.is_none());
+        if let Some((position, _)) = candidates
+            .clone()
+            .find(|(_, (candidate, _))| candidate.subject == target)
+        {
+            return Some(position);
+        }
+
+        if target.len() < 4 || !target.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+        let target = target.to_ascii_lowercase();
+        let mut matches =
+            candidates.filter(|(_, (candidate, _))| candidate.oid.starts_with(&target));
+        match (matches.next(), matches.next()) {
+            (Some((position, _)), None) => Some(position),
+            _ => None,
+        }
+    }
+
+    pub fn rebase_continue(&self) -> Result<RebaseOutcome, Error> {
+        let mut state = RebaseState::load(self)?;
+
+        if let Some(stopped) = state.stopped.take() {
+            // Refuse to continue while conflict markers are still present
+            let unresolved: Vec<String> = state
+                .conflicts
+                .iter()
+                .filter(|path| Self::has_conflict_markers(&Path::new(&self.worktree).join(path)))
+                .cloned()
+                .collect();
+            if !unresolved.is_empty() {
+                return Err(Error::Conflict(unresolved));
+            }
+
+            // Record the resolution, unless it made the commit empty
+            let action = state
+                .done
+                .last()
+                .map(|item| item.action)
+                .unwrap_or(TodoAction::Pick);
+            let head_tree = self.get_commit(self.get_oid_hash(HEAD)?)?.tree;
+            if self.get_working_tree()? != head_tree || action == TodoAction::Squash {
+                let commit = self.get_commit(stopped)?;
+                self.rebase_commit_step(action, &commit)?;
+            }
+
+            state.conflicts.clear();
+            state.save(self)?;
+        } else if let Some(amend) = state.amend.take() {
+            // Fold changes made while stopped at an "edit" step into that commit
+            let head_oid = self.get_oid_hash(HEAD)?;
+            let head = self.get_commit(head_oid)?;
+            if head_oid == amend && self.get_working_tree()? != head.tree {
+                self.rebase_amend_head(&head.message)?;
+            }
+            state.save(self)?;
+        }
+
+        self.rebase_run()
+    }
+
+    pub fn rebase_skip(&self) -> Result<RebaseOutcome, Error> {
+        let mut state = RebaseState::load(self)?;
+
+        // Throw away whatever the stopped commit left in the worktree
+        let head_tree = self.get_commit(self.get_oid_hash(HEAD)?)?.tree;
+        self.read_tree(head_tree, Path::new(&self.worktree))?;
+
+        state.stopped = None;
+        state.conflicts.clear();
+        state.amend = None;
+        state.save(self)?;
+
+        self.rebase_run()
+    }
+
+    // Returns the state of the abandoned rebase
+    pub fn rebase_abort(&self) -> Result<RebaseState, Error> {
+        let state = RebaseState::load(self)?;
+
+        // Restore the worktree and HEAD exactly as they were before the rebase
+        let orig_commit = self.get_commit(state.orig_head)?;
+        self.read_tree(orig_commit.tree, Path::new(&self.worktree))?;
+
+        match &state.head_name {
+            Some(branch_name) => {
+                self.set_ref(
+                    format!("refs/heads/{}", branch_name).as_str(),
+                    RefValue::Direct(state.orig_head),
+                    false,
+                )?;
+                self.set_ref(
+                    HEAD,
+                    RefValue::Symbolic(format!("refs/heads/{}", branch_name)),
+                    false,
+                )?;
+            }
+            None => {
+                self.set_ref(HEAD, RefValue::Direct(state.orig_head), false)?;
+            }
+        }
+
+        RebaseState::remove(self)?;
+        Ok(state)
+    }
+
+    // Replay the remaining todo items, stopping at the first conflict,
+    // "edit" step or failed "exec" command
+    fn rebase_run(&self) -> Result<RebaseOutcome, Error> {
+        let mut state = RebaseState::load(self)?;
+        let stop = |stopped: RebaseStop| RebaseOutcome {
+            onto: state.onto,
+            skipped: Vec::new(),
+            stopped: Some(stopped),
+        };
+
+        while !state.todo.is_empty() {
+            let item = state.todo.remove(0);
+            state.done.push(item.clone());
+
+            match item.action {
+                TodoAction::Drop => {
+                    state.save(self)?;
+                    continue;
+                }
+                TodoAction::Exec => {
+                    state.save(self)?;
+                    let status = std::process::Command::new("sh")
+                        .arg("-c")
+                        .arg(&item.subject)
+                        .current_dir(&self.worktree)
+                        .status()
+                        .map_err(|e| Error::io(format!("Failed to run {}", item.subject), e))?;
+                    if !status.success() {
+                        return Ok(stop(RebaseStop::ExecFailed(item.subject)));
+                    }
+                    continue;
+                }
+                _ => {}
+            }
+
+            // Apply only the changes this commit introduced, so that steps can
+            // be reordered or dropped independently of each other
+            let oid = self.get_oid_hash(&item.oid)?;
+            let commit = self.get_commit(oid)?;
+            let conflicts = self.replay_commit(&commit)?;
+
+            if !conflicts.is_empty() {
+                state.stopped = Some(oid);
+                state.conflicts = conflicts;
+                state.save(self)?;
+                return Ok(stop(RebaseStop::Conflicted {
+                    commit: oid,
+                    subject: item.subject,
+                    paths: state.conflicts,
+                }));
+            }
+
+            self.rebase_commit_step(item.action, &commit)?;
+
+            if item.action == TodoAction::Edit {
+                let head = self.get_oid_hash(HEAD)?;
+                state.amend = Some(head);
+                state.save(self)?;
+                return Ok(stop(RebaseStop::Edit {
+                    commit: head,
+                    subject: item.subject,
+                }));
+            }
+
+            state.save(self)?;
+        }
+
+        self.rebase_finish(&state)
+    }
+
+    // Record the applied worktree for one todo step, keeping the authorship
+    // of the replayed commit
+    fn rebase_commit_step(&self, action: TodoAction, commit: &Commit) -> Result<(), Error> {
+        match action {
+            TodoAction::Reword => {
+                let message = self.rebase_edit_message(&commit.message)?;
+                self.commit_replayed(commit, &message)?;
+            }
+            TodoAction::Squash => {
+                let head = self.get_commit(self.get_oid_hash(HEAD)?)?;
+                let combined = format!(
+                    "# This is a combination of 2 commits.\n{}\n\n{}",
+                    head.message, commit.message
+                );
+                let message = self.rebase_edit_message(&combined)?;
+                self.rebase_amend_head(&message)?;
+            }
+            TodoAction::Fixup => {
+                let head = self.get_commit(self.get_oid_hash(HEAD)?)?;
+                self.rebase_amend_head(&head.message)?;
+            }
+            _ => {
+                self.commit_replayed(commit, &commit.message)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Replace HEAD with a commit of the current worktree on the same parents
+    fn rebase_amend_head(&self, message: &str) -> Result<(), Error> {
+        let head = self.get_commit(self.get_oid_hash(HEAD)?)?;
+        let tree = self.get_working_tree()?;
+        let new_head = self.write_commit_as(tree, &head.parents, message, &head.signature())?;
+        self.set_ref(HEAD, RefValue::Direct(new_head), true)
+    }
+
+    // Let the user edit a commit message; comment lines are dropped
+    fn rebase_edit_message(&self, message: &str) -> Result<String, Error> {
+        let path = self.git_path(&format!("{}/message", REBASE_DIR))?;
+        fs::write(&path, format!("{}\n", message))
+            .map_err(|e| Error::io("Failed to write commit message", e))?;
+
+        self.launch_editor(Path::new(&path))?;
+
+        let edited =
+            fs::read_to_string(&path).map_err(|e| Error::io("Failed to read commit message", e))?;
+        let edited = edited
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let edited = edited.trim();
+        if edited.is_empty() {
+            return Err(Error::Other(
+                "Aborting commit due to empty commit message".to_string(),
+            ));
+        }
+        Ok(edited.to_string())
+    }
+
+    fn rebase_finish(&self, state: &RebaseState) -> Result<RebaseOutcome, Error> {
+        let new_head = self.get_oid_hash(HEAD)?;
+
+        // Move the rebased branch to the new tip and re-attach HEAD to it
+        if let Some(branch_name) = &state.head_name {
+            self.set_ref(
+                format!("refs/heads/{}", branch_name).as_str(),
+                RefValue::Direct(new_head),
+                false,
+            )?;
+            self.set_ref(
+                HEAD,
+                RefValue::Symbolic(format!("refs/heads/{}", branch_name)),
+                false,
+            )?;
+        }
+
+        RebaseState::remove(self)?;
+        Ok(RebaseOutcome {
+            onto: state.onto,
+            skipped: Vec::new(),
+            stopped: None,
+        })
+    }
+
+    pub(crate) fn has_conflict_markers(path: &Path) -> bool {
+        match fs::read(path) {
+            Ok(content) => content
+                .split(|&b| b == b'\n')
+                .any(|line| line.starts_with(b"<<<<<<< ") || line.starts_with(b">>>>>>> ")),
+            Err(_) => false,
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use crate::error::Error;
+use crate::oid::Oid;
+use crate::repository::Repository;
+use std::fs;
+use std::path::Path;
+
+// Directory inside the gitdir holding one log file per ref
+pub const LOGS_DIR: &str = "logs";
+
+// One update of a ref, stored as a line "<old> <new> <timestamp>\t<message>".
+// `old` is all zeros when the ref was created.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReflogEntry {
+    pub old: Oid,
+    pub new: Oid,
+    pub timestamp: String,
+    pub message: String,
+}
+
+impl ReflogEntry {
+    fn to_line(&self) -> String {
+        format!(
+            "{} {} {}\t{}\n",
+            self.old, self.new, self.timestamp, self.message
+        )
+    }
+
+    // The ids must be of the repository's object format
+    fn from_line(repo: &Repository, line: &str) -> Result<ReflogEntry, Error> {
+        let invalid = || Error::Corrupt(format!("Invalid reflog line: {}", line));
+
+        let (header, message) = line.split_once('\t').ok_or_else(invalid)?;
+        let mut parts = header.splitn(3, ' ');
+        let old = parts.next().ok_or_else(invalid)?;
+        let new = parts.next().ok_or_else(invalid)?;
+        let timestamp = parts.next().ok_or_else(invalid)?;
+
+        Ok(ReflogEntry {
+            old: repo.parse_oid(old).map_err(|_| invalid())?,
+            new: repo.parse_oid(new).map_err(|_| invalid())?,
+            timestamp: timestamp.to_string(),
+            message: message.to_string(),
+        })
+    }
+}
+
+impl Repository {
+    fn reflog_path(&self, ref_name: &str) -> Result<String, Error> {
+        self.git_path(&format!("{}/{}", LOGS_DIR, ref_name))
+    }
+
+    // Entries of a ref's log, oldest first. A ref without a log has none.
+    pub fn read_reflog(&self, ref_name: &str) -> Result<Vec<ReflogEntry>, Error> {
+        let path = self.reflog_path(ref_name)?;
+        if !Path::new(&path).exists() {
+            return Ok(Vec::new());
+        }
+
+        fs::read_to_string(&path)
+            .map_err(|e| Error::io(format!("Failed to read reflog of {}", ref_name), e))?
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(|line| ReflogEntry::from_line(self, line))
+            .collect()
+    }
+
+    pub fn append_reflog(
+        &self,
+        ref_name: &str,
+        old: Oid,
+        new: Oid,
+        message: &str,
+    ) -> Result<(), Error> {
+        let mut entries = self.read_reflog(ref_name)?;
+        entries.push(ReflogEntry {
+            old,
+            new,
+            timestamp: self.default_signature().timestamp,
+            // The message has to stay on a single line
+            message: message.lines().next().unwrap_or("").to_string(),
+        });
+        self.write_reflog(ref_name, &entries)
+    }
+
+    // Replace a ref's log, removing the file when no entries are left
+    pub fn write_reflog(&self, ref_name: &str, entries: &[ReflogEntry]) -> Result<(), Error> {
+        let path = self.reflog_path(ref_name)?;
+
+        if entries.is_empty() {
+            if Path::new(&path).exists() {
+                fs::remove_file(&path).map_err(|e| {
+                    Error::io(format!("Failed to delete reflog of {}", ref_name), e)
+                })?;
+            }
+            return Ok(());
+        }
+
+        if let Some(parent) = Path::new(&path).parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| Error::io(format!("Failed to create {} directory", LOGS_DIR), e))?;
+        }
+        self.write_ref_file(
+            Path::new(&path),
+            &entries.iter().map(ReflogEntry::to_line).collect::<String>(),
+        )
+    }
+}
@@ -0,0 +1,77 @@
+use crate::differ::Differ;
+use crate::error::Error;
+use crate::oid::Oid;
+use crate::repository::{Commit, HEAD, RefValue, Repository};
+use sha1::{Digest, Sha1};
+use std::collections::HashSet;
+
+// Replaying commits on top of another base: the building block shared by
+// rebase (and anything else that copies commits around)
+impl Repository {
+    // Apply the changes `commit` introduced relative to its first parent on
+    // top of HEAD, writing the result into the worktree. HEAD is "ours" and
+    // the replayed commit the side being merged, as in cherry-pick.
+    // Returns the paths left with conflict markers.
+    pub fn replay_commit(&self, commit: &Commit) -> Result<Vec<String>, Error> {
+        let head_tree = self.get_commit(self.get_oid_hash(HEAD)?)?.tree;
+        let parent_tree = match commit.parents.first() {
+            Some(parent) => Some(self.get_commit(*parent)?.tree),
+            None => None,
+        };
+
+        self.read_tree_merged(head_tree, commit.tree, parent_tree)
+    }
+
+    // Commit the worktree on top of HEAD, keeping the author and timestamp of
+    // the commit it was replayed from
+    pub fn commit_replayed(&self, original: &Commit, message: &str) -> Result<Oid, Error> {
+        let tree = self.get_working_tree()?;
+        let parents = vec![self.get_oid_hash(HEAD)?];
+        let hash = self.write_commit_as(tree, &parents, message, &original.signature())?;
+
+        self.set_ref(HEAD, RefValue::Direct(hash), true)?;
+
+        Ok(hash)
+    }
+
+    // Commits reachable from `head` but not from `upstream`, oldest first.
+    // Merge commits are left out, the result is a linear list to replay.
+    pub fn commits_to_replay(&self, upstream: Oid, head: Oid) -> Result<Vec<Oid>, Error> {
+        let excluded: HashSet<Oid> = self
+            .iter_commits_and_parents(vec![upstream])?
+            .into_iter()
+            .collect();
+
+        let mut commits = Vec::new();
+        for oid in self.iter_commits_and_parents(vec![head])?.into_iter().rev() {
+            if excluded.contains(&oid) {
+                continue;
+            }
+            if self.get_commit(oid)?.parents.len() > 1 {
+                continue;
+            }
+            commits.push(oid);
+        }
+        Ok(commits)
+    }
+
+    // Identifies the change a commit makes independently of where it sits in
+    // history: the hash of its diff against its first parent, ignoring line
+    // numbers and whitespace
+    pub fn patch_id(&self, commit: &Commit) -> Result<String, Error> {
+        let parent_tree = match commit.parents.first() {
+            Some(parent) => Some(self.get_commit(*parent)?.tree),
+            None => None,
+        };
+        let diff = Differ::new(self).diff_trees(parent_tree, Some(commit.tree))?;
+
+        let mut hasher = Sha1::new();
+        for line in String::from_utf8_lossy(&diff).lines() {
+            let line = if line.starts_with("@@") { "@@" } else { line };
+            let normalized: String = line.chars().filter(|c| !c.is_whitespace()).collect();
+            hasher.update(normalized.as_bytes());
+            hasher.update(b"\n");
+        }
+        Ok(hex::encode(hasher.finalize()))
+    }
+}
@@ -1,74 +1,342 @@
-use sha1::{Digest, Sha1};
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::fmt;
 use std::fs;
-use std::path::Path;
-
-use crate::differ::Differ;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use crate::atomic::FsyncPolicy;
+use crate::differ::{
+    ChangeKind, DiffFormat, DiffOptions, DiffTarget, Differ, FileChange, TreeDiff,
+    WhitespaceOptions,
+};
+use crate::error::Error;
+use crate::lock::{LOCK_SUFFIX, LockFile, LockOptions, WORKTREE_LOCK};
+use crate::odb::{LooseObjectDatabase, MemoryObjectDatabase, ObjectDatabase};
+use crate::oid::{HashAlgorithm, Oid};
+use crate::output::{Color, OutputFormat, paint};
 
 pub const GIT_DIR: &str = ".bgit";
 pub const HEAD: &str = "HEAD";
 pub const MERGE_HEAD: &str = "MERGE_HEAD";
+// Branch HEAD points to in a new repository
+pub const DEFAULT_BRANCH: &str = "master";
+// The config recording the object format of a repository other than SHA-1
+pub const OBJECT_FORMAT_CONFIG: &str = "extensions.objectFormat";
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ObjectType {
     Blob,
     Tree,
     Commit,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RefValue {
+    // A commit
+    Direct(Oid),
+    // Another ref, "refs/heads/master"
+    Symbolic(String),
+    // A branch without commits yet, the empty file written by init
+    Unborn,
+}
+
+impl RefValue {
+    // The commit of a direct ref
+    pub fn oid(&self) -> Option<Oid> {
+        match self {
+            RefValue::Direct(oid) => Some(*oid),
+            _ => None,
+        }
+    }
+}
+
+// Options of `log`
+#[derive(Debug, Clone, Default)]
+pub struct LogOptions {
+    // Only show the commits that changed this path, across renames
+    pub follow: Option<String>,
+    // Also diff each commit against its parent (-p, --stat, ...)
+    pub diff: Option<DiffFormat>,
+    // --format=json
+    pub format: OutputFormat,
+}
+
+// Options of `init`
+#[derive(Debug, Clone, Default)]
+pub struct InitOptions {
+    // No worktree: objects and refs directly in the directory (--bare)
+    pub bare: bool,
+    // Branch of the first commit, DEFAULT_BRANCH when None
+    pub initial_branch: Option<String>,
+    // The hash of object ids (--object-format), fixed for the life of the
+    // repository
+    pub object_format: HashAlgorithm,
+}
+
+// Options of `merge`
+#[derive(Debug, Clone, Default)]
+pub struct MergeOptions {
+    // -Xignore-space-change and friends: whitespace-only changes on one side
+    // do not conflict with the other side
+    pub whitespace: WhitespaceOptions,
+}
+
+// A commit as `log` shows it
 #[derive(Debug)]
-pub struct RefValue {
-    pub value: String,
-    pub is_symbolic: bool,
+pub struct LogEntry {
+    pub commit: Commit,
+    // Full names of the refs pointing at the commit
+    pub refs: Vec<String>,
+    // Changes against the first parent when `LogOptions::diff` asks for
+    // them, never for merges
+    pub diff: Option<TreeDiff>,
+}
+
+// A commit as `show` shows it
+#[derive(Debug)]
+pub struct ShowResult {
+    pub commit: Commit,
+    // Changes against each parent, in order. Empty for a root commit.
+    pub diffs: Vec<TreeDiff>,
+}
+
+// How `merge` left the worktree
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MergeOutcome {
+    // HEAD moved to the branch, there is nothing to commit
+    FastForward,
+    // Both sides merged cleanly into the worktree, to be committed
+    Merged,
+    // These paths were left with conflict markers
+    Conflicted(Vec<String>),
 }
 
 #[derive(Debug)]
 pub struct Commit {
-    pub _oid: String,
-    pub tree: String,
-    pub parents: Vec<String>,
+    pub oid: Oid,
+    pub tree: Oid,
+    pub parents: Vec<Oid>,
+    // "Name <email>", only recorded when an identity is configured
+    pub author: Option<String>,
     pub timestamp: String,
     pub message: String,
 }
 
+// Authorship recorded in a commit object
+#[derive(Debug, Clone)]
+pub struct Signature {
+    pub author: Option<String>,
+    pub timestamp: String,
+}
+
+impl Commit {
+    pub fn signature(&self) -> Signature {
+        Signature {
+            author: self.author.clone(),
+            timestamp: self.timestamp.clone(),
+        }
+    }
+}
+
 impl ObjectType {
-    fn as_str(&self) -> &'static str {
+    // The name in object headers and `cat-file -t`
+    pub fn as_str(&self) -> &'static str {
         match self {
             ObjectType::Blob => "blob",
             ObjectType::Tree => "tree",
             ObjectType::Commit => "commit",
         }
     }
+
+    pub fn from_name(name: &str) -> Option<ObjectType> {
+        match name {
+            "blob" => Some(ObjectType::Blob),
+            "tree" => Some(ObjectType::Tree),
+            "commit" => Some(ObjectType::Commit),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ObjectType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
 }
 
 pub struct Repository {
     pub worktree: String,
+    // Empty for an in-memory repository, see `git_path`
     pub gitdir: String,
+    // Where the current directory is in the worktree, "src/" or "" at the
+    // top. Paths on the command line are relative to it.
+    pub prefix: String,
+    // A bare repository has no worktree, `worktree` is then the same
+    // directory as `gitdir`
+    pub bare: bool,
+    // Where objects are read and written, loose files in `gitdir/objects`
+    // unless the repository was given another backend
+    pub odb: Box<dyn ObjectDatabase>,
+    // The core.fsync config when the repository was opened
+    pub fsync: FsyncPolicy,
+    // How to take the locks on refs and the worktree, from core.lockTimeout
+    pub locks: LockOptions,
+    // The hash of object ids, from extensions.objectFormat
+    pub object_format: HashAlgorithm,
 }
 
 impl Repository {
     pub fn new(path: &str) -> Repository {
+        Repository::with_dirs(path, &format!("{}/{}", path, GIT_DIR), false)
+    }
+
+    // A repository whose objects and refs are directly in `path`
+    pub fn new_bare(path: &str) -> Repository {
+        Repository::with_dirs(path, path, true)
+    }
+
+    // A worktree and a repository directory that need not be related
+    pub fn with_dirs(worktree: &str, gitdir: &str, bare: bool) -> Repository {
+        let mut repo = Repository {
+            worktree: worktree.to_string(),
+            gitdir: gitdir.to_string(),
+            prefix: String::new(),
+            bare,
+            odb: Box::new(LooseObjectDatabase::new(format!("{}/objects", gitdir))),
+            fsync: FsyncPolicy::default(),
+            locks: LockOptions::default(),
+            object_format: HashAlgorithm::default(),
+        };
+        repo.locks = LockOptions::from_config(repo.get_config("core.lockTimeout").as_deref());
+        // `open` rejects a format it does not know
+        if let Some(format) = repo
+            .get_config(OBJECT_FORMAT_CONFIG)
+            .and_then(|name| HashAlgorithm::from_name(&name))
+        {
+            repo.object_format = format;
+        }
+        if let Some(fsync) = repo.get_config("core.fsync") {
+            repo.fsync = FsyncPolicy::parse(&fsync);
+        }
+        repo.odb = Box::new(repo.loose_object_database());
+        repo
+    }
+
+    // The objects in `gitdir/objects`, as the config says to store them
+    fn loose_object_database(&self) -> LooseObjectDatabase {
+        LooseObjectDatabase::new(format!("{}/objects", self.gitdir))
+            .with_fsync(self.fsync.objects)
+            .with_object_format(self.object_format)
+    }
+
+    // A bare repository without a directory, whose objects only live in
+    // memory. Objects, trees and merges of trees work as usual; refs, the
+    // config and everything else stored in a directory do not.
+    pub fn in_memory() -> Repository {
         Repository {
-            worktree: path.to_string(),
-            gitdir: format!("{}/{}", path, GIT_DIR),
+            worktree: String::new(),
+            gitdir: String::new(),
+            prefix: String::new(),
+            bare: true,
+            odb: Box::new(MemoryObjectDatabase::new()),
+            fsync: FsyncPolicy::default(),
+            locks: LockOptions::default(),
+            object_format: HashAlgorithm::default(),
+        }
+    }
+
+    // The path of `name` inside the gitdir. An in-memory repository has
+    // none, so its refs, config and logs cannot be reached.
+    pub fn git_path(&self, name: &str) -> Result<String, Error> {
+        if self.gitdir.is_empty() {
+            return Err(Error::InMemory);
+        }
+        Ok(format!("{}/{}", self.gitdir, name))
+    }
+
+    // The same repository, its objects stored in `odb` instead
+    pub fn with_object_database(self, odb: impl ObjectDatabase + 'static) -> Repository {
+        Repository {
+            odb: Box::new(odb),
+            ..self
+        }
+    }
+
+    // The same repository, hashing objects with `object_format`. Only for
+    // one without objects yet, such as an in-memory one.
+    pub fn with_object_format(self, object_format: HashAlgorithm) -> Repository {
+        Repository {
+            object_format,
+            ..self
+        }
+    }
+
+    // An object id given by the user or read from the repository, which
+    // must be one of its object format
+    pub fn parse_oid(&self, hex: &str) -> Result<Oid, Error> {
+        let oid: Oid = hex.parse()?;
+        if oid.algorithm() != self.object_format {
+            return Err(Error::InvalidRef(format!(
+                "{} is a {} id in a {} repository",
+                hex,
+                oid.algorithm(),
+                self.object_format
+            )));
+        }
+        Ok(oid)
+    }
+
+    // Creates a repository in `path`, which is created if needed
+    pub fn init_at(path: &str, options: &InitOptions) -> Result<Repository, Error> {
+        let repo = if options.bare {
+            Repository::new_bare(path)
+        } else {
+            Repository::new(path)
         }
+        .with_object_format(options.object_format);
+        let odb = repo.loose_object_database();
+        let repo = repo.with_object_database(odb);
+        repo.init_with_branch(options.initial_branch.as_deref().unwrap_or(DEFAULT_BRANCH))?;
+        Ok(repo)
     }
 
-    pub fn init(&self) -> Result<(), String> {
-        // Check if .bgit directory already exists
-        if Path::new(&self.gitdir).exists() {
-            return Err(format!("{} directory already exists", GIT_DIR));
+    pub fn init(&self) -> Result<(), Error> {
+        self.init_with_branch(DEFAULT_BRANCH)
+    }
+
+    fn init_with_branch(&self, branch: &str) -> Result<(), Error> {
+        if !Self::is_valid_branch_name(branch) {
+            return Err(Error::InvalidRef(branch.to_string()));
+        }
+        // An in-memory repository has nowhere to be created
+        self.git_path(HEAD)?;
+
+        // Check if the repository already exists. A bare repository can be
+        // created in an existing directory, as long as it is not one.
+        if !self.bare && Path::new(&self.gitdir).exists() {
+            return Err(Error::Other(format!(
+                "{} directory already exists",
+                GIT_DIR
+            )));
+        }
+        if self.bare && Path::new(&self.gitdir).join(HEAD).exists() {
+            return Err(Error::Other(format!(
+                "A repository already exists in {}",
+                self.gitdir
+            )));
         }
 
         // Create worktree directory if it doesn't exist
         if !Path::new(&self.worktree).exists() {
             fs::create_dir_all(&self.worktree)
-                .map_err(|e| format!("Failed to create worktree directory: {}", e))?;
+                .map_err(|e| Error::io("Failed to create worktree directory", e))?;
         }
 
         // Create .bgit directory
-        fs::create_dir(&self.gitdir)
-            .map_err(|e| format!("Failed to create {} directory: {}", GIT_DIR, e))?;
+        if !self.bare {
+            fs::create_dir(&self.gitdir)
+                .map_err(|e| Error::io(format!("Failed to create {} directory", GIT_DIR), e))?;
+        }
 
         // Create subdirectories
         let subdirs = ["objects", "refs/heads", "refs/tags"];
@@ -77,33 +345,62 @@ impl Repository {
         for dir in subdirs.iter() {
             let path = format!("{}/{}", self.gitdir, dir);
             fs::create_dir_all(&path)
-                .map_err(|e| format!("Failed to create directory {}: {}", dir, e))?;
+                .map_err(|e| Error::io(format!("Failed to create directory {}", dir), e))?;
         }
 
-        // Create master branch
-        let master_branch = format!("{}/refs/heads/master", self.gitdir);
-        fs::write(&master_branch, "")
-            .map_err(|e| format!("Failed to create master branch: {}", e))?;
+        // Create the initial branch, without commits yet
+        let branch_path = Path::new(&self.gitdir).join("refs/heads").join(branch);
+        if let Some(parent) = branch_path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| Error::io(format!("Failed to create {} branch", branch), e))?;
+        }
+        self.write_ref_file(&branch_path, "")?;
 
         // Create HEAD file
         let head_path = format!("{}/HEAD", self.gitdir);
-        fs::write(&head_path, "ref: refs/heads/master\n")
-            .map_err(|e| format!("Failed to create HEAD file: {}", e))?;
+        self.write_ref_file(
+            Path::new(&head_path),
+            &format!("ref: refs/heads/{}\n", branch),
+        )?;
 
-        // Create settings file
-        let full_path = fs::canonicalize(&self.gitdir)
-            .map_err(|e| format!("Failed to get absolute path: {}", e))?;
+        if self.bare {
+            self.set_config("core.bare", "true")?;
+        }
+        // As in git, SHA-1 repositories do not record it, for older readers
+        if self.object_format != HashAlgorithm::Sha1 {
+            self.set_config("core.repositoryFormatVersion", "1")?;
+            self.set_config(OBJECT_FORMAT_CONFIG, self.object_format.as_str())?;
+        }
 
-        // Print success message
-        println!(
-            "Initialized empty bGit repository in {}",
-            full_path.display()
-        );
+        Ok(())
+    }
 
+    // Branch names are paths under refs/heads: no empty components, no
+    // "..", no whitespace, and none of the characters git reserves
+    fn is_valid_branch_name(name: &str) -> bool {
+        !name.is_empty()
+            && !name.starts_with('-')
+            && !name.ends_with(".lock")
+            && !name.contains("..")
+            && !name.contains("@{")
+            && name
+                .split('/')
+                .all(|part| !part.is_empty() && !part.starts_with('.'))
+            && !name
+                .chars()
+                .any(|c| c.is_whitespace() || c.is_control() || "~^:?*[\\".contains(c))
+    }
+
+    // Commands that read or write the worktree fail early in a bare
+    // repository
+    pub fn require_worktree(&self) -> Result<(), Error> {
+        if self.bare {
+            return Err(Error::BareRepository);
+        }
         Ok(())
     }
 
-    pub fn hash_object(&self, data: &[u8], obj_type: ObjectType) -> Result<String, String> {
+    pub fn hash_object(&self, data: &[u8], obj_type: ObjectType) -> Result<Oid, Error> {
         // Create header: "{type} {size}\0"
         let header = format!("{} {}\0", obj_type.as_str(), data.len());
 
@@ -112,98 +409,83 @@ impl Repository {
         object_data.extend_from_slice(header.as_bytes());
         object_data.extend_from_slice(data);
 
-        // Create a new SHA-1 hasher
-        let mut hasher = Sha1::new();
-
-        // Update the hasher with the data
-        hasher.update(&object_data);
-
-        // Finalize the hasher and get the hash
-        let hash = hasher.finalize();
-
-        // Encode the hash as a hex string
-        let hash_str = hex::encode(hash);
-
-        // Create object path
-        let (dir, file) = hash_str.split_at(2);
-        let object_dir = format!("{}/objects/{}", self.gitdir, dir);
-        let object_path = format!("{}/{}", object_dir, file);
-
-        // Create directory if it doesn't exist
-        fs::create_dir_all(&object_dir)
-            .map_err(|e| format!("Failed to create object directory: {}", e))?;
-
-        // Write the object data to the file
-        fs::write(&object_path, &object_data)
-            .map_err(|e| format!("Failed to write object file: {}", e))?;
-
-        Ok(hash_str)
+        let oid = self.object_format.hash(&object_data);
+        self.odb.write(oid, &object_data)?;
+        Ok(oid)
     }
 
-    fn validate_commit_hash(hash: &str) -> Result<(), String> {
-        if hash.len() != 40 || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
-            return Err(format!("Invalid hash format: {}", hash));
-        }
-        Ok(())
-    }
-
-    fn is_hash(value: &str) -> Result<bool, String> {
-        if value.len() != 40 || !value.chars().all(|c| c.is_ascii_hexdigit()) {
-            return Ok(false);
-        }
-        Ok(true)
-    }
-
-    pub fn get_object(&self, hash: &str) -> Result<Vec<u8>, String> {
-        // Validate hash format
-        let hash = self.get_oid_hash(hash)?;
-
-        // Create object path
-        let (dir, file) = hash.split_at(2);
-        let object_path = format!("{}/objects/{}/{}", self.gitdir, dir, file);
-
-        // Read the object file
-        let object_data =
-            fs::read(&object_path).map_err(|e| format!("Failed to read object: {}", e))?;
+    // The type and content of an object. The header must name a known type
+    // and the size of the content.
+    pub fn get_object(&self, hash: Oid) -> Result<(ObjectType, Vec<u8>), Error> {
+        let object_data = self.odb.read(hash)?;
+        let corrupt = |reason: &str| Error::Corrupt(format!("Invalid object {}: {}", hash, reason));
 
         // Parse the header
         let header_end = object_data
             .iter()
             .position(|&b| b == 0)
-            .ok_or_else(|| "Invalid object format: missing null byte".to_string())?;
+            .ok_or_else(|| corrupt("missing null byte"))?;
 
         // Convert the header to a string
         let header = String::from_utf8(object_data[..header_end].to_vec())
-            .map_err(|_| "Invalid header encoding".to_string())?;
+            .map_err(|_| corrupt("invalid header encoding"))?;
 
         // Split the header into parts
         let mut parts = header.split_whitespace();
 
         // Get the object type
-        let _obj_type = parts
-            .next()
-            .ok_or_else(|| "Missing object type".to_string())?;
+        let name = parts.next().ok_or_else(|| corrupt("missing object type"))?;
+        let obj_type = ObjectType::from_name(name)
+            .ok_or_else(|| corrupt(&format!("unknown object type {}", name)))?;
 
         // Get the object size
-        let _size = parts
+        let size: usize = parts
             .next()
-            .ok_or_else(|| "Missing object size".to_string())?;
+            .ok_or_else(|| corrupt("missing object size"))?
+            .parse()
+            .map_err(|_| corrupt("invalid object size"))?;
+
+        // The actual content is everything after the header
+        let content = object_data[header_end + 1..].to_vec();
+        if content.len() != size {
+            return Err(corrupt(&format!(
+                "header says {} bytes, content has {}",
+                size,
+                content.len()
+            )));
+        }
 
-        // Return the actual content (everything after the header)
-        Ok(object_data[header_end + 1..].to_vec())
+        Ok((obj_type, content))
     }
 
-    pub fn create_tree(&self, path: &Path) -> Result<String, String> {
+    // The content of an object that must be of type `expected`, so that a
+    // blob is never parsed as a commit
+    pub fn read_object(&self, hash: Oid, expected: ObjectType) -> Result<Vec<u8>, Error> {
+        let (actual, content) = self.get_object(hash)?;
+        if actual != expected {
+            return Err(Error::WrongType {
+                oid: hash,
+                expected,
+                actual,
+            });
+        }
+        Ok(content)
+    }
+
+    pub fn create_tree(&self, path: &Path) -> Result<Oid, Error> {
+        self.require_worktree()?;
         let mut entries = Vec::new();
 
         // Read the directory
-        for entry in fs::read_dir(path).map_err(|e| format!("Failed to read directory: {}", e))? {
-            let entry = entry.map_err(|e| format!("Failed to read directory entry: {}", e))?;
+        for entry in fs::read_dir(path).map_err(|e| Error::io("Failed to read directory", e))? {
+            let entry = entry.map_err(|e| Error::io("Failed to read directory entry", e))?;
             let entry_path = entry.path();
             let name = entry_path
                 .file_name()
                 .and_then(|n| n.to_str())
-                .ok_or_else(|| "Invalid file name".to_string())?;
+                .ok_or_else(|| {
+                    Error::Other(format!("Invalid file name: {}", entry_path.display()))
+                })?;
 
             // Ignore ignored files and directories
             if self.is_ignored(&entry_path) {
@@ -212,17 +494,17 @@ impl Repository {
 
             let metadata = entry
                 .metadata()
-                .map_err(|e| format!("Failed to get metadata: {}", e))?;
+                .map_err(|e| Error::io("Failed to get metadata", e))?;
 
             if metadata.is_file() {
                 // For files, create a blob object
                 let content =
-                    fs::read(&entry_path).map_err(|e| format!("Failed to read file: {}", e))?;
+                    fs::read(&entry_path).map_err(|e| Error::io("Failed to read file", e))?;
                 let hash = self.hash_object(&content, ObjectType::Blob)?;
 
                 // Format: "100644 {name}\0{hash}"
                 let mut entry_data = format!("100644 {}\0", name).into_bytes();
-                entry_data.extend_from_slice(&hex::decode(hash).unwrap());
+                entry_data.extend_from_slice(hash.as_bytes());
                 entries.push(entry_data);
             } else if metadata.is_dir() {
                 // For directories, recursively create tree objects
@@ -230,7 +512,7 @@ impl Repository {
 
                 // Format: "40000 {name}\0{hash}"
                 let mut entry_data = format!("40000 {}\0", name).into_bytes();
-                entry_data.extend_from_slice(&hex::decode(hash).unwrap());
+                entry_data.extend_from_slice(hash.as_bytes());
                 entries.push(entry_data);
             }
         }
@@ -248,6 +530,48 @@ impl Repository {
         self.hash_object(&tree_data, ObjectType::Tree)
     }
 
+    // Build a tree from "dir/file" paths mapped to blob hashes, without
+    // touching the worktree
+    pub fn create_tree_from_paths(&self, files: &BTreeMap<String, Oid>) -> Result<Oid, Error> {
+        let mut blobs = Vec::new();
+        let mut subdirs: BTreeMap<String, BTreeMap<String, Oid>> = BTreeMap::new();
+
+        for (path, hash) in files {
+            match path.split_once('/') {
+                Some((dir, rest)) => {
+                    subdirs
+                        .entry(dir.to_string())
+                        .or_default()
+                        .insert(rest.to_string(), *hash);
+                }
+                None => blobs.push((path.clone(), *hash)),
+            }
+        }
+
+        let mut entries = Vec::new();
+        for (name, hash) in blobs {
+            let mut entry_data = format!("100644 {}\0", name).into_bytes();
+            entry_data.extend_from_slice(hash.as_bytes());
+            entries.push(entry_data);
+        }
+        for (name, children) in subdirs {
+            let hash = self.create_tree_from_paths(&children)?;
+            let mut entry_data = format!("40000 {}\0", name).into_bytes();
+            entry_data.extend_from_slice(hash.as_bytes());
+            entries.push(entry_data);
+        }
+
+        // Same ordering as create_tree
+        entries.sort();
+
+        let mut tree_data = Vec::new();
+        for entry in entries {
+            tree_data.extend_from_slice(&entry);
+        }
+
+        self.hash_object(&tree_data, ObjectType::Tree)
+    }
+
     pub fn is_ignored(&self, path: &Path) -> bool {
         let paths_to_ignore = [
             // bGit directories
@@ -262,8 +586,15 @@ impl Repository {
             ".vscode",
         ];
 
+        // Get the relative path from the worktree, the directories above it
+        // do not matter
+        let path_str = match path.strip_prefix(&self.worktree) {
+            Ok(relative) => relative.to_string_lossy().to_string(),
+            Err(_) => path.to_string_lossy().to_string(),
+        };
+
         for ignore_path in paths_to_ignore {
-            if path.to_string_lossy().contains(ignore_path) {
+            if path_str.contains(ignore_path) {
                 return true;
             }
         }
@@ -281,12 +612,6 @@ impl Repository {
             }
         };
 
-        // Get the relative path from the worktree
-        let path_str = match path.strip_prefix(&self.worktree) {
-            Ok(relative) => relative.to_string_lossy().to_string(),
-            Err(_) => path.to_string_lossy().to_string(),
-        };
-
         let lines = gitignore_content.lines();
         for line in lines {
             if line.is_empty() || line.starts_with("#") {
@@ -307,10 +632,10 @@ impl Repository {
             // Handle wildcard patterns
             if pattern.contains('*') {
                 let regex_pattern = pattern.replace(".", "\\.").replace("*", ".*");
-                if let Ok(re) = regex::Regex::new(&regex_pattern) {
-                    if re.is_match(&path_str) {
-                        return true;
-                    }
+                if let Ok(re) = regex::Regex::new(&regex_pattern)
+                    && re.is_match(&path_str)
+                {
+                    return true;
                 }
                 continue;
             }
@@ -323,81 +648,68 @@ impl Repository {
         false
     }
 
-    pub fn read_tree(&self, tree_oid: &str, path: &Path) -> Result<(), String> {
-        // Empty the current directory first
-        self.empty_current_directory(path)?;
-
-        let tree_oid = self.get_oid_hash(tree_oid)?;
-
-        // Get the tree object
-        let tree_data = self.get_object(tree_oid.as_str())?;
-
-        // Parse tree entries
-        let mut pos = 0;
-        while pos < tree_data.len() {
-            // Find the null byte that separates the mode+name from the hash
-            let null_pos = tree_data[pos..]
-                .iter()
-                .position(|&b| b == 0)
-                .ok_or_else(|| "Invalid tree format: missing null byte".to_string())?;
-
-            // Parse mode and name
-            let mode_name = &tree_data[pos..pos + null_pos];
-            let mode_name_str = String::from_utf8(mode_name.to_vec())
-                .map_err(|_| "Invalid mode/name encoding".to_string())?;
-
-            let mut parts = mode_name_str.split_whitespace();
-            let mode = parts.next().ok_or_else(|| "Missing mode".to_string())?;
-            let name = parts.next().ok_or_else(|| "Missing name".to_string())?;
+    pub fn read_tree(&self, tree_oid: Oid, path: &Path) -> Result<(), Error> {
+        self.require_worktree()?;
+        // Every tree and blob is read first, so that a missing or corrupt
+        // object fails before anything in the directory is removed
+        let mut entries = Vec::new();
+        self.checkout_entries(tree_oid, path, &mut entries)?;
 
-            // Get the hash (20 bytes after the null byte)
-            let hash_start = pos + null_pos + 1;
-            let hash_end = hash_start + 20;
-            if hash_end > tree_data.len() {
-                return Err("Invalid tree format: truncated hash".to_string());
+        self.empty_current_directory(path)?;
+        for (entry_path, content) in entries {
+            match content {
+                Some(content) => fs::write(&entry_path, content).map_err(|e| {
+                    Error::io(format!("Failed to write file {}", entry_path.display()), e)
+                })?,
+                None => fs::create_dir_all(&entry_path).map_err(|e| {
+                    Error::io(
+                        format!("Failed to create directory {}", entry_path.display()),
+                        e,
+                    )
+                })?,
             }
+        }
 
-            let hash = hex::encode(&tree_data[hash_start..hash_end]);
-
-            // Create the full path
-            let entry_path = path.join(name);
+        Ok(())
+    }
 
-            // Skip ignored files
+    // The paths under `path` that checking out a tree writes, with the
+    // content of files and None for directories, each directory before its
+    // entries. Ignored paths are left out.
+    fn checkout_entries(
+        &self,
+        tree_oid: Oid,
+        path: &Path,
+        entries: &mut Vec<(PathBuf, Option<Vec<u8>>)>,
+    ) -> Result<(), Error> {
+        for (_, name, hash, obj_type) in self.get_tree_data(tree_oid)? {
+            let entry_path = path.join(&name);
             if self.is_ignored(&entry_path) {
-                pos = hash_end;
                 continue;
             }
 
-            if mode == "100644" {
-                // It's a file - create a blob
-                let content = self.get_object(&hash)?;
-                fs::write(&entry_path, content)
-                    .map_err(|e| format!("Failed to write file {}: {}", name, e))?;
-            } else if mode == "40000" {
-                // It's a directory - create it and recurse
-                fs::create_dir_all(&entry_path)
-                    .map_err(|e| format!("Failed to create directory {}: {}", name, e))?;
-                self.read_tree(&hash, &entry_path)?;
+            if obj_type == ObjectType::Blob {
+                entries.push((entry_path, Some(self.read_object(hash, ObjectType::Blob)?)));
             } else {
-                return Err(format!("Unsupported mode: {}", mode));
+                entries.push((entry_path.clone(), None));
+                self.checkout_entries(hash, &entry_path, entries)?;
             }
-
-            // Move to the next entry
-            pos = hash_end;
         }
-
         Ok(())
     }
 
-    pub fn empty_current_directory(&self, path: &Path) -> Result<(), String> {
+    pub fn empty_current_directory(&self, path: &Path) -> Result<(), Error> {
+        self.require_worktree()?;
         // Read all entries in the directory
-        for entry in fs::read_dir(path).map_err(|e| format!("Failed to read directory: {}", e))? {
-            let entry = entry.map_err(|e| format!("Failed to read directory entry: {}", e))?;
+        for entry in fs::read_dir(path).map_err(|e| Error::io("Failed to read directory", e))? {
+            let entry = entry.map_err(|e| Error::io("Failed to read directory entry", e))?;
             let entry_path = entry.path();
             let name = entry_path
                 .file_name()
                 .and_then(|n| n.to_str())
-                .ok_or_else(|| "Invalid file name".to_string())?;
+                .ok_or_else(|| {
+                    Error::Other(format!("Invalid file name: {}", entry_path.display()))
+                })?;
 
             // Skip ignored files
             if self.is_ignored(&entry_path) {
@@ -407,10 +719,10 @@ impl Repository {
             // Remove the entry
             if entry_path.is_dir() {
                 fs::remove_dir_all(&entry_path)
-                    .map_err(|e| format!("Failed to remove directory {}: {}", name, e))?;
+                    .map_err(|e| Error::io(format!("Failed to remove directory {}", name), e))?;
             } else {
                 fs::remove_file(&entry_path)
-                    .map_err(|e| format!("Failed to remove file {}: {}", name, e))?;
+                    .map_err(|e| Error::io(format!("Failed to remove file {}", name), e))?;
             }
         }
 
@@ -419,11 +731,12 @@ impl Repository {
 
     pub fn get_tree_data(
         &self,
-        tree_oid: &str,
-    ) -> Result<Vec<(String, String, String, ObjectType)>, String> {
+        tree_oid: Oid,
+    ) -> Result<Vec<(String, String, Oid, ObjectType)>, Error> {
         // Get the raw object data
-        let tree_oid = self.get_oid_hash(tree_oid)?;
-        let tree_data = self.get_object(tree_oid.as_str())?;
+        let tree_data = self.read_object(tree_oid, ObjectType::Tree)?;
+        let corrupt =
+            |reason: &str| Error::Corrupt(format!("Invalid tree {}: {}", tree_oid, reason));
 
         let mut entries = Vec::new();
         let mut pos = 0;
@@ -433,31 +746,31 @@ impl Repository {
             let null_pos = tree_data[pos..]
                 .iter()
                 .position(|&b| b == 0)
-                .ok_or_else(|| "Invalid tree format: missing null byte".to_string())?;
+                .ok_or_else(|| corrupt("missing null byte"))?;
 
             // Parse mode and name
             let mode_name = &tree_data[pos..pos + null_pos];
             let mode_name_str = String::from_utf8(mode_name.to_vec())
-                .map_err(|_| "Invalid mode/name encoding".to_string())?;
+                .map_err(|_| corrupt("invalid mode/name encoding"))?;
 
             let mut parts = mode_name_str.split_whitespace();
-            let mode = parts.next().ok_or_else(|| "Missing mode".to_string())?;
-            let name = parts.next().ok_or_else(|| "Missing name".to_string())?;
+            let mode = parts.next().ok_or_else(|| corrupt("missing mode"))?;
+            let name = parts.next().ok_or_else(|| corrupt("missing name"))?;
 
-            // Get the hash (20 bytes after the null byte)
+            // Get the hash (20 bytes after the null byte, 32 for SHA-256)
             let hash_start = pos + null_pos + 1;
-            let hash_end = hash_start + 20;
+            let hash_end = hash_start + self.object_format.oid_len();
             if hash_end > tree_data.len() {
-                return Err("Invalid tree format: truncated hash".to_string());
+                return Err(corrupt("truncated hash"));
             }
 
-            let hash = hex::encode(&tree_data[hash_start..hash_end]);
+            let hash = Oid::from_bytes(&tree_data[hash_start..hash_end])?;
 
             // Determine object type based on mode
             let obj_type = match mode {
                 "100644" => ObjectType::Blob,
                 "40000" => ObjectType::Tree,
-                _ => return Err(format!("Unsupported mode: {}", mode)),
+                _ => return Err(corrupt(&format!("unsupported mode {}", mode))),
             };
 
             // Add the entry to the result
@@ -470,45 +783,102 @@ impl Repository {
         Ok(entries)
     }
 
-    pub fn create_commit(&self, message: &str) -> Result<String, String> {
+    pub fn create_commit(&self, message: &str) -> Result<Oid, Error> {
         if message.trim().is_empty() {
-            return Err("Commit message cannot be empty".to_string());
+            return Err(Error::Other("Commit message cannot be empty".to_string()));
         }
 
-        let mut commit_data = Vec::new();
-
         // Create tree from worktree
         let tree_oid = self.create_tree(Path::new(&self.worktree))?;
 
-        // Add tree hash
-        commit_data.extend_from_slice(b"tree ");
-        commit_data.extend_from_slice(tree_oid.as_bytes());
-        commit_data.extend_from_slice(b"\n");
+        let mut parents = Vec::new();
 
-        // Add parent commit if HEAD exists and contains a valid commit hash
-        if let Ok(parent_hash) = self.get_ref(HEAD, true) {
-            // Only add parent if it's a valid commit hash (40 hex characters)
-            if Self::is_hash(&parent_hash.value)? {
-                commit_data.extend_from_slice(b"parent ");
-                commit_data.extend_from_slice(parent_hash.value.as_bytes());
-                commit_data.extend_from_slice(b"\n");
-            }
+        // Add parent commit if HEAD has one, not on an unborn branch
+        if let Ok(RefValue::Direct(parent)) = self.get_ref(HEAD, true) {
+            parents.push(parent);
         }
 
         // Merge HEAD
         if let Ok(merge_head) = self.get_ref(MERGE_HEAD, true) {
-            commit_data.extend_from_slice(b"parent ");
-            commit_data.extend_from_slice(merge_head.value.as_bytes());
-            commit_data.extend_from_slice(b"\n");
+            if let Some(oid) = merge_head.oid() {
+                parents.push(oid);
+            }
 
             // Remove MERGE_HEAD
             self.delete_ref(MERGE_HEAD, false)?;
         }
 
+        let hash = self.write_commit(tree_oid, &parents, message)?;
+
+        // Set HEAD to point to the new commit
+        self.set_ref(HEAD, RefValue::Direct(hash), true)?;
+
+        Ok(hash)
+    }
+
+    // Identity of whoever is committing right now.
+    // BGIT_AUTHOR_NAME/BGIT_AUTHOR_EMAIL take precedence over user.name/user.email
+    pub fn default_signature(&self) -> Signature {
+        let name = std::env::var("BGIT_AUTHOR_NAME")
+            .ok()
+            .or_else(|| self.get_config("user.name"));
+        let email = std::env::var("BGIT_AUTHOR_EMAIL")
+            .ok()
+            .or_else(|| self.get_config("user.email"));
+
+        let author = match (name, email) {
+            (Some(name), Some(email)) => Some(format!("{} <{}>", name, email)),
+            (Some(name), None) => Some(name),
+            (None, Some(email)) => Some(format!("<{}>", email)),
+            (None, None) => None,
+        };
+
+        Signature {
+            author,
+            timestamp: chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+        }
+    }
+
+    // Store a commit object for the given tree and parents without moving any ref
+    pub fn write_commit(
+        &self,
+        tree_oid: Oid,
+        parents: &[Oid],
+        message: &str,
+    ) -> Result<Oid, Error> {
+        self.write_commit_as(tree_oid, parents, message, &self.default_signature())
+    }
+
+    // Same as write_commit, with explicit authorship (used when replaying commits)
+    pub fn write_commit_as(
+        &self,
+        tree_oid: Oid,
+        parents: &[Oid],
+        message: &str,
+        signature: &Signature,
+    ) -> Result<Oid, Error> {
+        let mut commit_data = Vec::new();
+
+        // Add tree hash
+        commit_data.extend_from_slice(b"tree ");
+        commit_data.extend_from_slice(tree_oid.to_string().as_bytes());
+        commit_data.extend_from_slice(b"\n");
+
+        for parent in parents {
+            commit_data.extend_from_slice(b"parent ");
+            commit_data.extend_from_slice(parent.to_string().as_bytes());
+            commit_data.extend_from_slice(b"\n");
+        }
+
+        if let Some(author) = &signature.author {
+            commit_data.extend_from_slice(b"author ");
+            commit_data.extend_from_slice(author.as_bytes());
+            commit_data.extend_from_slice(b"\n");
+        }
+
         // Add datetime
-        let datetime = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
         commit_data.extend_from_slice(b"timestamp ");
-        commit_data.extend_from_slice(datetime.as_bytes());
+        commit_data.extend_from_slice(signature.timestamp.as_bytes());
 
         // 2 new lines
         commit_data.extend_from_slice(b"\n");
@@ -518,43 +888,70 @@ impl Repository {
         commit_data.extend_from_slice(message.as_bytes());
         commit_data.extend_from_slice(b"\n");
 
-        let hash = self.hash_object(&commit_data, ObjectType::Commit)?;
-
-        // Set HEAD to point to the new commit
-        self.set_ref(
-            HEAD,
-            RefValue {
-                value: hash.clone(),
-                is_symbolic: false,
-            },
-            true,
-        )?;
+        self.hash_object(&commit_data, ObjectType::Commit)
+    }
 
-        Ok(hash)
+    // Open the user's editor on a file and wait for it to exit.
+    // Resolution order: BGIT_EDITOR, core.editor, VISUAL, EDITOR, vi
+    pub fn launch_editor(&self, path: &Path) -> Result<(), Error> {
+        let editor = std::env::var("BGIT_EDITOR")
+            .ok()
+            .or_else(|| self.get_config("core.editor"))
+            .or_else(|| std::env::var("VISUAL").ok())
+            .or_else(|| std::env::var("EDITOR").ok())
+            .filter(|editor| !editor.trim().is_empty())
+            .unwrap_or_else(|| "vi".to_string());
+
+        // Run through the shell so editors with arguments ("code --wait") work
+        let status = std::process::Command::new("sh")
+            .arg("-c")
+            .arg(format!("{} \"$@\"", editor))
+            .arg(&editor)
+            .arg(path)
+            .current_dir(&self.worktree)
+            .status()
+            .map_err(|e| Error::io(format!("Failed to launch editor {}", editor), e))?;
+
+        if !status.success() {
+            return Err(Error::Other(format!(
+                "Editor {} exited with {}",
+                editor, status
+            )));
+        }
+        Ok(())
     }
 
-    pub fn set_ref(&self, ref_name: &str, ref_value: RefValue, deref: bool) -> Result<(), String> {
-        // Ability to set a symbolic ref
-        let new_value = if ref_value.is_symbolic {
-            // Set a symbolic ref
-            format!("ref: {}", ref_value.value)
-        } else {
-            // Set a direct ref
-            ref_value.value.clone()
+    pub fn set_ref(&self, ref_name: &str, ref_value: RefValue, deref: bool) -> Result<(), Error> {
+        let new_value = match ref_value {
+            RefValue::Direct(oid) => oid.to_string(),
+            RefValue::Symbolic(target) => format!("ref: {}", target),
+            RefValue::Unborn => String::new(),
         };
 
         // Try to get the actual reference, but if it doesn't exist, use the original name
         let ref_path = match self.get_ref_internal(ref_name, deref) {
-            Ok((deref_name, _)) => format!("{}/{}", self.gitdir, deref_name),
-            Err(_) => format!("{}/{}", self.gitdir, ref_name),
+            Ok((deref_name, _)) => self.git_path(&deref_name)?,
+            Err(_) => self.git_path(ref_name)?,
         };
 
-        fs::write(&ref_path, new_value)
-            .map_err(|e| format!("Failed to update {} file: {}", ref_name, e))?;
-        Ok(())
+        self.write_ref_file(Path::new(&ref_path), &new_value)
+    }
+
+    // Refs, and their logs, are written to their lock file and renamed over
+    // the old one, so that no other process writes them at the same time
+    // and a crash cannot leave one truncated
+    pub(crate) fn write_ref_file(&self, path: &Path, content: &str) -> Result<(), Error> {
+        LockFile::acquire(path, &self.locks)?
+            .commit(content.as_bytes(), self.fsync.refs)
+            .map_err(|e| Error::io(format!("Failed to write {}", path.display()), e))
     }
 
-    pub fn get_ref(&self, ref_name: &str, deref: bool) -> Result<RefValue, String> {
+    // Held by commands that change the worktree while they run
+    pub fn lock_worktree(&self) -> Result<LockFile, Error> {
+        LockFile::acquire(Path::new(&self.git_path(WORKTREE_LOCK)?), &self.locks)
+    }
+
+    pub fn get_ref(&self, ref_name: &str, deref: bool) -> Result<RefValue, Error> {
         let (_, ref_value) = self.get_ref_internal(ref_name, deref)?;
         Ok(ref_value)
     }
@@ -563,54 +960,59 @@ impl Repository {
         &self,
         ref_name: &str,
         deref: bool,
-    ) -> Result<(String, RefValue), String> {
+    ) -> Result<(String, RefValue), Error> {
         // Get the ref path
-        let ref_path = format!("{}/{}", self.gitdir, ref_name);
+        let ref_path = self.git_path(ref_name)?;
 
         // Read the ref file
-        let content = fs::read_to_string(&ref_path)
-            .map_err(|e| format!("Failed to read {} file: {}", ref_name, e))?;
+        let content = fs::read_to_string(&ref_path).map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => Error::InvalidRef(ref_name.to_string()),
+            _ => Error::io(format!("Failed to read {} file", ref_name), e),
+        })?;
 
         // Trim the content
         let content = content.trim();
 
-        let is_symbolic = content.starts_with("ref:");
+        let ref_value =
+            if let Some(target_ref) = content.strip_prefix("ref:") {
+                let target_ref = target_ref.trim();
+                if deref {
+                    // Recursively resolve the target ref
+                    return self.get_ref_internal(target_ref, deref);
+                }
+                RefValue::Symbolic(target_ref.to_string())
+            } else if content.is_empty() {
+                RefValue::Unborn
+            } else {
+                RefValue::Direct(self.parse_oid(content).map_err(|_| {
+                    Error::Corrupt(format!("Invalid ref {}: {}", ref_name, content))
+                })?)
+            };
 
-        if is_symbolic && deref {
-            // Extract the target ref name and recursively resolve it
-            let target_ref = content.strip_prefix("ref:").unwrap().trim();
-            self.get_ref_internal(target_ref, deref)
-        } else {
-            Ok((
-                ref_name.to_string(),
-                RefValue {
-                    value: content.to_string(),
-                    is_symbolic,
-                },
-            ))
-        }
+        Ok((ref_name.to_string(), ref_value))
     }
 
-    pub fn delete_ref(&self, ref_name: &str, deref: bool) -> Result<(), String> {
+    pub fn delete_ref(&self, ref_name: &str, deref: bool) -> Result<(), Error> {
         let ref_value = self.get_ref_internal(ref_name, deref)?;
 
-        let ref_path = format!("{}/{}", self.gitdir, ref_value.0);
+        let ref_path = self.git_path(&ref_value.0)?;
 
+        let _lock = LockFile::acquire(Path::new(&ref_path), &self.locks)?;
         fs::remove_file(&ref_path)
-            .map_err(|e| format!("Failed to delete {} file: {}", ref_name, e))?;
+            .map_err(|e| Error::io(format!("Failed to delete {} file", ref_name), e))?;
         Ok(())
     }
 
-    pub fn get_commit(&self, hash: &str) -> Result<Commit, String> {
+    pub fn get_commit(&self, hash: Oid) -> Result<Commit, Error> {
         // Get the raw commit data
-        let hash = self.get_oid_hash(hash)?;
-        let commit_data = self.get_object(hash.as_str())?;
-        let commit_str =
-            String::from_utf8(commit_data).map_err(|_| "Invalid commit encoding".to_string())?;
+        let commit_data = self.read_object(hash, ObjectType::Commit)?;
+        let corrupt = |reason: &str| Error::Corrupt(format!("Invalid commit {}: {}", hash, reason));
+        let commit_str = String::from_utf8(commit_data).map_err(|_| corrupt("invalid encoding"))?;
 
         // Parse the commit data
         let mut tree = None;
         let mut parents = Vec::new();
+        let mut author = None;
         let mut timestamp = None;
         let mut message = String::new();
         let mut in_message = false;
@@ -628,143 +1030,168 @@ impl Repository {
             }
 
             if let Some(rest) = line.strip_prefix("tree ") {
-                tree = Some(rest.to_string());
+                tree = Some(
+                    self.parse_oid(rest)
+                        .map_err(|_| corrupt("invalid tree hash"))?,
+                );
             } else if let Some(rest) = line.strip_prefix("parent ") {
-                parents.push(rest.to_string());
+                parents.push(
+                    self.parse_oid(rest)
+                        .map_err(|_| corrupt("invalid parent hash"))?,
+                );
+            } else if let Some(rest) = line.strip_prefix("author ") {
+                author = Some(rest.to_string());
             } else if let Some(rest) = line.strip_prefix("timestamp ") {
                 timestamp = Some(rest.to_string());
             }
         }
 
         // Validate required fields
-        let tree = tree.ok_or_else(|| "Missing tree hash in commit".to_string())?;
-        let timestamp = timestamp.ok_or_else(|| "Missing timestamp in commit".to_string())?;
+        let tree = tree.ok_or_else(|| corrupt("missing tree hash"))?;
+        let timestamp = timestamp.ok_or_else(|| corrupt("missing timestamp"))?;
 
         // Remove trailing newline from message
         message = message.trim_end().to_string();
 
         Ok(Commit {
-            _oid: hash,
+            oid: hash,
             tree,
             parents,
+            author,
             timestamp,
             message,
         })
     }
 
-    pub fn log(&self) -> Result<(), String> {
-        // Get the current HEAD commit
-        let head_hash = self
-            .get_ref(HEAD, true)
-            .map_err(|e| format!("No commits found: {}", e))?;
+    pub fn log(&self) -> Result<Vec<LogEntry>, Error> {
+        self.log_with_options(&LogOptions::default())
+    }
 
-        let current_hash = head_hash.value;
+    // The commits `log` shows, newest first, with their refs and, when
+    // `options.diff` asks for it, their changes
+    pub fn log_with_options(&self, options: &LogOptions) -> Result<Vec<LogEntry>, Error> {
+        self.log_commits(options)?
+            .into_iter()
+            .map(|hash| self.log_entry(hash, options.diff.is_some()))
+            .collect()
+    }
 
-        if current_hash.is_empty() {
-            return Ok(());
+    // The commits shown by `log`, newest first
+    pub fn log_commits(&self, options: &LogOptions) -> Result<Vec<Oid>, Error> {
+        if let Some(path) = &options.follow {
+            return Ok(self
+                .follow_path(path)?
+                .into_iter()
+                .map(|(hash, _)| hash)
+                .collect());
         }
 
-        let commits = self.iter_commits_and_parents(vec![current_hash])?;
-
-        for hash in commits {
-            let commit = self.get_commit(&hash)?;
-
-            // Get all refs pointing to this commit
-            let mut refs = Vec::new();
+        // Get the current HEAD commit, there is none on an unborn branch
+        match self.get_ref(HEAD, true)?.oid() {
+            Some(current_hash) => self.iter_commits_and_parents(vec![current_hash]),
+            None => Ok(Vec::new()),
+        }
+    }
 
-            // Check branches
-            let branch_refs = self.iter_refs("refs/heads/")?;
-            for (name, ref_hash) in branch_refs {
-                if ref_hash == hash {
-                    let branch_name = name.split("/").last().unwrap();
-                    refs.push(format!("branch: {}", branch_name));
-                }
-            }
+    // Walk the first-parent history from HEAD and return the commits that
+    // changed `path`, with the name the file had in each of them
+    pub fn follow_path(&self, path: &str) -> Result<Vec<(Oid, String)>, Error> {
+        let differ = Differ::new(self);
+        let mut history = Vec::new();
+        let mut path = path.to_string();
+        let mut next = self.get_oid_hash(HEAD).ok();
+
+        while let Some(current) = next {
+            let commit = self.get_commit(current)?;
+            let parent = commit.parents.first().copied();
+            let parent_tree = match parent {
+                Some(parent) => Some(self.get_commit(parent)?.tree),
+                None => None,
+            };
 
-            // Check tags
-            let tag_refs = self.iter_refs("refs/tags/")?;
-            for (name, ref_hash) in tag_refs {
-                if ref_hash == hash {
-                    let tag_name = name.split("/").last().unwrap();
-                    refs.push(format!("tag: {}", tag_name));
+            if let Some(change) = differ
+                .changed_files(parent_tree, Some(commit.tree))?
+                .into_iter()
+                .find(|change| change.path() == path)
+            {
+                history.push((current, path.clone()));
+                match change.kind {
+                    // The file did not exist before this commit
+                    ChangeKind::Added => break,
+                    ChangeKind::Renamed | ChangeKind::Copied => path = change.old_path,
+                    _ => {}
                 }
             }
 
-            // Print commit information
-            println!();
-            println!("\x1b[33mcommit {}\x1b[0m", hash);
-            if !commit.parents.is_empty() {
-                for parent in commit.parents {
-                    println!("parents {}", parent);
-                }
-            }
-            println!("tree {}", commit.tree);
-            println!("Date:   {}", commit.timestamp);
-            if !refs.is_empty() {
-                println!("Refs:   {}", refs.join(", "));
-            }
-            println!();
-            println!("    {}", commit.message);
-            println!();
+            next = parent;
         }
 
-        Ok(())
+        Ok(history)
     }
 
-    pub fn checkout(&self, value: &str) -> Result<(), String> {
-        // Get oid hash
-        let commit_hash = Self::get_oid_hash(self, value)?;
+    fn log_entry(&self, hash: Oid, diff: bool) -> Result<LogEntry, Error> {
+        let commit = self.get_commit(hash)?;
+        let refs = self.refs_pointing_at(hash)?;
 
-        // Validate hash format
-        Self::validate_commit_hash(&commit_hash)?;
+        // Changes against the first parent, merges are left out like git
+        let diff = if diff && commit.parents.len() <= 1 {
+            let parent_tree = match commit.parents.first() {
+                Some(parent) => Some(self.get_commit(*parent)?.tree),
+                None => None,
+            };
+            Some(Differ::new(self).tree_diff(parent_tree, Some(commit.tree))?)
+        } else {
+            None
+        };
+
+        Ok(LogEntry { commit, refs, diff })
+    }
+
+    // Full names of the refs pointing at a commit, "refs/heads/master"
+    pub fn refs_pointing_at(&self, hash: Oid) -> Result<Vec<String>, Error> {
+        Ok(self
+            .iter_refs("")?
+            .into_iter()
+            .filter(|(_, value)| *value == RefValue::Direct(hash))
+            .map(|(name, _)| name)
+            .collect())
+    }
+
+    pub fn checkout(&self, value: &str) -> Result<(), Error> {
+        // Get oid hash
+        let commit_hash = self.get_oid_hash(value)?;
 
         // Get the commit from the hash
-        let commit = self
-            .get_commit(commit_hash.as_str())
-            .map_err(|_| format!("Commit with hash: {} not found", commit_hash))?;
+        let commit = self.get_commit(commit_hash)?;
 
         // Read the commit tree
-        self.read_tree(&commit.tree, Path::new(&self.worktree))?;
+        self.read_tree(commit.tree, Path::new(&self.worktree))?;
 
-        // If the value is a branch, set the HEAD to the last commit of the branch
-        // else set the HEAD to the commit hash
+        // If the value is a branch, HEAD follows the branch, else it is
+        // detached at the commit
         let new_head = if self.is_branch(value)? {
-            format!("refs/heads/{}", value)
+            RefValue::Symbolic(format!("refs/heads/{}", value))
         } else {
-            commit_hash.clone()
+            RefValue::Direct(commit_hash)
         };
 
-        // If the value is a branch, the ref is symbolic, else it is direct
-        let new_is_symbolic = self.is_branch(value)?;
-
         // Set the HEAD to the new head
-        self.set_ref(
-            HEAD,
-            RefValue {
-                value: new_head,
-                is_symbolic: new_is_symbolic,
-            },
-            false,
-        )?;
+        self.set_ref(HEAD, new_head, false)?;
 
         Ok(())
     }
 
-    pub fn create_tag(&self, tag_name: &str, commit_hash: &str) -> Result<(), String> {
-        // Validate hash format
-        Self::validate_commit_hash(commit_hash)?;
-
+    pub fn create_tag(&self, tag_name: &str, commit_hash: Oid) -> Result<(), Error> {
         self.set_ref(
             format!("refs/tags/{}", tag_name).as_str(),
-            RefValue {
-                value: commit_hash.to_string(),
-                is_symbolic: false,
-            },
+            RefValue::Direct(commit_hash),
             true,
         )
     }
 
-    pub fn get_oid_hash(&self, value: &str) -> Result<String, String> {
+    // The commit a hash, ref, branch or tag names. An unborn branch names
+    // none.
+    pub fn get_oid_hash(&self, value: &str) -> Result<Oid, Error> {
         let mut value_to_search = value;
 
         if value == "@" {
@@ -772,8 +1199,8 @@ impl Repository {
         }
 
         // First check if it's a direct hash
-        if Self::is_hash(value_to_search)? {
-            return Ok(value_to_search.to_string());
+        if let Ok(oid) = self.parse_oid(value_to_search) {
+            return Ok(oid);
         }
 
         let refs_to_try = [
@@ -784,68 +1211,65 @@ impl Repository {
         ];
 
         for ref_to_try in refs_to_try {
-            match self.get_ref(ref_to_try.as_str(), true) {
-                Ok(ref_hash) => {
-                    return Ok(ref_hash.value);
-                }
-                Err(_e) => continue,
+            if let Ok(RefValue::Direct(oid)) = self.get_ref(ref_to_try.as_str(), true) {
+                return Ok(oid);
             }
         }
 
-        Err(format!("Oid hash not found for: {}", value_to_search))
+        Err(Error::InvalidRef(value_to_search.to_string()))
+    }
+
+    // Every ref under refs/ whose full name starts with `prefix`, sorted
+    pub fn iter_refs(&self, prefix: &str) -> Result<Vec<(String, RefValue)>, Error> {
+        self.ref_names(prefix)?
+            .into_iter()
+            .map(|name| Ok((name.clone(), self.get_ref(&name, false)?)))
+            .collect()
     }
 
-    pub fn iter_refs(&self, prefix: &str) -> Result<Vec<(String, String)>, String> {
+    // The full names of the refs `iter_refs` returns, without reading them
+    pub fn ref_names(&self, prefix: &str) -> Result<Vec<String>, Error> {
         let ref_folder = "refs";
-        let refs_dir = format!("{}/{}", self.gitdir, ref_folder);
+        let refs_dir = self.git_path(ref_folder)?;
         let mut refs = Vec::new();
 
         // Helper function to recursively collect refs
         fn collect_refs(
+            root: &Path,
             path: &Path,
             ref_folder: &str,
-            refs: &mut Vec<(String, String)>,
+            refs: &mut Vec<String>,
             prefix: &str,
-        ) -> Result<(), String> {
+        ) -> Result<(), Error> {
             let files_to_ignore = [".DS_Store"];
 
-            for entry in
-                fs::read_dir(path).map_err(|e| format!("Failed to read directory: {}", e))?
-            {
-                let entry = entry.map_err(|e| format!("Failed to read directory entry: {}", e))?;
+            for entry in fs::read_dir(path).map_err(|e| Error::io("Failed to read directory", e))? {
+                let entry = entry.map_err(|e| Error::io("Failed to read directory entry", e))?;
                 let entry_path = entry.path();
 
                 if !format!("{}/", entry_path.to_string_lossy()).starts_with(prefix) {
                     continue;
                 }
 
-                if files_to_ignore
-                    .iter()
-                    .any(|f| f == &entry_path.file_name().unwrap().to_string_lossy())
+                let file_name = entry_path.file_name().unwrap().to_string_lossy();
+                // Lock files of refs being written are not refs
+                if files_to_ignore.iter().any(|f| *f == file_name)
+                    || file_name.ends_with(LOCK_SUFFIX)
                 {
                     continue;
                 }
 
                 if entry_path.is_dir() {
-                    collect_refs(&entry_path, ref_folder, refs, prefix)?;
+                    collect_refs(root, &entry_path, ref_folder, refs, prefix)?;
                 } else {
-                    let content = fs::read_to_string(&entry_path).map_err(|e| {
-                        format!(
-                            "Failed to read ref file {}: {}",
-                            entry_path.to_string_lossy(),
-                            e
-                        )
-                    })?;
-                    let hash = content.trim();
-
                     // Get the relative path from refs directory
                     let ref_name = entry_path
-                        .strip_prefix(path.parent().unwrap())
-                        .map_err(|e| format!("Failed to get relative path: {}", e))?
+                        .strip_prefix(root)
+                        .map_err(|e| Error::Other(format!("Failed to get relative path: {}", e)))?
                         .to_string_lossy()
                         .to_string();
 
-                    refs.push((format!("{}/{}", ref_folder, ref_name), hash.to_string()));
+                    refs.push(format!("{}/{}", ref_folder, ref_name));
                 }
             }
             Ok(())
@@ -853,18 +1277,20 @@ impl Repository {
 
         // Start collecting refs from the refs directory
         collect_refs(
+            Path::new(&refs_dir),
             Path::new(&refs_dir),
             ref_folder,
             &mut refs,
-            format!("{}/{}", self.gitdir, prefix).as_str(),
+            self.git_path(prefix)?.as_str(),
         )?;
-
+        // Directory order is arbitrary
+        refs.sort();
         Ok(refs)
     }
 
-    pub fn iter_commits_and_parents(&self, oids: Vec<String>) -> Result<Vec<String>, String> {
-        let mut visited: Vec<String> = Vec::new();
-        let mut queue: VecDeque<String> = VecDeque::new();
+    pub fn iter_commits_and_parents(&self, oids: Vec<Oid>) -> Result<Vec<Oid>, Error> {
+        let mut visited: HashSet<Oid> = HashSet::new();
+        let mut queue: VecDeque<Oid> = VecDeque::new();
         let mut result = Vec::new();
 
         for oid in oids {
@@ -872,79 +1298,44 @@ impl Repository {
         }
 
         while let Some(oid) = queue.pop_back() {
-            if visited.contains(&oid) {
+            if !visited.insert(oid) {
                 continue;
             }
 
-            visited.push(oid.clone());
+            result.push(oid);
 
-            let oid_str = oid.clone();
-            result.push(oid_str.clone());
-
-            let commit = self.get_commit(&oid_str)?;
-
-            if !commit.parents.is_empty() {
-                for parent in commit.parents {
-                    queue.push_back(parent.clone());
-                }
-            }
+            let commit = self.get_commit(oid)?;
+            queue.extend(commit.parents);
         }
 
         Ok(result)
     }
 
-    pub fn create_branch(
-        &self,
-        branch_name: &str,
-        commit_hash: Option<String>,
-    ) -> Result<(), String> {
-        let hash = match commit_hash {
-            Some(hash) => hash,
-            None => {
-                let (_, head_value) = self.get_ref_internal(HEAD, true)?;
-                head_value.value
-            }
+    pub fn create_branch(&self, branch_name: &str, commit_hash: Option<Oid>) -> Result<(), Error> {
+        // Without a commit the branch starts where HEAD is, unborn if HEAD is
+        let value = match commit_hash {
+            Some(hash) => RefValue::Direct(hash),
+            None => self.get_ref(HEAD, true)?,
         };
 
-        self.set_ref(
-            format!("refs/heads/{}", branch_name).as_str(),
-            RefValue {
-                value: hash,
-                is_symbolic: false,
-            },
-            true,
-        )
+        self.set_ref(format!("refs/heads/{}", branch_name).as_str(), value, true)
     }
 
-    pub fn is_branch(&self, value: &str) -> Result<bool, String> {
-        let ref_value: RefValue =
-            match self.get_ref(format!("refs/heads/{}", value).as_str(), false) {
-                Ok(value) => value,
-                Err(_) => RefValue {
-                    value: "".to_string(),
-                    is_symbolic: false,
-                },
-            };
-
-        if ref_value.value.is_empty() {
-            return Ok(false);
-        }
-
-        Ok(true)
+    pub fn is_branch(&self, value: &str) -> Result<bool, Error> {
+        let ref_value = self.get_ref(format!("refs/heads/{}", value).as_str(), false);
+        Ok(matches!(ref_value, Ok(RefValue::Direct(_))))
     }
 
-    pub fn get_branch_name(&self) -> Result<Option<String>, String> {
-        let head_ref: RefValue = self.get_ref(HEAD, false)?;
-
-        if !head_ref.is_symbolic {
-            Ok(None)
-        } else {
-            assert!(head_ref.value.starts_with("ref: refs/heads/"));
-            Ok(Some(head_ref.value[16..].to_string()))
+    pub fn get_branch_name(&self) -> Result<Option<String>, Error> {
+        match self.get_ref(HEAD, false)? {
+            RefValue::Symbolic(target) => Ok(target
+                .strip_prefix("refs/heads/")
+                .map(|branch| branch.to_string())),
+            _ => Ok(None),
         }
     }
 
-    pub fn iter_branch_names(&self) -> Result<Vec<String>, String> {
+    pub fn iter_branch_names(&self) -> Result<Vec<String>, Error> {
         let refs = self.iter_refs("refs/heads/")?;
         let current_branch = self.get_branch_name()?;
 
@@ -954,7 +1345,7 @@ impl Repository {
                 let branch_name = name.clone().split("/").last().unwrap().to_string();
                 if let Some(current) = &current_branch {
                     if branch_name == *current {
-                        format!("\x1b[32m* {}\x1b[0m", branch_name)
+                        paint(Color::Green, &format!("* {}", branch_name))
                     } else {
                         branch_name
                     }
@@ -967,111 +1358,126 @@ impl Repository {
         Ok(branch_names)
     }
 
-    pub fn reset(&self, commit_hash: &str) -> Result<(), String> {
+    pub fn reset(&self, commit_hash: Oid) -> Result<(), Error> {
         // For now reset is working as --hard, so it will remove
         // all the changes in the working directory and set the HEAD to the commit hash
 
         // Check it the commit hash exists
-        let commit = self
-            .get_commit(commit_hash)
-            .map_err(|_e| format!("Commit with hash: {} not found", commit_hash))?;
+        let commit = self.get_commit(commit_hash)?;
 
         // Update the working directory to match the commit
-        self.read_tree(&commit.tree, Path::new(&self.worktree))?;
+        self.read_tree(commit.tree, Path::new(&self.worktree))?;
 
         // Set the HEAD to the commit hash
-        self.set_ref(
-            HEAD,
-            RefValue {
-                value: commit_hash.to_string(),
-                is_symbolic: false,
-            },
-            true,
-        )
-        .map_err(|e| format!("Failed to reset to commit: {}", e))?;
+        self.set_ref(HEAD, RefValue::Direct(commit_hash), true)
+    }
 
-        Ok(())
+    pub fn merge(&self, branch_name: &str) -> Result<MergeOutcome, Error> {
+        self.merge_with_options(branch_name, &MergeOptions::default())
     }
 
-    pub fn merge(&self, branch_name: &str) -> Result<(), String> {
+    pub fn merge_with_options(
+        &self,
+        branch_name: &str,
+        options: &MergeOptions,
+    ) -> Result<MergeOutcome, Error> {
         // Get refs
-        let head_ref = self.get_ref(HEAD, true).unwrap();
-        let branch_ref = self
+        let head_oid = self.get_oid_hash(HEAD)?;
+        let branch_oid = self
             .get_ref(format!("refs/heads/{}", branch_name).as_str(), true)
-            .unwrap();
+            .ok()
+            .and_then(|branch_ref| branch_ref.oid())
+            .ok_or_else(|| Error::InvalidRef(branch_name.to_string()))?;
 
         // Get head commits
-        let curr_head_commit = self.get_commit(&head_ref.value).unwrap();
-        let branch_head_commit = self.get_commit(&branch_ref.value).unwrap();
-        let base_commit = self
-            .get_commit(&self.get_merge_base(&curr_head_commit._oid, &branch_head_commit._oid)?)
-            .unwrap();
+        let curr_head_commit = self.get_commit(head_oid)?;
+        let branch_head_commit = self.get_commit(branch_oid)?;
+        let base_commit =
+            self.get_commit(self.get_merge_base(curr_head_commit.oid, branch_head_commit.oid)?)?;
 
         // Check if we can do a fast-forward merge
         // If the base commit is the same as the current HEAD, we can do a fast-forward merge
-        if base_commit._oid == curr_head_commit._oid {
+        if base_commit.oid == curr_head_commit.oid {
             // Update the working directory to match the branch head commit
-            self.read_tree(&branch_head_commit.tree, Path::new(&self.worktree))?;
+            self.read_tree(branch_head_commit.tree, Path::new(&self.worktree))?;
 
             // Update the HEAD to point to the branch head commit
-            self.set_ref(
-                HEAD,
-                RefValue {
-                    value: branch_ref.value,
-                    is_symbolic: false,
-                },
-                false,
-            )?;
-            println!(
-                "Successfully merged branch {} into current branch.\nFast-forward merge, no need to commit.",
-                branch_name
-            );
-            return Ok(());
+            self.set_ref(HEAD, RefValue::Direct(branch_oid), false)?;
+            return Ok(MergeOutcome::FastForward);
         }
 
         // If not a fast-forward merge, proceed with three-way merge
         // Set MERGE_HEAD
-        self.set_ref(
-            MERGE_HEAD,
-            RefValue {
-                value: branch_ref.value,
-                is_symbolic: false,
-            },
-            false,
-        )?;
+        self.set_ref(MERGE_HEAD, RefValue::Direct(branch_oid), false)?;
 
         // Merge the trees
-        self.read_tree_merged(
-            &curr_head_commit.tree,
-            &branch_head_commit.tree,
-            Some(&base_commit.tree),
+        let diff_options = DiffOptions {
+            whitespace: options.whitespace,
+            ..Default::default()
+        };
+        let conflicts = self.read_tree_merged_with_options(
+            curr_head_commit.tree,
+            branch_head_commit.tree,
+            Some(base_commit.tree),
+            &diff_options,
         )?;
 
         // TODO: Remove this later?
         // Remove MERGE_HEAD after successful merge
         self.delete_ref(MERGE_HEAD, false)?;
 
-        println!(
-            "Successfully merged branch {} into current branch.\nPlease commit the merge.",
-            branch_name
-        );
-
-        Ok(())
+        if conflicts.is_empty() {
+            Ok(MergeOutcome::Merged)
+        } else {
+            Ok(MergeOutcome::Conflicted(conflicts))
+        }
     }
 
     pub fn read_tree_merged(
         &self,
-        head_tree_oid: &str,
-        other_tree_oid: &str,
-        base_tree_oid: Option<&str>,
-    ) -> Result<(), String> {
-        // Empty the current directory first
-        self.empty_current_directory(Path::new(&self.worktree))?;
+        head_tree_oid: Oid,
+        other_tree_oid: Oid,
+        base_tree_oid: Option<Oid>,
+    ) -> Result<Vec<String>, Error> {
+        self.read_tree_merged_with_options(
+            head_tree_oid,
+            other_tree_oid,
+            base_tree_oid,
+            &DiffOptions::default(),
+        )
+    }
 
+    // read_tree_merged, comparing lines according to `options`
+    pub fn read_tree_merged_with_options(
+        &self,
+        head_tree_oid: Oid,
+        other_tree_oid: Oid,
+        base_tree_oid: Option<Oid>,
+        options: &DiffOptions,
+    ) -> Result<Vec<String>, Error> {
         // Get the merged tree contents (path -> Result<Content, IsDirectoryMarker>)
-        let differ = Differ::new(self);
-        let merged_tree_result =
-            differ.merge_trees(head_tree_oid, other_tree_oid, base_tree_oid)?;
+        // along with the paths that were left with conflict markers
+        let differ = Differ::with_options(self, options.clone());
+        let (merged_tree_result, conflicts) =
+            differ.merge_trees_with_conflicts(head_tree_oid, other_tree_oid, base_tree_oid)?;
+
+        // A tree naming a path outside the worktree is corrupt, refuse it
+        // before anything is removed
+        if let Some(path) = merged_tree_result.keys().find(|path| {
+            let path = Path::new(path);
+            path.is_absolute()
+                || path
+                    .components()
+                    .any(|component| component == Component::ParentDir)
+        }) {
+            return Err(Error::Corrupt(format!(
+                "Path outside the worktree in tree: {}",
+                path
+            )));
+        }
+
+        // Empty the current directory, then write the merged contents
+        self.empty_current_directory(Path::new(&self.worktree))?;
 
         // Use a HashSet to track created directories to avoid redundant checks/creation attempts
         let mut created_dirs = std::collections::HashSet::new();
@@ -1087,25 +1493,17 @@ impl Repository {
 
             // Ensure parent directory exists
             if let Some(parent) = full_path.parent() {
-                if !parent.starts_with(&self.worktree) {
-                    // Avoid trying to create outside worktree
-                    // This might happen with absolute paths, skip for safety
-                    eprintln!(
-                        "Warning: Skipping path outside worktree: {}",
-                        full_path.display()
-                    );
-                    continue;
-                }
                 // Only create if not already tracked and exists
                 if !created_dirs.contains(parent) && !parent.exists() {
+                    // A file in the way of a directory
                     if parent.is_file() {
-                        return Err(format!(
-                            "Merge conflict: trying to create directory {} where a file exists.",
-                            parent.display()
-                        ));
+                        return Err(Error::Conflict(vec![path]));
                     }
                     fs::create_dir_all(parent).map_err(|e| {
-                        format!("Failed to create directory {}: {}", parent.display(), e)
+                        Error::io(
+                            format!("Failed to create directory {}", parent.display()),
+                            e,
+                        )
                     })?;
                     created_dirs.insert(parent.to_path_buf()); // Track created dir
                 }
@@ -1115,28 +1513,25 @@ impl Repository {
                 Ok(content) => {
                     // It's a file
                     if full_path.is_dir() {
-                        return Err(format!(
-                            "Merge conflict: trying to write file {} where a directory exists.",
-                            full_path.display()
-                        ));
+                        return Err(Error::Conflict(vec![path]));
                     }
                     // Write the file content
                     fs::write(&full_path, content).map_err(|e| {
-                        format!("Failed to write file {}: {}", full_path.display(), e)
+                        Error::io(format!("Failed to write file {}", full_path.display()), e)
                     })?;
                 }
                 Err(_) => {
                     // It's a directory marker
                     if full_path.is_file() {
-                        return Err(format!(
-                            "Merge conflict: trying to create directory {} where a file exists.",
-                            full_path.display()
-                        ));
+                        return Err(Error::Conflict(vec![path]));
                     }
                     // Ensure the directory itself exists if it wasn't created as a parent earlier
                     if !created_dirs.contains(&full_path) && !full_path.exists() {
                         fs::create_dir_all(&full_path).map_err(|e| {
-                            format!("Failed to create directory {}: {}", full_path.display(), e)
+                            Error::io(
+                                format!("Failed to create directory {}", full_path.display()),
+                                e,
+                            )
                         })?;
                         created_dirs.insert(full_path.to_path_buf());
                     }
@@ -1144,109 +1539,144 @@ impl Repository {
             }
         }
 
-        Ok(())
+        Ok(conflicts)
     }
 
-    pub fn print_commit(&self, commit_hash: &str) -> Result<(), String> {
-        let commit = self
-            .get_commit(commit_hash)
-            .map_err(|_e| format!("Commit with hash: {} not found", commit_hash))?;
+    // A commit and its changes against each of its parents
+    pub fn show(&self, commit_hash: Oid) -> Result<ShowResult, Error> {
+        let commit = self.get_commit(commit_hash)?;
 
-        println!("Commit: {}", commit_hash);
-        println!("Tree: {}", commit.tree);
-        if !commit.parents.is_empty() {
-            for parent in commit.parents {
-                println!("Parent: {}", parent);
-            }
-        } else {
-            println!("Parent: None");
-        }
-        println!("Timestamp: {}", commit.timestamp);
-        println!("Message: {}", commit.message);
+        let differ = Differ::new(self);
+        let diffs = commit
+            .parents
+            .iter()
+            .map(|parent| {
+                let parent_commit = self.get_commit(*parent)?;
+                differ.tree_diff(Some(parent_commit.tree), Some(commit.tree))
+            })
+            .collect::<Result<_, _>>()?;
 
-        Ok(())
+        Ok(ShowResult { commit, diffs })
+    }
+
+    pub fn get_working_tree(&self) -> Result<Oid, Error> {
+        let tree = self.create_tree(Path::new(&self.worktree))?;
+        Ok(tree)
     }
 
-    pub fn show(&self, commit_hash: &str) -> Result<(), String> {
-        let commit = self
-            .get_commit(commit_hash)
-            .map_err(|_e| format!("Commit with hash: {} not found", commit_hash))?;
+    pub fn diff(&self) -> Result<String, Error> {
+        self.diff_with_options(&DiffOptions::default())
+    }
 
-        self.print_commit(commit_hash)?;
+    pub fn diff_with_options(&self, options: &DiffOptions) -> Result<String, Error> {
+        self.diff_target(&DiffTarget::WorkingTree(None), options)
+    }
 
-        if !commit.parents.is_empty() {
-            for parent in commit.parents {
-                let parent_commit = self
-                    .get_commit(&parent)
-                    .map_err(|_e| format!("Commit with hash: {} not found", parent))?;
+    pub fn diff_target(&self, target: &DiffTarget, options: &DiffOptions) -> Result<String, Error> {
+        let mut options = options.clone();
+        if options.word_regex.is_none() {
+            options.word_regex = self.get_config("diff.wordRegex");
+        }
+        let options = &options;
 
-                let diff = Differ::new(self).diff_trees(&parent_commit.tree, &commit.tree)?;
-                let colored_diff = Differ::colorize_diff(&diff);
-                println!("{}", colored_diff);
+        if let DiffTarget::NoIndex(from, to) = target {
+            if options.format != DiffFormat::Patch {
+                return Err(Error::Other("--no-index only prints patches".to_string()));
             }
+            let diff = Differ::diff_files(Path::new(from), Path::new(to), options)?;
+            return Ok(Differ::format_patch(&diff, options));
         }
 
-        Ok(())
+        let (old_tree, new_tree) = self.diff_target_trees(target)?;
+        Differ::with_options(self, options.clone()).format_diff(old_tree, new_tree)
     }
 
-    pub fn get_working_tree(&self) -> Result<String, String> {
-        let tree = self.create_tree(Path::new(&self.worktree))?;
-        Ok(tree)
+    // The files that differ between the two sides of `target`, such as for
+    // --exit-code: the rendered diff does not tell, JSON shows "[]" when
+    // nothing changed. There are no trees to compare with --no-index.
+    pub fn diff_target_changes(
+        &self,
+        target: &DiffTarget,
+        options: &DiffOptions,
+    ) -> Result<Vec<FileChange>, Error> {
+        let (old_tree, new_tree) = self.diff_target_trees(target)?;
+        Differ::with_options(self, options.clone()).changed_files(old_tree, new_tree)
     }
 
-    pub fn diff(&self) -> Result<String, String> {
-        // check if there is a HEAD
-        let head = self.get_ref(HEAD, false)?;
-        if head.value.is_empty() {
-            return Err("No commits found".to_string());
+    fn diff_target_trees(&self, target: &DiffTarget) -> Result<(Option<Oid>, Option<Oid>), Error> {
+        match target {
+            DiffTarget::NoIndex(..) => Err(Error::Other(
+                "--no-index compares files, not trees".to_string(),
+            )),
+            DiffTarget::Revisions(old, new) => Ok((
+                Some(self.revision_tree(old)?),
+                Some(self.revision_tree(new)?),
+            )),
+            // There is no index: the next commit records the worktree as is,
+            // so the staged state and the worktree are the same snapshot
+            DiffTarget::WorkingTree(Some(rev)) | DiffTarget::Cached(Some(rev)) => Ok((
+                Some(self.revision_tree(rev)?),
+                Some(self.get_working_tree()?),
+            )),
+            DiffTarget::WorkingTree(None) | DiffTarget::Cached(None) => {
+                // check if there is a HEAD
+                self.get_ref(HEAD, false)?;
+                // Without commits everything in the worktree is new
+                let head_tree = match self.get_oid_hash(HEAD) {
+                    Ok(head) => Some(self.get_commit(head)?.tree),
+                    Err(_) => None,
+                };
+                Ok((head_tree, Some(self.get_working_tree()?)))
+            }
         }
-
-        let diff = Differ::new(self).diff_current_working_tree()?;
-        let colored_diff = Differ::colorize_diff(&diff);
-        Ok(colored_diff)
     }
 
-    pub fn get_merge_base(&self, commit_hash1: &str, commit_hash2: &str) -> Result<String, String> {
-        // Validate both commit hashes
-        let commit1 = self.get_oid_hash(commit_hash1)?;
-        let commit2 = self.get_oid_hash(commit_hash2)?;
+    // Tree of the commit a branch, tag or hash names
+    fn revision_tree(&self, revision: &str) -> Result<Oid, Error> {
+        let oid = self.get_oid_hash(revision)?;
+        Ok(self.get_commit(oid)?.tree)
+    }
 
+    pub fn get_merge_base(&self, commit1: Oid, commit2: Oid) -> Result<Oid, Error> {
         // If commits are the same, return that commit
         if commit1 == commit2 {
             return Ok(commit1);
         }
 
         // Get all ancestors of both commits
-        let ancestors1 = self.get_commit_ancestors(&commit1)?;
-        let ancestors2 = self.get_commit_ancestors(&commit2)?;
+        let ancestors1 = self.get_commit_ancestors(commit1)?;
+        let ancestors2: HashSet<Oid> = self.get_commit_ancestors(commit2)?.into_iter().collect();
 
         // Find the first common ancestor
-        for ancestor in &ancestors1 {
-            if ancestors2.contains(ancestor) {
-                return Ok(ancestor.clone());
+        for ancestor in ancestors1 {
+            if ancestors2.contains(&ancestor) {
+                return Ok(ancestor);
             }
         }
 
         // If no common ancestor is found, return an error
-        Err("No common ancestor found between commits".to_string())
+        Err(Error::Other(
+            "No common ancestor found between commits".to_string(),
+        ))
     }
 
-    pub fn get_commit_ancestors(&self, commit_hash: &str) -> Result<Vec<String>, String> {
+    pub fn get_commit_ancestors(&self, commit_hash: Oid) -> Result<Vec<Oid>, Error> {
         let mut ancestors = Vec::new();
+        let mut seen = HashSet::new();
         let mut queue = VecDeque::new();
-        queue.push_back(commit_hash.to_string());
+        queue.push_back(commit_hash);
 
         while let Some(current) = queue.pop_front() {
             // Skip if we've already processed this commit
-            if ancestors.contains(&current) {
+            if !seen.insert(current) {
                 continue;
             }
 
             // Add current commit to ancestors
-            ancestors.push(current.clone());
+            ancestors.push(current);
 
             // Get the commit object
-            let commit = self.get_commit(&current)?;
+            let commit = self.get_commit(current)?;
 
             // Add all parents to the queue
             for parent in commit.parents {
@@ -1256,82 +1686,4 @@ impl Repository {
 
         Ok(ancestors)
     }
-
-    pub fn rebase(&self, target: &str) -> Result<(), String> {
-        // 1. Store original branch state (for potential abort)
-        let original_head = self.get_ref(HEAD, true)?;
-        let original_branch = self.get_branch_name()?;
-
-        // 2. Get target commit and current HEAD
-        let target_oid = self.get_oid_hash(target)?;
-        let current_head_oid = self.get_oid_hash("HEAD")?;
-
-        // 3. Find common ancestor
-        let base_commit = self.get_merge_base(&current_head_oid, &target_oid)?;
-
-        // 4. Collect commits to rebase (only those unique to our branch)
-        let commits_to_rebase = self.iter_commits_and_parents(vec![current_head_oid.clone()])?;
-        let base_commits = self.iter_commits_and_parents(vec![base_commit.clone()])?;
-        let commits_to_rebase: Vec<String> = commits_to_rebase
-            .into_iter()
-            .filter(|commit| !base_commits.contains(commit))
-            .collect();
-
-        // 5. Switch to target commit (detached HEAD)
-        self.checkout(&target_oid)?;
-
-        // 6. Apply each commit on top of the target
-        let mut new_base = target_oid;
-        for commit_oid in commits_to_rebase.iter().rev() {
-            let commit = self.get_commit(commit_oid)?;
-
-            // Get the tree objects
-            let target_tree = self.get_commit(&new_base)?.tree;
-            let commit_tree = commit.tree;
-            let base_tree = self.get_commit(&base_commit)?.tree;
-
-            // Apply the changes from this commit
-            self.read_tree_merged(&commit_tree, &target_tree, Some(&base_tree))?;
-
-            // Create new commit using the existing function
-            new_base = self.create_commit(&commit.message)?;
-        }
-
-        // 7. Update branch reference if we were on a branch
-        if let Some(branch_name) = original_branch.clone() {
-            self.set_ref(
-                format!("refs/heads/{}", branch_name).as_str(),
-                RefValue {
-                    value: new_base.clone(),
-                    is_symbolic: false,
-                },
-                true,
-            )?;
-        }
-
-        // 8. Update HEAD
-        if original_head.is_symbolic {
-            self.set_ref(
-                HEAD,
-                RefValue {
-                    value: format!("ref: refs/heads/{}", original_branch.unwrap()),
-                    is_symbolic: true,
-                },
-                false,
-            )?;
-        } else {
-            self.set_ref(
-                HEAD,
-                RefValue {
-                    value: new_base,
-                    is_symbolic: false,
-                },
-                false,
-            )?;
-        }
-
-        println!("Successfully rebased branch onto target {}.", target);
-
-        Ok(())
-    }
 }
@@ -0,0 +1,479 @@
+use crate::error::Error;
+use crate::oid::Oid;
+use crate::repository::{HEAD, RefValue, Repository};
+use std::fs;
+use std::path::Path;
+
+// Directory inside the gitdir holding the state of an in-progress
+// cherry-pick or revert
+pub const SEQUENCER_DIR: &str = "sequencer";
+pub const CHERRY_PICK_HEAD: &str = "CHERRY_PICK_HEAD";
+pub const REVERT_HEAD: &str = "REVERT_HEAD";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SequencerAction {
+    Pick,
+    Revert,
+}
+
+impl SequencerAction {
+    fn as_str(&self) -> &'static str {
+        match self {
+            SequencerAction::Pick => "pick",
+            SequencerAction::Revert => "revert",
+        }
+    }
+
+    // The command's name, "cherry-pick" or "revert"
+    pub fn command(&self) -> &'static str {
+        match self {
+            SequencerAction::Pick => "cherry-pick",
+            SequencerAction::Revert => "revert",
+        }
+    }
+
+    fn head_ref(&self) -> &'static str {
+        match self {
+            SequencerAction::Pick => CHERRY_PICK_HEAD,
+            SequencerAction::Revert => REVERT_HEAD,
+        }
+    }
+}
+
+// Options shared by cherry-pick and revert
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SequencerOptions {
+    // Parent number (1-based) to diff merge commits against (-m)
+    pub mainline: Option<usize>,
+    // Only update the working directory (--no-commit)
+    pub no_commit: bool,
+    // Append "(cherry picked from commit ...)" to the message (-x)
+    pub record_origin: bool,
+}
+
+// What a cherry-pick or revert command did
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SequencerOutcome {
+    pub action: SequencerAction,
+    // The commits created, in order
+    pub commits: Vec<Oid>,
+    // Commits whose changes HEAD already had, so nothing was committed
+    pub empty: Vec<Oid>,
+    // The commit whose changes left conflict markers, and in which paths.
+    // The operation waits for --continue or --abort.
+    pub conflicted: Option<(Oid, Vec<String>)>,
+}
+
+// Everything needed to continue or abort, persisted in .bgit/sequencer/
+#[derive(Debug, Clone)]
+pub struct SequencerState {
+    pub action: SequencerAction,
+    pub orig_head: Oid,
+    pub todo: Vec<Oid>,
+    pub options: SequencerOptions,
+    // Commit that could not be applied cleanly, with its conflicting paths
+    pub stopped: Option<Oid>,
+    pub conflicts: Vec<String>,
+}
+
+impl SequencerState {
+    fn dir(repo: &Repository) -> Result<String, Error> {
+        repo.git_path(SEQUENCER_DIR)
+    }
+
+    pub fn exists(repo: &Repository) -> bool {
+        repo.git_path(SEQUENCER_DIR)
+            .is_ok_and(|dir| Path::new(&dir).exists())
+    }
+
+    pub fn load(repo: &Repository) -> Result<SequencerState, Error> {
+        if !Self::exists(repo) {
+            return Err(Error::Other(
+                "No cherry-pick or revert in progress".to_string(),
+            ));
+        }
+
+        let dir = Self::dir(repo)?;
+        let read = |name: &str| -> String {
+            fs::read_to_string(format!("{}/{}", dir, name))
+                .map(|content| content.trim_end().to_string())
+                .unwrap_or_default()
+        };
+        let parse_oid = |name: &str, content: &str| -> Result<Oid, Error> {
+            repo.parse_oid(content)
+                .map_err(|_| Error::Corrupt(format!("Invalid sequencer state {}", name)))
+        };
+
+        let mut action = None;
+        let mut todo = Vec::new();
+        for line in read("todo").lines() {
+            let mut parts = line.split_whitespace();
+            let line_action = match parts.next() {
+                Some("pick") => SequencerAction::Pick,
+                Some("revert") => SequencerAction::Revert,
+                _ => {
+                    return Err(Error::Corrupt(format!(
+                        "Invalid sequencer todo line: {}",
+                        line
+                    )));
+                }
+            };
+            action.get_or_insert(line_action);
+            todo.push(
+                parts
+                    .next()
+                    .and_then(|oid| repo.parse_oid(oid).ok())
+                    .ok_or_else(|| {
+                        Error::Corrupt(format!("Invalid sequencer todo line: {}", line))
+                    })?,
+            );
+        }
+
+        let mut options = SequencerOptions::default();
+        for line in read("opts").lines() {
+            match line.split_once(' ') {
+                Some(("mainline", value)) => {
+                    options.mainline = Some(value.parse().map_err(|_| {
+                        Error::Corrupt(format!("Invalid sequencer option: {}", line))
+                    })?)
+                }
+                Some(("no-commit", value)) => options.no_commit = value == "true",
+                Some(("record-origin", value)) => options.record_origin = value == "true",
+                _ => {
+                    return Err(Error::Corrupt(format!(
+                        "Invalid sequencer option: {}",
+                        line
+                    )));
+                }
+            }
+        }
+
+        let action = match read("action").as_str() {
+            "revert" => SequencerAction::Revert,
+            "pick" => SequencerAction::Pick,
+            _ => action.unwrap_or(SequencerAction::Pick),
+        };
+        let stopped = read("stopped-sha");
+
+        Ok(SequencerState {
+            action,
+            orig_head: parse_oid("head", &read("head"))?,
+            todo,
+            options,
+            stopped: if stopped.is_empty() {
+                None
+            } else {
+                Some(parse_oid("stopped-sha", &stopped)?)
+            },
+            conflicts: read("conflicts")
+                .lines()
+                .map(|line| line.to_string())
+                .collect(),
+        })
+    }
+
+    pub fn save(&self, repo: &Repository) -> Result<(), Error> {
+        let dir = Self::dir(repo)?;
+        fs::create_dir_all(&dir)
+            .map_err(|e| Error::io(format!("Failed to create {} directory", SEQUENCER_DIR), e))?;
+
+        let write = |name: &str, content: String| -> Result<(), Error> {
+            fs::write(format!("{}/{}", dir, name), content)
+                .map_err(|e| Error::io(format!("Failed to write sequencer state {}", name), e))
+        };
+
+        let mut opts = String::new();
+        if let Some(mainline) = self.options.mainline {
+            opts.push_str(&format!("mainline {}\n", mainline));
+        }
+        opts.push_str(&format!("no-commit {}\n", self.options.no_commit));
+        opts.push_str(&format!("record-origin {}\n", self.options.record_origin));
+
+        write("action", format!("{}\n", self.action.as_str()))?;
+        write("head", format!("{}\n", self.orig_head))?;
+        write(
+            "todo",
+            self.todo
+                .iter()
+                .map(|oid| format!("{} {}\n", self.action.as_str(), oid))
+                .collect(),
+        )?;
+        write("opts", opts)?;
+        write(
+            "stopped-sha",
+            self.stopped
+                .as_ref()
+                .map(|oid| format!("{}\n", oid))
+                .unwrap_or_default(),
+        )?;
+        write(
+            "conflicts",
+            self.conflicts
+                .iter()
+                .map(|path| format!("{}\n", path))
+                .collect(),
+        )
+    }
+
+    pub fn remove(repo: &Repository) -> Result<(), Error> {
+        fs::remove_dir_all(Self::dir(repo)?)
+            .map_err(|e| Error::io(format!("Failed to remove {} directory", SEQUENCER_DIR), e))
+    }
+}
+
+impl Repository {
+    pub fn sequencer_in_progress(&self) -> bool {
+        SequencerState::exists(self)
+    }
+
+    // Apply the changes introduced by each commit on top of HEAD
+    pub fn cherry_pick(
+        &self,
+        commits: &[String],
+        options: &SequencerOptions,
+    ) -> Result<SequencerOutcome, Error> {
+        self.sequencer_start(SequencerAction::Pick, commits, options)
+    }
+
+    // Create commits undoing the changes introduced by each commit
+    pub fn revert(
+        &self,
+        commits: &[String],
+        options: &SequencerOptions,
+    ) -> Result<SequencerOutcome, Error> {
+        self.sequencer_start(SequencerAction::Revert, commits, options)
+    }
+
+    pub fn sequencer_continue(&self) -> Result<SequencerOutcome, Error> {
+        let mut state = SequencerState::load(self)?;
+        let mut committed = None;
+
+        if let Some(stopped) = state.stopped.take() {
+            // Refuse to continue while conflict markers are still present
+            let unresolved: Vec<String> = state
+                .conflicts
+                .iter()
+                .filter(|path| Self::has_conflict_markers(&Path::new(&self.worktree).join(path)))
+                .cloned()
+                .collect();
+            if !unresolved.is_empty() {
+                return Err(Error::Conflict(unresolved));
+            }
+
+            if !state.options.no_commit {
+                committed = Some((
+                    stopped,
+                    self.sequencer_commit(state.action, stopped, &state.options)?,
+                ));
+            }
+
+            self.delete_ref(state.action.head_ref(), false).ok();
+            state.conflicts.clear();
+            state.save(self)?;
+        }
+
+        let mut outcome = self.sequencer_run()?;
+        match committed {
+            Some((_, Some(commit))) => outcome.commits.insert(0, commit),
+            Some((stopped, None)) => outcome.empty.insert(0, stopped),
+            None => {}
+        }
+        Ok(outcome)
+    }
+
+    // Returns the state of the abandoned operation
+    pub fn sequencer_abort(&self) -> Result<SequencerState, Error> {
+        let state = SequencerState::load(self)?;
+
+        // Put HEAD (and the branch it points to) and the worktree back
+        let orig_commit = self.get_commit(state.orig_head)?;
+        self.read_tree(orig_commit.tree, Path::new(&self.worktree))?;
+        self.set_ref(HEAD, RefValue::Direct(state.orig_head), true)?;
+
+        self.delete_ref(state.action.head_ref(), false).ok();
+        SequencerState::remove(self)?;
+        Ok(state)
+    }
+
+    fn sequencer_start(
+        &self,
+        action: SequencerAction,
+        commits: &[String],
+        options: &SequencerOptions,
+    ) -> Result<SequencerOutcome, Error> {
+        if self.sequencer_in_progress() {
+            return Err(Error::Other(format!(
+                "A cherry-pick or revert is already in progress. Use {} --continue or --abort",
+                action.command()
+            )));
+        }
+        if commits.is_empty() {
+            return Err(Error::Other(format!(
+                "No commits given to {}",
+                action.command()
+            )));
+        }
+
+        let head = self.get_oid_hash(HEAD)?;
+        let head_tree = self.get_commit(head)?.tree;
+        if !options.no_commit && self.get_working_tree()? != head_tree {
+            return Err(Error::Other(format!(
+                "Your local changes would be overwritten by {}. Commit your changes or stash them to proceed.",
+                action.command()
+            )));
+        }
+
+        // Resolve and validate everything before touching the worktree
+        let mut todo = Vec::new();
+        for commit in commits {
+            let oid = self.get_oid_hash(commit)?;
+            let commit = self.get_commit(oid)?;
+            Self::sequencer_parent(oid, &commit.parents, options.mainline)?;
+            todo.push(oid);
+        }
+
+        SequencerState {
+            action,
+            orig_head: head,
+            todo,
+            options: options.clone(),
+            stopped: None,
+            conflicts: Vec::new(),
+        }
+        .save(self)?;
+
+        self.sequencer_run()
+    }
+
+    // Apply the remaining commits, stopping at the first conflict
+    fn sequencer_run(&self) -> Result<SequencerOutcome, Error> {
+        let mut state = SequencerState::load(self)?;
+        let mut outcome = SequencerOutcome {
+            action: state.action,
+            commits: Vec::new(),
+            empty: Vec::new(),
+            conflicted: None,
+        };
+
+        while !state.todo.is_empty() {
+            let oid = state.todo.remove(0);
+            let commit = self.get_commit(oid)?;
+            let parent = Self::sequencer_parent(oid, &commit.parents, state.options.mainline)?;
+            let parent_tree = match parent {
+                Some(parent) => self.get_commit(parent)?.tree,
+                // A root commit is compared with the empty tree
+                None => self.create_tree_from_paths(&Default::default())?,
+            };
+
+            // Cherry-pick: the commit's changes relative to its parent.
+            // Revert: the parent's content relative to the commit.
+            let (base_tree, other_tree) = match state.action {
+                SequencerAction::Pick => (parent_tree, commit.tree),
+                SequencerAction::Revert => (commit.tree, parent_tree),
+            };
+
+            // Without committing, changes accumulate in the worktree
+            let head_tree = if state.options.no_commit {
+                self.get_working_tree()?
+            } else {
+                self.get_commit(self.get_oid_hash(HEAD)?)?.tree
+            };
+
+            let conflicts = self.read_tree_merged(head_tree, other_tree, Some(base_tree))?;
+
+            if !conflicts.is_empty() {
+                state.stopped = Some(oid);
+                state.conflicts = conflicts;
+                state.save(self)?;
+                self.set_ref(state.action.head_ref(), RefValue::Direct(oid), false)?;
+
+                outcome.conflicted = Some((oid, state.conflicts));
+                return Ok(outcome);
+            }
+
+            if !state.options.no_commit {
+                match self.sequencer_commit(state.action, oid, &state.options)? {
+                    Some(commit) => outcome.commits.push(commit),
+                    None => outcome.empty.push(oid),
+                }
+            }
+            state.save(self)?;
+        }
+
+        SequencerState::remove(self)?;
+        Ok(outcome)
+    }
+
+    // Commit the worktree for one applied commit, None when it has no
+    // changes left to commit
+    fn sequencer_commit(
+        &self,
+        action: SequencerAction,
+        oid: Oid,
+        options: &SequencerOptions,
+    ) -> Result<Option<Oid>, Error> {
+        let commit = self.get_commit(oid)?;
+        let head = self.get_oid_hash(HEAD)?;
+        let tree = self.get_working_tree()?;
+
+        if tree == self.get_commit(head)?.tree {
+            return Ok(None);
+        }
+
+        let subject = commit.message.lines().next().unwrap_or("");
+        let (message, signature) = match action {
+            SequencerAction::Pick => {
+                let mut message = commit.message.clone();
+                if options.record_origin {
+                    message.push_str(&format!("\n\n(cherry picked from commit {})", oid));
+                }
+                // A cherry-picked commit keeps its original author
+                (message, commit.signature())
+            }
+            SequencerAction::Revert => {
+                let mut message = format!("Revert \"{}\"\n\nThis reverts commit {}", subject, oid);
+                match Self::sequencer_parent(oid, &commit.parents, options.mainline)? {
+                    Some(parent) if commit.parents.len() > 1 => {
+                        message.push_str(&format!(", reversing\nchanges made to {}.", parent))
+                    }
+                    _ => message.push('.'),
+                }
+                (message, self.default_signature())
+            }
+        };
+
+        let hash = self.write_commit_as(tree, &[head], &message, &signature)?;
+        self.set_ref(HEAD, RefValue::Direct(hash), true)?;
+
+        Ok(Some(hash))
+    }
+
+    // The parent whose diff is applied: the only parent, or the -m one for merges
+    fn sequencer_parent(
+        oid: Oid,
+        parents: &[Oid],
+        mainline: Option<usize>,
+    ) -> Result<Option<Oid>, Error> {
+        match (parents.len(), mainline) {
+            (0, None) => Ok(None),
+            (1, None) => Ok(Some(parents[0])),
+            (0 | 1, Some(_)) => Err(Error::Other(format!(
+                "Mainline was specified but commit {} is not a merge.",
+                oid
+            ))),
+            (_, None) => Err(Error::Other(format!(
+                "Commit {} is a merge but no -m option was given.",
+                oid
+            ))),
+            (count, Some(number)) => {
+                if number == 0 || number > count {
+                    return Err(Error::Other(format!(
+                        "Commit {} does not have parent {}",
+                        oid, number
+                    )));
+                }
+                Ok(Some(parents[number - 1]))
+            }
+        }
+    }
+}
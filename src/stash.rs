@@ -0,0 +1,258 @@
+use crate::differ::{Differ, matches_pathspec};
+use crate::error::Error;
+use crate::oid::Oid;
+use crate::reflog::ReflogEntry;
+use crate::repository::{HEAD, ObjectType, RefValue, Repository};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+// Ref pointing at the most recent stash; older ones live in its reflog
+pub const STASH_REF: &str = "refs/stash";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StashCommand {
+    Push {
+        message: Option<String>,
+        paths: Vec<String>,
+    },
+    List,
+    Show {
+        stash: Option<String>,
+        patch: bool,
+    },
+    Apply(Option<String>),
+    Pop(Option<String>),
+    Drop(Option<String>),
+    Branch(String, Option<String>),
+}
+
+impl Repository {
+    // Save the changes in the worktree (or only those under `paths`) as a
+    // commit on top of HEAD and put the worktree back to HEAD
+    pub fn stash_push(&self, message: Option<&str>, paths: &[String]) -> Result<Oid, Error> {
+        let head = self
+            .get_oid_hash(HEAD)
+            .map_err(|_| Error::Other("You do not have the initial commit yet".to_string()))?;
+        let head_commit = self.get_commit(head)?;
+        let working_tree = self.get_working_tree()?;
+
+        // HEAD with the selected paths taken from the worktree
+        let mut stashed = BTreeMap::new();
+        let mut changed = Vec::new();
+        for (path, obj_type, oids) in
+            Differ::new(self).compare_trees(&[Some(head_commit.tree), Some(working_tree)])?
+        {
+            if obj_type != ObjectType::Blob {
+                continue;
+            }
+            let (in_head, in_worktree) = (oids[0], oids[1]);
+            let version = if matches_pathspec(&path, paths) {
+                if in_head != in_worktree {
+                    changed.push((path.clone(), in_head));
+                }
+                in_worktree
+            } else {
+                in_head
+            };
+            if let Some(oid) = version {
+                stashed.insert(path, oid);
+            }
+        }
+
+        if changed.is_empty() {
+            return Err(Error::Other("No local changes to save".to_string()));
+        }
+
+        let subject = match message {
+            Some(message) => message.to_string(),
+            None => format!(
+                "{} {}",
+                head.short(7),
+                head_commit.message.lines().next().unwrap_or("")
+            ),
+        };
+        let branch = self
+            .get_branch_name()?
+            .unwrap_or_else(|| "(no branch)".to_string());
+        let message = match message {
+            Some(_) => format!("On {}: {}", branch, subject),
+            None => format!("WIP on {}: {}", branch, subject),
+        };
+
+        let tree = self.create_tree_from_paths(&stashed)?;
+        let stash = self.write_commit(tree, &[head], &message)?;
+        self.stash_update_ref(stash, &message)?;
+
+        // Put the stashed paths back to their HEAD version
+        if paths.is_empty() {
+            self.read_tree(head_commit.tree, Path::new(&self.worktree))?;
+        } else {
+            for (path, in_head) in changed {
+                let file = Path::new(&self.worktree).join(&path);
+                match in_head {
+                    Some(oid) => fs::write(&file, self.read_object(oid, ObjectType::Blob)?)
+                        .map_err(|e| Error::io(format!("Failed to write file {}", path), e))?,
+                    None => Self::stash_remove_file(&file)?,
+                }
+            }
+        }
+
+        Ok(stash)
+    }
+
+    // Stash entries, most recent first
+    pub fn stash_list(&self) -> Result<Vec<ReflogEntry>, Error> {
+        let mut entries = self.read_reflog(STASH_REF)?;
+        entries.reverse();
+        Ok(entries)
+    }
+
+    // The changes recorded in a stash: changed paths, or the full diff
+    pub fn stash_show(&self, stash: Option<&str>, patch: bool) -> Result<String, Error> {
+        let (_, _, oid) = self.stash_resolve(stash)?;
+        let commit = self.get_commit(oid)?;
+        let parent_tree = self.get_commit(commit.parents[0])?.tree;
+        let differ = Differ::new(self);
+
+        if patch {
+            let diff = differ.diff_trees(Some(parent_tree), Some(commit.tree))?;
+            return Ok(Differ::colorize_diff(&diff));
+        }
+
+        Ok(differ
+            .changed_files(Some(parent_tree), Some(commit.tree))?
+            .into_iter()
+            .map(|change| match change.similarity {
+                Some(_) => format!(
+                    "{}\t{} -> {}",
+                    change.kind.letter(),
+                    change.old_path,
+                    change.new_path
+                ),
+                None => format!("{}\t{}", change.kind.letter(), change.path()),
+            })
+            .collect::<Vec<_>>()
+            .join("\n"))
+    }
+
+    // Merge a stash into the worktree, keeping local changes.
+    // Returns the paths left with conflict markers.
+    pub fn stash_apply(&self, stash: Option<&str>) -> Result<Vec<String>, Error> {
+        let (_, _, oid) = self.stash_resolve(stash)?;
+        let commit = self.get_commit(oid)?;
+        let base_tree = self.get_commit(commit.parents[0])?.tree;
+        let working_tree = self.get_working_tree()?;
+
+        self.read_tree_merged(working_tree, commit.tree, Some(base_tree))
+    }
+
+    // Apply a stash and drop it when it applied cleanly, it is kept when
+    // there are conflicts
+    pub fn stash_pop(&self, stash: Option<&str>) -> Result<Vec<String>, Error> {
+        let conflicts = self.stash_apply(stash)?;
+        if conflicts.is_empty() {
+            self.stash_drop(stash)?;
+        }
+        Ok(conflicts)
+    }
+
+    // Returns the commit of the dropped stash
+    pub fn stash_drop(&self, stash: Option<&str>) -> Result<Oid, Error> {
+        let (_, index, oid) = self.stash_resolve(stash)?;
+        let mut entries = self.read_reflog(STASH_REF)?;
+
+        // The log is oldest first, stash@{0} is its last line
+        let position = entries.len() - 1 - index;
+        let removed = entries.remove(position);
+        // Keep the chain of old -> new values consistent
+        if let Some(next) = entries.get_mut(position) {
+            next.old = removed.old;
+        }
+
+        match entries.last() {
+            Some(top) => {
+                self.set_ref(STASH_REF, RefValue::Direct(top.new), false)?;
+            }
+            None => self.delete_ref(STASH_REF, false)?,
+        }
+        self.write_reflog(STASH_REF, &entries)?;
+
+        Ok(oid)
+    }
+
+    // Create a branch at the commit the stash was made on, check it out and
+    // pop the stash there. Returns the paths left with conflict markers.
+    pub fn stash_branch(
+        &self,
+        branch_name: &str,
+        stash: Option<&str>,
+    ) -> Result<Vec<String>, Error> {
+        let (_, _, oid) = self.stash_resolve(stash)?;
+        let commit = self.get_commit(oid)?;
+
+        if self.is_branch(branch_name)? {
+            return Err(Error::Other(format!(
+                "A branch named '{}' already exists",
+                branch_name
+            )));
+        }
+        if self.get_working_tree()? != self.get_commit(self.get_oid_hash(HEAD)?)?.tree {
+            return Err(Error::Other(
+                "Your local changes would be overwritten by checkout. Commit your changes or stash them to proceed."
+                    .to_string(),
+            ));
+        }
+
+        self.create_branch(branch_name, Some(commit.parents[0]))?;
+        self.checkout(branch_name)?;
+        self.stash_pop(stash)
+    }
+
+    // "stash@{N}" or "N" (default 0) -> ("stash@{N}", N, commit hash)
+    pub fn stash_resolve(&self, stash: Option<&str>) -> Result<(String, usize, Oid), Error> {
+        let entries = self.stash_list()?;
+        if entries.is_empty() {
+            return Err(Error::Other("No stash entries found.".to_string()));
+        }
+
+        let spec = stash.unwrap_or("stash@{0}");
+        let index = spec
+            .strip_prefix("stash@{")
+            .and_then(|rest| rest.strip_suffix('}'))
+            .unwrap_or(spec)
+            .parse::<usize>()
+            .map_err(|_| Error::InvalidRef(spec.to_string()))?;
+
+        let name = format!("stash@{{{}}}", index);
+        let entry = entries
+            .get(index)
+            .ok_or_else(|| Error::InvalidRef(name.clone()))?;
+        Ok((name, index, entry.new))
+    }
+
+    fn stash_update_ref(&self, stash: Oid, message: &str) -> Result<(), Error> {
+        let old = self
+            .get_ref(STASH_REF, false)
+            .ok()
+            .and_then(|value| value.oid())
+            .unwrap_or(Oid::zero(self.object_format));
+
+        self.set_ref(STASH_REF, RefValue::Direct(stash), false)?;
+        self.append_reflog(STASH_REF, old, stash, message)
+    }
+
+    // Remove a file and the directories it leaves empty
+    fn stash_remove_file(file: &Path) -> Result<(), Error> {
+        fs::remove_file(file)
+            .map_err(|e| Error::io(format!("Failed to remove file {}", file.display()), e))?;
+        let mut dir = file.parent();
+        while let Some(path) = dir {
+            if fs::remove_dir(path).is_err() {
+                break;
+            }
+            dir = path.parent();
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,263 @@
+use crate::cli::{
+    COMMANDS, CatFileMode, EXIT_FAILURE, EXIT_FATAL, UsageError, command_spec, error_exit_code, error_hint,
+    error_message, general_help,
+};
+use crate::stash::StashCommand;
+
+fn parse(values: &[&str]) -> Result<Command, UsageError> {
+    Command::from_args(&values.iter().map(|v| v.to_string()).collect::<Vec<_>>())
+}
+
+#[test]
+fn test_parse_short_and_long_options() {
+    // Grouped flags, and a value in the next argument
+    match parse(&["cherry-pick", "-nx", "-m", "2", "abc"]) {
+        Ok(Command::CherryPick(commits, options)) => {
+            assert_eq!(commits, vec!["abc".to_string()]);
+            assert!(options.no_commit);
+            assert!(options.record_origin);
+            assert_eq!(options.mainline, Some(2));
+        }
+        _ => panic!("expected cherry-pick"),
+    }
+
+    // Attached values, and --name=value or --name value
+    match parse(&["diff", "-M30%", "-w", "--word-diff=porcelain", "HEAD"]) {
+        Ok(Command::Diff(DiffTarget::WorkingTree(Some(rev)), options)) => {
+            assert_eq!(rev, "HEAD");
+            assert_eq!(options.rename_threshold, Some(30));
+            assert!(options.whitespace.ignore_all_space);
+            assert_eq!(options.word_diff, Some(WordDiffMode::Porcelain));
+        }
+        _ => panic!("expected diff"),
+    }
+    for args in [
+        &["rebase", "--onto=main", "topic"][..],
+        &["rebase", "--onto", "main", "topic"],
+    ] {
+        match parse(args) {
+            Ok(Command::Rebase(upstream, options)) => {
+                assert_eq!(upstream, "topic");
+                assert_eq!(options.onto.as_deref(), Some("main"));
+            }
+            _ => panic!("expected rebase"),
+        }
+    }
+    match parse(&["merge", "-Xignore-all-space", "-X", "ignore-cr-at-eol", "topic"]) {
+        Ok(Command::Merge(branch, options)) => {
+            assert_eq!(branch, "topic");
+            assert!(options.whitespace.ignore_all_space);
+            assert!(options.whitespace.ignore_cr_at_eol);
+        }
+        _ => panic!("expected merge"),
+    }
+
+    // An optional value must be attached
+    match parse(&["diff", "-M", "HEAD"]) {
+        Ok(Command::Diff(DiffTarget::WorkingTree(Some(rev)), options)) => {
+            assert_eq!(rev, "HEAD");
+            assert_eq!(options.rename_threshold, Some(50));
+        }
+        _ => panic!("expected diff"),
+    }
+}
+
+#[test]
+fn test_parse_init() {
+    match parse(&["init", "--bare", "-b", "main", "central.git"]) {
+        Ok(Command::Init(directory, options)) => {
+            assert_eq!(directory.as_deref(), Some("central.git"));
+            assert!(options.bare);
+            assert_eq!(options.initial_branch.as_deref(), Some("main"));
+            assert_eq!(options.object_format, HashAlgorithm::Sha1);
+        }
+        _ => panic!("expected init"),
+    }
+    match parse(&["init", "--object-format=sha256"]) {
+        Ok(Command::Init(_, options)) => {
+            assert_eq!(options.object_format, HashAlgorithm::Sha256)
+        }
+        _ => panic!("expected init"),
+    }
+    assert!(parse(&["init", "--object-format=md5"]).is_err());
+    match parse(&["init"]) {
+        Ok(command @ Command::Init(None, _)) => {
+            assert!(!command.needs_repository());
+            assert!(!command.needs_worktree());
+        }
+        _ => panic!("expected init"),
+    }
+
+    assert!(parse(&["status"]).unwrap().needs_worktree());
+    assert!(!parse(&["log"]).unwrap().needs_worktree());
+    assert!(!parse(&["diff", "a", "b"]).unwrap().needs_worktree());
+    assert!(parse(&["diff", "a"]).unwrap().needs_worktree());
+
+    // Only the commands that write the worktree lock it
+    assert!(parse(&["commit", "-m", "x"]).unwrap().changes_worktree());
+    assert!(parse(&["stash"]).unwrap().changes_worktree());
+    assert!(!parse(&["status"]).unwrap().changes_worktree());
+    assert!(!parse(&["diff", "a"]).unwrap().changes_worktree());
+    assert!(!parse(&["log"]).unwrap().changes_worktree());
+}
+
+#[test]
+fn test_parse_cat_file() {
+    for (flag, expected) in [
+        ("-t", CatFileMode::Type),
+        ("-s", CatFileMode::Size),
+        ("-e", CatFileMode::Exists),
+        ("-p", CatFileMode::Pretty),
+    ] {
+        match parse(&["cat-file", flag, "HEAD"]) {
+            Ok(Command::CatFile(name, mode)) => {
+                assert_eq!(name, "HEAD");
+                assert_eq!(mode, expected);
+            }
+            _ => panic!("expected cat-file"),
+        }
+    }
+    assert!(matches!(
+        parse(&["cat-file", "HEAD"]),
+        Ok(Command::CatFile(_, CatFileMode::Raw))
+    ));
+    assert!(parse(&["cat-file", "-t", "-s", "HEAD"]).is_err());
+}
+
+#[test]
+fn test_parse_gc_and_prune() {
+    match parse(&["gc", "--prune=now", "-n"]) {
+        Ok(Command::Gc(options)) => {
+            assert!(options.dry_run);
+            assert_eq!(options.expire.as_deref(), Some("now"));
+        }
+        _ => panic!("expected gc"),
+    }
+    match parse(&["prune", "--expire", "1.day.ago"]) {
+        Ok(Command::Gc(options)) => {
+            assert!(!options.dry_run);
+            assert_eq!(options.expire.as_deref(), Some("1.day.ago"));
+        }
+        _ => panic!("expected prune"),
+    }
+    assert!(parse(&["prune", "--prune=now"]).is_err());
+}
+
+#[test]
+fn test_parse_paths_after_separator() {
+    match parse(&["diff", "--stat", "main", "--", "src", "--not-an-option"]) {
+        Ok(Command::Diff(DiffTarget::WorkingTree(Some(rev)), options)) => {
+            assert_eq!(rev, "main");
+            assert_eq!(options.format, DiffFormat::Stat);
+            assert_eq!(options.paths, vec!["src", "--not-an-option"]);
+        }
+        _ => panic!("expected diff"),
+    }
+
+    match parse(&["log", "--follow", "--", "-file"]) {
+        Ok(Command::Log(options)) => assert_eq!(options.follow.as_deref(), Some("-file")),
+        _ => panic!("expected log"),
+    }
+    assert!(parse(&["log", "file"]).is_err());
+
+    // Positional arguments that look like options
+    match parse(&["commit", "--", "-m is not an option"]) {
+        Ok(Command::Commit(message)) => assert_eq!(message, "-m is not an option"),
+        _ => panic!("expected commit"),
+    }
+
+    match parse(&["stash", "-m", "work", "--", "a.txt"]) {
+        Ok(Command::Stash(StashCommand::Push { message, paths })) => {
+            assert_eq!(message.as_deref(), Some("work"));
+            assert_eq!(paths, vec!["a.txt"]);
+        }
+        _ => panic!("expected stash push"),
+    }
+}
+
+#[test]
+fn test_parse_errors() {
+    let usage = |result: Result<Command, UsageError>| match result {
+        Err(e) => e,
+        Ok(_) => panic!("expected an error"),
+    };
+
+    let error = usage(parse(&["diff", "--nope"]));
+    assert_eq!(error.message, "unknown option: --nope");
+    assert!(error.usage.unwrap().starts_with("usage: bgit diff "));
+
+    // Extra and missing arguments are no longer ignored
+    assert_eq!(
+        usage(parse(&["checkout", "a", "b"])).message,
+        "unexpected argument: b"
+    );
+    assert_eq!(
+        usage(parse(&["write-tree", "here"])).message,
+        "unexpected argument: here"
+    );
+    assert_eq!(usage(parse(&["tag", "v1"])).message, "missing <commit>");
+    assert_eq!(
+        usage(parse(&["status", "--branch=yes"])).message,
+        "--branch does not take a value"
+    );
+    assert_eq!(usage(parse(&["show", "--format"])).message, "--format requires a value");
+    assert_eq!(usage(parse(&["merge", "-z", "topic"])).message, "unknown option: -z");
+    assert!(usage(parse(&["stash", "frobnicate"])).message.contains("frobnicate"));
+    assert!(usage(parse(&["frobnicate"])).message.contains("not a bgit command"));
+    assert!(parse(&[]).is_err());
+}
+
+#[test]
+fn test_parse_help_and_version() {
+    assert!(matches!(parse(&["--help"]), Ok(Command::Help(None))));
+    assert!(matches!(parse(&["help"]), Ok(Command::Help(None))));
+    assert!(matches!(parse(&["help", "diff"]), Ok(Command::Help(Some("diff")))));
+    assert!(matches!(parse(&["merge", "-h"]), Ok(Command::Help(Some("merge")))));
+    assert!(matches!(
+        parse(&["stash", "pop", "--help"]),
+        Ok(Command::Help(Some("stash")))
+    ));
+    assert!(parse(&["help", "frobnicate"]).is_err());
+    assert!(matches!(parse(&["--version"]), Ok(Command::Version)));
+    assert!(matches!(parse(&["version"]), Ok(Command::Version)));
+}
+
+#[test]
+fn test_command_help() {
+    let help = command_spec("rebase").unwrap().help();
+    assert!(help.starts_with(
+        "usage: bgit rebase [-i] [--autosquash] [--onto <commit>] <upstream>\n   or: bgit rebase --continue | --skip | --abort\n\n"
+    ));
+    assert!(help.contains("\n    -i, --interactive "));
+    // Long-only options are aligned with the long names
+    assert!(help.contains("\n        --onto <commit> "));
+
+    // Every command has help, and is listed in the general help
+    let general = general_help();
+    for spec in COMMANDS {
+        assert!(spec.help().starts_with(&format!("usage: bgit {}", spec.name)));
+        assert!(general.contains(&format!("\n    {} ", spec.name)));
+    }
+}
+
+#[test]
+fn test_error_exit_codes_and_hints() {
+    let conflict = Error::Conflict(vec!["a.txt".to_string(), "b.txt".to_string()]);
+    assert_eq!(conflict.to_string(), "Unresolved conflicts in: a.txt, b.txt");
+    assert_eq!(error_exit_code(&conflict), EXIT_FAILURE);
+    assert!(error_hint(&conflict).is_some());
+
+    let missing = Error::NotARepository("not a bgit repository".to_string());
+    assert_eq!(error_exit_code(&missing), EXIT_FATAL);
+    assert!(error_hint(&missing).unwrap().contains("bgit init"));
+    assert_eq!(error_exit_code(&Error::InvalidRef("nope".to_string())), EXIT_FATAL);
+    assert!(error_hint(&Error::Other("Nothing to do".to_string())).is_none());
+
+    // The io error is the source, and shown after the context
+    let io = Error::io(
+        "Failed to read HEAD file",
+        std::io::Error::new(std::io::ErrorKind::PermissionDenied, "denied"),
+    );
+    assert_eq!(io.to_string(), "Failed to read HEAD file");
+    assert_eq!(error_message(&io), "Failed to read HEAD file: denied");
+}
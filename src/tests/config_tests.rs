@@ -0,0 +1,38 @@
+use crate::config::Config;
+
+#[test]
+fn test_config_parse_and_get() {
+    let config = Config::parse(
+        "# comment\n[core]\n\teditor = vim\n\tbare\n[Color \"diff\"]\n\tmeta = yellow\n",
+    )
+    .unwrap();
+
+    assert_eq!(config.get("core.editor"), Some("vim"));
+    assert_eq!(config.get_bool("core.bare"), Some(true));
+    assert_eq!(config.get("color.diff.meta"), Some("yellow"));
+    assert_eq!(config.get("core.missing"), None);
+}
+
+#[test]
+fn test_config_parse_key_outside_section() {
+    assert!(Config::parse("editor = vim\n").is_err());
+}
+
+#[test]
+fn test_repository_config_roundtrip() {
+    let temp_dir = tempdir().unwrap();
+    let repo = Repository::new(temp_dir.path().to_str().unwrap());
+    repo.init().unwrap();
+
+    assert_eq!(repo.get_config("core.editor"), None);
+
+    repo.set_config("core.editor", "nano").unwrap();
+    repo.set_config("user.name", "Jane").unwrap();
+    repo.set_config("core.editor", "vim").unwrap();
+
+    assert_eq!(repo.get_config("core.editor"), Some("vim".to_string()));
+    assert_eq!(repo.get_config("user.name"), Some("Jane".to_string()));
+
+    let content = fs::read_to_string(format!("{}/config", repo.gitdir)).unwrap();
+    assert_eq!(content, "[core]\n\teditor = vim\n[user]\n\tname = Jane\n");
+}
@@ -1,12 +1,9 @@
-use crate::differ::Differ;
-
 #[test]
 fn test_diff_trees_simple() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
 
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Create initial file
     let test_file = temp_dir.path().join("test.txt");
@@ -18,13 +15,13 @@ fn test_diff_trees_simple() {
     let second_commit = repo.create_commit("Second commit").unwrap();
 
     // Get commits
-    let first_commit_obj = repo.get_commit(&first_commit).unwrap();
-    let second_commit_obj = repo.get_commit(&second_commit).unwrap();
+    let first_commit_obj = repo.get_commit(first_commit).unwrap();
+    let second_commit_obj = repo.get_commit(second_commit).unwrap();
 
     // Create differ and get diff
     let differ = Differ::new(&repo);
     let diff = differ
-        .diff_trees(&first_commit_obj.tree, &second_commit_obj.tree)
+        .diff_trees(Some(first_commit_obj.tree), Some(second_commit_obj.tree))
         .unwrap();
 
     // Convert diff to string for easier testing
@@ -42,8 +39,7 @@ fn test_diff_trees_multiple_files() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
 
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Create initial files
     let file1 = temp_dir.path().join("file1.txt");
@@ -58,13 +54,13 @@ fn test_diff_trees_multiple_files() {
     let second_commit = repo.create_commit("Second commit").unwrap();
 
     // Get commits
-    let first_commit_obj = repo.get_commit(&first_commit).unwrap();
-    let second_commit_obj = repo.get_commit(&second_commit).unwrap();
+    let first_commit_obj = repo.get_commit(first_commit).unwrap();
+    let second_commit_obj = repo.get_commit(second_commit).unwrap();
 
     // Create differ and get diff
     let differ = Differ::new(&repo);
     let diff = differ
-        .diff_trees(&first_commit_obj.tree, &second_commit_obj.tree)
+        .diff_trees(Some(first_commit_obj.tree), Some(second_commit_obj.tree))
         .unwrap();
 
     // Convert diff to string for easier testing
@@ -82,8 +78,7 @@ fn test_diff_trees_added_removed_files() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
 
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Create initial file
     let file1 = temp_dir.path().join("file1.txt");
@@ -97,13 +92,13 @@ fn test_diff_trees_added_removed_files() {
     let second_commit = repo.create_commit("Second commit").unwrap();
 
     // Get commits
-    let first_commit_obj = repo.get_commit(&first_commit).unwrap();
-    let second_commit_obj = repo.get_commit(&second_commit).unwrap();
+    let first_commit_obj = repo.get_commit(first_commit).unwrap();
+    let second_commit_obj = repo.get_commit(second_commit).unwrap();
 
     // Create differ and get diff
     let differ = Differ::new(&repo);
     let diff = differ
-        .diff_trees(&first_commit_obj.tree, &second_commit_obj.tree)
+        .diff_trees(Some(first_commit_obj.tree), Some(second_commit_obj.tree))
         .unwrap();
 
     // Convert diff to string for easier testing
@@ -119,8 +114,7 @@ fn test_diff_modified_files() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
 
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Create initial file and commit
     let test_file = temp_dir.path().join("test.txt");
@@ -145,8 +139,7 @@ fn test_diff_added_files() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
 
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Create initial commit
     let test_file = temp_dir.path().join("test.txt");
@@ -171,8 +164,7 @@ fn test_diff_removed_files() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
 
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Create initial files and commit
     let test_file = temp_dir.path().join("test.txt");
@@ -198,8 +190,7 @@ fn test_diff_no_changes() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
 
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Create initial file and commit
     let test_file = temp_dir.path().join("test.txt");
@@ -216,8 +207,7 @@ fn test_diff_no_head() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
 
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Remove HEAD file
     let head_path = format!("{}/{}/HEAD", repo_path, GIT_DIR);
@@ -225,8 +215,7 @@ fn test_diff_no_head() {
 
     // Get diff should fail
     let result = repo.diff();
-    assert!(result.is_err());
-    assert!(result.unwrap_err().contains("Failed to read HEAD file"));
+    assert!(matches!(result, Err(Error::InvalidRef(name)) if name == HEAD));
 }
 
 #[test]
@@ -239,16 +228,14 @@ fn test_diff_invalid_repository() {
 
     // Get diff should fail
     let result = repo.diff();
-    assert!(result.is_err());
-    assert!(result.unwrap_err().contains("Failed to read HEAD file"));
+    assert!(matches!(result, Err(Error::InvalidRef(name)) if name == HEAD));
 }
 
 #[test]
 fn test_diff_file_rename() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Create and commit initial file
     let old_file = temp_dir.path().join("oldname.txt");
@@ -261,19 +248,153 @@ fn test_diff_file_rename() {
 
     // Get diff
     let diff = repo.diff().unwrap();
-    // Should show removal of old file and addition of new file
-    assert!(diff.contains("--- a/oldname.txt"));
-    assert!(diff.contains("+++ b/newname.txt"));
+    // Identical content is paired as a rename, without any hunk
+    assert!(diff.contains("similarity index 100%"));
+    assert!(diff.contains("rename from oldname.txt"));
+    assert!(diff.contains("rename to newname.txt"));
+    assert!(!diff.contains("-Rename me"));
+    assert!(!diff.contains("+Rename me"));
+
+    // Without rename detection it is a removal and an addition
+    let options = DiffOptions {
+        rename_threshold: None,
+        ..Default::default()
+    };
+    let diff = repo.diff_with_options(&options).unwrap();
+    assert!(!diff.contains("rename from"));
     assert!(diff.contains("-Rename me"));
     assert!(diff.contains("+Rename me"));
 }
 
+// "line 1" to "line <count>", one per line
+fn numbered_lines(count: usize) -> String {
+    (1..=count).map(|n| format!("line {}\n", n)).collect()
+}
+
+#[test]
+fn test_diff_rename_with_edits() {
+    let temp_dir = TempDir::new().unwrap();
+    let repo = init_repo(temp_dir.path().to_str().unwrap());
+
+    fs::write(temp_dir.path().join("old.txt"), numbered_lines(10)).unwrap();
+    let first = repo.create_commit("Initial commit").unwrap();
+
+    fs::remove_file(temp_dir.path().join("old.txt")).unwrap();
+    let edited = numbered_lines(10).replace("line 10\n", "line ten\n");
+    fs::write(temp_dir.path().join("new.txt"), edited).unwrap();
+    let second = repo.create_commit("Move and edit").unwrap();
+
+    let old_tree = repo.get_commit(first).unwrap().tree;
+    let new_tree = repo.get_commit(second).unwrap().tree;
+
+    let changes = Differ::new(&repo)
+        .changed_files(Some(old_tree), Some(new_tree))
+        .unwrap();
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].kind, ChangeKind::Renamed);
+    assert_eq!(changes[0].old_path, "old.txt");
+    assert_eq!(changes[0].new_path, "new.txt");
+    assert_eq!(changes[0].similarity, Some(87));
+
+    let diff = String::from_utf8(
+        Differ::new(&repo)
+            .diff_trees(Some(old_tree), Some(new_tree))
+            .unwrap(),
+    )
+    .unwrap();
+    assert!(diff.contains("similarity index 87%\nrename from old.txt\nrename to new.txt\n"));
+    assert!(diff.contains("--- a/old.txt"));
+    assert!(diff.contains("+++ b/new.txt"));
+    assert!(diff.contains("-line 10"));
+    assert!(diff.contains("+line ten"));
+
+    // Above the similarity of the files, they are not paired
+    let strict = DiffOptions {
+        rename_threshold: Some(95),
+        ..Default::default()
+    };
+    let kinds: Vec<ChangeKind> = Differ::with_options(&repo, strict)
+        .changed_files(Some(old_tree), Some(new_tree))
+        .unwrap()
+        .into_iter()
+        .map(|change| change.kind)
+        .collect();
+    assert_eq!(kinds, vec![ChangeKind::Added, ChangeKind::Deleted]);
+}
+
+#[test]
+fn test_diff_copy_detection() {
+    let temp_dir = TempDir::new().unwrap();
+    let repo = init_repo(temp_dir.path().to_str().unwrap());
+
+    fs::write(temp_dir.path().join("original.txt"), numbered_lines(10)).unwrap();
+    let first = repo.create_commit("Initial commit").unwrap();
+
+    fs::write(temp_dir.path().join("exact.txt"), numbered_lines(10)).unwrap();
+    fs::write(temp_dir.path().join("similar.txt"), numbered_lines(9)).unwrap();
+    let second = repo.create_commit("Copy files").unwrap();
+
+    let old_tree = repo.get_commit(first).unwrap().tree;
+    let new_tree = repo.get_commit(second).unwrap().tree;
+
+    // Copies are only looked for on request
+    let changes = Differ::new(&repo)
+        .changed_files(Some(old_tree), Some(new_tree))
+        .unwrap();
+    assert!(changes.iter().all(|change| change.kind == ChangeKind::Added));
+
+    let options = DiffOptions {
+        find_copies: true,
+        ..Default::default()
+    };
+    let changes = Differ::with_options(&repo, options.clone())
+        .changed_files(Some(old_tree), Some(new_tree))
+        .unwrap();
+    assert_eq!(changes.len(), 2);
+    assert!(
+        changes
+            .iter()
+            .all(|change| change.kind == ChangeKind::Copied && change.old_path == "original.txt")
+    );
+    assert_eq!(changes[0].new_path, "exact.txt");
+    assert_eq!(changes[0].similarity, Some(100));
+    assert_eq!(changes[1].new_path, "similar.txt");
+
+    let diff = String::from_utf8(
+        Differ::with_options(&repo, options)
+            .diff_trees(Some(old_tree), Some(new_tree))
+            .unwrap(),
+    )
+    .unwrap();
+    assert!(diff.contains("copy from original.txt\ncopy to exact.txt\n"));
+}
+
+#[test]
+fn test_diff_similarity() {
+    assert_eq!(Differ::similarity(b"", b""), 100);
+    assert_eq!(Differ::similarity(b"a\nb\n", b"a\nb\n"), 100);
+    assert_eq!(Differ::similarity(b"a\nb\n", b"c\nd\n"), 0);
+    assert_eq!(Differ::similarity(b"a\nb\nc\nd\n", b"a\nb\n"), 50);
+}
+
+#[test]
+fn test_status_shows_renames() {
+    let temp_dir = TempDir::new().unwrap();
+    let repo = init_repo(temp_dir.path().to_str().unwrap());
+
+    fs::write(temp_dir.path().join("a.txt"), numbered_lines(10)).unwrap();
+    repo.create_commit("Initial commit").unwrap();
+    fs::rename(temp_dir.path().join("a.txt"), temp_dir.path().join("b.txt")).unwrap();
+
+    let changed = Differ::new(&repo).iter_changed_files().unwrap();
+    assert_eq!(changed, vec!["\x1b[36mrenamed: a.txt -> b.txt\x1b[0m"]);
+}
+
 #[test]
 fn test_diff_ignored_files() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Add .bgitignore to ignore *.log files
     let gitignore = temp_dir.path().join(".bgitignore");
@@ -298,8 +419,7 @@ fn test_diff_ignored_files() {
 fn test_diff_subdirectory_changes() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Create subdirectory and file, commit
     let subdir = temp_dir.path().join("subdir");
@@ -328,8 +448,7 @@ fn test_diff_subdirectory_changes() {
 fn test_merge_trees_simple_add_new_line() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Create initial file and commit
     let test_file = temp_dir.path().join("test.txt");
@@ -341,13 +460,13 @@ fn test_merge_trees_simple_add_new_line() {
     let second_commit = repo.create_commit("Second commit").unwrap();
 
     // Get commits
-    let first_commit_obj = repo.get_commit(&first_commit).unwrap();
-    let second_commit_obj = repo.get_commit(&second_commit).unwrap();
+    let first_commit_obj = repo.get_commit(first_commit).unwrap();
+    let second_commit_obj = repo.get_commit(second_commit).unwrap();
 
     // Create differ and merge trees
     let differ = Differ::new(&repo);
     let merged = differ
-        .merge_trees(&first_commit_obj.tree, &second_commit_obj.tree, None)
+        .merge_trees(first_commit_obj.tree, second_commit_obj.tree, None)
         .unwrap();
 
     // Verify merged content contains both versions with proper merge markers
@@ -363,8 +482,7 @@ fn test_merge_trees_simple_add_new_line() {
 fn test_merge_trees_python_code() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Create initial file and commit
     let test_file = temp_dir.path().join("main.py");
@@ -389,13 +507,13 @@ fn test_merge_trees_python_code() {
     let second_commit = repo.create_commit("Second commit").unwrap();
 
     // Get commits
-    let first_commit_obj = repo.get_commit(&first_commit).unwrap();
-    let second_commit_obj = repo.get_commit(&second_commit).unwrap();
+    let first_commit_obj = repo.get_commit(first_commit).unwrap();
+    let second_commit_obj = repo.get_commit(second_commit).unwrap();
 
     // Create differ and merge trees
     let differ = Differ::new(&repo);
     let merged = differ
-        .merge_trees(&first_commit_obj.tree, &second_commit_obj.tree, None)
+        .merge_trees(first_commit_obj.tree, second_commit_obj.tree, None)
         .unwrap();
 
     // Verify merged content contains both versions with proper merge markers
@@ -426,8 +544,7 @@ fn test_merge_trees_python_code() {
 fn test_merge_trees_python_code_three_way() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Create initial file and commit
     let test_file = temp_dir.path().join("main.py");
@@ -452,19 +569,17 @@ fn test_merge_trees_python_code_three_way() {
     let second_commit = repo.create_commit("Second commit").unwrap();
 
     // Get commits
-    let first_commit_obj = repo.get_commit(&first_commit).unwrap();
-    let second_commit_obj = repo.get_commit(&second_commit).unwrap();
+    let first_commit_obj = repo.get_commit(first_commit).unwrap();
+    let second_commit_obj = repo.get_commit(second_commit).unwrap();
     let base_commit_obj = repo
-        .get_commit(&repo.get_merge_base(&first_commit, &second_commit).unwrap())
+        .get_commit(repo.get_merge_base(first_commit, second_commit).unwrap())
         .unwrap();
 
     // Create differ and merge trees
     let differ = Differ::new(&repo);
     let merged = differ
-        .merge_trees(
-            &first_commit_obj.tree,
-            &second_commit_obj.tree,
-            Some(&base_commit_obj.tree),
+        .merge_trees(first_commit_obj.tree, second_commit_obj.tree,
+            Some(base_commit_obj.tree),
         )
         .unwrap();
 
@@ -481,8 +596,7 @@ fn test_merge_trees_python_code_three_way() {
 fn test_merge_trees_multiple_files() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Create initial files and commit
     let file1 = temp_dir.path().join("file1.txt");
@@ -497,13 +611,13 @@ fn test_merge_trees_multiple_files() {
     let second_commit = repo.create_commit("Second commit").unwrap();
 
     // Get commits
-    let first_commit_obj = repo.get_commit(&first_commit).unwrap();
-    let second_commit_obj = repo.get_commit(&second_commit).unwrap();
+    let first_commit_obj = repo.get_commit(first_commit).unwrap();
+    let second_commit_obj = repo.get_commit(second_commit).unwrap();
 
     // Create differ and merge trees
     let differ = Differ::new(&repo);
     let merged = differ
-        .merge_trees(&first_commit_obj.tree, &second_commit_obj.tree, None)
+        .merge_trees(first_commit_obj.tree, second_commit_obj.tree, None)
         .unwrap();
 
     // Verify merged content for both files
@@ -520,8 +634,7 @@ fn test_merge_trees_multiple_files() {
 fn test_merge_trees_added_removed_files() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Create initial files and commit
     let file1 = temp_dir.path().join("file1.txt");
@@ -537,13 +650,13 @@ fn test_merge_trees_added_removed_files() {
     let second_commit = repo.create_commit("Second commit").unwrap();
 
     // Get commits
-    let first_commit_obj = repo.get_commit(&first_commit).unwrap();
-    let second_commit_obj = repo.get_commit(&second_commit).unwrap();
+    let first_commit_obj = repo.get_commit(first_commit).unwrap();
+    let second_commit_obj = repo.get_commit(second_commit).unwrap();
 
     // Create differ and merge trees
     let differ = Differ::new(&repo);
     let merged = differ
-        .merge_trees(&first_commit_obj.tree, &second_commit_obj.tree, None)
+        .merge_trees(first_commit_obj.tree, second_commit_obj.tree, None)
         .unwrap();
 
     // Verify merged content
@@ -561,8 +674,7 @@ fn test_merge_trees_added_removed_files() {
 fn test_merge_trees_empty_files() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Create empty file and commit
     let empty_file = temp_dir.path().join("empty.txt");
@@ -574,13 +686,13 @@ fn test_merge_trees_empty_files() {
     let second_commit = repo.create_commit("Second commit").unwrap();
 
     // Get commits
-    let first_commit_obj = repo.get_commit(&first_commit).unwrap();
-    let second_commit_obj = repo.get_commit(&second_commit).unwrap();
+    let first_commit_obj = repo.get_commit(first_commit).unwrap();
+    let second_commit_obj = repo.get_commit(second_commit).unwrap();
 
     // Create differ and merge trees
     let differ = Differ::new(&repo);
     let merged = differ
-        .merge_trees(&first_commit_obj.tree, &second_commit_obj.tree, None)
+        .merge_trees(first_commit_obj.tree, second_commit_obj.tree, None)
         .unwrap();
 
     // Verify merged content
@@ -592,8 +704,7 @@ fn test_merge_trees_empty_files() {
 fn test_merge_trees_subdirectories() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Create subdirectory and file, commit
     let subdir = temp_dir.path().join("subdir");
@@ -609,13 +720,13 @@ fn test_merge_trees_subdirectories() {
     let second_commit = repo.create_commit("Second commit").unwrap();
 
     // Get commits
-    let first_commit_obj = repo.get_commit(&first_commit).unwrap();
-    let second_commit_obj = repo.get_commit(&second_commit).unwrap();
+    let first_commit_obj = repo.get_commit(first_commit).unwrap();
+    let second_commit_obj = repo.get_commit(second_commit).unwrap();
 
     // Create differ and merge trees
     let differ = Differ::new(&repo);
     let merged = differ
-        .merge_trees(&first_commit_obj.tree, &second_commit_obj.tree, None)
+        .merge_trees(first_commit_obj.tree, second_commit_obj.tree, None)
         .unwrap();
 
     // Verify merged content
@@ -630,8 +741,7 @@ fn test_merge_trees_subdirectories() {
 fn test_merge_trees_three_way_merge() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Create initial file with common ancestor content
     let test_file = temp_dir.path().join("animals.py");
@@ -668,17 +778,15 @@ def be_a_dog():
     let commit_b = repo.create_commit("Version B commit").unwrap();
 
     // Get commits
-    let base_commit_obj = repo.get_commit(&base_commit).unwrap();
-    let commit_a_obj = repo.get_commit(&commit_a).unwrap();
-    let commit_b_obj = repo.get_commit(&commit_b).unwrap();
+    let base_commit_obj = repo.get_commit(base_commit).unwrap();
+    let commit_a_obj = repo.get_commit(commit_a).unwrap();
+    let commit_b_obj = repo.get_commit(commit_b).unwrap();
 
     // Create differ and merge trees using three-way merge
     let differ = Differ::new(&repo);
     let merged = differ
-        .merge_trees(
-            &commit_a_obj.tree,
-            &commit_b_obj.tree,
-            Some(&base_commit_obj.tree),
+        .merge_trees(commit_a_obj.tree, commit_b_obj.tree,
+            Some(base_commit_obj.tree),
         )
         .unwrap();
 
@@ -698,8 +806,7 @@ def be_a_dog():
 fn test_merge_trees_three_way_no_conflict() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // 1. Base commit
     let test_file = temp_dir.path().join("file.txt");
@@ -707,7 +814,7 @@ fn test_merge_trees_three_way_no_conflict() {
     let base_commit = repo.create_commit("Base commit").unwrap();
 
     // 2. Feature branch: Add line 2
-    repo.create_branch("feature", Some(base_commit.clone()))
+    repo.create_branch("feature", Some(base_commit))
         .unwrap();
     repo.checkout("feature").unwrap();
     fs::write(&test_file, "Line 1\n\nLine 2 feature\n").unwrap();
@@ -732,3 +839,566 @@ fn test_merge_trees_three_way_no_conflict() {
     let expected_content = "Line master 1\n\nLine 2 feature\n";
     assert_eq!(final_content, expected_content);
 }
+
+#[test]
+fn test_merge_trees_deleted_on_one_side() {
+    let temp_dir = TempDir::new().unwrap();
+    let repo = init_repo(temp_dir.path().to_str().unwrap());
+
+    fs::create_dir_all(temp_dir.path().join("dir")).unwrap();
+    fs::write(temp_dir.path().join("dir/nested.txt"), "nested\n").unwrap();
+    fs::write(temp_dir.path().join("keep.txt"), "keep\n").unwrap();
+    fs::write(temp_dir.path().join("gone.txt"), "gone\n").unwrap();
+    let base = repo.create_commit("Base").unwrap();
+
+    fs::remove_file(temp_dir.path().join("gone.txt")).unwrap();
+    fs::remove_dir_all(temp_dir.path().join("dir")).unwrap();
+    let deleted = repo.create_commit("Delete").unwrap();
+
+    let base_tree = repo.get_commit(base).unwrap().tree;
+    let deleted_tree = repo.get_commit(deleted).unwrap().tree;
+
+    // The unchanged side must not resurrect the deleted entries
+    let merged = Differ::new(&repo)
+        .merge_trees(base_tree, deleted_tree, Some(base_tree))
+        .unwrap();
+    assert!(merged.contains_key("keep.txt"));
+    assert!(!merged.contains_key("gone.txt"));
+    assert!(!merged.contains_key("dir"));
+    assert!(!merged.contains_key("dir/nested.txt"));
+}
+
+// Writes src/main.txt and docs/guide.txt at `version`
+fn write_revision_files(dir: &Path, version: u32) {
+    write_file(dir, "src/main.txt", format!("main v{}\n", version));
+    write_file(dir, "docs/guide.txt", format!("guide v{}\n", version));
+}
+
+#[test]
+fn test_diff_between_revisions() {
+    let temp_dir = TempDir::new().unwrap();
+    let (repo, first, _) = commit_twice(
+        &temp_dir,
+        |dir| write_revision_files(dir, 1),
+        |dir| write_revision_files(dir, 2),
+    );
+    repo.create_tag("v1", first).unwrap();
+
+    let target = DiffTarget::Revisions("v1".to_string(), "master".to_string());
+    let diff = repo.diff_target(&target, &DiffOptions::default()).unwrap();
+    assert!(diff.contains("-main v1"));
+    assert!(diff.contains("+main v2"));
+    assert!(diff.contains("-guide v1"));
+    assert!(diff.contains("+guide v2"));
+
+    // Reversed, the changes go the other way
+    let target = DiffTarget::Revisions("master".to_string(), "v1".to_string());
+    let diff = repo.diff_target(&target, &DiffOptions::default()).unwrap();
+    assert!(diff.contains("-main v2"));
+    assert!(diff.contains("+main v1"));
+
+    let target = DiffTarget::Revisions("v1".to_string(), "nope".to_string());
+    assert!(repo.diff_target(&target, &DiffOptions::default()).is_err());
+}
+
+#[test]
+fn test_diff_with_pathspec() {
+    let temp_dir = TempDir::new().unwrap();
+    let (repo, first, second) = commit_twice(
+        &temp_dir,
+        |dir| write_revision_files(dir, 1),
+        |dir| write_revision_files(dir, 2),
+    );
+
+    let options = DiffOptions {
+        paths: vec!["docs".to_string()],
+        ..Default::default()
+    };
+    let target = DiffTarget::Revisions(first.to_string(), second.to_string());
+    let diff = repo.diff_target(&target, &options).unwrap();
+    assert!(diff.contains("+++ b/docs/guide.txt"));
+    assert!(!diff.contains("src/main.txt"));
+}
+
+#[test]
+fn test_diff_revision_against_worktree_and_cached() {
+    let temp_dir = TempDir::new().unwrap();
+    let (repo, first, _) = commit_twice(
+        &temp_dir,
+        |dir| write_revision_files(dir, 1),
+        |dir| write_revision_files(dir, 2),
+    );
+    fs::write(temp_dir.path().join("src/main.txt"), "main v3\n").unwrap();
+
+    // A revision against the worktree
+    let target = DiffTarget::WorkingTree(Some(first.to_string()));
+    let diff = repo.diff_target(&target, &DiffOptions::default()).unwrap();
+    assert!(diff.contains("-main v1"));
+    assert!(diff.contains("+main v3"));
+    assert!(diff.contains("+guide v2"));
+
+    // Without an index, what would be committed is the worktree itself
+    let cached = repo
+        .diff_target(&DiffTarget::Cached(None), &DiffOptions::default())
+        .unwrap();
+    assert_eq!(cached, repo.diff().unwrap());
+    assert!(cached.contains("-main v2"));
+    assert!(cached.contains("+main v3"));
+    assert!(!cached.contains("guide"));
+}
+
+#[test]
+fn test_diff_no_index() {
+    let temp_dir = TempDir::new().unwrap();
+    let old_file = temp_dir.path().join("old.txt");
+    let new_file = temp_dir.path().join("new.txt");
+    fs::write(&old_file, "same\nold\n").unwrap();
+    fs::write(&new_file, "same\nnew\n").unwrap();
+
+    // Works outside of a repository
+    let repo = Repository::new(temp_dir.path().to_str().unwrap());
+    let target = DiffTarget::NoIndex(
+        old_file.to_str().unwrap().to_string(),
+        new_file.to_str().unwrap().to_string(),
+    );
+    let diff = repo.diff_target(&target, &DiffOptions::default()).unwrap();
+    assert!(diff.contains("-old"));
+    assert!(diff.contains("+new"));
+
+    let missing = DiffTarget::NoIndex(
+        old_file.to_str().unwrap().to_string(),
+        temp_dir.path().join("missing.txt").to_str().unwrap().to_string(),
+    );
+    assert!(repo.diff_target(&missing, &DiffOptions::default()).is_err());
+}
+
+// The worktree before and after the changes the summary formats show: a
+// modification, a deletion, an addition and a rename
+fn write_summary_before(dir: &Path) {
+    write_file(dir, "modified.txt", "a\nb\nc\n");
+    write_file(dir, "deleted.txt", "gone\n");
+    write_file(dir, "before.txt", numbered_lines(10));
+}
+
+fn write_summary_after(dir: &Path) {
+    write_file(dir, "modified.txt", "a\nB\nc\nd\n");
+    fs::remove_file(dir.join("deleted.txt")).unwrap();
+    write_file(dir, "added.txt", "new\nfile\n");
+    fs::rename(dir.join("before.txt"), dir.join("after.txt")).unwrap();
+}
+
+fn format_summary(repo: &Repository, old_tree: Oid, new_tree: Oid, format: DiffFormat) -> String {
+    let options = DiffOptions {
+        format,
+        ..Default::default()
+    };
+    Differ::with_options(repo, options)
+        .format_diff(Some(old_tree), Some(new_tree))
+        .unwrap()
+}
+
+#[test]
+fn test_diff_name_only_and_name_status() {
+    let temp_dir = TempDir::new().unwrap();
+    let (repo, first, second) =
+        commit_twice(&temp_dir, write_summary_before, write_summary_after);
+    let old_tree = repo.get_commit(first).unwrap().tree;
+    let new_tree = repo.get_commit(second).unwrap().tree;
+
+    assert_eq!(
+        format_summary(&repo, old_tree, new_tree, DiffFormat::NameOnly),
+        "added.txt\nafter.txt\ndeleted.txt\nmodified.txt\n"
+    );
+    assert_eq!(
+        format_summary(&repo, old_tree, new_tree, DiffFormat::NameStatus),
+        "A\tadded.txt\nR100\tbefore.txt\tafter.txt\nD\tdeleted.txt\nM\tmodified.txt\n"
+    );
+}
+
+#[test]
+fn test_diff_numstat_and_shortstat() {
+    let temp_dir = TempDir::new().unwrap();
+    let (repo, first, second) =
+        commit_twice(&temp_dir, write_summary_before, write_summary_after);
+    let old_tree = repo.get_commit(first).unwrap().tree;
+    let new_tree = repo.get_commit(second).unwrap().tree;
+
+    assert_eq!(
+        format_summary(&repo, old_tree, new_tree, DiffFormat::NumStat),
+        "2\t0\tadded.txt\n0\t0\tbefore.txt => after.txt\n0\t1\tdeleted.txt\n2\t1\tmodified.txt\n"
+    );
+    assert_eq!(
+        format_summary(&repo, old_tree, new_tree, DiffFormat::ShortStat),
+        " 4 files changed, 4 insertions(+), 2 deletions(-)\n"
+    );
+}
+
+#[test]
+fn test_diff_stat() {
+    let temp_dir = TempDir::new().unwrap();
+    let (repo, first, second) =
+        commit_twice(&temp_dir, write_summary_before, write_summary_after);
+    let old_tree = repo.get_commit(first).unwrap().tree;
+    let new_tree = repo.get_commit(second).unwrap().tree;
+
+    assert_eq!(
+        format_summary(&repo, old_tree, new_tree, DiffFormat::Stat),
+        concat!(
+            " added.txt               | 2 ++\n",
+            " before.txt => after.txt | 0\n",
+            " deleted.txt             | 1 -\n",
+            " modified.txt            | 3 ++-\n",
+            " 4 files changed, 4 insertions(+), 2 deletions(-)\n",
+        )
+    );
+}
+
+#[test]
+fn test_diff_stat_scales_large_changes() {
+    let change = |path: &str| FileChange {
+        kind: ChangeKind::Modified,
+        old_path: path.to_string(),
+        new_path: path.to_string(),
+        old_oid: None,
+        new_oid: None,
+        similarity: None,
+    };
+    let stats = vec![
+        FileStat {
+            change: change("big.txt"),
+            insertions: 300,
+            deletions: 100,
+            binary_sizes: None,
+        },
+        FileStat {
+            change: change("small.txt"),
+            insertions: 1,
+            deletions: 0,
+            binary_sizes: None,
+        },
+    ];
+
+    let output = Differ::format_stats(&stats, DiffFormat::Stat);
+    let lines: Vec<&str> = output.lines().collect();
+    assert_eq!(
+        lines[0],
+        format!(" big.txt   | 400 {}{}", "+".repeat(30), "-".repeat(10))
+    );
+    // Small changes still get a mark
+    assert_eq!(lines[1], " small.txt |   1 +");
+    assert_eq!(lines[2], " 2 files changed, 301 insertions(+), 100 deletions(-)");
+    assert_eq!(
+        Differ::format_stats(&stats[1..], DiffFormat::ShortStat),
+        " 1 file changed, 1 insertion(+)\n"
+    );
+}
+
+#[test]
+fn test_diff_target_with_format() {
+    let temp_dir = TempDir::new().unwrap();
+    let (repo, _, _) = commit_twice(&temp_dir, write_summary_before, write_summary_after);
+    fs::write(temp_dir.path().join("added.txt"), "changed\n").unwrap();
+
+    let options = DiffOptions {
+        format: DiffFormat::NameStatus,
+        ..Default::default()
+    };
+    let output = repo
+        .diff_target(&DiffTarget::WorkingTree(None), &options)
+        .unwrap();
+    assert_eq!(output, "M\tadded.txt\n");
+}
+
+#[test]
+fn test_diff_binary_files() {
+    let temp_dir = TempDir::new().unwrap();
+    let (repo, first, second) = commit_twice(
+        &temp_dir,
+        |dir| write_file(dir, "image.png", b"\x89PNG\0\x01"),
+        |dir| write_file(dir, "image.png", b"\x89PNG\0\x02\x03"),
+    );
+    let old_tree = repo.get_commit(first).unwrap().tree;
+    let new_tree = repo.get_commit(second).unwrap().tree;
+
+    let diff = Differ::new(&repo).diff_trees(Some(old_tree), Some(new_tree)).unwrap();
+    assert_eq!(
+        String::from_utf8(diff).unwrap(),
+        "Binary files a/image.png and b/image.png differ\n"
+    );
+    assert_eq!(
+        format_summary(&repo, old_tree, new_tree, DiffFormat::NumStat),
+        "-\t-\timage.png\n"
+    );
+    assert_eq!(
+        format_summary(&repo, old_tree, new_tree, DiffFormat::Stat),
+        " image.png | Bin 6 -> 7 bytes\n 1 file changed, 0 insertions(+), 0 deletions(-)\n"
+    );
+}
+
+#[test]
+fn test_diff_binary_patch() {
+    let temp_dir = TempDir::new().unwrap();
+    let (repo, first, second) = commit_twice(
+        &temp_dir,
+        |dir| write_file(dir, "image.png", b"\x89PNG\0\x01"),
+        |dir| write_file(dir, "image.png", b"\x89PNG\0\x02\x03"),
+    );
+    let old_tree = repo.get_commit(first).unwrap().tree;
+    let new_tree = repo.get_commit(second).unwrap().tree;
+
+    let options = DiffOptions {
+        binary: true,
+        ..Default::default()
+    };
+    let diff = Differ::with_options(&repo, options)
+        .diff_trees(Some(old_tree), Some(new_tree))
+        .unwrap();
+    let diff = String::from_utf8(diff).unwrap();
+    let lines: Vec<&str> = diff.lines().collect();
+
+    assert_eq!(lines[..4], ["--- a/image.png", "+++ b/image.png", "GIT binary patch", "literal 7"]);
+    // 7 bytes of content in an 18 byte zlib stream
+    assert!(lines[4].starts_with('R'));
+    assert_eq!(lines[5], "");
+    assert_eq!(lines[6], "literal 6");
+    assert!(lines[7].starts_with('Q'));
+
+    assert_eq!(crate::binary::encode_base85(b"hello"), "Xk~0{ZvX%Q");
+}
+
+#[test]
+fn test_diff_binary_attribute() {
+    let temp_dir = TempDir::new().unwrap();
+    let repo = init_repo(temp_dir.path().to_str().unwrap());
+
+    fs::write(
+        temp_dir.path().join(".bgitattributes"),
+        "# generated files\n*.dat binary\nkeep/*.dat diff\n",
+    )
+    .unwrap();
+    assert_eq!(repo.binary_attribute("data/model.dat"), Some(true));
+    assert_eq!(repo.binary_attribute("keep/model.dat"), Some(false));
+    assert_eq!(repo.binary_attribute("keep/sub/model.dat"), Some(true));
+    assert_eq!(repo.binary_attribute("notes.txt"), None);
+
+    fs::write(temp_dir.path().join("model.dat"), "weights 1\n").unwrap();
+    let first = repo.create_commit("First").unwrap();
+    fs::write(temp_dir.path().join("model.dat"), "weights 2\n").unwrap();
+    let second = repo.create_commit("Second").unwrap();
+
+    let diff = Differ::new(&repo)
+        .diff_trees(
+            Some(repo.get_commit(first).unwrap().tree),
+            Some(repo.get_commit(second).unwrap().tree),
+        )
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(diff).unwrap(),
+        "Binary files a/model.dat and b/model.dat differ\n"
+    );
+}
+
+#[test]
+fn test_merge_trees_binary_files() {
+    let temp_dir = TempDir::new().unwrap();
+    let repo = init_repo(temp_dir.path().to_str().unwrap());
+
+    fs::write(temp_dir.path().join("conflict.bin"), b"base\0").unwrap();
+    fs::write(temp_dir.path().join("clean.bin"), b"base\0").unwrap();
+    let base = repo.create_commit("Base").unwrap();
+
+    fs::write(temp_dir.path().join("conflict.bin"), b"ours\0").unwrap();
+    let ours = repo.create_commit("Ours").unwrap();
+
+    repo.read_tree(repo.get_commit(base).unwrap().tree, temp_dir.path())
+        .unwrap();
+    fs::write(temp_dir.path().join("conflict.bin"), b"theirs\0").unwrap();
+    fs::write(temp_dir.path().join("clean.bin"), b"theirs\0").unwrap();
+    let theirs = repo.create_commit("Theirs").unwrap();
+
+    let (merged, conflicts) = Differ::new(&repo)
+        .merge_trees_with_conflicts(
+            repo.get_commit(ours).unwrap().tree,
+            repo.get_commit(theirs).unwrap().tree,
+            Some(repo.get_commit(base).unwrap().tree),
+        )
+        .unwrap();
+
+    assert_eq!(conflicts, vec!["conflict.bin".to_string()]);
+    // Our version is kept untouched, without conflict markers
+    assert_eq!(merged["conflict.bin"].as_ref().unwrap(), b"ours\0");
+    assert_eq!(merged["clean.bin"].as_ref().unwrap(), b"theirs\0");
+}
+
+fn diff_with_whitespace(
+    repo: &Repository,
+    old_tree: Oid,
+    new_tree: Oid,
+    whitespace: WhitespaceOptions,
+) -> String {
+    let options = DiffOptions {
+        whitespace,
+        ..Default::default()
+    };
+    let diff = Differ::with_options(repo, options)
+        .diff_trees(Some(old_tree), Some(new_tree))
+        .unwrap();
+    String::from_utf8(diff).unwrap()
+}
+
+#[test]
+fn test_diff_ignore_whitespace() {
+    let temp_dir = TempDir::new().unwrap();
+    let (repo, first, second) = commit_twice(
+        &temp_dir,
+        |dir| write_file(dir, "file.txt", "fn main() {\n    call(a, b);\n}\n"),
+        |dir| write_file(dir, "file.txt", "fn main() {\r\n\n  call(a,  b);\n}\n"),
+    );
+    let old_tree = repo.get_commit(first).unwrap().tree;
+    let new_tree = repo.get_commit(second).unwrap().tree;
+
+    let all = WhitespaceOptions {
+        ignore_all_space: true,
+        ignore_blank_lines: true,
+        ignore_cr_at_eol: true,
+        ..Default::default()
+    };
+    assert_eq!(diff_with_whitespace(&repo, old_tree, new_tree, all), "");
+
+    // Without -B the added blank line is still a change
+    let no_blank_lines = WhitespaceOptions {
+        ignore_all_space: true,
+        ignore_cr_at_eol: true,
+        ..Default::default()
+    };
+    let diff = diff_with_whitespace(&repo, old_tree, new_tree, no_blank_lines);
+    assert!(diff.contains("\n+\n"));
+    assert!(!diff.contains("-    call(a, b);"));
+
+    // -b also ignores the CR at the end of the line, but not the blank line
+    let space_change = WhitespaceOptions {
+        ignore_space_change: true,
+        ..Default::default()
+    };
+    let diff = diff_with_whitespace(&repo, old_tree, new_tree, space_change);
+    assert!(diff.contains("\n+\n"));
+    assert!(!diff.contains("-fn main()"));
+    assert!(!diff.contains("-    call"));
+
+    let diff = diff_with_whitespace(&repo, old_tree, new_tree, WhitespaceOptions::default());
+    assert!(diff.contains("-    call(a, b);\n"));
+}
+
+#[test]
+fn test_word_segments() {
+    let words = regex::Regex::new(r"\S+").unwrap();
+    assert_eq!(
+        Differ::word_segments("The quick fox", "The slow fox", &words),
+        vec![
+            (' ', "The ".to_string()),
+            ('-', "quick".to_string()),
+            ('+', "slow".to_string()),
+            (' ', " fox".to_string()),
+        ]
+    );
+    assert_eq!(
+        Differ::word_segments("a b c", "a c d", &words),
+        vec![
+            (' ', "a ".to_string()),
+            ('-', "b".to_string()),
+            (' ', " c ".to_string()),
+            ('+', "d".to_string()),
+        ]
+    );
+
+    // Characters as words
+    let characters = regex::Regex::new(r"\w").unwrap();
+    assert_eq!(
+        Differ::word_segments("colour", "color", &characters),
+        vec![
+            (' ', "colo".to_string()),
+            ('-', "u".to_string()),
+            (' ', "r".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn test_word_diff_modes() {
+    let temp_dir = TempDir::new().unwrap();
+    let (repo, first, second) = commit_twice(
+        &temp_dir,
+        |dir| write_file(dir, "file.txt", "The quick brown fox\njumps over\nthe dog\n"),
+        |dir| write_file(dir, "file.txt", "The slow brown fox\njumps over\nthe lazy dog\n"),
+    );
+    let old_tree = repo.get_commit(first).unwrap().tree;
+    let new_tree = repo.get_commit(second).unwrap().tree;
+    let word_diff = |mode: WordDiffMode| {
+        let options = DiffOptions {
+            word_diff: Some(mode),
+            ..Default::default()
+        };
+        Differ::with_options(&repo, options)
+            .format_diff(Some(old_tree), Some(new_tree))
+            .unwrap()
+    };
+
+    assert_eq!(
+        word_diff(WordDiffMode::Plain),
+        "--- a/file.txt\n+++ b/file.txt\n@@ -1,3 +1,3 @@\n\
+         The [-quick-]{+slow+} brown fox\njumps over\nthe {+lazy+} dog\n"
+    );
+    assert_eq!(
+        word_diff(WordDiffMode::Porcelain),
+        "--- a/file.txt\n+++ b/file.txt\n@@ -1,3 +1,3 @@\n\
+         \x20The \n-quick\n+slow\n\x20 brown fox\n~\n\
+         \x20jumps over\n~\n\
+         \x20the \n+lazy\n\x20 dog\n~\n"
+    );
+    assert!(word_diff(WordDiffMode::Color).contains("The \x1b[31mquick\x1b[0m\x1b[32mslow\x1b[0m brown fox\n"));
+}
+
+#[test]
+fn test_merge_trees_ignore_space_change() {
+    let temp_dir = TempDir::new().unwrap();
+    let repo = init_repo(temp_dir.path().to_str().unwrap());
+    let file = temp_dir.path().join("code.txt");
+
+    // "same" keeps the changes apart, diff3 conflicts on adjacent lines
+    fs::write(&file, "one\n  two\nsame\n  three\nfour\n").unwrap();
+    let base = repo.create_commit("Base").unwrap();
+
+    // Ours reindents "two" and really changes "three"
+    fs::write(&file, "one\n    two\nsame\n  THREE\nfour\n").unwrap();
+    let ours = repo.create_commit("Ours").unwrap();
+
+    // Theirs really changes "two" and reindents "three"
+    fs::write(&file, "one\n  two 2\nsame\n\tthree\nfour\n").unwrap();
+    let theirs = repo.create_commit("Theirs").unwrap();
+
+    let trees: Vec<Oid> = [ours, theirs, base]
+        .iter()
+        .map(|commit| repo.get_commit(*commit).unwrap().tree)
+        .collect();
+    let merge = |options: DiffOptions| {
+        Differ::with_options(&repo, options)
+            .merge_trees_with_conflicts(trees[0], trees[1], Some(trees[2]))
+            .unwrap()
+    };
+
+    let (_, conflicts) = merge(DiffOptions::default());
+    assert_eq!(conflicts, vec!["code.txt".to_string()]);
+
+    let (merged, conflicts) = merge(DiffOptions {
+        whitespace: WhitespaceOptions {
+            ignore_space_change: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    });
+    assert!(conflicts.is_empty());
+    assert_eq!(
+        merged["code.txt"].as_ref().unwrap(),
+        b"one\n  two 2\nsame\n  THREE\nfour\n"
+    );
+}
@@ -0,0 +1,155 @@
+use crate::discovery::DiscoveryOptions;
+
+fn canonical_path(path: &Path) -> String {
+    fs::canonicalize(path).unwrap().to_string_lossy().to_string()
+}
+
+#[test]
+fn test_discover_from_subdirectory() {
+    let temp_dir = TempDir::new().unwrap();
+    let repo = Repository::new(temp_dir.path().to_str().unwrap());
+    repo.init().unwrap();
+    let nested = temp_dir.path().join("src/deep");
+    fs::create_dir_all(&nested).unwrap();
+
+    let options = DiscoveryOptions::default();
+    let found = Repository::discover_with_options(nested.to_str().unwrap(), &options).unwrap();
+    let top = canonical_path(temp_dir.path());
+    assert_eq!(found.worktree, top);
+    assert_eq!(found.gitdir, format!("{}/{}", top, GIT_DIR));
+    assert_eq!(found.prefix, "src/deep/");
+
+    let found =
+        Repository::discover_with_options(temp_dir.path().to_str().unwrap(), &options).unwrap();
+    assert_eq!(found.prefix, "");
+}
+
+#[test]
+fn test_discover_stops_at_ceiling() {
+    let temp_dir = TempDir::new().unwrap();
+    let repo = Repository::new(temp_dir.path().to_str().unwrap());
+    repo.init().unwrap();
+    let nested = temp_dir.path().join("src");
+    fs::create_dir_all(&nested).unwrap();
+
+    // The ceiling itself is not searched
+    let options = DiscoveryOptions {
+        ceilings: vec![fs::canonicalize(temp_dir.path()).unwrap()],
+        ..Default::default()
+    };
+    let error = Repository::discover_with_options(nested.to_str().unwrap(), &options)
+        .err()
+        .unwrap();
+    assert!(matches!(error, Error::NotARepository(_)));
+    assert!(error.to_string().starts_with("not a bgit repository"));
+
+    // Unless discovery starts there
+    assert!(
+        Repository::discover_with_options(temp_dir.path().to_str().unwrap(), &options).is_ok()
+    );
+}
+
+#[test]
+fn test_discover_bare_repository() {
+    let temp_dir = TempDir::new().unwrap();
+    let options = InitOptions {
+        bare: true,
+        ..Default::default()
+    };
+    Repository::init_at(temp_dir.path().to_str().unwrap(), &options).unwrap();
+    let refs = temp_dir.path().join("refs/heads");
+
+    let found =
+        Repository::discover_with_options(refs.to_str().unwrap(), &DiscoveryOptions::default())
+            .unwrap();
+    assert!(found.bare);
+    assert_eq!(found.gitdir, canonical_path(temp_dir.path()));
+    assert!(found.require_worktree().is_err());
+
+    // The .bgit directory of a repository with a worktree is not bare
+    let temp_dir = TempDir::new().unwrap();
+    Repository::new(temp_dir.path().to_str().unwrap())
+        .init()
+        .unwrap();
+    let gitdir = temp_dir.path().join(GIT_DIR);
+    let found =
+        Repository::discover_with_options(gitdir.to_str().unwrap(), &DiscoveryOptions::default())
+            .unwrap();
+    assert!(!found.bare);
+    assert_eq!(found.worktree, canonical_path(temp_dir.path()));
+}
+
+#[test]
+fn test_resolve_and_display_paths() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut repo = Repository::new(&canonical_path(temp_dir.path()));
+    repo.prefix = "src/deep/".to_string();
+
+    assert_eq!(repo.resolve_path("a.rs").unwrap(), "src/deep/a.rs");
+    assert_eq!(repo.resolve_path("./../b.rs").unwrap(), "src/b.rs");
+    assert_eq!(repo.resolve_path("../..").unwrap(), "");
+    assert!(repo.resolve_path("../../../outside").is_err());
+    assert_eq!(
+        repo.resolve_path(&format!("{}/top.txt", repo.worktree))
+            .unwrap(),
+        "top.txt"
+    );
+    assert!(repo.resolve_path("/elsewhere/file").is_err());
+
+    assert_eq!(repo.display_path("src/deep/a.rs"), "a.rs");
+    assert_eq!(repo.display_path("src/deep/more/c.rs"), "more/c.rs");
+    assert_eq!(repo.display_path("src/b.rs"), "../b.rs");
+    assert_eq!(repo.display_path("README.md"), "../../README.md");
+    assert_eq!(repo.display_path("srcx/d.rs"), "../../srcx/d.rs");
+
+    repo.prefix = String::new();
+    assert_eq!(repo.resolve_path("src/a.rs").unwrap(), "src/a.rs");
+    assert_eq!(repo.display_path("src/a.rs"), "src/a.rs");
+}
+
+#[test]
+fn test_status_relative_to_subdirectory() {
+    let temp_dir = TempDir::new().unwrap();
+    let repo = Repository::new(temp_dir.path().to_str().unwrap());
+    repo.init().unwrap();
+    fs::create_dir_all(temp_dir.path().join("src")).unwrap();
+    fs::write(temp_dir.path().join("top.txt"), "top\n").unwrap();
+    fs::write(temp_dir.path().join("src/lib.rs"), "lib\n").unwrap();
+
+    let repo = Repository::discover_with_options(
+        temp_dir.path().join("src").to_str().unwrap(),
+        &DiscoveryOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(
+        repo.status_porcelain(PorcelainVersion::V1, &StatusOptions::default())
+            .unwrap(),
+        "A  lib.rs\nA  ../top.txt\n"
+    );
+}
+
+#[test]
+fn test_ignore_rules_ignore_directories_above_worktree() {
+    // A worktree inside a directory whose name contains ".git"
+    let temp_dir = TempDir::new().unwrap();
+    let worktree = temp_dir.path().join("projects.github");
+    let repo = Repository::new(worktree.to_str().unwrap());
+    repo.init().unwrap();
+    fs::write(worktree.join("a.txt"), "a\n").unwrap();
+
+    assert!(!repo.is_ignored(&worktree.join("a.txt")));
+    assert!(repo.is_ignored(&worktree.join(".bgit")));
+}
+
+#[test]
+fn test_global_directory_option() {
+    let args = |values: &[&str]| values.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+
+    let (options, rest) =
+        GlobalOptions::from_args(&args(&["-C", "a", "-C", "../b", "diff", "-C"])).unwrap();
+    assert_eq!(options.directories, vec!["a", "../b"]);
+    // After the command, -C belongs to it
+    assert_eq!(rest, args(&["diff", "-C"]));
+
+    assert!(GlobalOptions::from_args(&args(&["-C"])).is_err());
+}
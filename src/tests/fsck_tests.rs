@@ -0,0 +1,196 @@
+use crate::fsck::{FsckOptions, FsckProblem, LOST_FOUND_DIR};
+
+// The first commit has a.txt, the second adds dir/b.txt
+fn write_a(dir: &Path) {
+    write_file(dir, "a.txt", "a\n");
+}
+
+fn write_dir_b(dir: &Path) {
+    write_file(dir, "dir/b.txt", "b\n");
+}
+
+fn object_file(repo: &Repository, oid: Oid) -> std::path::PathBuf {
+    let hex = oid.to_string();
+    let (dir, file) = hex.split_at(2);
+    Path::new(&repo.gitdir).join("objects").join(dir).join(file)
+}
+
+#[test]
+fn test_fsck_clean_repository() {
+    let temp_dir = TempDir::new().unwrap();
+    let (repo, _, _) = commit_twice(&temp_dir, write_a, write_dir_b);
+
+    for connectivity_only in [false, true] {
+        let options = FsckOptions {
+            connectivity_only,
+            ..Default::default()
+        };
+        let report = repo.fsck(&options).unwrap();
+        assert_eq!(report.problems, vec![]);
+        assert!(report.dangling.is_empty());
+        assert!(report.unreachable.is_empty());
+    }
+}
+
+#[test]
+fn test_fsck_dangling_and_unreachable() {
+    let temp_dir = TempDir::new().unwrap();
+    let (repo, _, _) = commit_twice(&temp_dir, write_a, write_dir_b);
+
+    // A commit no ref points at, and its new tree and blob
+    let blob = repo.hash_object(b"lost\n", ObjectType::Blob).unwrap();
+    let tree = repo
+        .create_tree_from_paths(&BTreeMap::from([("lost.txt".to_string(), blob)]))
+        .unwrap();
+    let commit = repo.write_commit(tree, &[], "Lost commit").unwrap();
+    let loose = repo.hash_object(b"loose\n", ObjectType::Blob).unwrap();
+
+    let report = repo.fsck(&FsckOptions::default()).unwrap();
+    assert_eq!(report.problems, vec![]);
+    let mut dangling = vec![(ObjectType::Commit, commit), (ObjectType::Blob, loose)];
+    dangling.sort_by_key(|(_, oid)| *oid);
+    assert_eq!(report.dangling, dangling);
+    let mut unreachable = vec![(ObjectType::Tree, tree), (ObjectType::Blob, blob)];
+    unreachable.sort_by_key(|(_, oid)| *oid);
+    assert_eq!(report.unreachable, unreachable);
+
+    // Objects only the stash's log keeps alive are reachable
+    fs::write(temp_dir.path().join("a.txt"), "stashed\n").unwrap();
+    repo.stash_push(None, &[]).unwrap();
+    let report = repo.fsck(&FsckOptions::default()).unwrap();
+    assert_eq!(report.dangling, dangling);
+}
+
+#[test]
+fn test_fsck_lost_found() {
+    let temp_dir = TempDir::new().unwrap();
+    let (repo, _, _) = commit_twice(&temp_dir, write_a, write_dir_b);
+    let blob = repo.hash_object(b"lost\n", ObjectType::Blob).unwrap();
+    let tree = repo.get_commit(repo.get_oid_hash(HEAD).unwrap()).unwrap().tree;
+    let commit = repo.write_commit(tree, &[], "Lost commit").unwrap();
+
+    let options = FsckOptions {
+        lost_found: true,
+        ..Default::default()
+    };
+    repo.fsck(&options).unwrap();
+
+    let lost_found = Path::new(&repo.gitdir).join(LOST_FOUND_DIR);
+    assert_eq!(
+        fs::read_to_string(lost_found.join("commit").join(commit.to_string())).unwrap(),
+        format!("{}\n", commit)
+    );
+    assert_eq!(
+        fs::read(lost_found.join("other").join(blob.to_string())).unwrap(),
+        b"lost\n"
+    );
+}
+
+#[test]
+fn test_fsck_hash_mismatch() {
+    let temp_dir = TempDir::new().unwrap();
+    let (repo, _, _) = commit_twice(&temp_dir, write_a, write_dir_b);
+    let blob = repo.hash_object(b"b\n", ObjectType::Blob).unwrap();
+
+    // Valid content, but not the one the id was computed from. Object
+    // files are read-only, so it replaces the file.
+    fs::remove_file(object_file(&repo, blob)).unwrap();
+    fs::write(object_file(&repo, blob), b"blob 2\0c\n").unwrap();
+
+    let report = repo.fsck(&FsckOptions::default()).unwrap();
+    assert_eq!(
+        report.problems,
+        vec![FsckProblem::HashMismatch {
+            oid: blob,
+            actual: repo.object_format.hash(b"blob 2\0c\n"),
+        }]
+    );
+
+    // Reachable blobs are not read without the full check
+    let options = FsckOptions {
+        connectivity_only: true,
+        ..Default::default()
+    };
+    assert_eq!(repo.fsck(&options).unwrap().problems, vec![]);
+}
+
+#[test]
+fn test_fsck_missing_and_corrupt_objects() {
+    let temp_dir = TempDir::new().unwrap();
+    let (repo, _, _) = commit_twice(&temp_dir, write_a, write_dir_b);
+    let blob = repo.hash_object(b"b\n", ObjectType::Blob).unwrap();
+    let head = repo.get_commit(repo.get_oid_hash(HEAD).unwrap()).unwrap();
+    let dir_tree = repo
+        .get_tree_data(head.tree)
+        .unwrap()
+        .into_iter()
+        .find(|(_, name, _, _)| name == "dir")
+        .unwrap()
+        .2;
+
+    fs::remove_file(object_file(&repo, blob)).unwrap();
+    let missing = FsckProblem::BrokenLink {
+        from: dir_tree,
+        to: blob,
+        expected: ObjectType::Blob,
+    };
+    for connectivity_only in [false, true] {
+        let options = FsckOptions {
+            connectivity_only,
+            ..Default::default()
+        };
+        assert_eq!(repo.fsck(&options).unwrap().problems, vec![missing.clone()]);
+    }
+
+    // An object that does not parse is reported with its id
+    let garbage = repo.object_format.hash(b"garbage");
+    repo.odb.write(garbage, b"garbage").unwrap();
+    let report = repo.fsck(&FsckOptions::default()).unwrap();
+    assert!(report.problems.contains(&missing));
+    assert!(report.problems.iter().any(|problem| matches!(
+        problem,
+        FsckProblem::Corrupt { oid, .. } if *oid == garbage
+    )));
+}
+
+#[test]
+fn test_fsck_bad_refs() {
+    let temp_dir = TempDir::new().unwrap();
+    let (repo, _, _) = commit_twice(&temp_dir, write_a, write_dir_b);
+    let blob = repo.hash_object(b"b\n", ObjectType::Blob).unwrap();
+    let refs = Path::new(&repo.gitdir).join("refs");
+
+    fs::write(refs.join("heads/garbage"), "not a hash\n").unwrap();
+    fs::write(refs.join("heads/blob"), format!("{}\n", blob)).unwrap();
+    let missing: Oid = "b".repeat(40).parse().unwrap();
+    fs::write(refs.join("tags/missing"), format!("{}\n", missing)).unwrap();
+    // Tags may point at any object
+    fs::write(refs.join("tags/blob"), format!("{}\n", blob)).unwrap();
+
+    let report = repo.fsck(&FsckOptions::default()).unwrap();
+    let mut names: Vec<&str> = report
+        .problems
+        .iter()
+        .map(|problem| match problem {
+            FsckProblem::BadRef { name, .. } => name.as_str(),
+            problem => panic!("unexpected {:?}", problem),
+        })
+        .collect();
+    names.sort();
+    assert_eq!(
+        names,
+        vec!["refs/heads/blob", "refs/heads/garbage", "refs/tags/missing"]
+    );
+    assert!(report.problems.contains(&FsckProblem::BadRef {
+        name: "refs/heads/blob".to_string(),
+        reason: format!("points to blob {}, not a commit", blob),
+    }));
+
+    // HEAD must be a branch
+    fs::write(Path::new(&repo.gitdir).join(HEAD), "ref: refs/tags/blob\n").unwrap();
+    let report = repo.fsck(&FsckOptions::default()).unwrap();
+    assert!(report.problems.contains(&FsckProblem::BadRef {
+        name: HEAD.to_string(),
+        reason: "points to refs/tags/blob, not a branch".to_string(),
+    }));
+}
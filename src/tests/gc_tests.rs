@@ -0,0 +1,139 @@
+use crate::gc::{GcOptions, parse_expire};
+use std::time::{Duration, SystemTime};
+
+fn gc_now(dry_run: bool) -> GcOptions {
+    GcOptions {
+        dry_run,
+        expire: Some("now".to_string()),
+    }
+}
+
+// Moves the modification time of a loose object back by `age`
+fn age_object(repo: &Repository, oid: Oid, age: Duration) {
+    let hex = oid.to_string();
+    let (dir, file) = hex.split_at(2);
+    let path = Path::new(&repo.gitdir).join("objects").join(dir).join(file);
+    fs::File::open(path)
+        .unwrap()
+        .set_modified(SystemTime::now() - age)
+        .unwrap();
+}
+
+#[test]
+fn test_parse_expire() {
+    assert_eq!(parse_expire("now").unwrap(), Some(Duration::ZERO));
+    assert_eq!(parse_expire("never").unwrap(), None);
+    assert_eq!(
+        parse_expire("2.weeks.ago").unwrap(),
+        Some(Duration::from_secs(14 * 24 * 60 * 60))
+    );
+    assert_eq!(
+        parse_expire("1 hour ago").unwrap(),
+        Some(Duration::from_secs(60 * 60))
+    );
+    for invalid in ["", "yesterday", "2.fortnights.ago", "two.weeks.ago", "2.weeks"] {
+        assert!(matches!(parse_expire(invalid), Err(Error::Other(_))));
+    }
+}
+
+#[test]
+fn test_gc_prunes_unreachable_objects() {
+    let temp_dir = TempDir::new().unwrap();
+    let (repo, _, _) = commit_twice(&temp_dir, write_a, write_dir_b);
+    let reachable = repo.odb.oids().unwrap();
+
+    let blob = repo.hash_object(b"lost\n", ObjectType::Blob).unwrap();
+    let tree = repo
+        .create_tree_from_paths(&BTreeMap::from([("lost.txt".to_string(), blob)]))
+        .unwrap();
+    let commit = repo.write_commit(tree, &[], "Lost commit").unwrap();
+    let mut lost = vec![blob, tree, commit];
+    lost.sort();
+    let size: u64 = lost.iter().map(|oid| repo.odb.stat(*oid).unwrap().size).sum();
+
+    // A dry run only lists them
+    let report = repo.gc(&gc_now(true)).unwrap();
+    let mut pruned = report.pruned.clone();
+    pruned.sort();
+    assert_eq!(pruned, lost);
+    assert_eq!(report.bytes, size);
+    assert!(repo.odb.exists(commit).unwrap());
+
+    let report = repo.gc(&gc_now(false)).unwrap();
+    assert_eq!(report.pruned.len(), 3);
+    assert_eq!(repo.odb.oids().unwrap(), reachable);
+    assert_eq!(repo.fsck(&Default::default()).unwrap().problems, vec![]);
+
+    assert!(repo.gc(&gc_now(false)).unwrap().pruned.is_empty());
+}
+
+#[test]
+fn test_gc_keeps_recent_objects() {
+    let temp_dir = TempDir::new().unwrap();
+    let (repo, _, _) = commit_twice(&temp_dir, write_a, write_dir_b);
+    let recent = repo.hash_object(b"recent\n", ObjectType::Blob).unwrap();
+    let old = repo.hash_object(b"old\n", ObjectType::Blob).unwrap();
+    age_object(&repo, old, Duration::from_secs(15 * 24 * 60 * 60));
+
+    // Two weeks by default
+    let report = repo.gc(&GcOptions::default()).unwrap();
+    assert_eq!(report.pruned, vec![old]);
+    assert_eq!(report.recent, vec![recent]);
+
+    // gc.pruneExpire replaces the default, and --prune replaces both
+    repo.set_config("gc.pruneExpire", "never").unwrap();
+    assert!(repo.gc(&GcOptions::default()).unwrap().pruned.is_empty());
+    assert_eq!(repo.gc(&gc_now(false)).unwrap().pruned, vec![recent]);
+
+    repo.set_config("gc.pruneExpire", "soon").unwrap();
+    assert!(repo.gc(&GcOptions::default()).is_err());
+}
+
+#[test]
+fn test_gc_keeps_reflog_stash_and_merge_head() {
+    let temp_dir = TempDir::new().unwrap();
+    let (repo, _, _) = commit_twice(&temp_dir, write_a, write_dir_b);
+    let head = repo.get_oid_hash(HEAD).unwrap();
+    let first = repo.get_commit(head).unwrap().parents[0];
+
+    // Only HEAD's log still has the second commit
+    repo.reset(first).unwrap();
+    repo.append_reflog(HEAD, head, first, "reset: moving to first")
+        .unwrap();
+    // Only the stash has the stashed change
+    fs::write(temp_dir.path().join("a.txt"), "stashed\n").unwrap();
+    let stash = repo.stash_push(None, &[]).unwrap();
+    // Only MERGE_HEAD has this commit
+    let tree = repo.get_commit(first).unwrap().tree;
+    let merging = repo.write_commit(tree, &[], "Merging").unwrap();
+    fs::write(
+        Path::new(&repo.gitdir).join(MERGE_HEAD),
+        format!("{}\n", merging),
+    )
+    .unwrap();
+
+    let before = repo.odb.oids().unwrap();
+    assert!(repo.gc(&gc_now(false)).unwrap().pruned.is_empty());
+    assert_eq!(repo.odb.oids().unwrap(), before);
+    for oid in [head, stash, merging] {
+        assert!(repo.odb.exists(oid).unwrap());
+    }
+}
+
+#[test]
+fn test_gc_fails_on_missing_objects() {
+    let temp_dir = TempDir::new().unwrap();
+    let (repo, _, _) = commit_twice(&temp_dir, write_a, write_dir_b);
+    let blob = repo.hash_object(b"b\n", ObjectType::Blob).unwrap();
+    let lost = repo.hash_object(b"lost\n", ObjectType::Blob).unwrap();
+
+    // A missing commit could hide objects still in use, so nothing goes
+    let head = repo.get_oid_hash(HEAD).unwrap();
+    repo.odb.delete(head).unwrap();
+    assert!(matches!(
+        repo.gc(&gc_now(false)),
+        Err(Error::NotFound(_))
+    ));
+    assert!(repo.odb.exists(lost).unwrap());
+    assert!(repo.odb.exists(blob).unwrap());
+}
@@ -0,0 +1,43 @@
+// Helpers shared by the suites, included in each module that runs them
+// next to the OBJECT_FORMAT its repositories use
+
+// Initializes a repository in `path` with the suite's object format
+fn init_repo(path: &str) -> crate::repository::Repository {
+    let options = crate::repository::InitOptions {
+        object_format: OBJECT_FORMAT,
+        ..Default::default()
+    };
+    crate::repository::Repository::init_at(path, &options).unwrap()
+}
+
+// Writes `content` to `path` in `dir`, creating its parent directories
+fn write_file(dir: &std::path::Path, path: &str, content: impl AsRef<[u8]>) {
+    let path = dir.join(path);
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(path, content).unwrap();
+}
+
+// Initializes a repository in `temp_dir` and commits what `write` put in
+// the worktree as "First"
+fn commit_once(
+    temp_dir: &tempfile::TempDir,
+    write: impl FnOnce(&std::path::Path),
+) -> (crate::repository::Repository, crate::Oid) {
+    let repo = init_repo(temp_dir.path().to_str().unwrap());
+    write(temp_dir.path());
+    let commit = repo.create_commit("First").unwrap();
+    (repo, commit)
+}
+
+// Same as `commit_once`, then commits again as "Second" after `second`
+// changed the worktree. Returns (repo, first_commit, second_commit).
+fn commit_twice(
+    temp_dir: &tempfile::TempDir,
+    first: impl FnOnce(&std::path::Path),
+    second: impl FnOnce(&std::path::Path),
+) -> (crate::repository::Repository, crate::Oid, crate::Oid) {
+    let (repo, first) = commit_once(temp_dir, first);
+    second(temp_dir.path());
+    let second = repo.create_commit("Second").unwrap();
+    (repo, first, second)
+}
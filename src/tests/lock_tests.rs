@@ -0,0 +1,173 @@
+use crate::lock::{DEFAULT_LOCK_TIMEOUT, LockFile, LockOptions, STALE_LOCK_AGE};
+
+fn no_wait() -> LockOptions {
+    LockOptions {
+        timeout: Some(Duration::ZERO),
+        ..Default::default()
+    }
+}
+
+// The lock file of a process that has exited
+fn write_dead_owner_lock(lock_path: &Path) {
+    let mut child = std::process::Command::new("true").spawn().unwrap();
+    let pid = child.id();
+    child.wait().unwrap();
+    fs::write(lock_path, format!("pid {}\n", pid)).unwrap();
+}
+
+#[test]
+fn test_lock_file_commit() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("HEAD");
+    fs::write(&path, "old\n").unwrap();
+
+    let lock = LockFile::acquire(&path, &no_wait()).unwrap();
+    assert!(temp_dir.path().join("HEAD.lock").is_file());
+    // Only one holder at a time
+    assert!(matches!(
+        LockFile::acquire(&path, &no_wait()),
+        Err(Error::Locked(lock_path)) if lock_path.ends_with("HEAD.lock")
+    ));
+
+    lock.commit(b"new\n", true).unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), "new\n");
+    assert!(!temp_dir.path().join("HEAD.lock").exists());
+
+    // Dropped without a commit, the file is left as it was
+    drop(LockFile::acquire(&path, &no_wait()).unwrap());
+    assert_eq!(fs::read_to_string(&path).unwrap(), "new\n");
+    assert!(!temp_dir.path().join("HEAD.lock").exists());
+}
+
+#[test]
+fn test_lock_released_on_panic() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("HEAD");
+
+    let result = std::panic::catch_unwind(|| {
+        let _lock = LockFile::acquire(&path, &no_wait()).unwrap();
+        panic!("while holding the lock");
+    });
+    assert!(result.is_err());
+    assert!(!temp_dir.path().join("HEAD.lock").exists());
+}
+
+#[test]
+fn test_lock_waits_for_holder() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("HEAD");
+
+    let lock = LockFile::acquire(&path, &no_wait()).unwrap();
+    let holder = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(50));
+        drop(lock);
+    });
+    let options = LockOptions {
+        timeout: Some(Duration::from_secs(10)),
+        ..Default::default()
+    };
+    LockFile::acquire(&path, &options).unwrap();
+    holder.join().unwrap();
+}
+
+#[test]
+fn test_lock_options_from_config() {
+    assert_eq!(LockOptions::from_config(None).timeout, Some(DEFAULT_LOCK_TIMEOUT));
+    assert_eq!(
+        LockOptions::from_config(Some("2000")).timeout,
+        Some(Duration::from_secs(2))
+    );
+    assert_eq!(LockOptions::from_config(Some("-1")).timeout, None);
+    assert_eq!(
+        LockOptions::from_config(Some("soon")).timeout,
+        Some(DEFAULT_LOCK_TIMEOUT)
+    );
+}
+
+#[test]
+fn test_stale_ref_lock() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut repo = Repository::new(temp_dir.path().to_str().unwrap());
+    repo.init().unwrap();
+    fs::write(temp_dir.path().join("a.txt"), "a\n").unwrap();
+    let commit = repo.create_commit("First commit").unwrap();
+
+    // Left by a process that was killed while updating the branch
+    let branch = Path::new(&repo.gitdir).join("refs/heads/master");
+    let lock_path = Path::new(&repo.gitdir).join("refs/heads/master.lock");
+    write_dead_owner_lock(&lock_path);
+    repo.locks.timeout = Some(Duration::ZERO);
+
+    // Lock files are not refs
+    let names = repo.ref_names("").unwrap();
+    assert!(names.iter().all(|name| !name.ends_with(".lock")), "{:?}", names);
+
+    let tree = repo.get_commit(commit).unwrap().tree;
+    let second = repo.write_commit(tree, &[commit], "Second commit").unwrap();
+    assert!(matches!(
+        repo.set_ref(HEAD, RefValue::Direct(second), true),
+        Err(Error::Locked(_))
+    ));
+    assert_eq!(fs::read_to_string(&branch).unwrap().trim(), commit.to_string());
+    // The worktree has a lock of its own
+    repo.lock_worktree().unwrap();
+
+    repo.locks.break_stale = true;
+    repo.set_ref(HEAD, RefValue::Direct(second), true).unwrap();
+    assert_eq!(repo.get_oid_hash(HEAD).unwrap(), second);
+    assert!(!lock_path.exists());
+}
+
+#[test]
+fn test_worktree_lock() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut repo = Repository::new(temp_dir.path().to_str().unwrap());
+    repo.init().unwrap();
+    repo.locks.timeout = Some(Duration::ZERO);
+
+    let lock = repo.lock_worktree().unwrap();
+    assert!(Path::new(&repo.gitdir).join("index.lock").is_file());
+    assert!(matches!(repo.lock_worktree(), Err(Error::Locked(_))));
+    drop(lock);
+    repo.lock_worktree().unwrap();
+}
+
+#[test]
+fn test_break_stale_locks_only() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("HEAD");
+    let lock_path = temp_dir.path().join("HEAD.lock");
+    let options = LockOptions {
+        break_stale: true,
+        ..no_wait()
+    };
+
+    // A lock another holder still has is not broken, and names its owner
+    let lock = LockFile::acquire(&path, &no_wait()).unwrap();
+    assert_eq!(
+        fs::read_to_string(&lock_path).unwrap(),
+        format!("pid {}\n", std::process::id())
+    );
+    assert!(matches!(LockFile::acquire(&path, &options), Err(Error::Locked(_))));
+    lock.commit(b"new\n", false).unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), "new\n");
+
+    // Nor is a recent one without an owner, which may be being committed
+    fs::write(&lock_path, "0123abcd\n").unwrap();
+    assert!(matches!(LockFile::acquire(&path, &options), Err(Error::Locked(_))));
+
+    // Unless it is old enough
+    fs::File::options()
+        .write(true)
+        .open(&lock_path)
+        .unwrap()
+        .set_modified(SystemTime::now() - STALE_LOCK_AGE - Duration::from_secs(1))
+        .unwrap();
+    drop(LockFile::acquire(&path, &options).unwrap());
+
+    // A lock whose owner has exited is broken at once
+    write_dead_owner_lock(&lock_path);
+    assert!(matches!(LockFile::acquire(&path, &no_wait()), Err(Error::Locked(_))));
+    drop(LockFile::acquire(&path, &options).unwrap());
+    assert!(!lock_path.exists());
+}
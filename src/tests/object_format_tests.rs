@@ -0,0 +1,248 @@
+// Every test below runs once per object format
+const OBJECT_FORMATS: [HashAlgorithm; 2] = [HashAlgorithm::Sha1, HashAlgorithm::Sha256];
+
+fn init_with_format(temp_dir: &TempDir, object_format: HashAlgorithm) -> Repository {
+    let options = InitOptions {
+        object_format,
+        ..Default::default()
+    };
+    Repository::init_at(temp_dir.path().to_str().unwrap(), &options).unwrap()
+}
+
+#[test]
+fn test_object_format_hashes_objects() {
+    for (format, empty_blob) in [
+        (
+            HashAlgorithm::Sha1,
+            "e69de29bb2d1d6434b8b29ae775ad8c2e48c5391",
+        ),
+        (
+            HashAlgorithm::Sha256,
+            "473a0f4c3be8a93681a267e3b1e9a7dcda1185436fe141f7749120a303721813",
+        ),
+    ] {
+        let temp_dir = TempDir::new().unwrap();
+        let repo = init_with_format(&temp_dir, format);
+        assert_eq!(repo.object_format, format);
+
+        let oid = repo.hash_object(b"", ObjectType::Blob).unwrap();
+        assert_eq!(oid.to_string(), empty_blob);
+        assert_eq!(oid.algorithm(), format);
+        assert_eq!(repo.get_object(oid).unwrap(), (ObjectType::Blob, Vec::new()));
+
+        // Reopened, the repository keeps its format
+        let reopened = Repository::open(temp_dir.path().to_str().unwrap()).unwrap();
+        assert_eq!(reopened.object_format, format);
+        assert_eq!(reopened.hash_object(b"", ObjectType::Blob).unwrap(), oid);
+    }
+}
+
+#[test]
+fn test_object_format_commits_and_trees() {
+    for format in OBJECT_FORMATS {
+        let temp_dir = TempDir::new().unwrap();
+        let repo = init_with_format(&temp_dir, format);
+        fs::write(temp_dir.path().join("a.txt"), "a\n").unwrap();
+        fs::create_dir_all(temp_dir.path().join("dir/sub")).unwrap();
+        fs::write(temp_dir.path().join("dir/sub/b.txt"), "b\n").unwrap();
+        let first = repo.create_commit("First commit").unwrap();
+        fs::write(temp_dir.path().join("a.txt"), "changed\n").unwrap();
+        let second = repo.create_commit("Second commit").unwrap();
+
+        // Trees hold ids of the repository's length
+        let commit = repo.get_commit(second).unwrap();
+        assert_eq!(commit.parents, vec![first]);
+        let entries = repo.get_tree_data(commit.tree).unwrap();
+        let names: Vec<&str> = entries.iter().map(|(_, name, _, _)| name.as_str()).collect();
+        assert_eq!(names, vec!["a.txt", "dir"]);
+        assert!(entries.iter().all(|(_, _, oid, _)| oid.algorithm() == format));
+        let dir = entries[1].2;
+        assert_eq!(repo.get_tree_data(dir).unwrap()[0].1, "sub");
+
+        assert_eq!(repo.get_oid_hash(&second.to_string()).unwrap(), second);
+        assert_eq!(repo.get_oid_hash("master").unwrap(), second);
+        let log: Vec<Oid> = repo.log().unwrap().iter().map(|entry| entry.commit.oid).collect();
+        assert_eq!(log, vec![second, first]);
+        let show = repo.show(second).unwrap();
+        assert!(String::from_utf8_lossy(&show.diffs[0].patch).contains("+changed"));
+
+        repo.checkout(&first.to_string()).unwrap();
+        assert_eq!(
+            fs::read_to_string(temp_dir.path().join("a.txt")).unwrap(),
+            "a\n"
+        );
+        assert_eq!(
+            fs::read_to_string(temp_dir.path().join("dir/sub/b.txt")).unwrap(),
+            "b\n"
+        );
+    }
+}
+
+#[test]
+fn test_object_format_merge_and_rebase() {
+    for format in OBJECT_FORMATS {
+        let temp_dir = TempDir::new().unwrap();
+        let repo = init_with_format(&temp_dir, format);
+        fs::write(temp_dir.path().join("shared.txt"), "one\ntwo\nthree\n").unwrap();
+        let base = repo.create_commit("Base").unwrap();
+        repo.create_branch("feature", Some(base)).unwrap();
+        fs::write(temp_dir.path().join("master.txt"), "master\n").unwrap();
+        let master = repo.create_commit("Master change").unwrap();
+
+        repo.checkout("feature").unwrap();
+        fs::write(temp_dir.path().join("feature.txt"), "feature\n").unwrap();
+        repo.create_commit("Feature change").unwrap();
+
+        let outcome = repo.rebase("master").unwrap();
+        assert_eq!(outcome.onto, master);
+        assert_eq!(outcome.stopped, None);
+        let head = repo.get_commit(repo.get_oid_hash(HEAD).unwrap()).unwrap();
+        assert_eq!(head.parents, vec![master]);
+
+        repo.checkout("master").unwrap();
+        assert!(matches!(
+            repo.merge("feature").unwrap(),
+            MergeOutcome::FastForward
+        ));
+        assert!(temp_dir.path().join("feature.txt").exists());
+    }
+}
+
+#[test]
+fn test_object_format_stash_fsck_and_gc() {
+    for format in OBJECT_FORMATS {
+        let temp_dir = TempDir::new().unwrap();
+        let repo = init_with_format(&temp_dir, format);
+        fs::write(temp_dir.path().join("a.txt"), "a\n").unwrap();
+        repo.create_commit("First commit").unwrap();
+
+        fs::write(temp_dir.path().join("a.txt"), "stashed\n").unwrap();
+        repo.stash_push(None, &[]).unwrap();
+        let log = repo.read_reflog("refs/stash").unwrap();
+        assert_eq!(log[0].old, Oid::zero(format));
+        assert_eq!(log[0].old.to_string().len(), format.oid_len() * 2);
+        repo.stash_pop(None).unwrap();
+        assert_eq!(
+            fs::read_to_string(temp_dir.path().join("a.txt")).unwrap(),
+            "stashed\n"
+        );
+
+        let lost = repo.hash_object(b"lost\n", ObjectType::Blob).unwrap();
+        let report = repo.fsck(&Default::default()).unwrap();
+        assert_eq!(report.problems, vec![]);
+        assert!(report.dangling.contains(&(ObjectType::Blob, lost)));
+
+        let options = GcOptions {
+            expire: Some("now".to_string()),
+            ..Default::default()
+        };
+        assert!(repo.gc(&options).unwrap().pruned.contains(&lost));
+        assert_eq!(repo.fsck(&Default::default()).unwrap().problems, vec![]);
+    }
+}
+
+#[test]
+fn test_object_format_rejects_other_ids() {
+    for format in OBJECT_FORMATS {
+        let temp_dir = TempDir::new().unwrap();
+        let repo = init_with_format(&temp_dir, format);
+        fs::write(temp_dir.path().join("a.txt"), "a\n").unwrap();
+        let commit = repo.create_commit("First commit").unwrap();
+
+        let other = match format {
+            HashAlgorithm::Sha1 => HashAlgorithm::Sha256,
+            HashAlgorithm::Sha256 => HashAlgorithm::Sha1,
+        };
+        let other_id = other.hash(b"blob 0\0").to_string();
+        assert!(matches!(repo.parse_oid(&other_id), Err(Error::InvalidRef(_))));
+        assert!(matches!(
+            repo.get_oid_hash(&other_id),
+            Err(Error::InvalidRef(_))
+        ));
+
+        // A ref holding an id of the other format is corrupt
+        let branch = Path::new(&repo.gitdir).join("refs/heads/other");
+        fs::write(&branch, format!("{}\n", other_id)).unwrap();
+        assert!(matches!(
+            repo.get_ref("refs/heads/other", false),
+            Err(Error::Corrupt(_))
+        ));
+        assert_eq!(repo.get_oid_hash("master").unwrap(), commit);
+
+        // So is one in a reflog or in the state of a rebase or cherry-pick
+        let zero = Oid::zero(format);
+        let log = Path::new(&repo.gitdir).join("logs/refs/heads/other");
+        fs::create_dir_all(log.parent().unwrap()).unwrap();
+        fs::write(&log, format!("{} {} 0 +0000\tcommit\n", zero, other_id)).unwrap();
+        assert!(matches!(
+            repo.read_reflog("refs/heads/other"),
+            Err(Error::Corrupt(_))
+        ));
+        let rebase_dir = Path::new(&repo.gitdir).join(REBASE_DIR);
+        fs::create_dir(&rebase_dir).unwrap();
+        for (name, content) in [
+            ("head-name", "refs/heads/master".to_string()),
+            ("onto", other_id.clone()),
+            ("orig-head", commit.to_string()),
+            ("base", commit.to_string()),
+            ("git-rebase-todo", String::new()),
+        ] {
+            fs::write(rebase_dir.join(name), content).unwrap();
+        }
+        assert!(matches!(RebaseState::load(&repo), Err(Error::Corrupt(_))));
+        let sequencer_dir = Path::new(&repo.gitdir).join(SEQUENCER_DIR);
+        fs::create_dir(&sequencer_dir).unwrap();
+        fs::write(sequencer_dir.join("todo"), format!("pick {}\n", other_id)).unwrap();
+        fs::write(sequencer_dir.join("head"), commit.to_string()).unwrap();
+        assert!(matches!(SequencerState::load(&repo), Err(Error::Corrupt(_))));
+
+        // Loose files named by an id of the other format are not objects
+        let (dir, file) = other_id.split_at(2);
+        let objects = Path::new(&repo.gitdir).join("objects").join(dir);
+        fs::create_dir_all(&objects).unwrap();
+        fs::write(objects.join(file), "blob 0\0").unwrap();
+        let oids = repo.odb.oids().unwrap();
+        assert!(!oids.is_empty());
+        assert!(oids.iter().all(|oid| oid.algorithm() == format));
+    }
+}
+
+#[test]
+fn test_object_format_in_memory() {
+    for format in OBJECT_FORMATS {
+        let repo = Repository::in_memory().with_object_format(format);
+        let blob = repo.hash_object(b"a\n", ObjectType::Blob).unwrap();
+        let tree = repo
+            .create_tree_from_paths(&BTreeMap::from([("dir/a.txt".to_string(), blob)]))
+            .unwrap();
+        assert_eq!(tree.algorithm(), format);
+        let dir = repo.get_tree_data(tree).unwrap()[0].2;
+        assert_eq!(repo.get_tree_data(dir).unwrap()[0].2, blob);
+    }
+}
+
+#[test]
+fn test_object_format_config() {
+    let temp_dir = TempDir::new().unwrap();
+    let repo = init_with_format(&temp_dir, HashAlgorithm::Sha256);
+    assert_eq!(
+        repo.get_config("extensions.objectFormat").as_deref(),
+        Some("sha256")
+    );
+    assert_eq!(
+        repo.get_config("core.repositoryFormatVersion").as_deref(),
+        Some("1")
+    );
+
+    // SHA-1 repositories are left as they always were
+    let sha1_dir = TempDir::new().unwrap();
+    let repo = init_with_format(&sha1_dir, HashAlgorithm::Sha1);
+    assert_eq!(repo.get_config("extensions.objectFormat"), None);
+
+    // A format bgit does not know cannot be opened
+    repo.set_config("extensions.objectFormat", "sha512").unwrap();
+    assert!(matches!(
+        Repository::open(sha1_dir.path().to_str().unwrap()),
+        Err(Error::Other(_))
+    ));
+}
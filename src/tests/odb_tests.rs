@@ -0,0 +1,175 @@
+use crate::odb::{LooseObjectDatabase, MemoryObjectDatabase, ObjectDatabase};
+use std::collections::BTreeMap;
+
+// The same checks against any backend
+fn check_object_database(odb: &dyn ObjectDatabase) {
+    let first = OBJECT_FORMAT.hash(b"blob 6\0first\n");
+    let second = OBJECT_FORMAT.hash(b"blob 7\0second\n");
+    assert_eq!(odb.oids().unwrap(), vec![]);
+    assert!(!odb.exists(first).unwrap());
+    assert!(matches!(odb.read(first), Err(Error::NotFound(oid)) if oid == first));
+
+    odb.write(second, b"blob 7\0second\n").unwrap();
+    odb.write(first, b"blob 6\0first\n").unwrap();
+    // Writing an object twice keeps one copy
+    odb.write(first, b"blob 6\0first\n").unwrap();
+
+    assert!(odb.exists(first).unwrap());
+    assert_eq!(odb.read(first).unwrap(), b"blob 6\0first\n");
+    let mut expected = vec![first, second];
+    expected.sort();
+    assert_eq!(odb.oids().unwrap(), expected);
+
+    // The size counts the header, as stored
+    assert_eq!(odb.stat(first).unwrap().size, 13);
+    odb.delete(second).unwrap();
+    assert_eq!(odb.oids().unwrap(), vec![first]);
+    assert!(matches!(odb.stat(second), Err(Error::NotFound(_))));
+    assert!(matches!(odb.delete(second), Err(Error::NotFound(_))));
+}
+
+#[test]
+fn test_loose_object_database() {
+    let temp_dir = TempDir::new().unwrap();
+    let objects = temp_dir.path().join("objects");
+    check_object_database(&LooseObjectDatabase::new(&objects));
+
+    // Other directories, as a pack directory would be, hold no loose objects
+    fs::create_dir_all(objects.join("pack")).unwrap();
+    fs::write(objects.join("pack").join("a".repeat(38)), "").unwrap();
+    assert_eq!(LooseObjectDatabase::new(&objects).oids().unwrap().len(), 1);
+}
+
+#[test]
+fn test_loose_objects_written_atomically() {
+    let temp_dir = TempDir::new().unwrap();
+    let odb = LooseObjectDatabase::new(temp_dir.path()).with_fsync(true);
+    let oid = OBJECT_FORMAT.hash(b"blob 6\0first\n");
+    odb.write(oid, b"blob 6\0first\n").unwrap();
+
+    // Only the object is left, read-only
+    let hex = oid.to_string();
+    let dir = temp_dir.path().join(&hex[..2]);
+    let names: Vec<_> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    assert_eq!(names, vec![&hex[2..]]);
+    assert!(fs::metadata(dir.join(&hex[2..])).unwrap().permissions().readonly());
+
+    // An object that exists is not written again, only made recent
+    let old = std::time::SystemTime::now() - std::time::Duration::from_secs(3600);
+    fs::File::open(dir.join(&hex[2..]))
+        .unwrap()
+        .set_modified(old)
+        .unwrap();
+    odb.write(oid, b"blob 6\0other\n").unwrap();
+    assert_eq!(odb.read(oid).unwrap(), b"blob 6\0first\n");
+    assert!(odb.stat(oid).unwrap().modified > old);
+}
+
+#[test]
+fn test_memory_object_database() {
+    check_object_database(&MemoryObjectDatabase::new());
+}
+
+#[test]
+fn test_repository_objects_in_loose_files() {
+    let temp_dir = TempDir::new().unwrap();
+    let repo = Repository::new(temp_dir.path().to_str().unwrap());
+    repo.init().unwrap();
+
+    let blob = repo.hash_object(b"content\n", ObjectType::Blob).unwrap();
+    assert_eq!(repo.odb.oids().unwrap(), vec![blob]);
+    let hex = blob.to_string();
+    let (dir, file) = hex.split_at(2);
+    assert!(temp_dir.path().join(GIT_DIR).join("objects").join(dir).join(file).is_file());
+}
+
+#[test]
+fn test_merge_trees_in_memory() {
+    let repo = Repository::in_memory();
+    let tree = |files: &[(&str, &str)]| {
+        let files: BTreeMap<String, Oid> = files
+            .iter()
+            .map(|(path, content)| {
+                let blob = repo.hash_object(content.as_bytes(), ObjectType::Blob).unwrap();
+                (path.to_string(), blob)
+            })
+            .collect();
+        repo.create_tree_from_paths(&files).unwrap()
+    };
+
+    let base = tree(&[("a.txt", "one\ntwo\nthree\n"), ("dir/b.txt", "b\n")]);
+    let ours = tree(&[("a.txt", "ONE\ntwo\nthree\n"), ("dir/b.txt", "b\n")]);
+    let theirs = tree(&[("a.txt", "one\ntwo\nTHREE\n"), ("dir/c.txt", "c\n")]);
+
+    let differ = Differ::new(&repo);
+    let changes = differ.changed_files(Some(base), Some(theirs)).unwrap();
+    let paths: Vec<&str> = changes.iter().map(|change| change.new_path.as_str()).collect();
+    assert_eq!(paths, vec!["a.txt", "dir/b.txt", "dir/c.txt"]);
+
+    let (merged, conflicts) = differ
+        .merge_trees_with_conflicts(ours, theirs, Some(base))
+        .unwrap();
+    assert!(conflicts.is_empty());
+    assert_eq!(merged["a.txt"], Ok(b"ONE\ntwo\nTHREE\n".to_vec()));
+    assert_eq!(merged["dir/c.txt"], Ok(b"c\n".to_vec()));
+    assert!(!merged.contains_key("dir/b.txt"));
+
+    // Nothing but objects is available without a directory
+    assert!(repo.odb.exists(ours).unwrap());
+    assert!(matches!(repo.create_commit("First"), Err(Error::BareRepository)));
+}
+
+#[test]
+fn test_in_memory_touches_no_directory() {
+    let repo = Repository::in_memory();
+    let blob = |content: &str| repo.hash_object(content.as_bytes(), ObjectType::Blob).unwrap();
+    let tree = |content: &str| {
+        repo.create_tree_from_paths(&BTreeMap::from([("a.txt".to_string(), blob(content))]))
+            .unwrap()
+    };
+    let base = tree("one\ntwo\n");
+    let ours = tree("ONE\ntwo\n");
+    let theirs = tree("uno\ntwo\n");
+
+    // Patches and conflicting merges only need the objects
+    let differ = Differ::new(&repo);
+    let patch = String::from_utf8(differ.diff_trees(Some(base), Some(ours)).unwrap()).unwrap();
+    assert!(patch.contains("-one\n+ONE\n"), "{}", patch);
+    let (merged, conflicts) = differ
+        .merge_trees_with_conflicts(ours, theirs, Some(base))
+        .unwrap();
+    assert_eq!(conflicts, vec!["a.txt".to_string()]);
+    assert_eq!(
+        merged["a.txt"],
+        Ok(b"<<<<<<< HEAD\nONE\n||||||| BASE\none\n=======\nuno\n>>>>>>> MERGE_HEAD\ntwo\n".to_vec())
+    );
+
+    // Refs, the config and their logs have nowhere to be
+    let in_memory = |result: Result<(), Error>| matches!(result, Err(Error::InMemory));
+    assert!(in_memory(repo.get_ref(HEAD, true).map(|_| ())));
+    assert!(in_memory(repo.set_ref(HEAD, RefValue::Direct(ours), false)));
+    assert!(in_memory(repo.iter_refs("").map(|_| ())));
+    assert!(in_memory(repo.config().map(|_| ())));
+    assert!(in_memory(repo.set_config("user.name", "A U Thor")));
+    assert!(in_memory(repo.read_reflog(HEAD).map(|_| ())));
+    assert!(in_memory(repo.lock_worktree().map(|_| ())));
+    assert!(in_memory(repo.init()));
+    assert!(in_memory(RebaseState::remove(&repo)));
+    assert!(in_memory(SequencerState::remove(&repo)));
+    assert_eq!(repo.get_config("user.name"), None);
+    for path in [
+        "/HEAD",
+        "/config",
+        "/refs",
+        "/logs",
+        "/objects",
+        "/index.lock",
+        "/rebase-merge",
+        "/sequencer",
+    ] {
+        assert!(!Path::new(path).exists(), "{}", path);
+    }
+}
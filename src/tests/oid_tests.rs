@@ -0,0 +1,63 @@
+#[test]
+fn test_oid_parse_and_display() {
+    let hex = "0123456789abcdef0123456789abcdef01234567";
+    let oid: Oid = hex.parse().unwrap();
+    assert_eq!(oid.to_string(), hex);
+    assert_eq!(oid.short(7), "0123456");
+    assert_eq!(oid.short(100), hex);
+    assert_eq!(format!("{:>42}", oid), format!("  {}", hex));
+
+    // Uppercase parses to the same id, printed lowercase
+    assert_eq!(hex.to_uppercase().parse::<Oid>().unwrap(), oid);
+}
+
+#[test]
+fn test_oid_parse_rejects_non_ids() {
+    for input in ["", "abc", "master", &"g".repeat(40), &"a".repeat(41)] {
+        assert!(
+            matches!(input.parse::<Oid>(), Err(Error::InvalidRef(name)) if name == input),
+            "{:?} parsed as an id",
+            input
+        );
+    }
+}
+
+#[test]
+fn test_oid_bytes() {
+    let oid = HashAlgorithm::Sha1.hash(b"blob 0\0");
+    assert_eq!(oid.to_string(), "e69de29bb2d1d6434b8b29ae775ad8c2e48c5391");
+    assert_eq!(Oid::from_bytes(oid.as_bytes()).unwrap(), oid);
+    assert!(matches!(Oid::from_bytes(&[1, 2, 3]), Err(Error::Corrupt(_))));
+}
+
+#[test]
+fn test_oid_zero_and_order() {
+    let zero = Oid::zero(HashAlgorithm::Sha1);
+    assert!(zero.is_zero());
+    assert_eq!(zero.to_string(), "0".repeat(40));
+    let low: Oid = format!("{}1", "0".repeat(39)).parse().unwrap();
+    let high: Oid = format!("f{}", "0".repeat(39)).parse().unwrap();
+    assert!(!low.is_zero());
+    assert!(zero < low && low < high);
+}
+
+#[test]
+fn test_oid_sha256() {
+    let hex = "473a0f4c3be8a93681a267e3b1e9a7dcda1185436fe141f7749120a303721813";
+    let oid: Oid = hex.parse().unwrap();
+    assert_eq!(oid.algorithm(), HashAlgorithm::Sha256);
+    assert_eq!(oid.to_string(), hex);
+    assert_eq!(oid, HashAlgorithm::Sha256.hash(b"blob 0\0"));
+    assert_eq!(oid.as_bytes().len(), 32);
+    assert_eq!(Oid::from_bytes(oid.as_bytes()).unwrap(), oid);
+
+    // Ids of the two formats never compare equal
+    let zero = Oid::zero(HashAlgorithm::Sha256);
+    assert!(zero.is_zero());
+    assert_eq!(zero.to_string(), "0".repeat(64));
+    assert_ne!(zero, Oid::zero(HashAlgorithm::Sha1));
+    assert!(format!("{}0", hex).parse::<Oid>().is_err());
+
+    assert_eq!(HashAlgorithm::from_name("SHA256"), Some(HashAlgorithm::Sha256));
+    assert_eq!(HashAlgorithm::from_name("md5"), None);
+}
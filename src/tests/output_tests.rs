@@ -0,0 +1,91 @@
+use crate::cli::{Command, GlobalOptions};
+use crate::output::{self, Color, ColorMode};
+
+fn args(values: &[&str]) -> Vec<String> {
+    values.iter().map(|value| value.to_string()).collect()
+}
+
+#[test]
+fn test_color_mode_parse() {
+    assert_eq!(ColorMode::parse("auto"), Ok(ColorMode::Auto));
+    assert_eq!(ColorMode::parse("true"), Ok(ColorMode::Auto));
+    assert_eq!(ColorMode::parse("Always"), Ok(ColorMode::Always));
+    assert_eq!(ColorMode::parse("never"), Ok(ColorMode::Never));
+    assert_eq!(ColorMode::parse("false"), Ok(ColorMode::Never));
+    assert!(ColorMode::parse("sometimes").is_err());
+}
+
+#[test]
+fn test_should_color() {
+    assert!(output::should_color(ColorMode::Always, false));
+    assert!(!output::should_color(ColorMode::Never, true));
+    // Auto never colors what does not go to a terminal
+    assert!(!output::should_color(ColorMode::Auto, false));
+}
+
+#[test]
+fn test_paint_without_color() {
+    assert_eq!(output::paint(Color::Red, "x"), "\x1b[31mx\x1b[0m");
+
+    output::set_color(false);
+    assert_eq!(output::paint(Color::Red, "x"), "x");
+    assert_eq!(Differ::colorize_diff(b"--- a/f\n+++ b/f\n-old\n+new\n"), "--- a/f\n+++ b/f\n-old\n+new\n");
+
+    let temp_dir = TempDir::new().unwrap();
+    let repo = Repository::new(temp_dir.path().to_str().unwrap());
+    repo.init().unwrap();
+    fs::write(temp_dir.path().join("a.txt"), "a").unwrap();
+    repo.create_commit("First").unwrap();
+    fs::write(temp_dir.path().join("a.txt"), "b").unwrap();
+
+    assert_eq!(Differ::new(&repo).iter_changed_files().unwrap(), vec!["a.txt"]);
+    assert_eq!(repo.iter_branch_names().unwrap(), vec!["* master"]);
+    output::set_color(true);
+
+    // Colored names end with a complete reset sequence
+    assert_eq!(
+        Differ::new(&repo).iter_changed_files().unwrap(),
+        vec!["\x1b[33ma.txt\x1b[0m"]
+    );
+}
+
+#[test]
+fn test_global_options() {
+    let (options, rest) =
+        GlobalOptions::from_args(&args(&["--no-pager", "--color=never", "diff", "HEAD"])).unwrap();
+    assert_eq!(options.color, Some(ColorMode::Never));
+    assert!(options.no_pager);
+    assert_eq!(rest, args(&["diff", "HEAD"]));
+
+    let (options, rest) = GlobalOptions::from_args(&args(&["--color", "log"])).unwrap();
+    assert_eq!(options.color, Some(ColorMode::Always));
+    assert!(!options.no_pager);
+    assert_eq!(rest, args(&["log"]));
+
+    // After the command they belong to it, as option values or paths
+    let (options, rest) =
+        GlobalOptions::from_args(&args(&["commit", "-m", "--no-pager"])).unwrap();
+    assert!(!options.no_pager);
+    assert_eq!(rest, args(&["commit", "-m", "--no-pager"]));
+    let (options, rest) =
+        GlobalOptions::from_args(&args(&["diff", "--", "--no-color"])).unwrap();
+    assert_eq!(options.color, None);
+    assert_eq!(rest, args(&["diff", "--", "--no-color"]));
+
+    assert!(GlobalOptions::from_args(&args(&["--color=maybe", "log"])).is_err());
+}
+
+#[test]
+fn test_command_pager_and_color_config() {
+    let log = Command::from_args(&args(&["log"])).unwrap();
+    assert!(log.uses_pager());
+    assert_eq!(log.color_config(), Some("color.diff"));
+
+    let status = Command::from_args(&args(&["status"])).unwrap();
+    assert!(!status.uses_pager());
+    assert_eq!(status.color_config(), Some("color.status"));
+
+    assert!(Command::from_args(&args(&["branch"])).unwrap().uses_pager());
+    assert!(!Command::from_args(&args(&["branch", "topic"])).unwrap().uses_pager());
+    assert_eq!(Command::from_args(&args(&["init"])).unwrap().color_config(), None);
+}
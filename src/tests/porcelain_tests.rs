@@ -0,0 +1,240 @@
+use crate::json::Json;
+use crate::output::OutputFormat;
+use crate::porcelain::{PorcelainVersion, StatusOptions};
+use crate::repository::LogOptions;
+
+// Repository with a committed a.txt and old.txt, then a.txt modified,
+// old.txt renamed to new.txt and b.txt added in the worktree
+fn setup_porcelain_repo(temp_dir: &TempDir) -> (Repository, Oid) {
+    let (repo, commit) = commit_once(temp_dir, |dir| {
+        write_file(dir, "a.txt", "a\n");
+        write_file(dir, "old.txt", "moved\n");
+    });
+
+    let dir = temp_dir.path();
+    write_file(dir, "a.txt", "changed\n");
+    write_file(dir, "b.txt", "b\n");
+    fs::rename(dir.join("old.txt"), dir.join("new.txt")).unwrap();
+    (repo, commit)
+}
+
+#[test]
+fn test_json_encoding() {
+    let json = Json::object(vec![
+        ("text", Json::string("quote \" backslash \\ newline \n tab \t bell \x07")),
+        ("number", Json::Number(-3)),
+        ("flags", Json::Array(vec![Json::Bool(true), Json::Null])),
+        ("empty", Json::Object(Vec::new())),
+    ]);
+    assert_eq!(
+        json.to_string(),
+        r#"{"text":"quote \" backslash \\ newline \n tab \t bell \u0007","number":-3,"flags":[true,null],"empty":{}}"#
+    );
+}
+
+#[test]
+fn test_status_porcelain_v1() {
+    let temp_dir = TempDir::new().unwrap();
+    let (repo, _) = setup_porcelain_repo(&temp_dir);
+
+    let options = StatusOptions {
+        branch: true,
+        ..Default::default()
+    };
+    assert_eq!(
+        repo.status_porcelain(PorcelainVersion::V1, &options).unwrap(),
+        "## master\nM  a.txt\nA  b.txt\nR  old.txt -> new.txt\n"
+    );
+
+    let options = StatusOptions {
+        nul_terminated: true,
+        ..Default::default()
+    };
+    assert_eq!(
+        repo.status_porcelain(PorcelainVersion::V1, &options).unwrap(),
+        "M  a.txt\0A  b.txt\0R  new.txt\0old.txt\0"
+    );
+}
+
+#[test]
+fn test_status_porcelain_v2() {
+    let temp_dir = TempDir::new().unwrap();
+    let (repo, commit) = setup_porcelain_repo(&temp_dir);
+    let blob = |content: &str| repo.hash_object(content.as_bytes(), ObjectType::Blob).unwrap();
+    let null = "0".repeat(40);
+
+    let options = StatusOptions {
+        branch: true,
+        ..Default::default()
+    };
+    let expected = format!(
+        "# branch.oid {commit}\n# branch.head master\n\
+         1 M. N... 100644 100644 100644 {a} {changed} a.txt\n\
+         1 A. N... 000000 100644 100644 {null} {b} b.txt\n\
+         2 R. N... 100644 100644 100644 {moved} {moved} R100 new.txt\told.txt\n",
+        a = blob("a\n"),
+        changed = blob("changed\n"),
+        b = blob("b\n"),
+        moved = blob("moved\n"),
+    );
+    assert_eq!(
+        repo.status_porcelain(PorcelainVersion::V2, &options).unwrap(),
+        expected
+    );
+
+    // -z separates the rename paths with NUL too
+    let options = StatusOptions {
+        nul_terminated: true,
+        ..Default::default()
+    };
+    let output = repo.status_porcelain(PorcelainVersion::V2, &options).unwrap();
+    assert!(output.ends_with(" R100 new.txt\0old.txt\0"));
+    assert!(!output.contains('\n'));
+}
+
+#[test]
+fn test_status_porcelain_before_first_commit() {
+    let temp_dir = TempDir::new().unwrap();
+    let repo = Repository::new(temp_dir.path().to_str().unwrap());
+    repo.init().unwrap();
+    fs::write(temp_dir.path().join("a.txt"), "a\n").unwrap();
+
+    let options = StatusOptions {
+        branch: true,
+        ..Default::default()
+    };
+    let output = repo.status_porcelain(PorcelainVersion::V2, &options).unwrap();
+    assert!(output.starts_with("# branch.oid (initial)\n# branch.head master\n1 A. "));
+}
+
+#[test]
+fn test_log_and_show_json() {
+    let temp_dir = TempDir::new().unwrap();
+    let (repo, first) = setup_porcelain_repo(&temp_dir);
+    let second = repo.create_commit("Second\n\nWith \"details\"").unwrap();
+    let first_tree = repo.get_commit(first).unwrap().tree;
+    let second_commit = repo.get_commit(second).unwrap();
+
+    let commit_json = |hash: Oid, tree: Oid, parents: &str, message: &str, refs: &str| {
+        let timestamp = repo.get_commit(hash).unwrap().timestamp;
+        format!(
+            r#"{{"commit":"{hash}","tree":"{tree}","parents":[{parents}],"author":null,"date":"{timestamp}","message":"{message}","refs":[{refs}]"#
+        )
+    };
+    let first_json = commit_json(first, first_tree, "", "First", "");
+    let second_json = commit_json(
+        second,
+        second_commit.tree,
+        &format!("\"{}\"", first),
+        r#"Second\n\nWith \"details\""#,
+        r#""refs/heads/master""#,
+    );
+
+    let options = LogOptions {
+        format: OutputFormat::Json,
+        ..Default::default()
+    };
+    assert_eq!(
+        repo.log_json(&options).unwrap(),
+        format!("[{}}},{}}}]", second_json, first_json)
+    );
+
+    assert_eq!(
+        repo.show_json(&second.to_string()).unwrap(),
+        format!(
+            "{},{}}}",
+            second_json,
+            r#""changes":[{"status":"M","path":"a.txt","old_path":null,"similarity":null},{"status":"A","path":"b.txt","old_path":null,"similarity":null},{"status":"R","path":"new.txt","old_path":"old.txt","similarity":100}]"#
+        )
+    );
+}
+
+#[test]
+fn test_branches_and_refs_json() {
+    let temp_dir = TempDir::new().unwrap();
+    let (repo, commit) = setup_porcelain_repo(&temp_dir);
+    repo.create_branch("topic", None).unwrap();
+    repo.create_tag("v1", commit).unwrap();
+
+    assert_eq!(
+        repo.branches_json().unwrap(),
+        format!(
+            r#"[{{"name":"master","commit":"{commit}","current":true}},{{"name":"topic","commit":"{commit}","current":false}}]"#
+        )
+    );
+    assert_eq!(
+        repo.refs_json().unwrap(),
+        format!(
+            r#"[{{"name":"refs/heads/master","oid":"{commit}"}},{{"name":"refs/heads/topic","oid":"{commit}"}},{{"name":"refs/tags/v1","oid":"{commit}"}}]"#
+        )
+    );
+}
+
+#[test]
+fn test_diff_name_status_json() {
+    let temp_dir = TempDir::new().unwrap();
+    let (repo, _) = setup_porcelain_repo(&temp_dir);
+
+    let options = DiffOptions {
+        format: DiffFormat::NameStatus,
+        output: OutputFormat::Json,
+        ..Default::default()
+    };
+    assert_eq!(
+        repo.diff_target(&DiffTarget::WorkingTree(None), &options)
+            .unwrap(),
+        r#"[{"status":"M","path":"a.txt","old_path":null,"similarity":null},{"status":"A","path":"b.txt","old_path":null,"similarity":null},{"status":"R","path":"new.txt","old_path":"old.txt","similarity":100}]"#
+    );
+    let changes = repo.diff_target_changes(&DiffTarget::WorkingTree(None), &options);
+    assert_eq!(changes.unwrap().len(), 3);
+
+    // Without changes the JSON is not empty, but the change list is
+    repo.create_commit("Second").unwrap();
+    let target = DiffTarget::WorkingTree(None);
+    assert_eq!(repo.diff_target(&target, &options).unwrap(), "[]");
+    assert!(repo.diff_target_changes(&target, &options).unwrap().is_empty());
+
+    let options = DiffOptions {
+        output: OutputFormat::Json,
+        ..Default::default()
+    };
+    assert!(
+        repo.diff_target(&DiffTarget::WorkingTree(None), &options)
+            .is_err()
+    );
+}
+
+#[test]
+fn test_parse_porcelain_and_format_flags() {
+    let args = |values: &[&str]| values.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+    let parse = |values: &[&str]| Command::from_args(&args(values));
+
+    match parse(&["status", "--porcelain=v2", "-z", "--branch"]) {
+        Ok(Command::Status(options)) => {
+            assert_eq!(options.porcelain, Some(PorcelainVersion::V2));
+            assert!(options.nul_terminated);
+            assert!(options.branch);
+        }
+        _ => panic!("expected status"),
+    }
+    // -z alone means v1
+    match parse(&["status", "-z"]) {
+        Ok(Command::Status(options)) => assert_eq!(options.porcelain, Some(PorcelainVersion::V1)),
+        _ => panic!("expected status"),
+    }
+    assert!(parse(&["status", "--porcelain=v3"]).is_err());
+
+    assert!(matches!(
+        parse(&["iter-refs", "--format=json"]),
+        Ok(Command::IterRefs(OutputFormat::Json))
+    ));
+    assert!(matches!(
+        parse(&["branch", "--format", "json"]),
+        Ok(Command::Branch(None, OutputFormat::Json))
+    ));
+    assert!(matches!(
+        parse(&["show", "HEAD", "--format=json"]),
+        Ok(Command::Show(_, _, OutputFormat::Json))
+    ));
+    assert!(parse(&["log", "--format=yaml"]).is_err());
+}
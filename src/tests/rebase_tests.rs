@@ -0,0 +1,573 @@
+// Sets up a repository where master and feature both rewrote shared.txt
+// Returns (repo, feature_commit, master_commit)
+fn setup_conflicting_rebase(temp_dir: &TempDir) -> (Repository, Oid, Oid) {
+    let repo = init_repo(temp_dir.path().to_str().unwrap());
+
+    fs::write(temp_dir.path().join("shared.txt"), "base\n").unwrap();
+    repo.create_commit("Initial commit").unwrap();
+
+    repo.create_branch("feature", None).unwrap();
+    repo.checkout("feature").unwrap();
+    fs::write(temp_dir.path().join("shared.txt"), "feature\n").unwrap();
+    let feature_commit = repo.create_commit("Feature changes").unwrap();
+
+    repo.checkout("master").unwrap();
+    fs::write(temp_dir.path().join("shared.txt"), "master\n").unwrap();
+    let master_commit = repo.create_commit("Master changes").unwrap();
+
+    repo.checkout("feature").unwrap();
+    (repo, feature_commit, master_commit)
+}
+
+#[test]
+fn test_rebase_state_persisted_on_conflict() {
+    let temp_dir = tempdir().unwrap();
+    let (repo, feature_commit, master_commit) = setup_conflicting_rebase(&temp_dir);
+
+    let outcome = repo.rebase("master").unwrap();
+    assert_eq!(outcome.onto, master_commit);
+    assert_eq!(
+        outcome.stopped,
+        Some(RebaseStop::Conflicted {
+            commit: feature_commit,
+            subject: "Feature changes".to_string(),
+            paths: vec!["shared.txt".to_string()],
+        })
+    );
+
+    let rebase_dir = format!("{}/{}", repo.gitdir, REBASE_DIR);
+    for name in ["onto", "orig-head", "head-name", "git-rebase-todo", "done", "msgnum", "end"] {
+        assert!(Path::new(&format!("{}/{}", rebase_dir, name)).exists());
+    }
+
+    let onto = fs::read_to_string(format!("{}/onto", rebase_dir)).unwrap();
+    let orig_head = fs::read_to_string(format!("{}/orig-head", rebase_dir)).unwrap();
+    let head_name = fs::read_to_string(format!("{}/head-name", rebase_dir)).unwrap();
+    let done = fs::read_to_string(format!("{}/done", rebase_dir)).unwrap();
+    assert_eq!(onto.trim(), master_commit.to_string());
+    assert_eq!(orig_head.trim(), feature_commit.to_string());
+    assert_eq!(head_name.trim(), "refs/heads/feature");
+    assert_eq!(done, format!("pick {} Feature changes\n", feature_commit));
+}
+
+#[test]
+fn test_rebase_conflict_markers_put_upstream_on_head_side() {
+    let temp_dir = tempdir().unwrap();
+    let (repo, _, _) = setup_conflicting_rebase(&temp_dir);
+
+    repo.rebase("master").unwrap();
+
+    // HEAD is the upstream being rebased onto, MERGE_HEAD the replayed commit
+    let content = fs::read_to_string(temp_dir.path().join("shared.txt")).unwrap();
+    assert_eq!(
+        content,
+        "<<<<<<< HEAD\nmaster\n||||||| BASE\nbase\n=======\nfeature\n>>>>>>> MERGE_HEAD\n"
+    );
+}
+
+#[test]
+fn test_rebase_refuses_to_start_twice() {
+    let temp_dir = tempdir().unwrap();
+    let (repo, _, _) = setup_conflicting_rebase(&temp_dir);
+
+    repo.rebase("master").unwrap();
+    let result = repo.rebase("master");
+    assert!(result.is_err());
+    assert!(result.unwrap_err().to_string().contains("already in progress"));
+}
+
+#[test]
+fn test_rebase_refuses_dirty_worktree() {
+    let temp_dir = tempdir().unwrap();
+    let (repo, feature_commit, _) = setup_conflicting_rebase(&temp_dir);
+    fs::write(temp_dir.path().join("wip.txt"), "work in progress\n").unwrap();
+
+    let result = repo.rebase("master");
+    assert!(result.unwrap_err().to_string().contains("local changes"));
+    assert!(!repo.rebase_in_progress());
+
+    // Nothing was touched: the uncommitted file is still there and HEAD
+    // still points at feature
+    let content = fs::read_to_string(temp_dir.path().join("wip.txt")).unwrap();
+    assert_eq!(content, "work in progress\n");
+    assert_eq!(repo.get_oid_hash(HEAD).unwrap(), feature_commit);
+    assert_eq!(
+        repo.get_ref(HEAD, false).unwrap(),
+        RefValue::Symbolic("refs/heads/feature".to_string())
+    );
+}
+
+#[test]
+fn test_rebase_abort_restores_original_branch() {
+    let temp_dir = tempdir().unwrap();
+    let (repo, feature_commit, _) = setup_conflicting_rebase(&temp_dir);
+
+    repo.rebase("master").unwrap();
+    assert!(repo.rebase_in_progress());
+
+    let state = repo.rebase_abort().unwrap();
+    assert_eq!(state.orig_head, feature_commit);
+    assert!(!repo.rebase_in_progress());
+
+    // Worktree, branch and HEAD are back to where they were
+    let content = fs::read_to_string(temp_dir.path().join("shared.txt")).unwrap();
+    assert_eq!(content, "feature\n");
+    assert_eq!(
+        repo.get_ref("refs/heads/feature", true).unwrap().oid().unwrap(),
+        feature_commit
+    );
+    let head_ref = repo.get_ref(HEAD, false).unwrap();
+    assert!(matches!(head_ref, RefValue::Symbolic(_)));
+    assert_eq!(head_ref, RefValue::Symbolic("refs/heads/feature".to_string()));
+}
+
+#[test]
+fn test_rebase_skip_drops_conflicting_commit() {
+    let temp_dir = tempdir().unwrap();
+    let (repo, _, master_commit) = setup_conflicting_rebase(&temp_dir);
+
+    repo.rebase("master").unwrap();
+    repo.rebase_skip().unwrap();
+    assert!(!repo.rebase_in_progress());
+
+    // The only feature commit was dropped, so feature now equals master
+    assert_eq!(
+        repo.get_ref("refs/heads/feature", true).unwrap().oid().unwrap(),
+        master_commit
+    );
+    let content = fs::read_to_string(temp_dir.path().join("shared.txt")).unwrap();
+    assert_eq!(content, "master\n");
+}
+
+#[test]
+fn test_rebase_continue_with_unresolved_conflicts() {
+    let temp_dir = tempdir().unwrap();
+    let (repo, _, _) = setup_conflicting_rebase(&temp_dir);
+
+    repo.rebase("master").unwrap();
+
+    let result = repo.rebase_continue();
+    assert!(matches!(result, Err(Error::Conflict(paths)) if paths == ["shared.txt"]));
+    assert!(repo.rebase_in_progress());
+}
+
+#[test]
+fn test_rebase_commands_without_rebase_in_progress() {
+    let temp_dir = tempdir().unwrap();
+    let repo = init_repo(temp_dir.path().to_str().unwrap());
+
+    assert!(repo.rebase_continue().is_err());
+    assert!(repo.rebase_skip().is_err());
+    assert!(repo.rebase_abort().is_err());
+}
+
+// Creates three feature commits on top of master, each adding its own file
+// Returns (repo, [commit_a, commit_b, commit_c])
+fn setup_feature_commits(temp_dir: &TempDir) -> (Repository, Vec<Oid>) {
+    let repo = init_repo(temp_dir.path().to_str().unwrap());
+
+    fs::write(temp_dir.path().join("base.txt"), "base\n").unwrap();
+    repo.create_commit("Initial commit").unwrap();
+
+    repo.create_branch("feature", None).unwrap();
+    repo.checkout("feature").unwrap();
+
+    let mut commits = Vec::new();
+    for name in ["a", "b", "c"] {
+        fs::write(temp_dir.path().join(format!("{}.txt", name)), format!("{}\n", name)).unwrap();
+        commits.push(repo.create_commit(&format!("Add {}", name)).unwrap());
+    }
+    (repo, commits)
+}
+
+// Configures an editor that replaces the todo list and commit messages with
+// the given contents. The script lives outside the worktree.
+fn set_scripted_editor(repo: &Repository, script_dir: &TempDir, todo: &str, message: &str) {
+    let dir = script_dir.path();
+    fs::write(dir.join("todo"), todo).unwrap();
+    fs::write(dir.join("message"), message).unwrap();
+    fs::write(
+        dir.join("editor.sh"),
+        format!(
+            "case \"$1\" in\n  *git-rebase-todo) cp '{0}/todo' \"$1\" ;;\n  *) cp '{0}/message' \"$1\" ;;\nesac\n",
+            dir.display()
+        ),
+    )
+    .unwrap();
+    repo.set_config("core.editor", &format!("sh '{}/editor.sh'", dir.display()))
+        .unwrap();
+}
+
+// Commit messages from HEAD down to (excluding) the root commit
+fn messages_since_root(repo: &Repository) -> Vec<String> {
+    let mut messages = Vec::new();
+    let mut commit = repo.get_commit(repo.get_oid_hash(HEAD).unwrap()).unwrap();
+    while let Some(parent) = commit.parents.first().cloned() {
+        messages.push(commit.message.clone());
+        commit = repo.get_commit(parent).unwrap();
+    }
+    messages
+}
+
+#[test]
+fn test_rebase_interactive_reorder_and_drop() {
+    let temp_dir = tempdir().unwrap();
+    let script_dir = tempdir().unwrap();
+    let (repo, commits) = setup_feature_commits(&temp_dir);
+
+    let todo = format!(
+        "pick {} Add c\ndrop {} Add b\n# comment lines are ignored\npick {} Add a\n",
+        commits[2], commits[1], commits[0]
+    );
+    set_scripted_editor(&repo, &script_dir, &todo, "");
+
+    let options = RebaseOptions {
+        interactive: true,
+        ..Default::default()
+    };
+    repo.rebase_with_options("master", &options).unwrap();
+    assert!(!repo.rebase_in_progress());
+
+    assert_eq!(messages_since_root(&repo), vec!["Add a", "Add c"]);
+    assert!(temp_dir.path().join("a.txt").exists());
+    assert!(!temp_dir.path().join("b.txt").exists());
+    assert!(temp_dir.path().join("c.txt").exists());
+    assert_eq!(repo.get_branch_name().unwrap(), Some("feature".to_string()));
+}
+
+#[test]
+fn test_rebase_interactive_reword_squash_fixup() {
+    let temp_dir = tempdir().unwrap();
+    let script_dir = tempdir().unwrap();
+    let (repo, commits) = setup_feature_commits(&temp_dir);
+
+    let todo = format!(
+        "reword {} Add a\nsquash {} Add b\nfixup {} Add c\n",
+        commits[0], commits[1], commits[2]
+    );
+    set_scripted_editor(&repo, &script_dir, &todo, "# dropped comment\nAdd a and b\n");
+
+    let options = RebaseOptions {
+        interactive: true,
+        ..Default::default()
+    };
+    repo.rebase_with_options("master", &options).unwrap();
+
+    // Everything collapsed into a single commit carrying all three files
+    assert_eq!(messages_since_root(&repo), vec!["Add a and b"]);
+    let head = repo.get_commit(repo.get_oid_hash(HEAD).unwrap()).unwrap();
+    assert_eq!(head.tree, repo.get_commit(commits[2]).unwrap().tree);
+}
+
+#[test]
+fn test_rebase_interactive_edit_and_continue() {
+    let temp_dir = tempdir().unwrap();
+    let script_dir = tempdir().unwrap();
+    let (repo, commits) = setup_feature_commits(&temp_dir);
+
+    let todo = format!(
+        "pick {} Add a\nedit {} Add b\npick {} Add c\n",
+        commits[0], commits[1], commits[2]
+    );
+    set_scripted_editor(&repo, &script_dir, &todo, "");
+
+    let options = RebaseOptions {
+        interactive: true,
+        ..Default::default()
+    };
+    let outcome = repo.rebase_with_options("master", &options).unwrap();
+
+    // Stopped after applying "Add b"
+    assert_eq!(
+        outcome.stopped,
+        Some(RebaseStop::Edit {
+            commit: repo.get_oid_hash(HEAD).unwrap(),
+            subject: "Add b".to_string(),
+        })
+    );
+    assert!(repo.rebase_in_progress());
+    assert_eq!(repo.get_commit(repo.get_oid_hash(HEAD).unwrap()).unwrap().message, "Add b");
+    assert!(!temp_dir.path().join("c.txt").exists());
+
+    // Amend the stopped commit and finish
+    fs::write(temp_dir.path().join("b.txt"), "b amended\n").unwrap();
+    assert_eq!(repo.rebase_continue().unwrap().stopped, None);
+    assert!(!repo.rebase_in_progress());
+
+    assert_eq!(messages_since_root(&repo), vec!["Add c", "Add b", "Add a"]);
+    let amended = repo.get_commit(repo.get_oid_hash(HEAD).unwrap()).unwrap().parents[0];
+    let amended_tree = repo.get_commit(amended).unwrap().tree;
+    let entries = repo.get_tree_data(amended_tree).unwrap();
+    let (_, _, b_oid, _) = entries.iter().find(|(_, name, _, _)| name == "b.txt").unwrap();
+    assert_eq!(repo.read_object(*b_oid, ObjectType::Blob).unwrap(), b"b amended\n");
+}
+
+#[test]
+fn test_rebase_interactive_exec() {
+    let temp_dir = tempdir().unwrap();
+    let script_dir = tempdir().unwrap();
+    let (repo, commits) = setup_feature_commits(&temp_dir);
+
+    let todo = format!(
+        "pick {}\nexec test -f a.txt\npick {}\nexec false\npick {}\n",
+        commits[0], commits[1], commits[2]
+    );
+    set_scripted_editor(&repo, &script_dir, &todo, "");
+
+    let options = RebaseOptions {
+        interactive: true,
+        ..Default::default()
+    };
+    repo.rebase_with_options("master", &options).unwrap();
+
+    // The failing command stops the rebase, continue resumes after it
+    assert!(repo.rebase_in_progress());
+    assert_eq!(repo.get_commit(repo.get_oid_hash(HEAD).unwrap()).unwrap().message, "Add b");
+    repo.rebase_continue().unwrap();
+    assert!(!repo.rebase_in_progress());
+    assert_eq!(messages_since_root(&repo), vec!["Add c", "Add b", "Add a"]);
+}
+
+#[test]
+fn test_rebase_interactive_empty_todo_aborts() {
+    let temp_dir = tempdir().unwrap();
+    let script_dir = tempdir().unwrap();
+    let (repo, commits) = setup_feature_commits(&temp_dir);
+    set_scripted_editor(&repo, &script_dir, "# nothing left\n", "");
+
+    let options = RebaseOptions {
+        interactive: true,
+        ..Default::default()
+    };
+    let result = repo.rebase_with_options("master", &options);
+    assert!(result.is_err());
+    assert!(result.unwrap_err().to_string().contains("Nothing to do"));
+
+    // Nothing was touched
+    assert!(!repo.rebase_in_progress());
+    assert_eq!(repo.get_oid_hash(HEAD).unwrap(), commits[2]);
+    assert_eq!(repo.get_branch_name().unwrap(), Some("feature".to_string()));
+}
+
+#[test]
+fn test_rebase_interactive_rejects_leading_squash() {
+    let temp_dir = tempdir().unwrap();
+    let script_dir = tempdir().unwrap();
+    let (repo, commits) = setup_feature_commits(&temp_dir);
+    set_scripted_editor(&repo, &script_dir, &format!("squash {}\n", commits[0]), "");
+
+    let options = RebaseOptions {
+        interactive: true,
+        ..Default::default()
+    };
+    let result = repo.rebase_with_options("master", &options);
+    assert!(result.unwrap_err().to_string().contains("without a previous commit"));
+    assert!(!repo.rebase_in_progress());
+}
+
+#[test]
+fn test_rebase_autosquash() {
+    let temp_dir = tempdir().unwrap();
+    let (repo, _) = setup_feature_commits(&temp_dir);
+
+    fs::write(temp_dir.path().join("a.txt"), "a fixed\n").unwrap();
+    repo.create_commit("fixup! Add a").unwrap();
+
+    let options = RebaseOptions {
+        autosquash: true,
+        ..Default::default()
+    };
+    repo.rebase_with_options("master", &options).unwrap();
+
+    // The fixup was moved after "Add a" and melded into it
+    assert_eq!(messages_since_root(&repo), vec!["Add c", "Add b", "Add a"]);
+    let content = fs::read_to_string(temp_dir.path().join("a.txt")).unwrap();
+    assert_eq!(content, "a fixed\n");
+}
+
+#[test]
+fn test_rebase_autosquash_by_hash_prefix() {
+    let temp_dir = tempdir().unwrap();
+    let (repo, commits) = setup_feature_commits(&temp_dir);
+
+    fs::write(temp_dir.path().join("b.txt"), "b fixed\n").unwrap();
+    repo.create_commit(&format!("fixup! {}", commits[1].short(7)))
+        .unwrap();
+
+    let options = RebaseOptions {
+        autosquash: true,
+        ..Default::default()
+    };
+    repo.rebase_with_options("master", &options).unwrap();
+
+    assert_eq!(messages_since_root(&repo), vec!["Add c", "Add b", "Add a"]);
+    let content = fs::read_to_string(temp_dir.path().join("b.txt")).unwrap();
+    assert_eq!(content, "b fixed\n");
+}
+
+#[test]
+fn test_rebase_autosquash_leaves_unmatched_fixups() {
+    // An empty target, a subject prefix shared by several commits and a
+    // hash prefix too short to tell commits apart match nothing
+    for target in ["", "Add", "Add ", "abc"] {
+        let temp_dir = tempdir().unwrap();
+        let (repo, _) = setup_feature_commits(&temp_dir);
+
+        fs::write(temp_dir.path().join("a.txt"), "a fixed\n").unwrap();
+        // A body keeps the space of an empty target in the subject line
+        let fixup = format!("fixup! {}\n\nFix a", target);
+        repo.create_commit(&fixup).unwrap();
+
+        let options = RebaseOptions {
+            autosquash: true,
+            ..Default::default()
+        };
+        repo.rebase_with_options("master", &options).unwrap();
+
+        // The fixup is replayed as a commit of its own, where it was
+        assert_eq!(
+            messages_since_root(&repo),
+            vec![fixup.as_str(), "Add c", "Add b", "Add a"],
+            "{:?}",
+            target
+        );
+    }
+}
+
+#[test]
+fn test_rebase_applies_each_commit_once() {
+    let temp_dir = tempdir().unwrap();
+    let repo = init_repo(temp_dir.path().to_str().unwrap());
+
+    fs::write(temp_dir.path().join("file.txt"), "1\n2\n3\n").unwrap();
+    repo.create_commit("Initial commit").unwrap();
+
+    repo.create_branch("feature", None).unwrap();
+    repo.checkout("feature").unwrap();
+    fs::write(temp_dir.path().join("file.txt"), "one\n2\n3\n").unwrap();
+    repo.create_commit("Change first line").unwrap();
+    fs::write(temp_dir.path().join("file.txt"), "one\n2\nthree\n").unwrap();
+    repo.create_commit("Change last line").unwrap();
+
+    repo.checkout("master").unwrap();
+    fs::write(temp_dir.path().join("other.txt"), "other\n").unwrap();
+    repo.create_commit("Master changes").unwrap();
+
+    repo.checkout("feature").unwrap();
+    repo.rebase("master").unwrap();
+
+    // The second commit only brings its own line, nothing conflicts
+    assert!(!repo.rebase_in_progress());
+    let content = fs::read_to_string(temp_dir.path().join("file.txt")).unwrap();
+    assert_eq!(content, "one\n2\nthree\n");
+    assert!(temp_dir.path().join("other.txt").exists());
+    assert_eq!(
+        messages_since_root(&repo),
+        vec!["Change last line", "Change first line", "Master changes"]
+    );
+}
+
+#[test]
+fn test_rebase_preserves_authorship() {
+    let temp_dir = tempdir().unwrap();
+    let repo = init_repo(temp_dir.path().to_str().unwrap());
+    repo.set_config("user.name", "Original Author").unwrap();
+    repo.set_config("user.email", "author@example.com").unwrap();
+
+    fs::write(temp_dir.path().join("base.txt"), "base\n").unwrap();
+    repo.create_commit("Initial commit").unwrap();
+    repo.create_branch("feature", None).unwrap();
+    repo.checkout("feature").unwrap();
+    fs::write(temp_dir.path().join("feature.txt"), "feature\n").unwrap();
+    let feature_commit = repo.create_commit("Feature changes").unwrap();
+
+    // Someone else performs the rebase later
+    repo.set_config("user.name", "Rebaser").unwrap();
+    repo.checkout("master").unwrap();
+    fs::write(temp_dir.path().join("master.txt"), "master\n").unwrap();
+    repo.create_commit("Master changes").unwrap();
+    repo.checkout("feature").unwrap();
+    repo.rebase("master").unwrap();
+
+    let original = repo.get_commit(feature_commit).unwrap();
+    let rebased = repo.get_commit(repo.get_oid_hash(HEAD).unwrap()).unwrap();
+    assert_ne!(rebased.oid, original.oid);
+    assert_eq!(
+        rebased.author,
+        Some("Original Author <author@example.com>".to_string())
+    );
+    assert_eq!(rebased.author, original.author);
+    assert_eq!(rebased.timestamp, original.timestamp);
+    assert_eq!(rebased.message, original.message);
+}
+
+#[test]
+fn test_rebase_skips_commits_already_upstream() {
+    let temp_dir = tempdir().unwrap();
+    let repo = init_repo(temp_dir.path().to_str().unwrap());
+
+    fs::write(temp_dir.path().join("file.txt"), "1\n2\n3\n").unwrap();
+    repo.create_commit("Initial commit").unwrap();
+
+    repo.create_branch("feature", None).unwrap();
+    repo.checkout("feature").unwrap();
+    fs::write(temp_dir.path().join("file.txt"), "one\n2\n3\n").unwrap();
+    let feature_fix = repo.create_commit("Fix first line").unwrap();
+    fs::write(temp_dir.path().join("feature.txt"), "feature\n").unwrap();
+    repo.create_commit("Add feature").unwrap();
+
+    // The same fix lands on master independently
+    repo.checkout("master").unwrap();
+    fs::write(temp_dir.path().join("file.txt"), "one\n2\n3\n").unwrap();
+    let master_fix = repo.create_commit("Fix first line on master").unwrap();
+
+    repo.checkout("feature").unwrap();
+    let outcome = repo.rebase("master").unwrap();
+
+    assert_eq!(outcome.skipped, vec![feature_fix]);
+    assert_eq!(outcome.stopped, None);
+    assert!(!repo.rebase_in_progress());
+    let head = repo.get_commit(repo.get_oid_hash(HEAD).unwrap()).unwrap();
+    assert_eq!(head.message, "Add feature");
+    assert_eq!(head.parents, vec![master_fix]);
+}
+
+#[test]
+fn test_rebase_onto() {
+    let temp_dir = tempdir().unwrap();
+    let repo = init_repo(temp_dir.path().to_str().unwrap());
+
+    fs::write(temp_dir.path().join("base.txt"), "base\n").unwrap();
+    repo.create_commit("Initial commit").unwrap();
+
+    // next: N1, topic (based on next): T1
+    repo.create_branch("next", None).unwrap();
+    repo.checkout("next").unwrap();
+    fs::write(temp_dir.path().join("next.txt"), "next\n").unwrap();
+    repo.create_commit("Next work").unwrap();
+
+    repo.create_branch("topic", None).unwrap();
+    repo.checkout("topic").unwrap();
+    fs::write(temp_dir.path().join("topic.txt"), "topic\n").unwrap();
+    repo.create_commit("Topic work").unwrap();
+
+    repo.checkout("master").unwrap();
+    fs::write(temp_dir.path().join("master.txt"), "master\n").unwrap();
+    let master_commit = repo.create_commit("Master work").unwrap();
+
+    // Move only the topic commits from next over to master
+    repo.checkout("topic").unwrap();
+    let options = RebaseOptions {
+        onto: Some("master".to_string()),
+        ..Default::default()
+    };
+    repo.rebase_with_options("next", &options).unwrap();
+
+    let head = repo.get_commit(repo.get_oid_hash(HEAD).unwrap()).unwrap();
+    assert_eq!(head.message, "Topic work");
+    assert_eq!(head.parents, vec![master_commit]);
+    assert!(temp_dir.path().join("topic.txt").exists());
+    assert!(temp_dir.path().join("master.txt").exists());
+    assert!(!temp_dir.path().join("next.txt").exists());
+    assert_eq!(repo.get_branch_name().unwrap(), Some("topic".to_string()));
+}
@@ -1,8 +1,7 @@
-use crate::repository::{GIT_DIR, HEAD, MERGE_HEAD, ObjectType, RefValue, Repository};
-use std::fs;
-use std::path::Path;
-use tempfile::TempDir;
-use tempfile::tempdir;
+// Hex digits in an object id of the suite's object format
+fn oid_hex_len() -> usize {
+    OBJECT_FORMAT.oid_len() * 2
+}
 
 #[test]
 fn test_repository_init_success() {
@@ -38,7 +37,87 @@ fn test_repository_init_already_exists() {
     // Try to initialize again
     let result = repo.init();
     assert!(result.is_err());
-    assert!(result.unwrap_err().contains("already exists"));
+    assert!(result.unwrap_err().to_string().contains("already exists"));
+}
+
+#[test]
+fn test_repository_init_bare() {
+    let temp_dir = TempDir::new().unwrap();
+    let repo_path = temp_dir.path().join("central.git");
+    let options = InitOptions {
+        bare: true,
+        ..Default::default()
+    };
+
+    let repo = Repository::init_at(repo_path.to_str().unwrap(), &options).unwrap();
+    assert!(repo.bare);
+    assert_eq!(repo.gitdir, repo_path.to_str().unwrap());
+
+    // Objects and refs are directly in the directory
+    assert!(repo_path.join("objects").is_dir());
+    assert!(repo_path.join("refs/heads").is_dir());
+    assert!(!repo_path.join(GIT_DIR).exists());
+    assert_eq!(repo.get_config("core.bare").as_deref(), Some("true"));
+
+    let result = Repository::init_at(repo_path.to_str().unwrap(), &options);
+    assert!(result.err().unwrap().to_string().contains("already exists"));
+}
+
+#[test]
+fn test_repository_init_initial_branch() {
+    let temp_dir = TempDir::new().unwrap();
+    let repo_path = temp_dir.path().join("new/dir");
+    let options = InitOptions {
+        initial_branch: Some("release/main".to_string()),
+        ..Default::default()
+    };
+
+    let repo = Repository::init_at(repo_path.to_str().unwrap(), &options).unwrap();
+    assert!(!repo.bare);
+    let head_content = fs::read_to_string(repo_path.join(GIT_DIR).join("HEAD")).unwrap();
+    assert_eq!(head_content, "ref: refs/heads/release/main\n");
+
+    fs::write(repo_path.join("a.txt"), "a").unwrap();
+    let commit = repo.create_commit("First").unwrap();
+    assert_eq!(repo.get_oid_hash("release/main").unwrap(), commit);
+
+    for name in ["", "-x", "a..b", "a b", "a/", ".hidden", "x.lock", "a:b"] {
+        let options = InitOptions {
+            initial_branch: Some(name.to_string()),
+            ..Default::default()
+        };
+        let path = temp_dir.path().join("invalid");
+        assert!(
+            Repository::init_at(path.to_str().unwrap(), &options).is_err(),
+            "{:?} should be rejected",
+            name
+        );
+    }
+}
+
+#[test]
+fn test_bare_repository_refuses_worktree_operations() {
+    let temp_dir = TempDir::new().unwrap();
+    let options = InitOptions {
+        bare: true,
+        ..Default::default()
+    };
+    let repo = Repository::init_at(temp_dir.path().to_str().unwrap(), &options).unwrap();
+
+    assert!(matches!(repo.create_commit("First"), Err(Error::BareRepository)));
+    assert!(matches!(repo.get_working_tree(), Err(Error::BareRepository)));
+    assert!(matches!(
+        repo.read_tree(Oid::zero(repo.object_format), Path::new(&repo.worktree)),
+        Err(Error::BareRepository)
+    ));
+
+    // Nothing was written in the repository directory
+    let mut entries: Vec<_> = fs::read_dir(temp_dir.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    entries.sort();
+    assert_eq!(entries, vec!["HEAD", "config", "objects", "refs"]);
 }
 
 #[test]
@@ -47,19 +126,19 @@ fn test_hash_object_success() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
 
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Test with simple data
     let data = b"Hello, world!";
     let hash = repo.hash_object(data, ObjectType::Blob).unwrap();
 
     // Verify hash format (40 hex characters)
-    assert_eq!(hash.len(), 40);
-    assert!(hash.chars().all(|c| c.is_ascii_hexdigit()));
+    assert_eq!(hash.to_string().len(), oid_hex_len());
+    assert!(hash.to_string().chars().all(|c| c.is_ascii_hexdigit()));
 
     // Verify object file exists
-    let (dir, file) = hash.split_at(2);
+    let hex = hash.to_string();
+    let (dir, file) = hex.split_at(2);
     let object_path = format!("{}/{}/objects/{}/{}", repo_path, GIT_DIR, dir, file);
     assert!(Path::new(&object_path).exists());
 }
@@ -70,16 +149,15 @@ fn test_hash_object_empty_data() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
 
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Test with empty data
     let data = b"";
     let hash = repo.hash_object(data, ObjectType::Blob).unwrap();
 
     // Verify hash format
-    assert_eq!(hash.len(), 40);
-    assert!(hash.chars().all(|c| c.is_ascii_hexdigit()));
+    assert_eq!(hash.to_string().len(), oid_hex_len());
+    assert!(hash.to_string().chars().all(|c| c.is_ascii_hexdigit()));
 }
 
 #[test]
@@ -88,18 +166,17 @@ fn test_get_object_success() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
 
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Store some data
     let original_data = b"Test content for get_object";
     let hash = repo.hash_object(original_data, ObjectType::Blob).unwrap();
 
     // Retrieve the data
-    let retrieved_data = repo.get_object(&hash).unwrap();
+    let retrieved_data = repo.get_object(hash).unwrap();
 
     // Verify the retrieved data matches the original
-    assert_eq!(retrieved_data, original_data);
+    assert_eq!(retrieved_data, (ObjectType::Blob, original_data.to_vec()));
 }
 
 #[test]
@@ -108,18 +185,16 @@ fn test_get_object_invalid_hash() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
 
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Test with invalid hash format
-    let result = repo.get_object("invalidhash");
-    assert!(result.is_err());
-    assert!(result.unwrap_err().contains("Oid hash not found for"));
+    let result = "invalidhash".parse::<Oid>();
+    assert!(matches!(result, Err(Error::InvalidRef(name)) if name == "invalidhash"));
 
     // Test with non-existent hash
-    let result = repo.get_object("a".repeat(40).as_str());
-    assert!(result.is_err());
-    assert!(result.unwrap_err().contains("Failed to read object"));
+    let missing: Oid = "a".repeat(oid_hex_len()).parse().unwrap();
+    let result = repo.get_object(missing);
+    assert!(matches!(result, Err(Error::NotFound(oid)) if oid == missing));
 }
 
 #[test]
@@ -128,22 +203,67 @@ fn test_get_object_corrupted_data() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
 
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Store some data
     let original_data = b"Test content";
     let hash = repo.hash_object(original_data, ObjectType::Blob).unwrap();
 
     // Corrupt the object file
-    let (dir, file) = hash.split_at(2);
+    let hex = hash.to_string();
+    let (dir, file) = hex.split_at(2);
     let object_path = format!("{}/{}/objects/{}/{}", repo_path, GIT_DIR, dir, file);
+    // Object files are read-only
+    fs::remove_file(&object_path).unwrap();
     fs::write(&object_path, b"corrupted data").unwrap();
 
     // Try to retrieve the corrupted data
-    let result = repo.get_object(&hash);
-    assert!(result.is_err());
-    assert!(result.unwrap_err().contains("Invalid object format"));
+    let result = repo.get_object(hash);
+    assert!(matches!(result, Err(Error::Corrupt(_))));
+}
+
+#[test]
+fn test_get_object_checks_header() {
+    let repo = Repository::in_memory();
+    let write = |data: &[u8]| {
+        let oid = repo.object_format.hash(data);
+        repo.odb.write(oid, data).unwrap();
+        oid
+    };
+
+    let blob = write(b"blob 5\0hello");
+    assert_eq!(repo.get_object(blob).unwrap(), (ObjectType::Blob, b"hello".to_vec()));
+
+    // The declared size must match the content, and the type be known
+    for data in [&b"blob 6\0hello"[..], b"blob 4\0hello", b"blob x\0hello", b"tag 5\0hello"] {
+        let result = repo.get_object(write(data));
+        assert!(matches!(result, Err(Error::Corrupt(_))), "{:?}", data);
+    }
+}
+
+#[test]
+fn test_read_object_rejects_other_types() {
+    let temp_dir = TempDir::new().unwrap();
+    let repo = init_repo(temp_dir.path().to_str().unwrap());
+
+    fs::write(temp_dir.path().join("file.txt"), "tree abc\n\nnot a commit\n").unwrap();
+    let commit = repo.create_commit("First commit").unwrap();
+    let tree = repo.get_commit(commit).unwrap().tree;
+    let blob = repo.get_tree_data(tree).unwrap()[0].2;
+
+    assert_eq!(repo.read_object(blob, ObjectType::Blob).unwrap(), b"tree abc\n\nnot a commit\n");
+    let result = repo.get_commit(blob);
+    assert!(matches!(
+        result,
+        Err(Error::WrongType { oid, expected: ObjectType::Commit, actual: ObjectType::Blob })
+            if oid == blob
+    ));
+    assert_eq!(
+        result.unwrap_err().to_string(),
+        format!("Object {} is a blob, not a commit", blob)
+    );
+    assert!(matches!(repo.get_tree_data(commit), Err(Error::WrongType { .. })));
+    assert!(matches!(repo.get_commit(tree), Err(Error::WrongType { .. })));
 }
 
 #[test]
@@ -152,8 +272,7 @@ fn test_create_tree_success() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
 
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Create test directory structure
     let test_dir = temp_dir.path().join("test_dir");
@@ -171,11 +290,12 @@ fn test_create_tree_success() {
     let tree_hash = repo.create_tree(&test_dir).unwrap();
 
     // Verify hash format
-    assert_eq!(tree_hash.len(), 40);
-    assert!(tree_hash.chars().all(|c| c.is_ascii_hexdigit()));
+    assert_eq!(tree_hash.to_string().len(), oid_hex_len());
+    assert!(tree_hash.to_string().chars().all(|c| c.is_ascii_hexdigit()));
 
     // Verify object exists
-    let (dir, file) = tree_hash.split_at(2);
+    let hex = tree_hash.to_string();
+    let (dir, file) = hex.split_at(2);
     let object_path = format!("{}/{}/objects/{}/{}", repo_path, GIT_DIR, dir, file);
     assert!(Path::new(&object_path).exists());
 }
@@ -185,8 +305,7 @@ fn test_create_tree_empty_directory() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
 
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Create empty directory
     let empty_dir = temp_dir.path().join("empty_dir");
@@ -194,7 +313,7 @@ fn test_create_tree_empty_directory() {
 
     // Create tree
     let tree_hash = repo.create_tree(&empty_dir).unwrap();
-    assert_eq!(tree_hash.len(), 40);
+    assert_eq!(tree_hash.to_string().len(), oid_hex_len());
 }
 
 #[test]
@@ -202,8 +321,7 @@ fn test_is_ignored() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
 
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Create test files and directories
     let test_file = temp_dir.path().join("test.txt");
@@ -229,8 +347,7 @@ fn test_read_tree_success() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
 
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Create test directory structure
     let test_dir = temp_dir.path().join("test_dir");
@@ -251,7 +368,7 @@ fn test_read_tree_success() {
     fs::create_dir(&target_dir).unwrap();
 
     // Read tree into target directory
-    assert!(repo.read_tree(&tree_hash, &target_dir).is_ok());
+    assert!(repo.read_tree(tree_hash, &target_dir).is_ok());
 
     // Verify structure
     assert!(target_dir.join("file1.txt").exists());
@@ -275,8 +392,7 @@ fn test_read_tree_with_existing_files() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
 
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Create test directory structure
     let test_dir = temp_dir.path().join("test_dir");
@@ -293,7 +409,7 @@ fn test_read_tree_with_existing_files() {
     fs::write(target_dir.join("test.txt"), "Old test content").unwrap();
 
     // Read tree into target directory
-    assert!(repo.read_tree(&tree_hash, &target_dir).is_ok());
+    assert!(repo.read_tree(tree_hash, &target_dir).is_ok());
 
     // Verify old files are gone and new structure is correct
     assert!(!target_dir.join("old.txt").exists());
@@ -304,13 +420,79 @@ fn test_read_tree_with_existing_files() {
     );
 }
 
+#[test]
+fn test_read_tree_missing_object_keeps_directory() {
+    let temp_dir = TempDir::new().unwrap();
+    let repo = init_repo(temp_dir.path().to_str().unwrap());
+
+    let test_dir = temp_dir.path().join("test_dir");
+    write_file(&test_dir, "a.txt", "a");
+    write_file(&test_dir, "sub/b.txt", "b");
+    let tree_hash = repo.create_tree(&test_dir).unwrap();
+    let target_dir = temp_dir.path().join("new_dir");
+    write_file(&target_dir, "old.txt", "Old content");
+
+    // A tree that cannot be read, or one with a blob that is gone
+    let missing = repo.hash_object(b"never written", ObjectType::Tree).unwrap();
+    repo.odb.delete(missing).unwrap();
+    assert!(matches!(repo.read_tree(missing, &target_dir), Err(Error::NotFound(_))));
+    repo.odb.delete(repo.hash_object(b"b", ObjectType::Blob).unwrap()).unwrap();
+    assert!(matches!(repo.read_tree(tree_hash, &target_dir), Err(Error::NotFound(_))));
+
+    // Nothing was removed or written
+    assert_eq!(fs::read_to_string(target_dir.join("old.txt")).unwrap(), "Old content");
+    assert!(!target_dir.join("a.txt").exists());
+    assert!(!target_dir.join("sub").exists());
+}
+
+#[test]
+fn test_corrupt_trees_are_errors() {
+    let temp_dir = TempDir::new().unwrap();
+    let (repo, first, _) = commit_twice(
+        &temp_dir,
+        |dir| write_file(dir, "sub/b.txt", "b\n"),
+        |dir| write_file(dir, "sub/b.txt", "b changed\n"),
+    );
+    let head_tree = repo.get_commit(first).unwrap().tree;
+    let other_tree = repo.get_commit(repo.get_oid_hash(HEAD).unwrap()).unwrap().tree;
+
+    // A subtree that is gone fails diffs and merges instead of leaving
+    // its files out
+    let (_, _, sub, _) = repo.get_tree_data(head_tree).unwrap().remove(0);
+    repo.odb.delete(sub).unwrap();
+    let differ = Differ::new(&repo);
+    assert!(matches!(
+        differ.compare_trees(&[Some(head_tree), Some(other_tree)]),
+        Err(Error::NotFound(oid)) if oid == sub
+    ));
+    assert!(differ.diff_trees(Some(head_tree), Some(other_tree)).is_err());
+    assert!(matches!(
+        repo.read_tree_merged(head_tree, other_tree, None),
+        Err(Error::NotFound(_))
+    ));
+
+    // A tree naming a path outside the worktree is corrupt, and nothing is
+    // written or removed
+    let blob = repo.hash_object(b"outside\n", ObjectType::Blob).unwrap();
+    let mut content = b"100644 ..\0".to_vec();
+    content.extend(blob.as_bytes());
+    let outside = repo.hash_object(&content, ObjectType::Tree).unwrap();
+    assert!(matches!(
+        repo.read_tree_merged(other_tree, outside, None),
+        Err(Error::Corrupt(_))
+    ));
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("sub/b.txt")).unwrap(),
+        "b changed\n"
+    );
+}
+
 #[test]
 fn test_empty_current_directory() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
 
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Create test directory with various files and subdirectories
     let test_dir = temp_dir.path().join("test_dir");
@@ -336,8 +518,7 @@ fn test_empty_current_directory_with_bgitignore() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
 
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Create .bgitignore file
     let gitignore_path = Path::new(&repo.worktree).join(".bgitignore");
@@ -372,8 +553,7 @@ fn test_get_tree_data_simple() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
 
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Create a simple directory structure
     let test_dir = temp_dir.path().join("test_dir");
@@ -384,14 +564,14 @@ fn test_get_tree_data_simple() {
     let tree_hash = repo.create_tree(&test_dir).unwrap();
 
     // Get tree data
-    let entries = repo.get_tree_data(&tree_hash).unwrap();
+    let entries = repo.get_tree_data(tree_hash).unwrap();
 
     // Verify entries
     assert_eq!(entries.len(), 1);
     let (mode, name, hash, obj_type) = &entries[0];
     assert_eq!(mode, "100644");
     assert_eq!(name, "file.txt");
-    assert_eq!(hash.len(), 40);
+    assert_eq!(hash.to_string().len(), oid_hex_len());
     assert!(matches!(obj_type, ObjectType::Blob));
 }
 
@@ -400,8 +580,7 @@ fn test_get_tree_data_complex() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
 
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Create a complex directory structure
     let test_dir = temp_dir.path().join("test_dir");
@@ -420,7 +599,7 @@ fn test_get_tree_data_complex() {
     let tree_hash = repo.create_tree(&test_dir).unwrap();
 
     // Get tree data
-    let entries = repo.get_tree_data(&tree_hash).unwrap();
+    let entries = repo.get_tree_data(tree_hash).unwrap();
 
     // Verify entries
     assert_eq!(entries.len(), 3);
@@ -433,21 +612,21 @@ fn test_get_tree_data_complex() {
     let (mode, name, hash, obj_type) = &entries[0];
     assert_eq!(mode, "100644");
     assert_eq!(name, "file1.txt");
-    assert_eq!(hash.len(), 40);
+    assert_eq!(hash.to_string().len(), oid_hex_len());
     assert!(matches!(obj_type, ObjectType::Blob));
 
     // Verify file2.txt
     let (mode, name, hash, obj_type) = &entries[1];
     assert_eq!(mode, "100644");
     assert_eq!(name, "file2.txt");
-    assert_eq!(hash.len(), 40);
+    assert_eq!(hash.to_string().len(), oid_hex_len());
     assert!(matches!(obj_type, ObjectType::Blob));
 
     // Verify subdir
     let (mode, name, hash, obj_type) = &entries[2];
     assert_eq!(mode, "40000");
     assert_eq!(name, "subdir");
-    assert_eq!(hash.len(), 40);
+    assert_eq!(hash.to_string().len(), oid_hex_len());
     assert!(matches!(obj_type, ObjectType::Tree));
 }
 
@@ -456,8 +635,7 @@ fn test_get_tree_data_empty() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
 
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Create empty directory
     let empty_dir = temp_dir.path().join("empty_dir");
@@ -467,7 +645,7 @@ fn test_get_tree_data_empty() {
     let tree_hash = repo.create_tree(&empty_dir).unwrap();
 
     // Get tree data
-    let entries = repo.get_tree_data(&tree_hash).unwrap();
+    let entries = repo.get_tree_data(tree_hash).unwrap();
 
     // Verify empty tree
     assert!(entries.is_empty());
@@ -478,18 +656,15 @@ fn test_get_tree_data_invalid_hash() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
 
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Test with invalid hash
-    let result = repo.get_tree_data("invalidhash");
-    assert!(result.is_err());
-    assert!(result.unwrap_err().contains("Oid hash not found for"));
+    let result = "invalidhash".parse::<Oid>();
+    assert!(matches!(result, Err(Error::InvalidRef(_))));
 
     // Test with non-existent hash
-    let result = repo.get_tree_data(&"a".repeat(40));
-    assert!(result.is_err());
-    assert!(result.unwrap_err().contains("Failed to read object"));
+    let result = repo.get_tree_data("a".repeat(oid_hex_len()).parse().unwrap());
+    assert!(matches!(result, Err(Error::NotFound(_))));
 }
 
 #[test]
@@ -497,8 +672,7 @@ fn test_commit_success() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
 
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Create some files
     let test_dir = temp_dir.path().join("test_dir");
@@ -507,23 +681,24 @@ fn test_commit_success() {
     fs::write(test_dir.join("file2.txt"), "Content 2").unwrap();
 
     // Create tree
-    let tree_hash = repo.create_tree(&Path::new(&repo_path)).unwrap();
+    let tree_hash = repo.create_tree(Path::new(&repo_path)).unwrap();
 
     // Create commit
     let commit_message = "Initial commit";
     let commit_hash = repo.create_commit(commit_message).unwrap();
 
     // Verify commit hash format
-    assert_eq!(commit_hash.len(), 40);
-    assert!(commit_hash.chars().all(|c| c.is_ascii_hexdigit()));
+    assert_eq!(commit_hash.to_string().len(), oid_hex_len());
+    assert!(commit_hash.to_string().chars().all(|c| c.is_ascii_hexdigit()));
 
     // Verify commit object exists
-    let (dir, file) = commit_hash.split_at(2);
+    let hex = commit_hash.to_string();
+    let (dir, file) = hex.split_at(2);
     let object_path = format!("{}/{}/objects/{}/{}", repo_path, GIT_DIR, dir, file);
     assert!(Path::new(&object_path).exists());
 
     // Verify commit content
-    let commit_data = repo.get_object(&commit_hash).unwrap();
+    let commit_data = repo.read_object(commit_hash, ObjectType::Commit).unwrap();
     let commit_str = String::from_utf8(commit_data).unwrap();
     println!("Commit string: {}", commit_str);
     assert!(commit_str.contains(&format!("tree {}", tree_hash)));
@@ -536,8 +711,7 @@ fn test_commit_with_parent() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
 
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Create initial commit
     let first_commit_message = "First commit";
@@ -548,7 +722,7 @@ fn test_commit_with_parent() {
     let second_commit_hash = repo.create_commit(second_commit_message).unwrap();
 
     // Verify second commit has parent
-    let commit_data = repo.get_object(&second_commit_hash).unwrap();
+    let commit_data = repo.read_object(second_commit_hash, ObjectType::Commit).unwrap();
     let commit_str = String::from_utf8(commit_data).unwrap();
     assert!(commit_str.contains(&format!("parent {}", first_commit_hash)));
 }
@@ -558,8 +732,7 @@ fn test_set_head() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
 
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Create a commit
     let commit_message = "Test commit";
@@ -569,10 +742,7 @@ fn test_set_head() {
     assert!(
         repo.set_ref(
             HEAD,
-            RefValue {
-                value: commit_hash.to_string(),
-                is_symbolic: false,
-            },
+            RefValue::Direct(commit_hash),
             true,
         )
         .is_ok()
@@ -586,7 +756,7 @@ fn test_set_head() {
     // Verify master branch points to the commit
     let master_path = format!("{}/{}/refs/heads/master", repo_path, GIT_DIR);
     let master_content = fs::read_to_string(&master_path).unwrap();
-    assert_eq!(master_content.trim(), commit_hash);
+    assert_eq!(master_content.trim(), commit_hash.to_string());
 }
 
 #[test]
@@ -594,8 +764,7 @@ fn test_get_head() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
 
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Create a commit
     let commit_message = "Test commit";
@@ -603,7 +772,7 @@ fn test_get_head() {
 
     // Get HEAD
     let head_hash = repo.get_ref(HEAD, true).unwrap();
-    assert_eq!(head_hash.value, commit_hash);
+    assert_eq!(head_hash, RefValue::Direct(commit_hash));
 
     // Test getting HEAD when it doesn't exist
     let head_path = format!("{}/{}/HEAD", repo_path, GIT_DIR);
@@ -616,8 +785,7 @@ fn test_commit_empty_message() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
 
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Create empty directory
     let test_dir = temp_dir.path().join("test_dir");
@@ -629,6 +797,7 @@ fn test_commit_empty_message() {
     assert!(
         result
             .unwrap_err()
+            .to_string()
             .contains("Commit message cannot be empty")
     );
 
@@ -638,6 +807,7 @@ fn test_commit_empty_message() {
     assert!(
         result
             .unwrap_err()
+            .to_string()
             .contains("Commit message cannot be empty")
     );
 }
@@ -647,8 +817,7 @@ fn test_commit_multiple_files() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
 
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Create complex directory structure
     let test_dir = temp_dir.path().join("test_dir");
@@ -664,18 +833,18 @@ fn test_commit_multiple_files() {
     fs::write(subdir.join("nested.txt"), "Nested content").unwrap();
 
     // Create tree
-    let tree_hash = repo.create_tree(&Path::new(&repo_path)).unwrap();
+    let tree_hash = repo.create_tree(Path::new(&repo_path)).unwrap();
 
     // Create commit
     let commit_message = "Commit with multiple files";
     let commit_hash = repo.create_commit(commit_message).unwrap();
 
     // Verify commit hash format
-    assert_eq!(commit_hash.len(), 40);
-    assert!(commit_hash.chars().all(|c| c.is_ascii_hexdigit()));
+    assert_eq!(commit_hash.to_string().len(), oid_hex_len());
+    assert!(commit_hash.to_string().chars().all(|c| c.is_ascii_hexdigit()));
 
     // Verify commit content
-    let commit_data = repo.get_object(&commit_hash).unwrap();
+    let commit_data = repo.read_object(commit_hash, ObjectType::Commit).unwrap();
     let commit_str = String::from_utf8(commit_data).unwrap();
     assert!(commit_str.contains(&format!("tree {}", tree_hash)));
     assert!(commit_str.contains(commit_message));
@@ -686,33 +855,32 @@ fn test_get_commit() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
 
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Create initial commit
     let commit_message = "Initial commit";
     let commit_hash = repo.create_commit(commit_message).unwrap();
 
     // Get and verify commit
-    let commit = repo.get_commit(&commit_hash).unwrap();
+    let commit = repo.get_commit(commit_hash).unwrap();
     assert_eq!(commit.message, commit_message);
     assert!(commit.parents.is_empty());
     assert!(!commit.timestamp.is_empty());
-    assert!(!commit.tree.is_empty());
+    assert!(!commit.tree.is_zero());
 
     // Create second commit
     let second_message = "Second commit";
     let second_hash = repo.create_commit(second_message).unwrap();
 
     // Get and verify second commit
-    let second_commit = repo.get_commit(&second_hash).unwrap();
+    let second_commit = repo.get_commit(second_hash).unwrap();
     assert_eq!(second_commit.message, second_message);
     assert_eq!(second_commit.parents[0], commit_hash);
     assert!(!second_commit.timestamp.is_empty());
-    assert!(!second_commit.tree.is_empty());
+    assert!(!second_commit.tree.is_zero());
 
     // Test invalid commit hash
-    let result = repo.get_commit("invalidhash");
+    let result = repo.get_commit("a".repeat(oid_hex_len()).parse().unwrap());
     assert!(result.is_err());
 }
 
@@ -721,8 +889,7 @@ fn test_log() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
 
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Create first commit
     let first_message = "First commit";
@@ -738,7 +905,7 @@ fn test_log() {
 
     // Get all commits in order
     let commits = repo
-        .iter_commits_and_parents(vec![third_hash.clone()])
+        .iter_commits_and_parents(vec![third_hash])
         .unwrap();
 
     // Verify commits are in correct order
@@ -748,15 +915,15 @@ fn test_log() {
     assert_eq!(commits[2], first_hash);
 
     // Verify commit data
-    let third_commit = repo.get_commit(&third_hash).unwrap();
+    let third_commit = repo.get_commit(third_hash).unwrap();
     assert_eq!(third_commit.message, third_message);
-    assert_eq!(third_commit.parents, vec![second_hash.clone()]);
+    assert_eq!(third_commit.parents, vec![second_hash]);
 
-    let second_commit = repo.get_commit(&second_hash).unwrap();
+    let second_commit = repo.get_commit(second_hash).unwrap();
     assert_eq!(second_commit.message, second_message);
-    assert_eq!(second_commit.parents, vec![first_hash.clone()]);
+    assert_eq!(second_commit.parents, vec![first_hash]);
 
-    let first_commit = repo.get_commit(&first_hash).unwrap();
+    let first_commit = repo.get_commit(first_hash).unwrap();
     assert_eq!(first_commit.message, first_message);
     assert!(first_commit.parents.is_empty());
 }
@@ -766,21 +933,66 @@ fn test_log_empty_repository() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
 
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Log should work on an empty repository
     let result = repo.log();
     assert!(result.is_ok());
 }
 
+#[test]
+fn test_log_entries() {
+    let temp_dir = TempDir::new().unwrap();
+    let repo = init_repo(temp_dir.path().to_str().unwrap());
+
+    fs::write(temp_dir.path().join("file.txt"), "one\n").unwrap();
+    let first = repo.create_commit("First commit").unwrap();
+    repo.create_tag("v1", first).unwrap();
+    fs::write(temp_dir.path().join("file.txt"), "two\n").unwrap();
+    let second = repo.create_commit("Second commit").unwrap();
+
+    // Newest first, with the refs pointing at each commit
+    let entries = repo.log().unwrap();
+    let oids: Vec<Oid> = entries.iter().map(|entry| entry.commit.oid).collect();
+    assert_eq!(oids, vec![second, first]);
+    assert_eq!(entries[0].refs, vec!["refs/heads/master"]);
+    assert_eq!(entries[1].refs, vec!["refs/tags/v1"]);
+    assert!(entries.iter().all(|entry| entry.diff.is_none()));
+
+    let options = LogOptions {
+        diff: Some(DiffFormat::Patch),
+        ..Default::default()
+    };
+    let entries = repo.log_with_options(&options).unwrap();
+    let diff = entries[0].diff.as_ref().unwrap();
+    assert_eq!(diff.changes[0].path(), "file.txt");
+    assert_eq!(diff.changes[0].kind, ChangeKind::Modified);
+    let patch = String::from_utf8(diff.patch.clone()).unwrap();
+    assert!(patch.contains("-one\n+two\n"), "{}", patch);
+    let diff = entries[1].diff.as_ref().unwrap();
+    assert_eq!(diff.changes[0].kind, ChangeKind::Added);
+    assert!(String::from_utf8_lossy(&diff.patch).contains("+one"));
+
+    // The changes are left uncolored for the caller to render
+    assert!(!patch.contains('\x1b'));
+    let differ = Differ::new(&repo);
+    assert!(differ.format_tree_diff(diff).unwrap().contains("\x1b[32m+one\x1b[0m"));
+    let differ = Differ::with_options(
+        &repo,
+        DiffOptions {
+            format: DiffFormat::NameStatus,
+            ..Default::default()
+        },
+    );
+    assert_eq!(differ.format_tree_diff(diff).unwrap(), "A\tfile.txt\n");
+}
+
 #[test]
 fn test_checkout_success() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
 
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Create initial commit
     let first_message = "First commit";
@@ -797,22 +1009,22 @@ fn test_checkout_success() {
     let second_hash = repo.create_commit(second_message).unwrap();
 
     // Checkout first commit
-    assert!(repo.checkout(&first_hash).is_ok());
+    assert!(repo.checkout(&first_hash.to_string()).is_ok());
 
     // Verify HEAD points to first commit
     let head_hash = repo.get_ref(HEAD, true).unwrap();
-    assert_eq!(head_hash.value, first_hash);
+    assert_eq!(head_hash, RefValue::Direct(first_hash));
 
     // Verify worktree is empty (first commit had no files)
     assert!(!test_dir.join("file1.txt").exists());
     assert!(!test_dir.join("file2.txt").exists());
 
     // Checkout second commit
-    assert!(repo.checkout(&second_hash).is_ok());
+    assert!(repo.checkout(&second_hash.to_string()).is_ok());
 
     // Verify HEAD points to second commit
     let head_hash = repo.get_ref(HEAD, true).unwrap();
-    assert_eq!(head_hash.value, second_hash);
+    assert_eq!(head_hash, RefValue::Direct(second_hash));
 
     // Verify worktree has the files from second commit
     assert!(test_dir.join("file1.txt").exists());
@@ -832,27 +1044,23 @@ fn test_checkout_invalid_hash() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
 
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Try to checkout with invalid hash format
     let result = repo.checkout("not40chars");
-    assert!(result.is_err());
     assert!(
+        matches!(&result, Err(Error::InvalidRef(name)) if name == "not40chars"),
+        "Expected an invalid reference, but got: {:?}",
         result
-            .as_ref()
-            .unwrap_err()
-            .contains("Oid hash not found for"),
-        "Expected error message to contain 'Oid hash not found for', but got: {}",
-        result.unwrap_err()
     );
 
     // Try to checkout non-existent commit with valid hash format
-    let result = repo.checkout("0000000000000000000000000000000000000000");
-    assert!(result.is_err());
-    let error = result.unwrap_err();
-    println!("Actual error message: {}", error);
-    assert!(error.contains("Commit with hash: 0000000000000000000000000000000000000000 not found"));
+    let result = repo.checkout(&"0".repeat(oid_hex_len()));
+    assert!(
+        matches!(&result, Err(Error::NotFound(oid)) if oid.is_zero()),
+        "Expected a missing object, but got: {:?}",
+        result
+    );
 }
 
 #[test]
@@ -860,8 +1068,7 @@ fn test_checkout_with_existing_files() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
 
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Create initial commit with some files
     let test_dir = temp_dir.path().join("test_dir");
@@ -874,7 +1081,7 @@ fn test_checkout_with_existing_files() {
     fs::write(test_dir.join("file3.txt"), "Another uncommitted file").unwrap();
 
     // Checkout the same commit (should work and preserve uncommitted files)
-    let result = repo.checkout(&first_hash);
+    let result = repo.checkout(&first_hash.to_string());
     assert!(
         result.is_ok(),
         "Error checking out commit: {:?}",
@@ -898,8 +1105,7 @@ fn test_checkout_directory_structure() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
 
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Create initial commit with nested directory structure
     let test_dir = temp_dir.path().join("test_dir");
@@ -916,7 +1122,7 @@ fn test_checkout_directory_structure() {
     let second_hash = repo.create_commit("Second commit").unwrap();
 
     // Checkout first commit
-    assert!(repo.checkout(&first_hash).is_ok());
+    assert!(repo.checkout(&first_hash.to_string()).is_ok());
 
     // Verify directory structure from first commit
     assert!(test_dir.join("subdir").exists());
@@ -928,7 +1134,7 @@ fn test_checkout_directory_structure() {
     assert!(!test_dir.join("file.txt").exists());
 
     // Checkout second commit
-    assert!(repo.checkout(&second_hash).is_ok());
+    assert!(repo.checkout(&second_hash.to_string()).is_ok());
 
     // Verify directory structure from second commit
     assert!(!test_dir.join("subdir").exists());
@@ -944,8 +1150,7 @@ fn test_ignore_directories() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
 
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Create test directory structure
     let test_dir = temp_dir.path().join("test_dir");
@@ -998,8 +1203,7 @@ fn test_is_ignored_with_directory_pattern() {
     let repo_path = temp_dir.path();
 
     // Initialize a repository
-    let repo = Repository::new(repo_path.to_str().unwrap());
-    repo.init().unwrap();
+    let repo = init_repo(repo_path.to_str().unwrap());
 
     // Create a .bgitignore file with a directory pattern
     let gitignore_path = Path::new(&repo.worktree).join(".bgitignore");
@@ -1036,8 +1240,7 @@ fn test_head_content_after_init() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
 
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Verify HEAD content after initialization
     let head_path = format!("{}/{}/HEAD", repo_path, GIT_DIR);
@@ -1058,8 +1261,7 @@ fn test_first_commit_handling() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
 
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Create a file for the first commit
     let test_file = temp_dir.path().join("test.txt");
@@ -1077,12 +1279,12 @@ fn test_first_commit_handling() {
     // Verify master branch points to the commit
     let master_path = format!("{}/{}/refs/heads/master", repo_path, GIT_DIR);
     let master_content = fs::read_to_string(&master_path).unwrap();
-    assert_eq!(master_content.trim(), commit_hash);
+    assert_eq!(master_content.trim(), commit_hash.to_string());
 
     // Verify commit has no parent
-    let commit = repo.get_commit(&commit_hash).unwrap();
+    let commit = repo.get_commit(commit_hash).unwrap();
     assert!(commit.parents.is_empty());
-    assert!(!commit.tree.is_empty());
+    assert!(!commit.tree.is_zero());
 
     // Verify log works now
     assert!(repo.log().is_ok());
@@ -1093,8 +1295,7 @@ fn test_empty_to_committed_transition() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
 
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Verify initial HEAD content
     let head_path = format!("{}/{}/HEAD", repo_path, GIT_DIR);
@@ -1113,7 +1314,7 @@ fn test_empty_to_committed_transition() {
     // Verify master branch points to the commit
     let master_path = format!("{}/{}/refs/heads/master", repo_path, GIT_DIR);
     let master_content = fs::read_to_string(&master_path).unwrap();
-    assert_eq!(master_content.trim(), first_commit_hash);
+    assert_eq!(master_content.trim(), first_commit_hash.to_string());
 
     // Create second commit
     fs::write(&test_file, "Updated content").unwrap();
@@ -1125,11 +1326,11 @@ fn test_empty_to_committed_transition() {
 
     // Verify master branch points to the second commit
     let master_content = fs::read_to_string(&master_path).unwrap();
-    assert_eq!(master_content.trim(), second_commit_hash);
+    assert_eq!(master_content.trim(), second_commit_hash.to_string());
 
     // Verify second commit has first commit as parent
-    let second_commit = repo.get_commit(&second_commit_hash).unwrap();
-    assert_eq!(second_commit.parents, vec![first_commit_hash.clone()]);
+    let second_commit = repo.get_commit(second_commit_hash).unwrap();
+    assert_eq!(second_commit.parents, vec![first_commit_hash]);
 }
 
 #[test]
@@ -1137,8 +1338,7 @@ fn test_create_tag() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
 
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Create a commit to tag
     let test_file = temp_dir.path().join("test.txt");
@@ -1147,12 +1347,12 @@ fn test_create_tag() {
 
     // Create a tag
     let tag_name = "v1.0.0";
-    assert!(repo.create_tag(tag_name, &commit_hash).is_ok());
+    assert!(repo.create_tag(tag_name, commit_hash).is_ok());
 
     // Verify tag file exists and contains correct hash
     let tag_path = format!("{}/{}/refs/tags/{}", repo_path, GIT_DIR, tag_name);
     let tag_content = fs::read_to_string(&tag_path).unwrap();
-    assert_eq!(tag_content.trim(), commit_hash);
+    assert_eq!(tag_content.trim(), commit_hash.to_string());
 }
 
 #[test]
@@ -1160,8 +1360,7 @@ fn test_create_multiple_tags() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
 
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Create initial commit
     let test_file = temp_dir.path().join("test.txt");
@@ -1173,8 +1372,8 @@ fn test_create_multiple_tags() {
     let second_commit = repo.create_commit("Second commit").unwrap();
 
     // Create tags for both commits
-    assert!(repo.create_tag("v1.0.0", &first_commit).is_ok());
-    assert!(repo.create_tag("v1.1.0", &second_commit).is_ok());
+    assert!(repo.create_tag("v1.0.0", first_commit).is_ok());
+    assert!(repo.create_tag("v1.1.0", second_commit).is_ok());
 
     // Verify both tags exist with correct hashes
     let tag1_path = format!("{}/{}/refs/tags/v1.0.0", repo_path, GIT_DIR);
@@ -1183,8 +1382,8 @@ fn test_create_multiple_tags() {
     let tag1_content = fs::read_to_string(&tag1_path).unwrap();
     let tag2_content = fs::read_to_string(&tag2_path).unwrap();
 
-    assert_eq!(tag1_content.trim(), first_commit);
-    assert_eq!(tag2_content.trim(), second_commit);
+    assert_eq!(tag1_content.trim(), first_commit.to_string());
+    assert_eq!(tag2_content.trim(), second_commit.to_string());
 }
 
 #[test]
@@ -1192,17 +1391,11 @@ fn test_create_tag_invalid_commit() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
 
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
-    // Try to create tag with invalid commit hash
-    let result = repo.create_tag("v1.0.0", "invalidhash");
-    assert!(result.is_err());
-    assert!(
-        result
-            .unwrap_err()
-            .contains("Invalid hash format: invalidhash")
-    );
+    // A tag target is resolved first, an invalid name names no commit
+    let result = repo.get_oid_hash("invalidhash");
+    assert!(matches!(result, Err(Error::InvalidRef(name)) if name == "invalidhash"));
 }
 
 #[test]
@@ -1210,24 +1403,23 @@ fn test_create_tag_overwrite() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
 
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Create initial commit and tag
     let test_file = temp_dir.path().join("test.txt");
     fs::write(&test_file, "Test content").unwrap();
     let first_commit = repo.create_commit("Initial commit").unwrap();
-    assert!(repo.create_tag("v1.0.0", &first_commit).is_ok());
+    assert!(repo.create_tag("v1.0.0", first_commit).is_ok());
 
     // Create second commit and overwrite tag
     fs::write(&test_file, "Updated content").unwrap();
     let second_commit = repo.create_commit("Second commit").unwrap();
-    assert!(repo.create_tag("v1.0.0", &second_commit).is_ok());
+    assert!(repo.create_tag("v1.0.0", second_commit).is_ok());
 
     // Verify tag now points to second commit
     let tag_path = format!("{}/{}/refs/tags/v1.0.0", repo_path, GIT_DIR);
     let tag_content = fs::read_to_string(&tag_path).unwrap();
-    assert_eq!(tag_content.trim(), second_commit);
+    assert_eq!(tag_content.trim(), second_commit.to_string());
 }
 
 #[test]
@@ -1235,8 +1427,7 @@ fn test_get_oid_hash() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
 
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Create initial commit
     let test_file = temp_dir.path().join("test.txt");
@@ -1248,7 +1439,7 @@ fn test_get_oid_hash() {
     let second_commit = repo.create_commit("Second commit").unwrap();
 
     // Test with direct hash
-    let result = repo.get_oid_hash(&first_commit);
+    let result = repo.get_oid_hash(&first_commit.to_string());
     assert!(result.is_ok());
     assert_eq!(result.unwrap(), first_commit);
 
@@ -1258,28 +1449,18 @@ fn test_get_oid_hash() {
     assert_eq!(result.unwrap(), second_commit);
 
     // Test with tag reference
-    assert!(repo.create_tag("v1.0", &first_commit).is_ok());
+    assert!(repo.create_tag("v1.0", first_commit).is_ok());
     let result = repo.get_oid_hash("refs/tags/v1.0");
     assert!(result.is_ok());
     assert_eq!(result.unwrap(), first_commit);
 
     // Test with invalid hash
     let result = repo.get_oid_hash("invalidhash");
-    assert!(result.is_err());
-    assert!(
-        result
-            .unwrap_err()
-            .contains("Oid hash not found for: invalidhash")
-    );
+    assert!(matches!(result, Err(Error::InvalidRef(name)) if name == "invalidhash"));
 
     // Test with non-existent reference
     let result = repo.get_oid_hash("refs/heads/nonexistent");
-    assert!(result.is_err());
-    assert!(
-        result
-            .unwrap_err()
-            .contains("Oid hash not found for: refs/heads/nonexistent")
-    );
+    assert!(matches!(result, Err(Error::InvalidRef(name)) if name == "refs/heads/nonexistent"));
 
     // Test with reference to reference (HEAD -> refs/heads/master)
     let result = repo.get_oid_hash("HEAD");
@@ -1292,8 +1473,7 @@ fn test_get_oid_hash_reference_chain() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
 
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Create initial commit
     let test_file = temp_dir.path().join("test.txt");
@@ -1306,7 +1486,7 @@ fn test_get_oid_hash_reference_chain() {
     assert_eq!(result.unwrap(), first_commit);
 
     // Create a tag pointing to the branch
-    assert!(repo.create_tag("feature-tag", &first_commit).is_ok());
+    assert!(repo.create_tag("feature-tag", first_commit).is_ok());
 
     // Test resolving tag through reference chain
     let result = repo.get_oid_hash("refs/tags/feature-tag");
@@ -1317,21 +1497,20 @@ fn test_get_oid_hash_reference_chain() {
 #[test]
 fn test_branch_creation() {
     let temp_dir = TempDir::new().unwrap();
-    let repo = Repository::new(temp_dir.path().to_str().unwrap());
-    repo.init().unwrap();
+    let repo = init_repo(temp_dir.path().to_str().unwrap());
 
     // Create initial commit
     let commit_hash = repo.create_commit("Initial commit").unwrap();
 
     // Create a new branch
-    repo.create_branch("feature", Some(commit_hash.clone()))
+    repo.create_branch("feature", Some(commit_hash))
         .unwrap();
 
     // Verify branch exists and points to the correct commit
     let (ref_name, ref_value) = repo.get_ref_internal("refs/heads/feature", true).unwrap();
     assert_eq!(ref_name, "refs/heads/feature");
-    assert_eq!(ref_value.value, commit_hash);
-    assert!(!ref_value.is_symbolic);
+    assert_eq!(ref_value, RefValue::Direct(commit_hash));
+    assert!(matches!(ref_value, RefValue::Direct(_)));
 
     // Create another branch without specifying commit (should use HEAD)
     repo.create_branch("develop", None).unwrap();
@@ -1339,14 +1518,13 @@ fn test_branch_creation() {
     // Verify new branch points to the same commit as HEAD
     let (_, head_value) = repo.get_ref_internal("HEAD", true).unwrap();
     let (_, develop_value) = repo.get_ref_internal("refs/heads/develop", true).unwrap();
-    assert_eq!(develop_value.value, head_value.value);
+    assert_eq!(develop_value, head_value);
 }
 
 #[test]
 fn test_checkout() {
     let temp_dir = TempDir::new().unwrap();
-    let repo = Repository::new(temp_dir.path().to_str().unwrap());
-    repo.init().unwrap();
+    let repo = init_repo(temp_dir.path().to_str().unwrap());
 
     // Create initial commit
     let first_commit = repo.create_commit("First commit").unwrap();
@@ -1359,7 +1537,7 @@ fn test_checkout() {
     let second_commit = repo.create_commit("Second commit").unwrap();
 
     // Create a branch at the first commit
-    repo.create_branch("old_version", Some(first_commit.clone()))
+    repo.create_branch("old_version", Some(first_commit))
         .unwrap();
 
     // Checkout the branch
@@ -1367,17 +1545,17 @@ fn test_checkout() {
 
     // Verify we're at the first commit
     let (_, head_value) = repo.get_ref_internal("HEAD", true).unwrap();
-    assert_eq!(head_value.value, first_commit);
+    assert_eq!(head_value, RefValue::Direct(first_commit));
 
     // Verify the file doesn't exist (was created after first commit)
     assert!(!file_path.exists());
 
     // Checkout back to the second commit
-    repo.checkout(&second_commit).unwrap();
+    repo.checkout(&second_commit.to_string()).unwrap();
 
     // Verify we're at the second commit
     let (_, head_value) = repo.get_ref_internal("HEAD", true).unwrap();
-    assert_eq!(head_value.value, second_commit);
+    assert_eq!(head_value, RefValue::Direct(second_commit));
 
     // Verify the file exists again
     assert!(file_path.exists());
@@ -1402,13 +1580,13 @@ fn test_master_branch_creation_and_updates() {
 
     // Check that HEAD points to master
     let head_ref = repo.get_ref("HEAD", false).unwrap();
-    assert!(head_ref.is_symbolic);
-    assert_eq!(head_ref.value, "ref: refs/heads/master");
+    assert!(matches!(head_ref, RefValue::Symbolic(_)));
+    assert_eq!(head_ref, RefValue::Symbolic("refs/heads/master".to_string()));
 
     // Check that master branch exists but is empty
     let master_ref = repo.get_ref("refs/heads/master", false);
     assert!(master_ref.is_ok());
-    assert!(master_ref.unwrap().value.is_empty());
+    assert_eq!(master_ref.unwrap(), RefValue::Unborn);
 
     // Create a test file and commit it
     let test_file_path = format!("{}/test.txt", repo_path);
@@ -1419,13 +1597,13 @@ fn test_master_branch_creation_and_updates() {
 
     // Check that HEAD still points to master
     let head_ref: RefValue = repo.get_ref("HEAD", false).unwrap();
-    assert!(head_ref.is_symbolic);
-    assert_eq!(head_ref.value, "ref: refs/heads/master");
+    assert!(matches!(head_ref, RefValue::Symbolic(_)));
+    assert_eq!(head_ref, RefValue::Symbolic("refs/heads/master".to_string()));
 
     // Check that master branch now points to the commit
     let master_ref = repo.get_ref("refs/heads/master", false).unwrap();
-    assert!(!master_ref.is_symbolic);
-    assert_eq!(master_ref.value, commit_hash);
+    assert!(matches!(master_ref, RefValue::Direct(_)));
+    assert_eq!(master_ref, RefValue::Direct(commit_hash));
 
     // Clean up
     temp_dir.close().unwrap();
@@ -1448,13 +1626,13 @@ fn test_master_branch_dereferencing() {
 
     // Check that HEAD dereferences to the commit hash
     let head_ref = repo.get_ref("HEAD", true).unwrap();
-    assert!(!head_ref.is_symbolic);
-    assert_eq!(head_ref.value, commit_hash);
+    assert!(matches!(head_ref, RefValue::Direct(_)));
+    assert_eq!(head_ref, RefValue::Direct(commit_hash));
 
     // Check that master branch points to the commit
     let master_ref = repo.get_ref("refs/heads/master", true).unwrap();
-    assert!(!master_ref.is_symbolic);
-    assert_eq!(master_ref.value, commit_hash);
+    assert!(matches!(master_ref, RefValue::Direct(_)));
+    assert_eq!(master_ref, RefValue::Direct(commit_hash));
 
     // Clean up
     temp_dir.close().unwrap();
@@ -1465,8 +1643,7 @@ fn test_detached_head_and_branch_creation() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
 
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Create initial commit
     let test_file = temp_dir.path().join("test.txt");
@@ -1478,22 +1655,22 @@ fn test_detached_head_and_branch_creation() {
     let second_commit = repo.create_commit("Second commit").unwrap();
 
     // Checkout the first commit (detached HEAD)
-    assert!(repo.checkout(&first_commit).is_ok());
+    assert!(repo.checkout(&first_commit.to_string()).is_ok());
 
     // Verify HEAD is detached (points directly to commit hash)
     let head_ref = repo.get_ref("HEAD", false).unwrap();
-    assert!(!head_ref.is_symbolic);
-    assert_eq!(head_ref.value, first_commit);
+    assert!(matches!(head_ref, RefValue::Direct(_)));
+    assert_eq!(head_ref, RefValue::Direct(first_commit));
 
     // Verify that branch master points to the last commit
     let master_ref = repo.get_ref("refs/heads/master", false).unwrap();
-    assert!(!master_ref.is_symbolic);
-    assert_eq!(master_ref.value, second_commit);
+    assert!(matches!(master_ref, RefValue::Direct(_)));
+    assert_eq!(master_ref, RefValue::Direct(second_commit));
 
     // Create a new branch from the first commit
     let new_branch_name = "feature-branch";
     assert!(
-        repo.create_branch(new_branch_name, Some(first_commit.clone()))
+        repo.create_branch(new_branch_name, Some(first_commit))
             .is_ok()
     );
 
@@ -1502,17 +1679,16 @@ fn test_detached_head_and_branch_creation() {
 
     // Verify HEAD is now on the new branch
     let head_ref = repo.get_ref("HEAD", false).unwrap();
-    assert!(head_ref.is_symbolic);
     assert_eq!(
-        head_ref.value,
-        format!("ref: refs/heads/{}", new_branch_name)
+        head_ref,
+        RefValue::Symbolic(format!("refs/heads/{}", new_branch_name))
     );
 
     // Verify the branch points to the first commit
     let branch_ref = repo
         .get_ref(&format!("refs/heads/{}", new_branch_name), true)
         .unwrap();
-    assert_eq!(branch_ref.value, first_commit);
+    assert_eq!(branch_ref, RefValue::Direct(first_commit));
 }
 
 #[test]
@@ -1520,8 +1696,7 @@ fn test_get_branch_name() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
 
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Initially HEAD points to master
     let branch_name = repo.get_branch_name().unwrap();
@@ -1539,7 +1714,7 @@ fn test_get_branch_name() {
     // Create a new branch and checkout to it
     let new_branch = "feature-branch";
     assert!(
-        repo.create_branch(new_branch, Some(first_commit.clone()))
+        repo.create_branch(new_branch, Some(first_commit))
             .is_ok()
     );
     assert!(repo.checkout(new_branch).is_ok());
@@ -1549,7 +1724,7 @@ fn test_get_branch_name() {
     assert_eq!(branch_name, Some(new_branch.to_string()));
 
     // Checkout to a commit hash (detached HEAD)
-    assert!(repo.checkout(&first_commit).is_ok());
+    assert!(repo.checkout(&first_commit.to_string()).is_ok());
     let branch_name = repo.get_branch_name().unwrap();
     assert!(branch_name.is_none());
 }
@@ -1559,8 +1734,7 @@ fn test_iter_branch_names() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
 
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Initially only master exists
     let branch_names = repo.iter_branch_names().unwrap();
@@ -1575,7 +1749,7 @@ fn test_iter_branch_names() {
     let branches = vec!["feature-1", "feature-2", "develop"];
     for branch in &branches {
         assert!(
-            repo.create_branch(branch, Some(first_commit.clone()))
+            repo.create_branch(branch, Some(first_commit))
                 .is_ok()
         );
     }
@@ -1606,7 +1780,7 @@ fn test_iter_branch_names() {
     assert_eq!(branch_names, expected);
 
     // Checkout to a commit hash (detached HEAD) and verify no branch is marked with *
-    assert!(repo.checkout(&first_commit).is_ok());
+    assert!(repo.checkout(&first_commit.to_string()).is_ok());
     let mut branch_names = repo.iter_branch_names().unwrap();
     branch_names.sort();
 
@@ -1621,8 +1795,7 @@ fn test_reset_success() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
 
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Create initial commit
     let test_file = temp_dir.path().join("test.txt");
@@ -1635,26 +1808,26 @@ fn test_reset_success() {
 
     // Verify HEAD points to second commit
     let head_ref = repo.get_ref(HEAD, true).unwrap();
-    assert_eq!(head_ref.value, second_commit);
+    assert_eq!(head_ref, RefValue::Direct(second_commit));
 
     // Verify master branch points to second commit
     let master_ref = repo.get_ref("refs/heads/master", true).unwrap();
-    assert_eq!(master_ref.value, second_commit);
+    assert_eq!(master_ref, RefValue::Direct(second_commit));
 
     // Reset to first commit
     assert!(
-        repo.reset(&first_commit).is_ok(),
+        repo.reset(first_commit).is_ok(),
         "Failed to reset to commit, {}",
-        repo.reset(&first_commit).unwrap_err()
+        repo.reset(first_commit).unwrap_err()
     );
 
     // Verify HEAD points to first commit
     let head_ref = repo.get_ref(HEAD, true).unwrap();
-    assert_eq!(head_ref.value, first_commit);
+    assert_eq!(head_ref, RefValue::Direct(first_commit));
 
     // Verify master branch points to first commit
     let master_ref = repo.get_ref("refs/heads/master", true).unwrap();
-    assert_eq!(master_ref.value, first_commit);
+    assert_eq!(master_ref, RefValue::Direct(first_commit));
 }
 
 #[test]
@@ -1662,13 +1835,11 @@ fn test_reset_invalid_commit() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
 
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Try to reset to non-existent commit
-    let result = repo.reset("a".repeat(40).as_str());
-    assert!(result.is_err());
-    assert!(result.unwrap_err().contains("Commit with hash:"));
+    let result = repo.reset("a".repeat(oid_hex_len()).parse().unwrap());
+    assert!(matches!(result, Err(Error::NotFound(_))));
 }
 
 #[test]
@@ -1676,8 +1847,7 @@ fn test_reset_detached_head() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
 
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Create initial commit
     let test_file = temp_dir.path().join("test.txt");
@@ -1689,17 +1859,17 @@ fn test_reset_detached_head() {
     let _second_commit = repo.create_commit("Second commit").unwrap();
 
     // Reset to first commit
-    assert!(repo.reset(&first_commit).is_ok());
+    assert!(repo.reset(first_commit).is_ok());
 
     // Verify HEAD is in normal state
     let head_ref = repo.get_ref(HEAD, false).unwrap();
-    assert!(head_ref.is_symbolic);
-    assert_eq!(head_ref.value, "ref: refs/heads/master");
+    assert!(matches!(head_ref, RefValue::Symbolic(_)));
+    assert_eq!(head_ref, RefValue::Symbolic("refs/heads/master".to_string()));
 
     // Verify master branch points to first commit
     let master_ref = repo.get_ref("refs/heads/master", false).unwrap();
-    assert!(!master_ref.is_symbolic);
-    assert_eq!(master_ref.value, first_commit);
+    assert!(matches!(master_ref, RefValue::Direct(_)));
+    assert_eq!(master_ref, RefValue::Direct(first_commit));
 }
 
 #[test]
@@ -1707,8 +1877,7 @@ fn test_reset_multiple_commits() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
 
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Create initial commit
     let test_file = temp_dir.path().join("test.txt");
@@ -1729,22 +1898,22 @@ fn test_reset_multiple_commits() {
 
     // Verify HEAD points to fourth commit
     let head_ref = repo.get_ref(HEAD, true).unwrap();
-    assert_eq!(head_ref.value, fourth_commit);
+    assert_eq!(head_ref, RefValue::Direct(fourth_commit));
 
     // Verify master branch points to fourth commit
     let master_ref = repo.get_ref("refs/heads/master", true).unwrap();
-    assert_eq!(master_ref.value, fourth_commit);
+    assert_eq!(master_ref, RefValue::Direct(fourth_commit));
 
     // Reset to second commit
-    assert!(repo.reset(&second_commit).is_ok());
+    assert!(repo.reset(second_commit).is_ok());
 
     // Verify HEAD points to second commit
     let head_ref = repo.get_ref(HEAD, true).unwrap();
-    assert_eq!(head_ref.value, second_commit);
+    assert_eq!(head_ref, RefValue::Direct(second_commit));
 
     // Verify master branch points to second commit
     let master_ref = repo.get_ref("refs/heads/master", true).unwrap();
-    assert_eq!(master_ref.value, second_commit);
+    assert_eq!(master_ref, RefValue::Direct(second_commit));
 
     // Verify file content matches second commit
     assert_eq!(fs::read_to_string(&test_file).unwrap(), "Second content");
@@ -1752,7 +1921,7 @@ fn test_reset_multiple_commits() {
     // Create a new branch at fourth commit
     let new_branch = "feature-branch";
     assert!(
-        repo.create_branch(new_branch, Some(fourth_commit.clone()))
+        repo.create_branch(new_branch, Some(fourth_commit))
             .is_ok()
     );
 
@@ -1760,18 +1929,18 @@ fn test_reset_multiple_commits() {
     let branch_ref = repo
         .get_ref(&format!("refs/heads/{}", new_branch), true)
         .unwrap();
-    assert_eq!(branch_ref.value, fourth_commit);
+    assert_eq!(branch_ref, RefValue::Direct(fourth_commit));
 
     // Reset to first commit
-    assert!(repo.reset(&first_commit).is_ok());
+    assert!(repo.reset(first_commit).is_ok());
 
     // Verify HEAD points to first commit
     let head_ref = repo.get_ref(HEAD, true).unwrap();
-    assert_eq!(head_ref.value, first_commit);
+    assert_eq!(head_ref, RefValue::Direct(first_commit));
 
     // Verify master branch points to first commit
     let master_ref = repo.get_ref("refs/heads/master", true).unwrap();
-    assert_eq!(master_ref.value, first_commit);
+    assert_eq!(master_ref, RefValue::Direct(first_commit));
 
     // Verify file content matches first commit
     assert_eq!(fs::read_to_string(&test_file).unwrap(), "Initial content");
@@ -1780,7 +1949,7 @@ fn test_reset_multiple_commits() {
     let branch_ref = repo
         .get_ref(&format!("refs/heads/{}", new_branch), true)
         .unwrap();
-    assert_eq!(branch_ref.value, fourth_commit);
+    assert_eq!(branch_ref, RefValue::Direct(fourth_commit));
 
     // Create a new commit after reset
     fs::write(&test_file, "New content after reset").unwrap();
@@ -1788,11 +1957,11 @@ fn test_reset_multiple_commits() {
 
     // Verify HEAD points to new commit
     let head_ref = repo.get_ref(HEAD, true).unwrap();
-    assert_eq!(head_ref.value, new_commit);
+    assert_eq!(head_ref, RefValue::Direct(new_commit));
 
     // Verify master branch points to new commit
     let master_ref = repo.get_ref("refs/heads/master", true).unwrap();
-    assert_eq!(master_ref.value, new_commit);
+    assert_eq!(master_ref, RefValue::Direct(new_commit));
 
     // Verify file content matches new commit
     assert_eq!(
@@ -1803,7 +1972,7 @@ fn test_reset_multiple_commits() {
     // Create another branch at the new commit
     let another_branch = "another-branch";
     assert!(
-        repo.create_branch(another_branch, Some(new_commit.clone()))
+        repo.create_branch(another_branch, Some(new_commit))
             .is_ok()
     );
 
@@ -1811,18 +1980,18 @@ fn test_reset_multiple_commits() {
     let branch_ref = repo
         .get_ref(&format!("refs/heads/{}", another_branch), true)
         .unwrap();
-    assert_eq!(branch_ref.value, new_commit);
+    assert_eq!(branch_ref, RefValue::Direct(new_commit));
 
     // Verify feature branch still points to fourth commit
     let branch_ref = repo
         .get_ref(&format!("refs/heads/{}", new_branch), true)
         .unwrap();
-    assert_eq!(branch_ref.value, fourth_commit);
+    assert_eq!(branch_ref, RefValue::Direct(fourth_commit));
 
     // Verify HEAD is symbolic and points to master
     let head_ref = repo.get_ref(HEAD, false).unwrap();
-    assert!(head_ref.is_symbolic);
-    assert_eq!(head_ref.value, "ref: refs/heads/master");
+    assert!(matches!(head_ref, RefValue::Symbolic(_)));
+    assert_eq!(head_ref, RefValue::Symbolic("refs/heads/master".to_string()));
 }
 
 #[test]
@@ -1830,8 +1999,7 @@ fn test_show_simple() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
 
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Create initial file
     let test_file = temp_dir.path().join("test.txt");
@@ -1843,7 +2011,7 @@ fn test_show_simple() {
     let second_commit = repo.create_commit("Second commit").unwrap();
 
     // Show second commit
-    assert!(repo.show(&second_commit).is_ok());
+    assert!(repo.show(second_commit).is_ok());
 }
 
 #[test]
@@ -1851,8 +2019,7 @@ fn test_show_nested_changes() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
 
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Create nested directory structure
     let nested_dir = temp_dir.path().join("src").join("nested");
@@ -1866,7 +2033,7 @@ fn test_show_nested_changes() {
     let second_commit = repo.create_commit("Second commit").unwrap();
 
     // Show second commit
-    assert!(repo.show(&second_commit).is_ok());
+    assert!(repo.show(second_commit).is_ok());
 }
 
 #[test]
@@ -1874,8 +2041,7 @@ fn test_show_multiple_files() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
 
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Create multiple files
     let file1 = temp_dir.path().join("file1.txt");
@@ -1890,7 +2056,7 @@ fn test_show_multiple_files() {
     let second_commit = repo.create_commit("Second commit").unwrap();
 
     // Show second commit
-    assert!(repo.show(&second_commit).is_ok());
+    assert!(repo.show(second_commit).is_ok());
 }
 
 #[test]
@@ -1898,8 +2064,7 @@ fn test_show_added_removed_files() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
 
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Create initial file
     let file1 = temp_dir.path().join("file1.txt");
@@ -1913,7 +2078,7 @@ fn test_show_added_removed_files() {
     let second_commit = repo.create_commit("Second commit").unwrap();
 
     // Show second commit
-    assert!(repo.show(&second_commit).is_ok());
+    assert!(repo.show(second_commit).is_ok());
 }
 
 #[test]
@@ -1921,13 +2086,11 @@ fn test_show_invalid_commit() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
 
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Try to show non-existent commit
-    let result = repo.show("a".repeat(40).as_str());
-    assert!(result.is_err());
-    assert!(result.unwrap_err().contains("Commit with hash:"));
+    let result = repo.show("a".repeat(oid_hex_len()).parse().unwrap());
+    assert!(matches!(result, Err(Error::NotFound(_))));
 }
 
 #[test]
@@ -1935,8 +2098,7 @@ fn test_show_first_commit() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
 
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Create first commit
     let test_file = temp_dir.path().join("test.txt");
@@ -1944,7 +2106,81 @@ fn test_show_first_commit() {
     let first_commit = repo.create_commit("First commit").unwrap();
 
     // Show first commit (should work even though it has no parent)
-    assert!(repo.show(&first_commit).is_ok());
+    assert!(repo.show(first_commit).is_ok());
+}
+
+#[test]
+fn test_show_result() {
+    let temp_dir = TempDir::new().unwrap();
+    let repo = init_repo(temp_dir.path().to_str().unwrap());
+
+    fs::write(temp_dir.path().join("test.txt"), "Initial content\n").unwrap();
+    let first_commit = repo.create_commit("First commit").unwrap();
+    fs::write(temp_dir.path().join("test.txt"), "Updated content\n").unwrap();
+    let second_commit = repo.create_commit("Second commit").unwrap();
+
+    // One diff per parent
+    let show = repo.show(second_commit).unwrap();
+    assert_eq!(show.commit.oid, second_commit);
+    assert_eq!(show.commit.message, "Second commit");
+    assert_eq!(show.diffs.len(), 1);
+    assert_eq!(show.diffs[0].changes[0].path(), "test.txt");
+    let patch = String::from_utf8_lossy(&show.diffs[0].patch);
+    assert!(patch.contains("-Initial content"));
+    assert!(patch.contains("+Updated content"));
+
+    let show = repo.show(first_commit).unwrap();
+    assert!(show.diffs.is_empty());
+}
+
+#[test]
+fn test_set_ref_replaces_file_whole() {
+    let temp_dir = TempDir::new().unwrap();
+    let repo = init_repo(temp_dir.path().to_str().unwrap());
+    repo.set_config("core.fsync", "refs").unwrap();
+    // The config is read when the repository is opened
+    let repo = Repository::new(temp_dir.path().to_str().unwrap());
+    assert_eq!(
+        repo.fsync,
+        FsyncPolicy {
+            objects: false,
+            refs: true
+        }
+    );
+
+    let oid = repo.hash_object(b"content", ObjectType::Blob).unwrap();
+    repo.set_ref("refs/tags/v1", RefValue::Direct(oid), false)
+        .unwrap();
+    assert_eq!(
+        repo.get_ref("refs/tags/v1", false).unwrap(),
+        RefValue::Direct(oid)
+    );
+    // No temporary file is left behind
+    let tags: Vec<_> = fs::read_dir(Path::new(&repo.gitdir).join("refs/tags"))
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    assert_eq!(tags, vec!["v1"]);
+}
+
+#[test]
+fn test_fsync_policy() {
+    assert_eq!(FsyncPolicy::parse("none"), FsyncPolicy::default());
+    assert_eq!(
+        FsyncPolicy::parse("all"),
+        FsyncPolicy {
+            objects: true,
+            refs: true
+        }
+    );
+    assert_eq!(
+        FsyncPolicy::parse("objects, unknown"),
+        FsyncPolicy {
+            objects: true,
+            refs: false
+        }
+    );
+    assert_eq!(FsyncPolicy::parse("all,none,refs"), FsyncPolicy::parse("refs"));
 }
 
 #[test]
@@ -1952,8 +2188,7 @@ fn test_delete_ref_branch() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
 
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Create initial commit
     let test_file = temp_dir.path().join("test.txt");
@@ -1979,8 +2214,7 @@ fn test_delete_ref_tag() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
 
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Create initial commit
     let test_file = temp_dir.path().join("test.txt");
@@ -1988,7 +2222,7 @@ fn test_delete_ref_tag() {
     let commit_hash = repo.create_commit("First commit").unwrap();
 
     // Create a tag
-    repo.create_tag("v1.0", &commit_hash).unwrap();
+    repo.create_tag("v1.0", commit_hash).unwrap();
 
     // Verify tag exists
     let refs = repo.iter_refs("refs/tags/").unwrap();
@@ -2007,8 +2241,7 @@ fn test_delete_ref_head() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
 
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Create initial commit
     let test_file = temp_dir.path().join("test.txt");
@@ -2017,7 +2250,7 @@ fn test_delete_ref_head() {
 
     // Verify HEAD exists
     let head_ref = repo.get_ref("HEAD", false).unwrap();
-    assert!(head_ref.value.starts_with("ref: refs/heads/"));
+    assert!(matches!(head_ref, RefValue::Symbolic(name) if name.starts_with("refs/heads/")));
 
     // Delete HEAD
     repo.delete_ref("HEAD", false).unwrap();
@@ -2031,26 +2264,15 @@ fn test_delete_ref_nonexistent() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
 
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Try to delete a non-existent branch
     let result = repo.delete_ref("refs/heads/nonexistent", false);
-    assert!(result.is_err());
-    assert!(
-        result
-            .unwrap_err()
-            .contains("Failed to read refs/heads/nonexistent file")
-    );
+    assert!(matches!(result, Err(Error::InvalidRef(name)) if name == "refs/heads/nonexistent"));
 
     // Try to delete a non-existent tag
     let result = repo.delete_ref("refs/tags/nonexistent", false);
-    assert!(result.is_err());
-    assert!(
-        result
-            .unwrap_err()
-            .contains("Failed to read refs/tags/nonexistent file")
-    );
+    assert!(matches!(result, Err(Error::InvalidRef(name)) if name == "refs/tags/nonexistent"));
 }
 
 #[test]
@@ -2058,8 +2280,7 @@ fn test_get_merge_base() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
 
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Create initial commit
     let test_file = temp_dir.path().join("test.txt");
@@ -2067,9 +2288,9 @@ fn test_get_merge_base() {
     let first_commit = repo.create_commit("First commit").unwrap();
 
     // Create two branches from first commit
-    repo.create_branch("branch1", Some(first_commit.clone()))
+    repo.create_branch("branch1", Some(first_commit))
         .unwrap();
-    repo.create_branch("branch2", Some(first_commit.clone()))
+    repo.create_branch("branch2", Some(first_commit))
         .unwrap();
 
     // Switch to branch1 and make changes
@@ -2084,7 +2305,7 @@ fn test_get_merge_base() {
 
     // Find merge base between branch1 and branch2
     let merge_base = repo
-        .get_merge_base(&branch1_commit, &branch2_commit)
+        .get_merge_base(branch1_commit, branch2_commit)
         .unwrap();
     assert_eq!(merge_base, first_commit);
 }
@@ -2094,8 +2315,7 @@ fn test_get_merge_base_same_commit() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
 
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Create initial commit
     let test_file = temp_dir.path().join("test.txt");
@@ -2103,7 +2323,7 @@ fn test_get_merge_base_same_commit() {
     let commit = repo.create_commit("First commit").unwrap();
 
     // Find merge base between same commit
-    let merge_base = repo.get_merge_base(&commit, &commit).unwrap();
+    let merge_base = repo.get_merge_base(commit, commit).unwrap();
     assert_eq!(merge_base, commit);
 }
 
@@ -2112,8 +2332,7 @@ fn test_get_merge_base_ancestor() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
 
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Create initial commit
     let test_file = temp_dir.path().join("test.txt");
@@ -2125,7 +2344,7 @@ fn test_get_merge_base_ancestor() {
     let second_commit = repo.create_commit("Second commit").unwrap();
 
     // Find merge base between first and second commit
-    let merge_base = repo.get_merge_base(&first_commit, &second_commit).unwrap();
+    let merge_base = repo.get_merge_base(first_commit, second_commit).unwrap();
     assert_eq!(merge_base, first_commit);
 }
 
@@ -2134,18 +2353,16 @@ fn test_get_merge_base_invalid_commit() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
 
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Create initial commit
     let test_file = temp_dir.path().join("test.txt");
     fs::write(&test_file, "Initial content").unwrap();
     let commit = repo.create_commit("First commit").unwrap();
 
-    // Try to find merge base with invalid commit
-    let result = repo.get_merge_base(&commit, "invalidhash");
-    assert!(result.is_err());
-    assert!(result.unwrap_err().contains("Oid hash not found for"));
+    // Try to find merge base with a missing commit
+    let result = repo.get_merge_base(commit, "a".repeat(oid_hex_len()).parse().unwrap());
+    assert!(matches!(result, Err(Error::NotFound(_))));
 }
 
 #[test]
@@ -2153,8 +2370,7 @@ fn test_get_merge_base_different_branches() {
     let temp_dir = TempDir::new().unwrap();
     let repo_path = temp_dir.path().to_str().unwrap();
 
-    let repo = Repository::new(repo_path);
-    repo.init().unwrap();
+    let repo = init_repo(repo_path);
 
     // Create initial commit
     let test_file = temp_dir.path().join("test.txt");
@@ -2162,7 +2378,7 @@ fn test_get_merge_base_different_branches() {
     let initial_commit = repo.create_commit("Initial commit").unwrap();
 
     // Create and checkout branch1
-    repo.create_branch("branch1", Some(initial_commit.clone()))
+    repo.create_branch("branch1", Some(initial_commit))
         .unwrap();
     repo.checkout("branch1").unwrap();
 
@@ -2171,7 +2387,7 @@ fn test_get_merge_base_different_branches() {
     let commit1 = repo.create_commit("Branch1 commit").unwrap();
 
     // Create and checkout branch2
-    repo.create_branch("branch2", Some(initial_commit.clone()))
+    repo.create_branch("branch2", Some(initial_commit))
         .unwrap();
     repo.checkout("branch2").unwrap();
 
@@ -2180,15 +2396,14 @@ fn test_get_merge_base_different_branches() {
     let commit2 = repo.create_commit("Branch2 commit").unwrap();
 
     // Find merge base between the two commits
-    let merge_base = repo.get_merge_base(&commit1, &commit2).unwrap();
+    let merge_base = repo.get_merge_base(commit1, commit2).unwrap();
     assert_eq!(merge_base, initial_commit);
 }
 
 #[test]
 fn test_merge_success() {
     let temp_dir = tempdir().unwrap();
-    let repo = Repository::new(temp_dir.path().to_str().unwrap());
-    repo.init().unwrap();
+    let repo = init_repo(temp_dir.path().to_str().unwrap());
 
     // Create initial commit
     fs::write(temp_dir.path().join("file1.txt"), "initial content").unwrap();
@@ -2229,8 +2444,7 @@ fn test_merge_success() {
 #[test]
 fn test_merge_complex_structure() {
     let temp_dir = tempdir().unwrap();
-    let repo = Repository::new(temp_dir.path().to_str().unwrap());
-    repo.init().unwrap();
+    let repo = init_repo(temp_dir.path().to_str().unwrap());
 
     // Create initial structure
     fs::create_dir_all(temp_dir.path().join("src/modules")).unwrap();
@@ -2359,15 +2573,14 @@ fn test_merge_complex_structure() {
 #[test]
 fn test_merge_fast_forward() {
     let temp_dir = tempdir().unwrap();
-    let repo = Repository::new(temp_dir.path().to_str().unwrap());
-    repo.init().unwrap();
+    let repo = init_repo(temp_dir.path().to_str().unwrap());
 
     // Create initial commit
     fs::write(temp_dir.path().join("file1.txt"), "initial content").unwrap();
     let initial_commit = repo.create_commit("Initial commit").unwrap();
 
     // Create and checkout a new branch
-    repo.create_branch("feature", Some(initial_commit.clone()))
+    repo.create_branch("feature", Some(initial_commit))
         .unwrap();
     repo.checkout("feature").unwrap();
 
@@ -2391,21 +2604,20 @@ fn test_merge_fast_forward() {
 
     // Verify HEAD points to the feature commit
     let head_ref = repo.get_ref(HEAD, true).unwrap();
-    assert_eq!(head_ref.value, feature_commit);
+    assert_eq!(head_ref, RefValue::Direct(feature_commit));
 }
 
 #[test]
 fn test_merge_fast_forward_with_multiple_commits() {
     let temp_dir = tempdir().unwrap();
-    let repo = Repository::new(temp_dir.path().to_str().unwrap());
-    repo.init().unwrap();
+    let repo = init_repo(temp_dir.path().to_str().unwrap());
 
     // Create initial commit
     fs::write(temp_dir.path().join("file1.txt"), "initial content").unwrap();
     let initial_commit = repo.create_commit("Initial commit").unwrap();
 
     // Create and checkout a new branch
-    repo.create_branch("feature", Some(initial_commit.clone()))
+    repo.create_branch("feature", Some(initial_commit))
         .unwrap();
     repo.checkout("feature").unwrap();
 
@@ -2436,14 +2648,70 @@ fn test_merge_fast_forward_with_multiple_commits() {
 
     // Verify HEAD points to the final feature commit
     let head_ref = repo.get_ref(HEAD, true).unwrap();
-    assert_eq!(head_ref.value, final_commit);
+    assert_eq!(head_ref, RefValue::Direct(final_commit));
+}
+
+#[test]
+fn test_merge_outcomes() {
+    let temp_dir = tempdir().unwrap();
+    let repo = init_repo(temp_dir.path().to_str().unwrap());
+
+    fs::write(temp_dir.path().join("shared.txt"), "base\n").unwrap();
+    repo.create_commit("Initial commit").unwrap();
+
+    repo.create_branch("feature", None).unwrap();
+    repo.checkout("feature").unwrap();
+    fs::write(temp_dir.path().join("feature.txt"), "feature\n").unwrap();
+    repo.create_commit("Add feature.txt").unwrap();
+    repo.checkout("master").unwrap();
+    assert_eq!(repo.merge("feature").unwrap(), MergeOutcome::FastForward);
+
+    repo.create_branch("conflicting", None).unwrap();
+    repo.checkout("conflicting").unwrap();
+    fs::write(temp_dir.path().join("shared.txt"), "conflicting\n").unwrap();
+    repo.create_commit("Rewrite shared.txt").unwrap();
+    repo.checkout("master").unwrap();
+    repo.create_branch("side", None).unwrap();
+    repo.checkout("side").unwrap();
+    fs::write(temp_dir.path().join("side.txt"), "side\n").unwrap();
+    repo.create_commit("Add side.txt").unwrap();
+
+    repo.checkout("master").unwrap();
+    fs::write(temp_dir.path().join("shared.txt"), "master\n").unwrap();
+    repo.create_commit("Rewrite shared.txt on master").unwrap();
+    assert_eq!(repo.merge("side").unwrap(), MergeOutcome::Merged);
+    repo.create_commit("Merge side").unwrap();
+
+    // The conflicting paths are reported, their markers left in the worktree
+    assert_eq!(
+        repo.merge("conflicting").unwrap(),
+        MergeOutcome::Conflicted(vec!["shared.txt".to_string()])
+    );
+    let content = fs::read_to_string(temp_dir.path().join("shared.txt")).unwrap();
+    assert!(content.contains("<<<<<<<"));
+}
+
+#[test]
+fn test_commit_graph_dot() {
+    let temp_dir = tempdir().unwrap();
+    let repo = init_repo(temp_dir.path().to_str().unwrap());
+
+    fs::write(temp_dir.path().join("file.txt"), "one\n").unwrap();
+    let first = repo.create_commit("First commit").unwrap();
+    fs::write(temp_dir.path().join("file.txt"), "two\n").unwrap();
+    let second = repo.create_commit("Second commit").unwrap();
+
+    let dot = repo.commit_graph_dot().unwrap();
+    assert!(dot.starts_with("digraph"));
+    assert!(dot.contains(&first.to_string()));
+    assert!(dot.contains(&second.to_string()));
+    assert!(dot.contains("master"));
 }
 
 #[test]
 fn test_merge_fast_forward_with_deleted_files() {
     let temp_dir = tempdir().unwrap();
-    let repo = Repository::new(temp_dir.path().to_str().unwrap());
-    repo.init().unwrap();
+    let repo = init_repo(temp_dir.path().to_str().unwrap());
 
     // Create initial commit with multiple files
     fs::write(temp_dir.path().join("file1.txt"), "initial content 1").unwrap();
@@ -2452,7 +2720,7 @@ fn test_merge_fast_forward_with_deleted_files() {
     let initial_commit = repo.create_commit("Initial commit").unwrap();
 
     // Create and checkout a new branch
-    repo.create_branch("feature", Some(initial_commit.clone()))
+    repo.create_branch("feature", Some(initial_commit))
         .unwrap();
     repo.checkout("feature").unwrap();
 
@@ -2477,21 +2745,20 @@ fn test_merge_fast_forward_with_deleted_files() {
 
     // Verify HEAD points to the feature commit
     let head_ref = repo.get_ref(HEAD, true).unwrap();
-    assert_eq!(head_ref.value, feature_commit);
+    assert_eq!(head_ref, RefValue::Direct(feature_commit));
 }
 
 #[test]
 fn test_rebase_simple() {
     let temp_dir = tempdir().unwrap();
-    let repo = Repository::new(temp_dir.path().to_str().unwrap());
-    repo.init().unwrap();
+    let repo = init_repo(temp_dir.path().to_str().unwrap());
 
     // Create initial commit
     fs::write(temp_dir.path().join("file1.txt"), "initial content").unwrap();
     let initial_commit = repo.create_commit("Initial commit").unwrap();
 
     // Create and checkout a new branch
-    repo.create_branch("feature", Some(initial_commit.clone()))
+    repo.create_branch("feature", Some(initial_commit))
         .unwrap();
     repo.checkout("feature").unwrap();
 
@@ -2517,23 +2784,22 @@ fn test_rebase_simple() {
 
     // Verify feature branch points to new commit
     let feature_ref = repo.get_ref("refs/heads/feature", true).unwrap();
-    assert_ne!(feature_ref.value, feature_commit);
+    assert_ne!(feature_ref, RefValue::Direct(feature_commit));
 
     // Verify the new commits structure
-    let new_commit = repo.get_commit(&feature_ref.value).unwrap();
-    let ancestors = repo.get_commit_ancestors(&new_commit._oid).unwrap();
+    let new_commit = repo.get_commit(feature_ref.oid().unwrap()).unwrap();
+    let ancestors = repo.get_commit_ancestors(new_commit.oid).unwrap();
 
     assert_eq!(ancestors.len(), 3);
     assert_eq!(ancestors[2], initial_commit);
     assert_eq!(ancestors[1], master_commit);
-    assert_eq!(ancestors[0], repo.get_ref(HEAD, true).unwrap().value);
+    assert_eq!(ancestors[0], repo.get_ref(HEAD, true).unwrap().oid().unwrap());
 }
 
 #[test]
 fn test_rebase_multiple_commits() {
     let temp_dir = tempdir().unwrap();
-    let repo = Repository::new(temp_dir.path().to_str().unwrap());
-    repo.init().unwrap();
+    let repo = init_repo(temp_dir.path().to_str().unwrap());
 
     // Create initial commit with a base file
     fs::write(temp_dir.path().join("base.txt"), "base content").unwrap();
@@ -2578,6 +2844,9 @@ fn test_rebase_multiple_commits() {
     repo.checkout("feature").unwrap();
     repo.rebase("master").unwrap();
 
+    // Each commit only brings its own changes, so nothing conflicts
+    assert!(!repo.rebase_in_progress());
+
     // Verify all files exist with correct content
     let feature1_content =
         fs::read_to_string(temp_dir.path().join("feature/commit1/file.txt")).unwrap();
@@ -2587,37 +2856,30 @@ fn test_rebase_multiple_commits() {
         fs::read_to_string(temp_dir.path().join("feature/commit3/file.txt")).unwrap();
     let master_content = fs::read_to_string(temp_dir.path().join("master/file.txt")).unwrap();
 
-    assert_eq!(
-        feature1_content,
-        "<<<<<<< HEAD\nfeature commit 1||||||| BASE\n=======\n<<<<<<< BASE\n=======\nfeature commit 1>>>>>>> MERGE_HEAD\n>>>>>>> MERGE_HEAD\n"
-    );
-    assert_eq!(
-        feature2_content,
-        "<<<<<<< BASE\n=======\nfeature commit 2>>>>>>> MERGE_HEAD\n"
-    );
+    assert_eq!(feature1_content, "feature commit 1");
+    assert_eq!(feature2_content, "feature commit 2");
     assert_eq!(feature3_content, "feature commit 3");
     assert_eq!(master_content, "master commit 1");
 
     // Verify feature branch points to new commit
     let feature_ref = repo.get_ref("refs/heads/feature", true).unwrap();
-    assert_ne!(feature_ref.value, feature_commit);
+    assert_ne!(feature_ref, RefValue::Direct(feature_commit));
 
     // Verify the new commits structure
-    let new_commit = repo.get_commit(&feature_ref.value).unwrap();
-    let ancestors = repo.get_commit_ancestors(&new_commit._oid).unwrap();
+    let new_commit = repo.get_commit(feature_ref.oid().unwrap()).unwrap();
+    let ancestors = repo.get_commit_ancestors(new_commit.oid).unwrap();
 
     assert_eq!(ancestors.len(), 5);
     assert_eq!(ancestors[4], initial_commit);
     assert_eq!(ancestors[3], master_commit);
     // New commits with different hashes
-    assert_eq!(ancestors[0], repo.get_ref(HEAD, true).unwrap().value);
+    assert_eq!(ancestors[0], repo.get_ref(HEAD, true).unwrap().oid().unwrap());
 }
 
 #[test]
 fn test_rebase_detached_head() {
     let temp_dir = tempdir().unwrap();
-    let repo = Repository::new(temp_dir.path().to_str().unwrap());
-    repo.init().unwrap();
+    let repo = init_repo(temp_dir.path().to_str().unwrap());
 
     // Create initial commit
     fs::write(temp_dir.path().join("file1.txt"), "initial content").unwrap();
@@ -2632,14 +2894,14 @@ fn test_rebase_detached_head() {
     let third_commit = repo.create_commit("Third commit").unwrap();
 
     // Checkout second commit (detached HEAD)
-    repo.checkout(&second_commit).unwrap();
+    repo.checkout(&second_commit.to_string()).unwrap();
 
     // Make changes in detached HEAD state
     fs::write(temp_dir.path().join("detached.txt"), "detached changes").unwrap();
     let detached_commit = repo.create_commit("Detached HEAD changes").unwrap();
 
     // Rebase detached HEAD onto third commit
-    repo.rebase(&third_commit).unwrap();
+    repo.rebase(&third_commit.to_string()).unwrap();
 
     // Verify the rebased state
     let detached_content = fs::read_to_string(temp_dir.path().join("detached.txt")).unwrap();
@@ -2650,14 +2912,13 @@ fn test_rebase_detached_head() {
 
     // Verify HEAD points to new commit
     let head_ref = repo.get_ref(HEAD, true).unwrap();
-    assert_ne!(head_ref.value, detached_commit);
+    assert_ne!(head_ref, RefValue::Direct(detached_commit));
 }
 
 #[test]
 fn test_rebase_onto_ancestor() {
     let temp_dir = tempdir().unwrap();
-    let repo = Repository::new(temp_dir.path().to_str().unwrap());
-    repo.init().unwrap();
+    let repo = init_repo(temp_dir.path().to_str().unwrap());
 
     // Create initial commit with a base file
     fs::write(temp_dir.path().join("base.txt"), "base content").unwrap();
@@ -2669,7 +2930,7 @@ fn test_rebase_onto_ancestor() {
     let second_commit = repo.create_commit("Second commit").unwrap();
 
     // Create feature branch at second commit
-    repo.create_branch("feature", Some(second_commit.clone()))
+    repo.create_branch("feature", Some(second_commit))
         .unwrap();
     repo.checkout("feature").unwrap();
 
@@ -2679,28 +2940,28 @@ fn test_rebase_onto_ancestor() {
     let feature_commit = repo.create_commit("Feature changes").unwrap();
 
     // Try to rebase onto initial commit (ancestor)
-    repo.rebase(&_initial_commit).unwrap();
+    repo.rebase(&_initial_commit.to_string()).unwrap();
+
+    assert!(!repo.rebase_in_progress());
 
     // Verify the rebased state
     let feature_content = fs::read_to_string(temp_dir.path().join("feature/file.txt")).unwrap();
     let second_content = fs::read_to_string(temp_dir.path().join("second/file.txt")).unwrap();
 
     assert_eq!(feature_content, "feature content");
-    assert_eq!(
-        second_content,
-        "<<<<<<< BASE\n=======\nsecond content>>>>>>> MERGE_HEAD\n"
-    );
+    assert_eq!(second_content, "second content");
 
-    // Verify feature branch points to new commit
+    // Verify feature branch points to the replayed feature commit
     let feature_ref = repo.get_ref("refs/heads/feature", true).unwrap();
-    assert_ne!(feature_ref.value, feature_commit);
+    let new_commit = repo.get_commit(feature_ref.oid().unwrap()).unwrap();
+    assert_eq!(new_commit.message, "Feature changes");
+    assert_eq!(new_commit.tree, repo.get_commit(feature_commit).unwrap().tree);
 }
 
 #[test]
 fn test_rebase_with_directory_structure() {
     let temp_dir = tempdir().unwrap();
-    let repo = Repository::new(temp_dir.path().to_str().unwrap());
-    repo.init().unwrap();
+    let repo = init_repo(temp_dir.path().to_str().unwrap());
 
     // Create initial commit with base structure
     fs::create_dir_all(temp_dir.path().join("base")).unwrap();
@@ -2744,14 +3005,13 @@ fn test_rebase_with_directory_structure() {
 
     // Verify feature branch points to new commit
     let feature_ref = repo.get_ref("refs/heads/feature", true).unwrap();
-    assert_ne!(feature_ref.value, feature_commit);
+    assert_ne!(feature_ref, RefValue::Direct(feature_commit));
 }
 
 #[test]
 fn test_rebase_with_conflicts() {
     let temp_dir = tempdir().unwrap();
-    let repo = Repository::new(temp_dir.path().to_str().unwrap());
-    repo.init().unwrap();
+    let repo = init_repo(temp_dir.path().to_str().unwrap());
 
     // Create initial commit with a file that will be modified by both branches
     fs::write(
@@ -2786,7 +3046,13 @@ fn test_rebase_with_conflicts() {
     repo.checkout("feature").unwrap();
     repo.rebase("master").unwrap();
 
-    // Verify the rebased state with conflict markers
+    // The rebase stops at the conflicting commit
+    assert!(repo.rebase_in_progress());
+    let state = RebaseState::load(&repo).unwrap();
+    assert_eq!(state.stopped, Some(feature_commit));
+    assert_eq!(state.conflicts, vec!["shared.txt".to_string()]);
+
+    // Verify the worktree holds the conflict markers
     let shared_content = fs::read_to_string(temp_dir.path().join("shared.txt")).unwrap();
     assert!(shared_content.contains("<<<<<<< HEAD"));
     assert!(shared_content.contains("feature content"));
@@ -2796,7 +3062,94 @@ fn test_rebase_with_conflicts() {
     assert!(shared_content.contains("master line 4"));
     assert!(shared_content.contains(">>>>>>> MERGE_HEAD"));
 
-    // Verify feature branch points to new commit
+    // Nothing was committed and the branch was not moved yet
     let feature_ref = repo.get_ref("refs/heads/feature", true).unwrap();
-    assert_ne!(feature_ref.value, feature_commit);
+    assert_eq!(feature_ref, RefValue::Direct(feature_commit));
+    assert_eq!(repo.get_oid_hash(HEAD).unwrap(), _master_commit);
+
+    // Resolve the conflict and continue
+    fs::write(
+        temp_dir.path().join("shared.txt"),
+        "resolved content\nline 2\nline 3\nfeature line 4",
+    )
+    .unwrap();
+    repo.rebase_continue().unwrap();
+    assert!(!repo.rebase_in_progress());
+
+    // Verify feature branch points to the new commit on top of master
+    let feature_ref = repo.get_ref("refs/heads/feature", true).unwrap();
+    assert_ne!(feature_ref, RefValue::Direct(feature_commit));
+    let new_commit = repo.get_commit(feature_ref.oid().unwrap()).unwrap();
+    assert_eq!(new_commit.parents, vec![_master_commit]);
+    assert_eq!(new_commit.message, "Feature changes");
+    assert_eq!(repo.get_branch_name().unwrap(), Some("feature".to_string()));
+}
+
+#[test]
+fn test_commit_author_from_config() {
+    let temp_dir = tempdir().unwrap();
+    let repo = init_repo(temp_dir.path().to_str().unwrap());
+
+    fs::write(temp_dir.path().join("file.txt"), "content").unwrap();
+    let anonymous = repo.create_commit("No identity").unwrap();
+    assert_eq!(repo.get_commit(anonymous).unwrap().author, None);
+
+    repo.set_config("user.name", "Jane Doe").unwrap();
+    repo.set_config("user.email", "jane@example.com").unwrap();
+    fs::write(temp_dir.path().join("file.txt"), "changed").unwrap();
+    let authored = repo.create_commit("With identity").unwrap();
+    assert_eq!(
+        repo.get_commit(authored).unwrap().author,
+        Some("Jane Doe <jane@example.com>".to_string())
+    );
+}
+
+#[test]
+fn test_follow_path_across_renames() {
+    let temp_dir = tempdir().unwrap();
+    let repo = init_repo(temp_dir.path().to_str().unwrap());
+
+    fs::write(temp_dir.path().join("first.txt"), "one\ntwo\nthree\n").unwrap();
+    let created = repo.create_commit("Create first.txt").unwrap();
+    fs::write(temp_dir.path().join("unrelated.txt"), "unrelated\n").unwrap();
+    repo.create_commit("Unrelated change").unwrap();
+    fs::rename(
+        temp_dir.path().join("first.txt"),
+        temp_dir.path().join("second.txt"),
+    )
+    .unwrap();
+    let renamed = repo.create_commit("Rename to second.txt").unwrap();
+    fs::write(temp_dir.path().join("second.txt"), "one\ntwo\nthree\nfour\n").unwrap();
+    let edited = repo.create_commit("Edit second.txt").unwrap();
+
+    let history = repo.follow_path("second.txt").unwrap();
+    assert_eq!(
+        history,
+        vec![
+            (edited, "second.txt".to_string()),
+            (renamed, "second.txt".to_string()),
+            (created, "first.txt".to_string()),
+        ]
+    );
+    assert!(repo.follow_path("missing.txt").unwrap().is_empty());
+}
+
+#[test]
+fn test_errors_tell_missing_from_corrupt() {
+    let temp_dir = TempDir::new().unwrap();
+    let repo = init_repo(temp_dir.path().to_str().unwrap());
+    fs::write(temp_dir.path().join("a.txt"), "a").unwrap();
+    repo.create_commit("First").unwrap();
+
+    // A blob is not a commit
+    let blob = repo.hash_object(b"not a commit", ObjectType::Blob).unwrap();
+    assert!(matches!(repo.get_commit(blob), Err(Error::WrongType { .. })));
+    assert!(matches!(repo.get_commit("b".repeat(oid_hex_len()).parse().unwrap()), Err(Error::NotFound(_))));
+    assert!(matches!(repo.merge("nope"), Err(Error::InvalidRef(name)) if name == "nope"));
+
+    // A ref that cannot be read is an io error, with the cause as source
+    fs::create_dir(temp_dir.path().join(GIT_DIR).join("refs/heads/dir")).unwrap();
+    let error = repo.get_ref("refs/heads/dir", false).unwrap_err();
+    assert!(matches!(error, Error::Io { .. }));
+    assert!(std::error::Error::source(&error).is_some());
 }
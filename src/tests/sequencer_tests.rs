@@ -0,0 +1,280 @@
+use crate::sequencer::{
+    CHERRY_PICK_HEAD, SEQUENCER_DIR, SequencerAction, SequencerOptions, SequencerState,
+};
+
+// Sets up master with one extra commit and a feature branch with two commits:
+// one adding new.txt, one rewriting shared.txt. HEAD is left on master.
+// Returns (repo, [add_commit, rewrite_commit])
+fn setup_pick_source(temp_dir: &TempDir) -> (Repository, Vec<Oid>) {
+    let (repo, _) = commit_once(temp_dir, |dir| write_file(dir, "shared.txt", "base\n"));
+
+    repo.create_branch("feature", None).unwrap();
+    repo.checkout("feature").unwrap();
+    fs::write(temp_dir.path().join("new.txt"), "new\n").unwrap();
+    let add_commit = repo.create_commit("Add new.txt").unwrap();
+    fs::write(temp_dir.path().join("shared.txt"), "feature\n").unwrap();
+    let rewrite_commit = repo.create_commit("Rewrite shared.txt").unwrap();
+
+    repo.checkout("master").unwrap();
+    fs::write(temp_dir.path().join("other.txt"), "other\n").unwrap();
+    repo.create_commit("Master changes").unwrap();
+
+    (repo, vec![add_commit, rewrite_commit])
+}
+
+// The commits as revisions for cherry-pick and revert
+fn revisions(commits: &[Oid]) -> Vec<String> {
+    commits.iter().map(Oid::to_string).collect()
+}
+
+#[test]
+fn test_cherry_pick_applies_commit() {
+    let temp_dir = tempdir().unwrap();
+    let (repo, commits) = setup_pick_source(&temp_dir);
+    let master_before = repo.get_oid_hash("master").unwrap();
+
+    repo.cherry_pick(&[commits[0].to_string()], &SequencerOptions::default())
+        .unwrap();
+
+    assert!(!repo.sequencer_in_progress());
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("new.txt")).unwrap(),
+        "new\n"
+    );
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("other.txt")).unwrap(),
+        "other\n"
+    );
+
+    // The branch moved forward by one commit carrying the original message
+    let head = repo.get_commit(repo.get_oid_hash(HEAD).unwrap()).unwrap();
+    assert_eq!(head.parents, vec![master_before]);
+    assert_eq!(head.message, "Add new.txt");
+    assert_eq!(repo.get_oid_hash("master").unwrap(), head.oid);
+    assert!(matches!(
+        repo.get_ref(HEAD, false).unwrap(),
+        RefValue::Symbolic(_)
+    ));
+}
+
+#[test]
+fn test_cherry_pick_record_origin() {
+    let temp_dir = tempdir().unwrap();
+    let (repo, commits) = setup_pick_source(&temp_dir);
+
+    let options = SequencerOptions {
+        record_origin: true,
+        ..Default::default()
+    };
+    repo.cherry_pick(&revisions(&commits), &options).unwrap();
+
+    let head = repo.get_commit(repo.get_oid_hash(HEAD).unwrap()).unwrap();
+    assert_eq!(
+        head.message,
+        format!(
+            "Rewrite shared.txt\n\n(cherry picked from commit {})",
+            commits[1]
+        )
+    );
+    let parent = repo.get_commit(head.parents[0]).unwrap();
+    assert!(parent.message.ends_with(&format!("{})", commits[0])));
+}
+
+#[test]
+fn test_cherry_pick_no_commit() {
+    let temp_dir = tempdir().unwrap();
+    let (repo, commits) = setup_pick_source(&temp_dir);
+    let master_before = repo.get_oid_hash("master").unwrap();
+
+    let options = SequencerOptions {
+        no_commit: true,
+        ..Default::default()
+    };
+    repo.cherry_pick(&revisions(&commits), &options).unwrap();
+
+    // Both changes are in the worktree but nothing was committed
+    assert_eq!(repo.get_oid_hash("master").unwrap(), master_before);
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("new.txt")).unwrap(),
+        "new\n"
+    );
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("shared.txt")).unwrap(),
+        "feature\n"
+    );
+    assert!(!repo.sequencer_in_progress());
+}
+
+#[test]
+fn test_revert_undoes_commit() {
+    let temp_dir = tempdir().unwrap();
+    let (repo, commits) = setup_pick_source(&temp_dir);
+    repo.checkout("feature").unwrap();
+
+    repo.revert(&[commits[0].to_string()], &SequencerOptions::default())
+        .unwrap();
+
+    assert!(!temp_dir.path().join("new.txt").exists());
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("shared.txt")).unwrap(),
+        "feature\n"
+    );
+
+    let head = repo.get_commit(repo.get_oid_hash(HEAD).unwrap()).unwrap();
+    assert_eq!(head.parents, vec![commits[1]]);
+    assert_eq!(
+        head.message,
+        format!(
+            "Revert \"Add new.txt\"\n\nThis reverts commit {}.",
+            commits[0]
+        )
+    );
+}
+
+#[test]
+fn test_cherry_pick_merge_commit_requires_mainline() {
+    let temp_dir = tempdir().unwrap();
+    let (repo, commits) = setup_pick_source(&temp_dir);
+    let master_commit = repo.get_oid_hash("master").unwrap();
+
+    // Merge feature into master, then pick the merge onto a fresh branch
+    repo.merge("feature").unwrap();
+    let merge_commit = repo
+        .write_commit(
+            repo.get_working_tree().unwrap(),
+            &[master_commit, commits[1]],
+            "Merge feature",
+        )
+        .unwrap();
+
+    repo.create_branch("picked", Some(master_commit)).unwrap();
+    repo.checkout("picked").unwrap();
+
+    let result = repo.cherry_pick(&[merge_commit.to_string()], &SequencerOptions::default());
+    assert!(result.unwrap_err().to_string().contains("no -m option"));
+
+    let result = repo.cherry_pick(
+        &[merge_commit.to_string()],
+        &SequencerOptions {
+            mainline: Some(3),
+            ..Default::default()
+        },
+    );
+    assert!(result.unwrap_err().to_string().contains("does not have parent 3"));
+
+    // Relative to the first parent, the merge brought in the feature branch
+    repo.cherry_pick(
+        &[merge_commit.to_string()],
+        &SequencerOptions {
+            mainline: Some(1),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("new.txt")).unwrap(),
+        "new\n"
+    );
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("shared.txt")).unwrap(),
+        "feature\n"
+    );
+}
+
+#[test]
+fn test_cherry_pick_mainline_on_regular_commit() {
+    let temp_dir = tempdir().unwrap();
+    let (repo, commits) = setup_pick_source(&temp_dir);
+
+    let options = SequencerOptions {
+        mainline: Some(1),
+        ..Default::default()
+    };
+    let result = repo.cherry_pick(&[commits[0].to_string()], &options);
+    assert!(result.unwrap_err().to_string().contains("is not a merge"));
+    assert!(!repo.sequencer_in_progress());
+}
+
+#[test]
+fn test_cherry_pick_conflict_continue() {
+    let temp_dir = tempdir().unwrap();
+    let (repo, commits) = setup_pick_source(&temp_dir);
+    fs::write(temp_dir.path().join("shared.txt"), "master\n").unwrap();
+    repo.create_commit("Master rewrite").unwrap();
+
+    // The second commit conflicts, the first one is already committed
+    let outcome = repo
+        .cherry_pick(&revisions(&commits), &SequencerOptions::default())
+        .unwrap();
+    assert_eq!(outcome.action, SequencerAction::Pick);
+    assert_eq!(outcome.commits, vec![repo.get_oid_hash(HEAD).unwrap()]);
+    assert_eq!(outcome.conflicted, Some((commits[1], vec!["shared.txt".to_string()])));
+    assert!(repo.sequencer_in_progress());
+    assert!(Path::new(&format!("{}/{}/todo", repo.gitdir, SEQUENCER_DIR)).exists());
+    assert_eq!(
+        repo.get_ref(CHERRY_PICK_HEAD, true).unwrap().oid().unwrap(),
+        commits[1]
+    );
+    assert_eq!(repo.get_commit(repo.get_oid_hash(HEAD).unwrap()).unwrap().message, "Add new.txt");
+
+    let result = repo.sequencer_continue();
+    assert!(matches!(result, Err(Error::Conflict(paths)) if paths == ["shared.txt"]));
+
+    fs::write(temp_dir.path().join("shared.txt"), "resolved\n").unwrap();
+    let outcome = repo.sequencer_continue().unwrap();
+
+    assert!(!repo.sequencer_in_progress());
+    assert!(repo.get_ref(CHERRY_PICK_HEAD, true).is_err());
+    let head = repo.get_commit(repo.get_oid_hash(HEAD).unwrap()).unwrap();
+    assert_eq!(outcome.commits, vec![head.oid]);
+    assert_eq!(outcome.conflicted, None);
+    assert_eq!(head.message, "Rewrite shared.txt");
+    assert_eq!(repo.get_oid_hash("master").unwrap(), head.oid);
+}
+
+#[test]
+fn test_cherry_pick_abort_restores_head() {
+    let temp_dir = tempdir().unwrap();
+    let (repo, commits) = setup_pick_source(&temp_dir);
+    fs::write(temp_dir.path().join("shared.txt"), "master\n").unwrap();
+    let master_commit = repo.create_commit("Master rewrite").unwrap();
+
+    repo.cherry_pick(&revisions(&commits), &SequencerOptions::default())
+        .unwrap();
+    assert!(repo.sequencer_in_progress());
+
+    let state = repo.sequencer_abort().unwrap();
+    assert_eq!(state.orig_head, master_commit);
+
+    assert!(!repo.sequencer_in_progress());
+    assert_eq!(repo.get_oid_hash("master").unwrap(), master_commit);
+    assert!(!temp_dir.path().join("new.txt").exists());
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("shared.txt")).unwrap(),
+        "master\n"
+    );
+    assert!(repo.cherry_pick(&revisions(&commits), &SequencerOptions::default()).is_ok());
+}
+
+#[test]
+fn test_cherry_pick_refuses_dirty_worktree() {
+    let temp_dir = tempdir().unwrap();
+    let (repo, commits) = setup_pick_source(&temp_dir);
+    fs::write(temp_dir.path().join("other.txt"), "local edit\n").unwrap();
+
+    let result = repo.cherry_pick(&revisions(&commits), &SequencerOptions::default());
+    assert!(result.unwrap_err().to_string().contains("local changes"));
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("other.txt")).unwrap(),
+        "local edit\n"
+    );
+}
+
+#[test]
+fn test_sequencer_commands_without_operation_in_progress() {
+    let temp_dir = tempdir().unwrap();
+    let (repo, _) = setup_pick_source(&temp_dir);
+
+    assert!(repo.sequencer_continue().is_err());
+    assert!(repo.sequencer_abort().is_err());
+}
@@ -0,0 +1,205 @@
+// Repository with one commit containing a.txt and dir/b.txt
+fn setup_stash_repo(temp_dir: &TempDir) -> Repository {
+    let (repo, _) = commit_once(temp_dir, |dir| {
+        write_file(dir, "a.txt", "a\n");
+        write_file(dir, "dir/b.txt", "b\n");
+    });
+    repo
+}
+
+#[test]
+fn test_stash_push_and_pop() {
+    let temp_dir = tempdir().unwrap();
+    let repo = setup_stash_repo(&temp_dir);
+    let head = repo.get_oid_hash(HEAD).unwrap();
+
+    fs::write(temp_dir.path().join("a.txt"), "changed\n").unwrap();
+    fs::write(temp_dir.path().join("new.txt"), "new\n").unwrap();
+    let stash = repo.stash_push(None, &[]).unwrap();
+
+    // The worktree is back to HEAD and the stash is an ordinary commit
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("a.txt")).unwrap(),
+        "a\n"
+    );
+    assert!(!temp_dir.path().join("new.txt").exists());
+    let commit = repo.get_commit(stash).unwrap();
+    assert_eq!(commit.parents, vec![head]);
+    assert_eq!(
+        commit.message,
+        format!("WIP on master: {} First", head.short(7))
+    );
+    assert_eq!(repo.get_ref(STASH_REF, false).unwrap().oid().unwrap(), stash);
+
+    let conflicts = repo.stash_pop(None).unwrap();
+    assert!(conflicts.is_empty());
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("a.txt")).unwrap(),
+        "changed\n"
+    );
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("new.txt")).unwrap(),
+        "new\n"
+    );
+    assert!(repo.stash_list().unwrap().is_empty());
+    assert!(repo.get_ref(STASH_REF, false).is_err());
+}
+
+#[test]
+fn test_stash_push_without_changes() {
+    let temp_dir = tempdir().unwrap();
+    let repo = setup_stash_repo(&temp_dir);
+
+    let result = repo.stash_push(None, &[]);
+    assert!(result.unwrap_err().to_string().contains("No local changes"));
+    assert!(repo.stash_list().unwrap().is_empty());
+}
+
+#[test]
+fn test_stash_push_with_paths() {
+    let temp_dir = tempdir().unwrap();
+    let repo = setup_stash_repo(&temp_dir);
+
+    fs::write(temp_dir.path().join("a.txt"), "changed a\n").unwrap();
+    fs::write(temp_dir.path().join("dir/b.txt"), "changed b\n").unwrap();
+    fs::write(temp_dir.path().join("dir/c.txt"), "c\n").unwrap();
+    let stash = repo
+        .stash_push(Some("only dir"), &["dir".to_string()])
+        .unwrap();
+
+    // Only the paths under dir/ were stashed and reset
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("a.txt")).unwrap(),
+        "changed a\n"
+    );
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("dir/b.txt")).unwrap(),
+        "b\n"
+    );
+    assert!(!temp_dir.path().join("dir/c.txt").exists());
+
+    let commit = repo.get_commit(stash).unwrap();
+    assert_eq!(commit.message, "On master: only dir");
+    let shown = repo.stash_show(None, false).unwrap();
+    assert_eq!(shown, "M\tdir/b.txt\nA\tdir/c.txt");
+}
+
+#[test]
+fn test_stash_list_and_reflog() {
+    let temp_dir = tempdir().unwrap();
+    let repo = setup_stash_repo(&temp_dir);
+
+    fs::write(temp_dir.path().join("a.txt"), "first\n").unwrap();
+    let first = repo.stash_push(Some("first"), &[]).unwrap();
+    fs::write(temp_dir.path().join("a.txt"), "second\n").unwrap();
+    let second = repo.stash_push(Some("second"), &[]).unwrap();
+
+    let entries = repo.stash_list().unwrap();
+    let messages: Vec<&str> = entries.iter().map(|e| e.message.as_str()).collect();
+    assert_eq!(messages, vec!["On master: second", "On master: first"]);
+
+    // The reflog chains the values of refs/stash, oldest first
+    let log = repo.read_reflog(STASH_REF).unwrap();
+    assert_eq!(log[0].old, Oid::zero(OBJECT_FORMAT));
+    assert_eq!(log[0].new, first);
+    assert_eq!(log[1].old, first);
+    assert_eq!(log[1].new, second);
+
+    // Stashes are reachable from the refs the visualizer walks
+    assert!(
+        repo.iter_refs("")
+            .unwrap()
+            .contains(&(STASH_REF.to_string(), RefValue::Direct(second)))
+    );
+}
+
+#[test]
+fn test_stash_apply_and_drop_by_index() {
+    let temp_dir = tempdir().unwrap();
+    let repo = setup_stash_repo(&temp_dir);
+
+    fs::write(temp_dir.path().join("a.txt"), "first\n").unwrap();
+    let first = repo.stash_push(Some("first"), &[]).unwrap();
+    fs::write(temp_dir.path().join("a.txt"), "second\n").unwrap();
+    let second = repo.stash_push(Some("second"), &[]).unwrap();
+
+    repo.stash_apply(Some("stash@{1}")).unwrap();
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("a.txt")).unwrap(),
+        "first\n"
+    );
+    assert_eq!(repo.stash_list().unwrap().len(), 2);
+
+    assert_eq!(
+        repo.stash_resolve(Some("1")).unwrap(),
+        ("stash@{1}".to_string(), 1, first)
+    );
+    assert_eq!(repo.stash_drop(Some("1")).unwrap(), first);
+    let entries = repo.stash_list().unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].new, second);
+    assert_eq!(entries[0].old, Oid::zero(OBJECT_FORMAT));
+    assert_eq!(repo.get_ref(STASH_REF, false).unwrap().oid().unwrap(), second);
+
+    assert!(repo.stash_drop(Some("stash@{3}")).is_err());
+}
+
+#[test]
+fn test_stash_show_patch() {
+    let temp_dir = tempdir().unwrap();
+    let repo = setup_stash_repo(&temp_dir);
+
+    fs::write(temp_dir.path().join("a.txt"), "changed\n").unwrap();
+    repo.stash_push(None, &[]).unwrap();
+
+    let patch = repo.stash_show(Some("stash@{0}"), true).unwrap();
+    assert!(patch.contains("-a"));
+    assert!(patch.contains("+changed"));
+}
+
+#[test]
+fn test_stash_pop_keeps_entry_on_conflict() {
+    let temp_dir = tempdir().unwrap();
+    let repo = setup_stash_repo(&temp_dir);
+
+    fs::write(temp_dir.path().join("a.txt"), "stashed\n").unwrap();
+    repo.stash_push(None, &[]).unwrap();
+    fs::write(temp_dir.path().join("a.txt"), "committed\n").unwrap();
+    repo.create_commit("Change a").unwrap();
+
+    let conflicts = repo.stash_pop(None).unwrap();
+    assert_eq!(conflicts, vec!["a.txt".to_string()]);
+    assert_eq!(repo.stash_list().unwrap().len(), 1);
+}
+
+#[test]
+fn test_stash_branch() {
+    let temp_dir = tempdir().unwrap();
+    let repo = setup_stash_repo(&temp_dir);
+    let base = repo.get_oid_hash(HEAD).unwrap();
+
+    fs::write(temp_dir.path().join("a.txt"), "stashed\n").unwrap();
+    repo.stash_push(None, &[]).unwrap();
+    fs::write(temp_dir.path().join("a.txt"), "committed\n").unwrap();
+    repo.create_commit("Change a").unwrap();
+
+    repo.stash_branch("from-stash", None).unwrap();
+
+    assert_eq!(repo.get_branch_name().unwrap(), Some("from-stash".to_string()));
+    assert_eq!(repo.get_oid_hash(HEAD).unwrap(), base);
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("a.txt")).unwrap(),
+        "stashed\n"
+    );
+    assert!(repo.stash_list().unwrap().is_empty());
+}
+
+#[test]
+fn test_stash_without_entries() {
+    let temp_dir = tempdir().unwrap();
+    let repo = setup_stash_repo(&temp_dir);
+
+    assert!(repo.stash_apply(None).is_err());
+    assert!(repo.stash_drop(None).is_err());
+    assert!(repo.stash_show(None, false).is_err());
+}
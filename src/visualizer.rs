@@ -1,7 +1,5 @@
-use crate::repository::Repository;
-use graphviz_rust::dot_generator::*;
-use graphviz_rust::dot_structures::*;
-use graphviz_rust::printer::{DotPrinter, PrinterContext};
+use bgit::Error;
+use bgit::repository::Repository;
 use std::fs;
 use std::io::Write;
 use std::net::TcpListener;
@@ -19,136 +17,20 @@ impl Visualizer {
         Self { repo }
     }
 
-    pub fn visualize(&self) -> Result<(), String> {
-        let mut graph = graph!(di id!("commit_graph"));
-
-        // Add graph attributes for vertical layout
-        graph.add_stmt(Stmt::Attribute(attr!("rankdir", "TB")));
-        graph.add_stmt(Stmt::Attribute(attr!("nodesep", "0.5")));
-        graph.add_stmt(Stmt::Attribute(attr!("ranksep", "0.5")));
-        graph.add_stmt(Stmt::Attribute(attr!("splines", "ortho")));
-
-        // Get current HEAD
-        let head_hash = self.repo.get_ref("HEAD", true)?;
-
-        let refs = self.repo.iter_refs("")?;
-        let commits = self
-            .repo
-            .iter_commits_and_parents(refs.iter().map(|(_, hash)| hash.clone()).collect())?;
-
-        // Create nodes for all commits
-        for commit_hash in &commits {
-            let commit = self.repo.get_commit(commit_hash)?;
-            let short_hash = &commit_hash[..7];
-            let first_line = commit
-                .message
-                .lines()
-                .next()
-                .unwrap_or("")
-                .replace('"', "\\\"");
-
-            // Find any tags pointing to this commit
-            let tags: Vec<String> = refs
-                .iter()
-                .filter(|(ref_name, hash)| {
-                    ref_name.starts_with("refs/tags/") && hash == commit_hash
-                })
-                .map(|(ref_name, _)| {
-                    ref_name
-                        .split('/')
-                        .next_back()
-                        .unwrap_or(ref_name)
-                        .to_string()
-                })
-                .collect();
-
-            // Create label with hash, tags, and commit message
-            let mut label = format!("\"{}\\n{}\"", short_hash, first_line);
-            if !tags.is_empty() {
-                label = format!(
-                    "\"{}\\n{}\\ntag: {}\"",
-                    short_hash,
-                    first_line,
-                    tags.join(", ")
-                );
-            }
-
-            let node_id = format!("\"{}\"", commit_hash);
-
-            // Style HEAD commit differently
-            let is_head = commit_hash == &head_hash.value;
-            let node_style = if is_head {
-                node!(node_id;
-                    attr!("label", label),
-                    attr!("shape", "box"),
-                    attr!("style", "filled"),
-                    attr!("fillcolor", "gold"),
-                    attr!("penwidth", "2")
-                )
-            } else {
-                node!(node_id;
-                    attr!("label", label),
-                    attr!("shape", "box"),
-                    attr!("style", "filled"),
-                    attr!("fillcolor", "lightblue")
-                )
-            };
-
-            graph.add_stmt(Stmt::Node(node_style));
-        }
-
-        // Add edges for parent relationships
-        for commit_hash in &commits {
-            let commit = self.repo.get_commit(commit_hash)?;
-            if !commit.parents.is_empty() {
-                for parent in commit.parents {
-                    let from_id = format!("\"{}\"", commit_hash);
-                    let to_id = format!("\"{}\"", parent);
-                    graph.add_stmt(Stmt::Edge(edge!(node_id!(from_id) => node_id!(to_id);
-                        attr!("arrowhead", "normal")
-                    )));
-                }
-            }
-        }
-
-        // Add branch refs as special nodes
-        for (ref_name, commit_hash) in refs {
-            // Skip tags since they're now shown in the commit node
-            if ref_name.starts_with("refs/tags/") {
-                continue;
-            }
-
-            let ref_label = ref_name.split('/').next_back().unwrap_or(&ref_name);
-            let ref_label = format!("\"{}\"", ref_label);
-            let ref_id = format!("\"{}\"", ref_name);
-            let commit_id = format!("\"{}\"", commit_hash);
-
-            // Style ref nodes
-            let node_style = node!(ref_id;
-                attr!("label", ref_label),
-                attr!("shape", "box"),
-                attr!("style", "filled"),
-                attr!("fillcolor", "lightgreen")
-            );
-
-            graph.add_stmt(Stmt::Node(node_style));
-            graph.add_stmt(Stmt::Edge(edge!(node_id!(ref_id) => node_id!(commit_id);
-                attr!("style", "dashed")
-            )));
-        }
-
-        // Generate DOT format output
-        let dot_output = graph.print(&mut PrinterContext::default());
+    pub fn visualize(&self) -> Result<(), Error> {
+        let dot_output = self.repo.commit_graph_dot()?;
 
         // Create a temporary file for the DOT output
-        let mut dot_file = NamedTempFile::new().map_err(|e| e.to_string())?;
+        let mut dot_file =
+            NamedTempFile::new().map_err(|e| Error::io("Failed to create temp file", e))?;
         dot_file
             .write_all(dot_output.as_bytes())
-            .map_err(|e| e.to_string())?;
+            .map_err(|e| Error::io("Failed to write temp file", e))?;
         let dot_path = dot_file.path().to_path_buf();
 
         // Create a temporary file for the SVG output
-        let svg_file = NamedTempFile::new().map_err(|e| e.to_string())?;
+        let svg_file =
+            NamedTempFile::new().map_err(|e| Error::io("Failed to create temp file", e))?;
         let svg_path = svg_file.path().to_path_buf();
 
         // Convert DOT to SVG using Graphviz
@@ -158,17 +40,18 @@ impl Visualizer {
             .arg("-o")
             .arg(&svg_path)
             .output()
-            .map_err(|e| e.to_string())?;
+            .map_err(|e| Error::io("Failed to run dot, ensure Graphviz is installed", e))?;
 
         if !output.status.success() {
-            return Err(format!(
+            return Err(Error::Other(format!(
                 "Failed to generate SVG: {}",
                 String::from_utf8_lossy(&output.stderr)
-            ));
+            )));
         }
 
         // Read the SVG content
-        let svg_content = fs::read_to_string(&svg_path).map_err(|e| e.to_string())?;
+        let svg_content = fs::read_to_string(&svg_path)
+            .map_err(|e| Error::io("Failed to read the generated SVG", e))?;
 
         // Wrap SVG content with interactive controls
         let interactive_svg = format!(
@@ -256,8 +139,12 @@ impl Visualizer {
         );
 
         // Start a simple HTTP server
-        let listener = TcpListener::bind("127.0.0.1:0").map_err(|e| e.to_string())?;
-        let port = listener.local_addr().map_err(|e| e.to_string())?.port();
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .map_err(|e| Error::io("Failed to start the HTTP server", e))?;
+        let port = listener
+            .local_addr()
+            .map_err(|e| Error::io("Failed to start the HTTP server", e))?
+            .port();
         let url = format!("http://127.0.0.1:{}", port);
 
         println!("Opening visualization in browser at {}...", url);
@@ -281,9 +168,9 @@ impl Visualizer {
                     );
                     stream
                         .write_all(response.as_bytes())
-                        .map_err(|e| e.to_string())?;
+                        .map_err(|e| Error::io("Failed to send the page", e))?;
                 }
-                Err(e) => return Err(format!("Failed to accept connection: {}", e)),
+                Err(e) => return Err(Error::io("Failed to accept connection", e)),
             }
         }
 